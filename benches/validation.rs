@@ -0,0 +1,85 @@
+use cddl::{lexer_from_str, parser::cddl_from_str, validator::schema::CompiledSchema};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use serde_cbor::Value;
+use std::collections::BTreeMap;
+
+const REPUTON_CDDL: &str = include_str!("../tests/fixtures/cddl/reputon.cddl");
+const REPUTON_JSON: &str = include_str!("../tests/fixtures/json/reputon.json");
+const SHELLEY_CDDL: &str = include_str!("../tests/fixtures/cddl/shelley.cddl");
+
+const SIZES: [usize; 3] = [10, 100, 1000];
+
+fn schema_from_str(input: &str) -> CompiledSchema {
+  let mut lexer = lexer_from_str(input);
+  let cddl = cddl_from_str(&mut lexer, input, false).unwrap();
+
+  CompiledSchema::from_cddl(cddl).unwrap()
+}
+
+// Reputation object with the reputons of the fixture repeated to the given
+// number
+fn reputation_object(reputons: usize) -> serde_json::Value {
+  let mut json = serde_json::from_str::<serde_json::Value>(REPUTON_JSON).unwrap();
+  let fixture = json["reputons"].as_array().unwrap().clone();
+  json["reputons"] = fixture.into_iter().cycle().take(reputons).collect();
+
+  json
+}
+
+// Shelley protocol parameter updates, each with integer, rational and protocol
+// version parameters
+fn protocol_param_updates(updates: usize) -> Value {
+  let uint = |n: usize| Value::Integer(n as i128);
+
+  Value::Array(
+    (0..updates)
+      .map(|i| {
+        let mut update = BTreeMap::new();
+        for param in 0..6 {
+          update.insert(uint(param), uint(i));
+        }
+        update.insert(uint(7), Value::Array(vec![uint(1), uint(2)]));
+        update.insert(uint(11), uint(i));
+        update.insert(uint(19), Value::Array(vec![uint(2), uint(0)]));
+
+        Value::Map(update)
+      })
+      .collect(),
+  )
+}
+
+fn reputon(c: &mut Criterion) {
+  let schema = schema_from_str(REPUTON_CDDL);
+
+  let mut group = c.benchmark_group("reputon");
+  for size in SIZES.iter() {
+    let json = reputation_object(*size);
+    assert!(schema.validate_json(&json).is_ok());
+
+    group.throughput(Throughput::Elements(*size as u64));
+    group.bench_with_input(BenchmarkId::from_parameter(size), &json, |b, json| {
+      b.iter(|| schema.validate_json(json))
+    });
+  }
+  group.finish();
+}
+
+fn shelley(c: &mut Criterion) {
+  let input = format!("updates = [* protocol_param_update]\n{}", SHELLEY_CDDL);
+  let schema = schema_from_str(&input);
+
+  let mut group = c.benchmark_group("shelley");
+  for size in SIZES.iter() {
+    let cbor = protocol_param_updates(*size);
+    assert!(schema.validate_cbor(&cbor).is_ok());
+
+    group.throughput(Throughput::Elements(*size as u64));
+    group.bench_with_input(BenchmarkId::from_parameter(size), &cbor, |b, cbor| {
+      b.iter(|| schema.validate_cbor(cbor))
+    });
+  }
+  group.finish();
+}
+
+criterion_group!(benches, reputon, shelley);
+criterion_main!(benches);
@@ -1,18 +1,24 @@
 #[cfg(target_arch = "wasm32")]
 extern crate console_error_panic_hook;
 
-use super::token::{ByteValue, RangeValue, SocketPlug, Token, Value};
+use super::{
+  prelude,
+  token::{write_escaped, ByteValue, RangeValue, SocketPlug, Token, Value},
+};
 use std::fmt;
 
 #[cfg(feature = "std")]
 use std::borrow::Cow;
 
-#[cfg(target_arch = "wasm32")]
+#[cfg(any(target_arch = "wasm32", feature = "ast-serde"))]
 use serde::{self, Serialize};
 
+#[cfg(feature = "ast-serde")]
+use serde::Deserialize;
+
 #[cfg(not(feature = "std"))]
 use alloc::{
-  borrow::Cow,
+  borrow::{Cow, ToOwned},
   boxed::Box,
   string::{String, ToString},
   vec::Vec,
@@ -21,9 +27,12 @@ use alloc::{
 /// Starting index, ending index and line number
 pub type Span = (usize, usize, usize);
 
+#[cfg_attr(feature = "ast-serde", derive(Serialize, Deserialize))]
 #[derive(Default, Debug, PartialEq, Clone)]
-#[doc(hidden)]
-pub struct Comments<'a>(pub Vec<&'a str>);
+/// Comments without their leading `;`, one per line. A `"\n"` entry stands for
+/// a line break which isn't part of a comment. When the first entry is a
+/// comment, it follows the preceding token on the same line
+pub struct Comments<'a>(pub Vec<Cow<'a, str>>);
 
 impl<'a> Comments<'a> {
   fn any_non_newline(&self) -> bool {
@@ -60,15 +69,17 @@ impl<'a> fmt::Display for Comments<'a> {
 /// ```abnf
 /// cddl = S 1*(rule S)
 /// ```
-#[cfg_attr(target_arch = "wasm32", derive(Serialize))]
+#[cfg_attr(any(target_arch = "wasm32", feature = "ast-serde"), derive(Serialize))]
+#[cfg_attr(feature = "ast-serde", derive(Deserialize))]
 #[derive(Default, Debug, PartialEq, Clone)]
 pub struct CDDL<'a> {
   /// Zero or more production rules
   #[cfg_attr(target_arch = "wasm32", serde(borrow))]
   pub rules: Vec<Rule<'a>>,
 
-  #[cfg_attr(target_arch = "wasm32", serde(skip))]
-  #[doc(hidden)]
+  /// Comments which aren't attached to any rule, i.e. those of a definition
+  /// without rules
+  #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
   pub comments: Option<Comments<'a>>,
 }
 
@@ -116,20 +127,37 @@ impl<'a> fmt::Display for CDDL<'a> {
 /// EALPHA = ALPHA / "@" / "_" / "$"
 /// DIGIT = %x30-39
 /// ```
-#[cfg_attr(target_arch = "wasm32", derive(Serialize))]
+#[cfg_attr(any(target_arch = "wasm32", feature = "ast-serde"), derive(Serialize))]
+#[cfg_attr(feature = "ast-serde", derive(Deserialize))]
 #[derive(Debug, Clone)]
 pub struct Identifier<'a> {
   /// Identifier
-  pub ident: &'a str,
+  pub ident: Cow<'a, str>,
   /// Optional socket
   pub socket: Option<SocketPlug>,
   /// Span
   pub span: Span,
 }
 
+impl<'a> Identifier<'a> {
+  pub(crate) fn socket_prefix(&self) -> &'static str {
+    match self.socket {
+      Some(SocketPlug::TYPE) => "$",
+      Some(SocketPlug::GROUP) => "$$",
+      None => "",
+    }
+  }
+}
+
 impl<'a> PartialEq for Identifier<'a> {
   fn eq(&self, other: &Self) -> bool {
-    self.to_string() == other.to_string()
+    // Same as comparing the displayed identifiers, without allocating them for
+    // every rule lookup
+    self
+      .socket_prefix()
+      .bytes()
+      .chain(self.ident.bytes())
+      .eq(other.socket_prefix().bytes().chain(other.ident.bytes()))
   }
 }
 
@@ -154,7 +182,7 @@ impl<'a> From<&'static str> for Identifier<'a> {
         if let Some(c) = socket.next() {
           if c == '$' {
             return Identifier {
-              ident,
+              ident: ident.into(),
               socket: Some(SocketPlug::GROUP),
               span: (0, 0, 0),
             };
@@ -162,7 +190,7 @@ impl<'a> From<&'static str> for Identifier<'a> {
         }
 
         return Identifier {
-          ident,
+          ident: ident.into(),
           socket: Some(SocketPlug::TYPE),
           span: (0, 0, 0),
         };
@@ -170,7 +198,7 @@ impl<'a> From<&'static str> for Identifier<'a> {
     }
 
     Identifier {
-      ident,
+      ident: ident.into(),
       socket: None,
       span: (0, 0, 0),
     }
@@ -195,7 +223,8 @@ impl<'a> From<Token<'a>> for Identifier<'a> {
 /// rule = typename [genericparm] S assignt S type
 ///     / groupname [genericparm] S assigng S grpent
 /// ```
-#[cfg_attr(target_arch = "wasm32", derive(Serialize))]
+#[cfg_attr(any(target_arch = "wasm32", feature = "ast-serde"), derive(Serialize))]
+#[cfg_attr(feature = "ast-serde", derive(Deserialize))]
 #[derive(Debug, PartialEq, Clone)]
 pub enum Rule<'a> {
   /// Type expression
@@ -206,8 +235,11 @@ pub enum Rule<'a> {
     /// Span
     span: Span,
 
-    #[cfg_attr(target_arch = "wasm32", serde(skip))]
-    #[doc(hidden)]
+    /// Comments on the lines preceding the rule
+    #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
+    comments_before_rule: Option<Comments<'a>>,
+    /// Comments following the rule
+    #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
     comments_after_rule: Option<Comments<'a>>,
   },
   /// Group expression
@@ -217,8 +249,11 @@ pub enum Rule<'a> {
     /// Span
     span: Span,
 
-    #[cfg_attr(target_arch = "wasm32", serde(skip))]
-    #[doc(hidden)]
+    /// Comments on the lines preceding the rule
+    #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
+    comments_before_rule: Option<Comments<'a>>,
+    /// Comments following the rule
+    #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
     comments_after_rule: Option<Comments<'a>>,
   },
 }
@@ -278,16 +313,21 @@ impl<'a> fmt::Display for Rule<'a> {
     match self {
       Rule::Type {
         rule,
+        comments_before_rule,
         comments_after_rule,
         ..
       } => {
         let mut rule_str = String::new();
 
+        if let Some(comments) = comments_before_rule {
+          rule_str.push_str(&comments.to_string());
+        }
+
         rule_str.push_str(&rule.to_string());
 
         if let Some(comments) = comments_after_rule {
           if comments.any_non_newline() {
-            if let Some(&"\n") = comments.0.first() {
+            if comments.0.first().map(|c| c.as_ref()) == Some("\n") {
               rule_str.push_str(&comments.to_string());
             } else {
               rule_str.push_str(&format!(" {}", comments));
@@ -299,16 +339,21 @@ impl<'a> fmt::Display for Rule<'a> {
       }
       Rule::Group {
         rule,
+        comments_before_rule,
         comments_after_rule,
         ..
       } => {
         let mut rule_str = String::new();
 
+        if let Some(comments) = comments_before_rule {
+          rule_str.push_str(&comments.to_string());
+        }
+
         rule_str.push_str(&rule.to_string());
 
         if let Some(comments) = comments_after_rule {
           if comments.any_non_newline() {
-            if let Some(&"\n") = comments.0.first() {
+            if comments.0.first().map(|c| c.as_ref()) == Some("\n") {
               rule_str.push_str(&comments.to_string());
             } else {
               rule_str.push_str(&format!(" {}", comments.to_string()));
@@ -339,6 +384,174 @@ impl<'a> Rule<'a> {
       Rule::Group { rule, .. } => rule.is_group_choice_alternate,
     }
   }
+
+  /// Returns the doc comment of the rule, made up of the comments on the lines
+  /// directly preceding it followed by any comment on the same line as its last
+  /// token
+  pub fn doc_comment(&self) -> Option<String> {
+    match self {
+      Rule::Type {
+        comments_before_rule,
+        comments_after_rule,
+        ..
+      }
+      | Rule::Group {
+        comments_before_rule,
+        comments_after_rule,
+        ..
+      } => doc_comment(comments_before_rule.as_ref(), comments_after_rule.as_ref()),
+    }
+  }
+}
+
+/// Doc comment of a rule or member key
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocComment<'a, 'b> {
+  /// Rule which is described or contains the member key which is described
+  pub rule: &'b Rule<'a>,
+  /// Member key which is described
+  pub member_key: Option<&'b MemberKey<'a>>,
+  /// Span of the rule or group entry which is described
+  pub span: Span,
+  /// Text of the comments, one line per comment
+  pub text: String,
+}
+
+// Text of the comments on the lines directly preceding a construct, i.e. those
+// which aren't separated from it by a blank line, followed by the comment on the
+// same line as its last token
+fn doc_comment(leading: Option<&Comments>, trailing: Option<&Comments>) -> Option<String> {
+  let mut lines = Vec::new();
+
+  if let Some(leading) = leading {
+    lines.extend(leading.0.iter().rev().take_while(|c| **c != "\n"));
+    lines.reverse();
+  }
+
+  if let Some(comment) = trailing.and_then(|c| c.0.first()) {
+    if *comment != "\n" {
+      lines.push(comment);
+    }
+  }
+
+  if lines.is_empty() {
+    return None;
+  }
+
+  let lines = lines
+    .iter()
+    .map(|l| {
+      let l = l.trim_end();
+      l.strip_prefix(' ').unwrap_or(l)
+    })
+    .collect::<Vec<_>>();
+
+  Some(lines.join("\n"))
+}
+
+impl<'a> CDDL<'a> {
+  /// Returns the doc comments of the rules and member keys, in the order they
+  /// appear
+  ///
+  /// # Example
+  ///
+  /// ```
+  /// use cddl::{lexer_from_str, parser::cddl_from_str};
+  ///
+  /// let input = r#"
+  /// ; A person
+  /// person = {
+  ///   name: tstr, ; full name
+  ///   ; age in years
+  ///   age: uint,
+  /// }
+  /// "#;
+  ///
+  /// let mut lexer = lexer_from_str(input);
+  /// let cddl = cddl_from_str(&mut lexer, input, false).unwrap();
+  /// let docs = cddl.doc_comments().into_iter().map(|d| d.text).collect::<Vec<_>>();
+  ///
+  /// assert_eq!(docs, ["A person", "full name", "age in years"]);
+  /// ```
+  pub fn doc_comments(&self) -> Vec<DocComment<'a, '_>> {
+    let mut docs = Vec::new();
+
+    for rule in self.rules.iter() {
+      if let Some(text) = rule.doc_comment() {
+        docs.push(DocComment {
+          rule,
+          member_key: None,
+          span: rule.span(),
+          text,
+        });
+      }
+
+      match rule {
+        Rule::Type { rule: tr, .. } => member_key_doc_comments(rule, &tr.value, &mut docs),
+        Rule::Group { rule: gr, .. } => match &gr.entry {
+          GroupEntry::ValueMemberKey { ge, .. } => {
+            member_key_doc_comments(rule, &ge.entry_type, &mut docs)
+          }
+          GroupEntry::InlineGroup { group, .. } => group_doc_comments(rule, group, &mut docs),
+          GroupEntry::TypeGroupname { .. } => (),
+        },
+      }
+    }
+
+    docs
+  }
+}
+
+fn member_key_doc_comments<'a, 'b>(
+  rule: &'b Rule<'a>,
+  t: &'b Type<'a>,
+  docs: &mut Vec<DocComment<'a, 'b>>,
+) {
+  for tc in t.type_choices.iter() {
+    let type2s =
+      core::iter::once(&tc.type1.type2).chain(tc.type1.operator.iter().map(|o| &o.type2));
+
+    for t2 in type2s {
+      match t2 {
+        Type2::ParenthesizedType { pt: t, .. } | Type2::TaggedData { t, .. } => {
+          member_key_doc_comments(rule, t, docs)
+        }
+        Type2::Map { group, .. }
+        | Type2::Array { group, .. }
+        | Type2::ChoiceFromInlineGroup { group, .. } => group_doc_comments(rule, group, docs),
+        _ => (),
+      }
+    }
+  }
+}
+
+fn group_doc_comments<'a, 'b>(
+  rule: &'b Rule<'a>,
+  group: &'b Group<'a>,
+  docs: &mut Vec<DocComment<'a, 'b>>,
+) {
+  for gc in group.group_choices.iter() {
+    for (entry, comma) in gc.group_entries.iter() {
+      match entry {
+        GroupEntry::ValueMemberKey { ge, span, .. } => {
+          if let Some(member_key) = &ge.member_key {
+            if let Some(text) = entry.doc_comment(comma) {
+              docs.push(DocComment {
+                rule,
+                member_key: Some(member_key),
+                span: *span,
+                text,
+              });
+            }
+          }
+
+          member_key_doc_comments(rule, &ge.entry_type, docs);
+        }
+        GroupEntry::InlineGroup { group, .. } => group_doc_comments(rule, group, docs),
+        GroupEntry::TypeGroupname { .. } => (),
+      }
+    }
+  }
 }
 
 /// Type expression
@@ -346,7 +559,8 @@ impl<'a> Rule<'a> {
 /// ```abnf
 /// typename [genericparm] S assignt S type
 /// ```
-#[cfg_attr(target_arch = "wasm32", derive(Serialize))]
+#[cfg_attr(any(target_arch = "wasm32", feature = "ast-serde"), derive(Serialize))]
+#[cfg_attr(feature = "ast-serde", derive(Deserialize))]
 #[derive(Debug, PartialEq, Clone)]
 pub struct TypeRule<'a> {
   /// Type name identifier
@@ -359,10 +573,10 @@ pub struct TypeRule<'a> {
   /// Type value
   pub value: Type<'a>,
 
-  #[cfg_attr(target_arch = "wasm32", serde(skip))]
+  #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
   #[doc(hidden)]
   pub comments_before_assignt: Option<Comments<'a>>,
-  #[cfg_attr(target_arch = "wasm32", serde(skip))]
+  #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
   #[doc(hidden)]
   pub comments_after_assignt: Option<Comments<'a>>,
 }
@@ -400,7 +614,8 @@ impl<'a> fmt::Display for TypeRule<'a> {
 /// ```abnf
 /// groupname [genericparm] S assigng S grpent
 /// ```
-#[cfg_attr(target_arch = "wasm32", derive(Serialize))]
+#[cfg_attr(any(target_arch = "wasm32", feature = "ast-serde"), derive(Serialize))]
+#[cfg_attr(feature = "ast-serde", derive(Deserialize))]
 #[derive(Debug, PartialEq, Clone)]
 pub struct GroupRule<'a> {
   /// Group name identifier
@@ -413,10 +628,10 @@ pub struct GroupRule<'a> {
   /// Group entry
   pub entry: GroupEntry<'a>,
 
-  #[cfg_attr(target_arch = "wasm32", serde(skip))]
+  #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
   #[doc(hidden)]
   pub comments_before_assigng: Option<Comments<'a>>,
-  #[cfg_attr(target_arch = "wasm32", serde(skip))]
+  #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
   #[doc(hidden)]
   pub comments_after_assigng: Option<Comments<'a>>,
 }
@@ -454,7 +669,8 @@ impl<'a> fmt::Display for GroupRule<'a> {
 /// ```abnf
 /// genericparm =  "<" S id S *("," S id S ) ">"
 /// ```
-#[cfg_attr(target_arch = "wasm32", derive(Serialize))]
+#[cfg_attr(any(target_arch = "wasm32", feature = "ast-serde"), derive(Serialize))]
+#[cfg_attr(feature = "ast-serde", derive(Deserialize))]
 #[derive(Debug, PartialEq, Clone)]
 pub struct GenericParams<'a> {
   /// List of generic parameters
@@ -473,16 +689,17 @@ impl<'a> Default for GenericParams<'a> {
 }
 
 /// Generic parameter
-#[cfg_attr(target_arch = "wasm32", derive(Serialize))]
+#[cfg_attr(any(target_arch = "wasm32", feature = "ast-serde"), derive(Serialize))]
+#[cfg_attr(feature = "ast-serde", derive(Deserialize))]
 #[derive(Debug, PartialEq, Clone)]
 pub struct GenericParam<'a> {
   /// Generic parameter
   pub param: Identifier<'a>,
 
-  #[cfg_attr(target_arch = "wasm32", serde(skip))]
+  #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
   #[doc(hidden)]
   pub comments_before_ident: Option<Comments<'a>>,
-  #[cfg_attr(target_arch = "wasm32", serde(skip))]
+  #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
   #[doc(hidden)]
   pub comments_after_ident: Option<Comments<'a>>,
 }
@@ -517,7 +734,8 @@ impl<'a> fmt::Display for GenericParams<'a> {
 /// ```abnf
 /// genericarg = "<" S type1 S *("," S type1 S )  ">"
 /// ```
-#[cfg_attr(target_arch = "wasm32", derive(Serialize))]
+#[cfg_attr(any(target_arch = "wasm32", feature = "ast-serde"), derive(Serialize))]
+#[cfg_attr(feature = "ast-serde", derive(Deserialize))]
 #[derive(Debug, Clone, PartialEq)]
 pub struct GenericArgs<'a> {
   /// Generic arguments
@@ -537,16 +755,17 @@ impl<'a> GenericArgs<'a> {
 }
 
 /// Generic argument
-#[cfg_attr(target_arch = "wasm32", derive(Serialize))]
+#[cfg_attr(any(target_arch = "wasm32", feature = "ast-serde"), derive(Serialize))]
+#[cfg_attr(feature = "ast-serde", derive(Deserialize))]
 #[derive(Debug, Clone, PartialEq)]
 pub struct GenericArg<'a> {
   /// Generic argument
   pub arg: Box<Type1<'a>>,
 
-  #[cfg_attr(target_arch = "wasm32", serde(skip))]
+  #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
   #[doc(hidden)]
   pub comments_before_type: Option<Comments<'a>>,
-  #[cfg_attr(target_arch = "wasm32", serde(skip))]
+  #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
   #[doc(hidden)]
   pub comments_after_type: Option<Comments<'a>>,
 }
@@ -581,7 +800,8 @@ impl<'a> fmt::Display for GenericArgs<'a> {
 /// ```abnf
 /// type = type1 *(S "/" S  type1)
 /// ```
-#[cfg_attr(target_arch = "wasm32", derive(Serialize))]
+#[cfg_attr(any(target_arch = "wasm32", feature = "ast-serde"), derive(Serialize))]
+#[cfg_attr(feature = "ast-serde", derive(Deserialize))]
 #[derive(Debug, Clone, PartialEq)]
 pub struct Type<'a> {
   /// Type choices
@@ -613,17 +833,18 @@ impl<'a> Type<'a> {
 }
 
 /// Type choice
-#[cfg_attr(target_arch = "wasm32", derive(Serialize))]
+#[cfg_attr(any(target_arch = "wasm32", feature = "ast-serde"), derive(Serialize))]
+#[cfg_attr(feature = "ast-serde", derive(Deserialize))]
 #[derive(Debug, Clone, PartialEq)]
 
 pub struct TypeChoice<'a> {
   /// Type choice
   pub type1: Type1<'a>,
-  #[cfg_attr(target_arch = "wasm32", serde(skip))]
-  #[doc(hidden)]
+  /// Comments preceding the type choice
+  #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
   pub comments_before_type: Option<Comments<'a>>,
-  #[cfg_attr(target_arch = "wasm32", serde(skip))]
-  #[doc(hidden)]
+  /// Comments following the type choice
+  #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
   pub comments_after_type: Option<Comments<'a>>,
 }
 
@@ -690,7 +911,8 @@ impl<'a> Type<'a> {
 /// ```abnf
 /// type1 = type2 [S (rangeop / ctlop) S type2]
 /// ```
-#[cfg_attr(target_arch = "wasm32", derive(Serialize))]
+#[cfg_attr(any(target_arch = "wasm32", feature = "ast-serde"), derive(Serialize))]
+#[cfg_attr(feature = "ast-serde", derive(Deserialize))]
 #[derive(Debug, Clone, PartialEq)]
 pub struct Type1<'a> {
   /// Type
@@ -700,7 +922,7 @@ pub struct Type1<'a> {
   /// Span
   pub span: Span,
 
-  #[cfg_attr(target_arch = "wasm32", serde(skip))]
+  #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
   #[doc(hidden)]
   pub comments_after_type: Option<Comments<'a>>,
 }
@@ -727,7 +949,8 @@ impl<'a> From<Value<'a>> for Type1<'a> {
   }
 }
 
-#[cfg_attr(target_arch = "wasm32", derive(Serialize))]
+#[cfg_attr(any(target_arch = "wasm32", feature = "ast-serde"), derive(Serialize))]
+#[cfg_attr(feature = "ast-serde", derive(Deserialize))]
 #[derive(Debug, Clone, PartialEq)]
 /// Range or control operator
 pub struct Operator<'a> {
@@ -736,10 +959,10 @@ pub struct Operator<'a> {
   /// Type bound by range or control operator
   pub type2: Type2<'a>,
 
-  #[cfg_attr(target_arch = "wasm32", serde(skip))]
+  #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
   #[doc(hidden)]
   pub comments_before_operator: Option<Comments<'a>>,
-  #[cfg_attr(target_arch = "wasm32", serde(skip))]
+  #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
   #[doc(hidden)]
   pub comments_after_operator: Option<Comments<'a>>,
 }
@@ -788,7 +1011,8 @@ impl<'a> fmt::Display for Type1<'a> {
 /// rangeop = "..." / ".."
 /// ctlop = "." id
 /// ```
-#[cfg_attr(target_arch = "wasm32", derive(Serialize))]
+#[cfg_attr(any(target_arch = "wasm32", feature = "ast-serde"), derive(Serialize))]
+#[cfg_attr(feature = "ast-serde", derive(Deserialize))]
 #[derive(Debug, PartialEq, Clone)]
 pub enum RangeCtlOp<'a> {
   /// Range operator
@@ -801,7 +1025,7 @@ pub enum RangeCtlOp<'a> {
   /// Control operator
   CtlOp {
     /// Control identifier
-    ctrl: &'a str,
+    ctrl: Cow<'a, str>,
     /// Span
     span: Span,
   },
@@ -822,6 +1046,31 @@ impl<'a> fmt::Display for RangeCtlOp<'a> {
   }
 }
 
+/// Tag number of tagged data, or argument of a data item of a major type,
+/// given either as a literal or as a type it must match
+///
+/// ```abnf
+/// head-number = uint / ("<" type ">")
+/// ```
+#[cfg_attr(any(target_arch = "wasm32", feature = "ast-serde"), derive(Serialize))]
+#[cfg_attr(feature = "ast-serde", derive(Deserialize))]
+#[derive(Debug, PartialEq, Clone)]
+pub enum HeadNumber<'a> {
+  /// Literal number
+  Uint(u64),
+  /// Type the number must match, e.g. `#6.<1000..1999>(any)`
+  Type(Type<'a>),
+}
+
+impl<'a> fmt::Display for HeadNumber<'a> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      HeadNumber::Uint(n) => write!(f, "{}", n),
+      HeadNumber::Type(t) => write!(f, "<{}>", t),
+    }
+  }
+}
+
 /// Type
 ///
 /// ```abnf
@@ -833,17 +1082,18 @@ impl<'a> fmt::Display for RangeCtlOp<'a> {
 ///     / "~" S typename [genericarg]
 ///     / "&" S "(" S group S ")"
 ///     / "&" S groupname [genericarg]
-///     / "#" "6" ["." uint] "(" S type S ")"
-///     / "#" DIGIT ["." uint]                ; major/ai
+///     / "#" "6" ["." head-number] "(" S type S ")"
+///     / "#" DIGIT ["." head-number]         ; major/ai
 ///     / "#"                                 ; any
 /// ```
-#[cfg_attr(target_arch = "wasm32", derive(Serialize))]
+#[cfg_attr(any(target_arch = "wasm32", feature = "ast-serde"), derive(Serialize))]
+#[cfg_attr(feature = "ast-serde", derive(Deserialize))]
 #[derive(Debug, Clone, PartialEq)]
 pub enum Type2<'a> {
   /// Integer value
   IntValue {
     /// Value
-    value: isize,
+    value: i128,
     /// Span
     span: Span,
   },
@@ -851,7 +1101,7 @@ pub enum Type2<'a> {
   /// Unsigned integer value
   UintValue {
     /// Value
-    value: usize,
+    value: u64,
     /// Span
     span: Span,
   },
@@ -867,7 +1117,7 @@ pub enum Type2<'a> {
   /// Text string value (enclosed by '"')
   TextValue {
     /// Value
-    value: &'a str,
+    value: Cow<'a, str>,
     /// Span
     span: Span,
   },
@@ -913,10 +1163,10 @@ pub enum Type2<'a> {
     /// Span
     span: Span,
 
-    #[cfg_attr(target_arch = "wasm32", serde(skip))]
+    #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
     #[doc(hidden)]
     comments_before_type: Option<Comments<'a>>,
-    #[cfg_attr(target_arch = "wasm32", serde(skip))]
+    #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
     #[doc(hidden)]
     comments_after_type: Option<Comments<'a>>,
   },
@@ -928,10 +1178,10 @@ pub enum Type2<'a> {
     /// Span
     span: Span,
 
-    #[cfg_attr(target_arch = "wasm32", serde(skip))]
+    #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
     #[doc(hidden)]
     comments_before_group: Option<Comments<'a>>,
-    #[cfg_attr(target_arch = "wasm32", serde(skip))]
+    #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
     #[doc(hidden)]
     comments_after_group: Option<Comments<'a>>,
   },
@@ -943,10 +1193,10 @@ pub enum Type2<'a> {
     /// Span
     span: Span,
 
-    #[cfg_attr(target_arch = "wasm32", serde(skip))]
+    #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
     #[doc(hidden)]
     comments_before_group: Option<Comments<'a>>,
-    #[cfg_attr(target_arch = "wasm32", serde(skip))]
+    #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
     #[doc(hidden)]
     comments_after_group: Option<Comments<'a>>,
   },
@@ -960,7 +1210,7 @@ pub enum Type2<'a> {
     /// Span
     span: Span,
 
-    #[cfg_attr(target_arch = "wasm32", serde(skip))]
+    #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
     #[doc(hidden)]
     comments: Option<Comments<'a>>,
   },
@@ -972,13 +1222,13 @@ pub enum Type2<'a> {
     /// Span
     span: Span,
 
-    #[cfg_attr(target_arch = "wasm32", serde(skip))]
+    #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
     #[doc(hidden)]
     comments: Option<Comments<'a>>,
-    #[cfg_attr(target_arch = "wasm32", serde(skip))]
+    #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
     #[doc(hidden)]
     comments_before_group: Option<Comments<'a>>,
-    #[cfg_attr(target_arch = "wasm32", serde(skip))]
+    #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
     #[doc(hidden)]
     comments_after_group: Option<Comments<'a>>,
   },
@@ -992,7 +1242,7 @@ pub enum Type2<'a> {
     /// Span
     span: Span,
 
-    #[cfg_attr(target_arch = "wasm32", serde(skip))]
+    #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
     #[doc(hidden)]
     comments: Option<Comments<'a>>,
   },
@@ -1001,16 +1251,16 @@ pub enum Type2<'a> {
   /// is the type of the tagged value
   TaggedData {
     /// Tag
-    tag: Option<usize>,
+    tag: Option<HeadNumber<'a>>,
     /// Type
     t: Type<'a>,
     /// Span
     span: Span,
 
-    #[cfg_attr(target_arch = "wasm32", serde(skip))]
+    #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
     #[doc(hidden)]
     comments_before_type: Option<Comments<'a>>,
-    #[cfg_attr(target_arch = "wasm32", serde(skip))]
+    #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
     #[doc(hidden)]
     comments_after_type: Option<Comments<'a>>,
   },
@@ -1020,7 +1270,7 @@ pub enum Type2<'a> {
     /// Major type
     mt: u8,
     /// Constraint
-    constraint: Option<usize>,
+    constraint: Option<HeadNumber<'a>>,
     /// Span
     span: Span,
   },
@@ -1035,13 +1285,17 @@ impl<'a> fmt::Display for Type2<'a> {
     match self {
       Type2::IntValue { value, .. } => write!(f, "{}", value),
       Type2::UintValue { value, .. } => write!(f, "{}", value),
-      Type2::FloatValue { value, .. } => write!(f, "{}", value),
-      Type2::TextValue { value, .. } => write!(f, "\"{}\"", value),
-      Type2::UTF8ByteString { value, .. } => write!(
-        f,
-        "'{}'",
-        std::str::from_utf8(value).map_err(|_| fmt::Error)?
-      ),
+      Type2::FloatValue { value, .. } => write!(f, "{}", Value::FLOAT(*value)),
+      Type2::TextValue { value, .. } => {
+        write!(f, "\"")?;
+        write_escaped(f, value, '"')?;
+        write!(f, "\"")
+      }
+      Type2::UTF8ByteString { value, .. } => {
+        write!(f, "'")?;
+        write_escaped(f, std::str::from_utf8(value).map_err(|_| fmt::Error)?, '\'')?;
+        write!(f, "'")
+      }
       Type2::B16ByteString { value, .. } => {
         write!(f, "{}", std::str::from_utf8(value).map_err(|_| fmt::Error)?)
       }
@@ -1283,10 +1537,10 @@ impl<'a> fmt::Display for Type2<'a> {
       }
       Type2::DataMajorType { mt, constraint, .. } => {
         if let Some(c) = constraint {
-          return write!(f, "{}.{}", mt, c);
+          return write!(f, "#{}.{}", mt, c);
         }
 
-        write!(f, "{}", mt)
+        write!(f, "#{}", mt)
       }
       Type2::Any(_) => write!(f, "#"),
     }
@@ -1300,7 +1554,7 @@ impl<'a> From<RangeValue<'a>> for Type2<'a> {
     match rv {
       RangeValue::IDENT(ident) => Type2::Typename {
         ident: Identifier {
-          ident: ident.0,
+          ident: ident.0.into(),
           socket: ident.1,
           span,
         },
@@ -1316,109 +1570,19 @@ impl<'a> From<RangeValue<'a>> for Type2<'a> {
 
 /// Retrieve `Type2` from token if it is a tag type in the standard prelude
 pub fn tag_from_token<'a>(token: &Token) -> Option<Type2<'a>> {
-  match token {
-    Token::TDATE => Some(Type2::TaggedData {
-      tag: Some(0),
-      t: type_from_token(Token::TSTR),
-      comments_after_type: None,
-      comments_before_type: None,
-      span: Span::default(),
-    }),
-    Token::TIME => Some(Type2::TaggedData {
-      tag: Some(1),
-      t: type_from_token(Token::NUMBER),
-      comments_before_type: None,
-      comments_after_type: None,
-      span: Span::default(),
-    }),
-    Token::BIGUINT => Some(Type2::TaggedData {
-      tag: Some(2),
-      t: type_from_token(Token::BSTR),
-      comments_before_type: None,
-      comments_after_type: None,
-      span: Span::default(),
-    }),
-    Token::BIGNINT => Some(Type2::TaggedData {
-      tag: Some(3),
-      t: type_from_token(Token::BSTR),
-      comments_before_type: None,
-      comments_after_type: None,
-      span: Span::default(),
-    }),
-    Token::DECFRAC => unimplemented!(),
-    Token::BIGFLOAT => unimplemented!(),
-    Token::EB64URL => Some(Type2::TaggedData {
-      tag: Some(21),
-      t: type_from_token(Token::ANY),
-      comments_before_type: None,
-      comments_after_type: None,
-      span: Span::default(),
-    }),
-    Token::EB64LEGACY => Some(Type2::TaggedData {
-      tag: Some(22),
-      t: type_from_token(Token::ANY),
-      comments_before_type: None,
-      comments_after_type: None,
-      span: Span::default(),
-    }),
-    Token::EB16 => Some(Type2::TaggedData {
-      tag: Some(23),
-      t: type_from_token(Token::ANY),
-      comments_before_type: None,
-      comments_after_type: None,
-      span: Span::default(),
-    }),
-    Token::ENCODEDCBOR => Some(Type2::TaggedData {
-      tag: Some(24),
-      t: type_from_token(Token::BSTR),
-      comments_before_type: None,
-      comments_after_type: None,
-      span: Span::default(),
-    }),
-    Token::URI => Some(Type2::TaggedData {
-      tag: Some(32),
-      t: type_from_token(Token::TSTR),
-      comments_before_type: None,
-      comments_after_type: None,
-      span: Span::default(),
-    }),
-    Token::B64URL => Some(Type2::TaggedData {
-      tag: Some(33),
-      t: type_from_token(Token::TSTR),
-      comments_before_type: None,
-      comments_after_type: None,
-      span: Span::default(),
-    }),
-    Token::B64LEGACY => Some(Type2::TaggedData {
-      tag: Some(34),
-      t: type_from_token(Token::TSTR),
-      comments_before_type: None,
-      comments_after_type: None,
-      span: Span::default(),
-    }),
-    Token::REGEXP => Some(Type2::TaggedData {
-      tag: Some(35),
-      t: type_from_token(Token::TSTR),
-      comments_before_type: None,
-      comments_after_type: None,
-      span: Span::default(),
-    }),
-    Token::MIMEMESSAGE => Some(Type2::TaggedData {
-      tag: Some(36),
-      t: type_from_token(Token::TSTR),
-      comments_before_type: None,
-      comments_after_type: None,
-      span: Span::default(),
-    }),
-    Token::CBORANY => Some(Type2::TaggedData {
-      tag: Some(55799),
-      t: type_from_token(Token::ANY),
-      comments_before_type: None,
-      comments_after_type: None,
-      span: Span::default(),
-    }),
-    _ => None,
-  }
+  let name = token.in_standard_prelude()?;
+
+  prelude::standard_prelude()
+    .rules
+    .iter()
+    .find_map(|rule| match rule {
+      Rule::Type { rule, .. } if rule.name.ident == name => {
+        rule.value.type_choices.first().map(|tc| &tc.type1.type2)
+      }
+      _ => None,
+    })
+    .filter(|t2| matches!(t2, Type2::TaggedData { .. }))
+    .cloned()
 }
 
 /// New `Type` from a given `token::Token`
@@ -1447,7 +1611,8 @@ pub fn type_from_token(token: Token) -> Type {
 /// ```abnf
 /// group = grpchoice * (S "//" S grpchoice)
 /// ```
-#[cfg_attr(target_arch = "wasm32", derive(Serialize))]
+#[cfg_attr(any(target_arch = "wasm32", feature = "ast-serde"), derive(Serialize))]
+#[cfg_attr(feature = "ast-serde", derive(Deserialize))]
 #[derive(Debug, Clone, PartialEq)]
 pub struct Group<'a> {
   /// Group choices
@@ -1519,7 +1684,8 @@ impl<'a> fmt::Display for Group<'a> {
 /// ```
 ///
 /// If tuple is true, then entry is marked by a trailing comma
-#[cfg_attr(target_arch = "wasm32", derive(Serialize))]
+#[cfg_attr(any(target_arch = "wasm32", feature = "ast-serde"), derive(Serialize))]
+#[cfg_attr(feature = "ast-serde", derive(Deserialize))]
 #[derive(Debug, Clone, PartialEq)]
 pub struct GroupChoice<'a> {
   /// Group entries where the second item in the tuple indicates where or not a
@@ -1531,8 +1697,8 @@ pub struct GroupChoice<'a> {
 
   // No trailing comments since these will be captured by the S ["," S] matching
   // rule
-  #[cfg_attr(target_arch = "wasm32", serde(skip))]
-  #[doc(hidden)]
+  /// Comments preceding the first group entry of the group choice
+  #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
   pub comments_before_grpchoice: Option<Comments<'a>>,
 }
 
@@ -1710,7 +1876,8 @@ impl<'a> fmt::Display for GroupChoice<'a> {
 ///       / [occur S] groupname [genericarg]  ; preempted by above
 ///       / [occur S] "(" S group S ")"
 /// ```
-#[cfg_attr(target_arch = "wasm32", derive(Serialize))]
+#[cfg_attr(any(target_arch = "wasm32", feature = "ast-serde"), derive(Serialize))]
+#[cfg_attr(feature = "ast-serde", derive(Deserialize))]
 #[derive(Debug, Clone, PartialEq)]
 pub enum GroupEntry<'a> {
   /// Value group entry type
@@ -1721,11 +1888,11 @@ pub enum GroupEntry<'a> {
     /// Span
     span: Span,
 
-    #[cfg_attr(target_arch = "wasm32", serde(skip))]
-    #[doc(hidden)]
+    /// Comments preceding the group entry
+    #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
     leading_comments: Option<Comments<'a>>,
-    #[cfg_attr(target_arch = "wasm32", serde(skip))]
-    #[doc(hidden)]
+    /// Comments following the group entry, before any comma
+    #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
     trailing_comments: Option<Comments<'a>>,
   },
 
@@ -1737,11 +1904,11 @@ pub enum GroupEntry<'a> {
     /// span
     span: Span,
 
-    #[cfg_attr(target_arch = "wasm32", serde(skip))]
-    #[doc(hidden)]
+    /// Comments preceding the group entry
+    #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
     leading_comments: Option<Comments<'a>>,
-    #[cfg_attr(target_arch = "wasm32", serde(skip))]
-    #[doc(hidden)]
+    /// Comments following the group entry, before any comma
+    #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
     trailing_comments: Option<Comments<'a>>,
   },
 
@@ -1754,16 +1921,43 @@ pub enum GroupEntry<'a> {
     /// Span
     span: Span,
 
-    #[cfg_attr(target_arch = "wasm32", serde(skip))]
+    #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
     #[doc(hidden)]
     comments_before_group: Option<Comments<'a>>,
-    #[cfg_attr(target_arch = "wasm32", serde(skip))]
+    #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
     #[doc(hidden)]
     comments_after_group: Option<Comments<'a>>,
   },
 }
 
 impl<'a> GroupEntry<'a> {
+  /// Returns the doc comment of the group entry followed by the given comma,
+  /// made up of the comments on the lines directly preceding it followed by any
+  /// comment on the same line as its last token. Inline groups have no doc
+  /// comment
+  pub fn doc_comment(&self, comma: &OptionalComma) -> Option<String> {
+    match self {
+      GroupEntry::ValueMemberKey {
+        leading_comments,
+        trailing_comments,
+        ..
+      }
+      | GroupEntry::TypeGroupname {
+        leading_comments,
+        trailing_comments,
+        ..
+      } => {
+        let trailing = match trailing_comments {
+          Some(comments) if comments.any_non_newline() => Some(comments),
+          _ => comma.trailing_comments.as_ref(),
+        };
+
+        doc_comment(leading_comments.as_ref(), trailing)
+      }
+      GroupEntry::InlineGroup { .. } => None,
+    }
+  }
+
   fn has_trailing_comments(&self) -> bool {
     matches!(self,
       GroupEntry::ValueMemberKey {
@@ -1779,14 +1973,15 @@ impl<'a> GroupEntry<'a> {
 }
 
 /// Optional comma
-#[cfg_attr(target_arch = "wasm32", derive(Serialize))]
+#[cfg_attr(any(target_arch = "wasm32", feature = "ast-serde"), derive(Serialize))]
+#[cfg_attr(feature = "ast-serde", derive(Deserialize))]
 #[derive(Debug, Clone, PartialEq, Default)]
 pub struct OptionalComma<'a> {
   /// Optional comma
   pub optional_comma: bool,
 
-  #[cfg_attr(target_arch = "wasm32", serde(skip))]
-  #[doc(hidden)]
+  /// Comments following the comma, or the group entry if there's no comma
+  #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
   pub trailing_comments: Option<Comments<'a>>,
 }
 
@@ -1806,8 +2001,8 @@ impl<'a> fmt::Display for OptionalComma<'a> {
           }
         }
 
-        for (idx, &comment) in comments.0.iter().enumerate() {
-          if idx == 0 && comment != "\n" {
+        for (idx, comment) in comments.0.iter().enumerate() {
+          if idx == 0 && *comment != "\n" {
             optcomma_str.push_str(&format!(";{}\n", comment));
           } else if idx == 0 {
             optcomma_str.push_str(&comment.to_string());
@@ -1944,13 +2139,14 @@ impl<'a> fmt::Display for GroupEntry<'a> {
 }
 
 /// Occurrence indicator
-#[cfg_attr(target_arch = "wasm32", derive(Serialize))]
+#[cfg_attr(any(target_arch = "wasm32", feature = "ast-serde"), derive(Serialize))]
+#[cfg_attr(feature = "ast-serde", derive(Deserialize))]
 #[derive(Debug, Clone, PartialEq)]
 pub struct Occurrence<'a> {
   /// Occurrence indicator
   pub occur: Occur,
 
-  #[cfg_attr(target_arch = "wasm32", serde(skip))]
+  #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
   #[doc(hidden)]
   pub comments: Option<Comments<'a>>,
 }
@@ -1973,7 +2169,8 @@ impl<'a> fmt::Display for Occurrence<'a> {
 /// ```abnf
 /// [occur S] [memberkey S] type
 /// ```
-#[cfg_attr(target_arch = "wasm32", derive(Serialize))]
+#[cfg_attr(any(target_arch = "wasm32", feature = "ast-serde"), derive(Serialize))]
+#[cfg_attr(feature = "ast-serde", derive(Deserialize))]
 #[derive(Debug, Clone, PartialEq)]
 pub struct ValueMemberKeyEntry<'a> {
   /// Optional occurrence indicator
@@ -2004,7 +2201,8 @@ impl<'a> fmt::Display for ValueMemberKeyEntry<'a> {
 }
 
 /// Group entry from a named type or group
-#[cfg_attr(target_arch = "wasm32", derive(Serialize))]
+#[cfg_attr(any(target_arch = "wasm32", feature = "ast-serde"), derive(Serialize))]
+#[cfg_attr(feature = "ast-serde", derive(Deserialize))]
 #[derive(Debug, Clone, PartialEq)]
 pub struct TypeGroupnameEntry<'a> {
   /// Optional occurrence indicator
@@ -2040,7 +2238,8 @@ impl<'a> fmt::Display for TypeGroupnameEntry<'a> {
 ///           / bareword S ":"
 ///           / value S ":"
 /// ```
-#[cfg_attr(target_arch = "wasm32", derive(Serialize))]
+#[cfg_attr(any(target_arch = "wasm32", feature = "ast-serde"), derive(Serialize))]
+#[cfg_attr(feature = "ast-serde", derive(Deserialize))]
 #[derive(Debug, Clone, PartialEq)]
 pub enum MemberKey<'a> {
   /// Type expression
@@ -2053,13 +2252,13 @@ pub enum MemberKey<'a> {
     /// Span
     span: Span,
 
-    #[cfg_attr(target_arch = "wasm32", serde(skip))]
+    #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
     #[doc(hidden)]
     comments_before_cut: Option<Comments<'a>>,
-    #[cfg_attr(target_arch = "wasm32", serde(skip))]
+    #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
     #[doc(hidden)]
     comments_after_cut: Option<Comments<'a>>,
-    #[cfg_attr(target_arch = "wasm32", serde(skip))]
+    #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
     #[doc(hidden)]
     comments_after_arrowmap: Option<Comments<'a>>,
   },
@@ -2072,10 +2271,10 @@ pub enum MemberKey<'a> {
     /// Span
     span: Span,
 
-    #[cfg_attr(target_arch = "wasm32", serde(skip))]
+    #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
     #[doc(hidden)]
     comments: Option<Comments<'a>>,
-    #[cfg_attr(target_arch = "wasm32", serde(skip))]
+    #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
     #[doc(hidden)]
     comments_after_colon: Option<Comments<'a>>,
   },
@@ -2088,15 +2287,15 @@ pub enum MemberKey<'a> {
     /// Span
     span: Span,
 
-    #[cfg_attr(target_arch = "wasm32", serde(skip))]
+    #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
     #[doc(hidden)]
     comments: Option<Comments<'a>>,
-    #[cfg_attr(target_arch = "wasm32", serde(skip))]
+    #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
     #[doc(hidden)]
     comments_after_colon: Option<Comments<'a>>,
   },
 
-  #[cfg_attr(target_arch = "wasm32", serde(skip))]
+  #[cfg_attr(all(target_arch = "wasm32", not(feature = "ast-serde")), serde(skip))]
   #[doc(hidden)]
   NonMemberKey {
     non_member_key: NonMemberKey<'a>,
@@ -2105,6 +2304,7 @@ pub enum MemberKey<'a> {
   },
 }
 
+#[cfg_attr(feature = "ast-serde", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone, PartialEq)]
 #[doc(hidden)]
 pub enum NonMemberKey<'a> {
@@ -2247,7 +2447,8 @@ impl<'a> fmt::Display for MemberKey<'a> {
 ///       / "+"
 ///       / "?"
 /// ```
-#[cfg_attr(target_arch = "wasm32", derive(Serialize))]
+#[cfg_attr(any(target_arch = "wasm32", feature = "ast-serde"), derive(Serialize))]
+#[cfg_attr(feature = "ast-serde", derive(Deserialize))]
 #[derive(Debug, Clone, PartialEq)]
 pub enum Occur {
   /// Occurrence indicator in the form n*m, where n is an optional lower limit
@@ -2296,6 +2497,494 @@ impl fmt::Display for Occur {
   }
 }
 
+// Converting into an owned AST. Each node is converted by value so that only
+// borrowed text and byte strings need to be copied
+
+fn into_owned_cow<B: ?Sized + ToOwned>(c: Cow<B>) -> Cow<'static, B> {
+  Cow::Owned(c.into_owned())
+}
+
+fn into_owned_comments(comments: Option<Comments>) -> Option<Comments<'static>> {
+  comments.map(Comments::into_owned)
+}
+
+impl<'a> Comments<'a> {
+  /// Converts into comments which own their text
+  pub fn into_owned(self) -> Comments<'static> {
+    Comments(self.0.into_iter().map(into_owned_cow).collect())
+  }
+}
+
+impl<'a> CDDL<'a> {
+  /// Converts into an AST which owns all of its text and byte strings, and so
+  /// no longer borrows from the input it was parsed from
+  ///
+  /// # Example
+  ///
+  /// ```
+  /// use cddl::{ast::CDDL, lexer_from_str, parser::cddl_from_str};
+  ///
+  /// fn parse(input: String) -> CDDL<'static> {
+  ///   let mut lexer = lexer_from_str(&input);
+  ///   cddl_from_str(&mut lexer, &input, false).unwrap().into_owned()
+  /// }
+  ///
+  /// assert_eq!(parse("myrule = int".to_string()).rules.len(), 1);
+  /// ```
+  pub fn into_owned(self) -> CDDL<'static> {
+    CDDL {
+      rules: self.rules.into_iter().map(Rule::into_owned).collect(),
+      comments: into_owned_comments(self.comments),
+    }
+  }
+}
+
+impl<'a> Identifier<'a> {
+  /// Converts into an identifier which owns its text
+  pub fn into_owned(self) -> Identifier<'static> {
+    Identifier {
+      ident: into_owned_cow(self.ident),
+      socket: self.socket,
+      span: self.span,
+    }
+  }
+}
+
+impl<'a> Rule<'a> {
+  /// Converts into a rule which owns all of its text and byte strings
+  pub fn into_owned(self) -> Rule<'static> {
+    match self {
+      Rule::Type {
+        rule,
+        span,
+        comments_before_rule,
+        comments_after_rule,
+      } => Rule::Type {
+        rule: rule.into_owned(),
+        span,
+        comments_before_rule: into_owned_comments(comments_before_rule),
+        comments_after_rule: into_owned_comments(comments_after_rule),
+      },
+      Rule::Group {
+        rule,
+        span,
+        comments_before_rule,
+        comments_after_rule,
+      } => Rule::Group {
+        rule: Box::new(rule.into_owned()),
+        span,
+        comments_before_rule: into_owned_comments(comments_before_rule),
+        comments_after_rule: into_owned_comments(comments_after_rule),
+      },
+    }
+  }
+}
+
+impl<'a> TypeRule<'a> {
+  /// Converts into a type rule which owns all of its text and byte strings
+  pub fn into_owned(self) -> TypeRule<'static> {
+    TypeRule {
+      name: self.name.into_owned(),
+      generic_params: self.generic_params.map(GenericParams::into_owned),
+      is_type_choice_alternate: self.is_type_choice_alternate,
+      value: self.value.into_owned(),
+      comments_before_assignt: into_owned_comments(self.comments_before_assignt),
+      comments_after_assignt: into_owned_comments(self.comments_after_assignt),
+    }
+  }
+}
+
+impl<'a> GroupRule<'a> {
+  /// Converts into a group rule which owns all of its text and byte strings
+  pub fn into_owned(self) -> GroupRule<'static> {
+    GroupRule {
+      name: self.name.into_owned(),
+      generic_params: self.generic_params.map(GenericParams::into_owned),
+      is_group_choice_alternate: self.is_group_choice_alternate,
+      entry: self.entry.into_owned(),
+      comments_before_assigng: into_owned_comments(self.comments_before_assigng),
+      comments_after_assigng: into_owned_comments(self.comments_after_assigng),
+    }
+  }
+}
+
+impl<'a> GenericParams<'a> {
+  /// Converts into generic parameters which own their text
+  pub fn into_owned(self) -> GenericParams<'static> {
+    GenericParams {
+      params: self
+        .params
+        .into_iter()
+        .map(|p| GenericParam {
+          param: p.param.into_owned(),
+          comments_before_ident: into_owned_comments(p.comments_before_ident),
+          comments_after_ident: into_owned_comments(p.comments_after_ident),
+        })
+        .collect(),
+      span: self.span,
+    }
+  }
+}
+
+impl<'a> GenericArgs<'a> {
+  /// Converts into generic arguments which own all of their text and byte
+  /// strings
+  pub fn into_owned(self) -> GenericArgs<'static> {
+    GenericArgs {
+      args: self
+        .args
+        .into_iter()
+        .map(|a| GenericArg {
+          arg: Box::new(a.arg.into_owned()),
+          comments_before_type: into_owned_comments(a.comments_before_type),
+          comments_after_type: into_owned_comments(a.comments_after_type),
+        })
+        .collect(),
+      span: self.span,
+    }
+  }
+}
+
+impl<'a> Type<'a> {
+  /// Converts into a type which owns all of its text and byte strings
+  pub fn into_owned(self) -> Type<'static> {
+    Type {
+      type_choices: self
+        .type_choices
+        .into_iter()
+        .map(|tc| TypeChoice {
+          type1: tc.type1.into_owned(),
+          comments_before_type: into_owned_comments(tc.comments_before_type),
+          comments_after_type: into_owned_comments(tc.comments_after_type),
+        })
+        .collect(),
+      span: self.span,
+    }
+  }
+}
+
+impl<'a> Type1<'a> {
+  /// Converts into a type which owns all of its text and byte strings
+  pub fn into_owned(self) -> Type1<'static> {
+    Type1 {
+      type2: self.type2.into_owned(),
+      operator: self.operator.map(|o| Operator {
+        operator: o.operator.into_owned(),
+        type2: o.type2.into_owned(),
+        comments_before_operator: into_owned_comments(o.comments_before_operator),
+        comments_after_operator: into_owned_comments(o.comments_after_operator),
+      }),
+      span: self.span,
+      comments_after_type: into_owned_comments(self.comments_after_type),
+    }
+  }
+}
+
+impl<'a> RangeCtlOp<'a> {
+  /// Converts into an operator which owns its text
+  pub fn into_owned(self) -> RangeCtlOp<'static> {
+    match self {
+      RangeCtlOp::RangeOp { is_inclusive, span } => RangeCtlOp::RangeOp { is_inclusive, span },
+      RangeCtlOp::CtlOp { ctrl, span } => RangeCtlOp::CtlOp {
+        ctrl: into_owned_cow(ctrl),
+        span,
+      },
+    }
+  }
+}
+
+impl<'a> HeadNumber<'a> {
+  /// Converts into a head number which owns all of its text and byte strings
+  pub fn into_owned(self) -> HeadNumber<'static> {
+    match self {
+      HeadNumber::Uint(n) => HeadNumber::Uint(n),
+      HeadNumber::Type(t) => HeadNumber::Type(t.into_owned()),
+    }
+  }
+}
+
+impl<'a> Type2<'a> {
+  /// Converts into a type which owns all of its text and byte strings
+  pub fn into_owned(self) -> Type2<'static> {
+    match self {
+      Type2::IntValue { value, span } => Type2::IntValue { value, span },
+      Type2::UintValue { value, span } => Type2::UintValue { value, span },
+      Type2::FloatValue { value, span } => Type2::FloatValue { value, span },
+      Type2::TextValue { value, span } => Type2::TextValue {
+        value: into_owned_cow(value),
+        span,
+      },
+      Type2::UTF8ByteString { value, span } => Type2::UTF8ByteString {
+        value: into_owned_cow(value),
+        span,
+      },
+      Type2::B16ByteString { value, span } => Type2::B16ByteString {
+        value: into_owned_cow(value),
+        span,
+      },
+      Type2::B64ByteString { value, span } => Type2::B64ByteString {
+        value: into_owned_cow(value),
+        span,
+      },
+      Type2::Typename {
+        ident,
+        generic_args,
+        span,
+      } => Type2::Typename {
+        ident: ident.into_owned(),
+        generic_args: generic_args.map(GenericArgs::into_owned),
+        span,
+      },
+      Type2::ParenthesizedType {
+        pt,
+        span,
+        comments_before_type,
+        comments_after_type,
+      } => Type2::ParenthesizedType {
+        pt: pt.into_owned(),
+        span,
+        comments_before_type: into_owned_comments(comments_before_type),
+        comments_after_type: into_owned_comments(comments_after_type),
+      },
+      Type2::Map {
+        group,
+        span,
+        comments_before_group,
+        comments_after_group,
+      } => Type2::Map {
+        group: group.into_owned(),
+        span,
+        comments_before_group: into_owned_comments(comments_before_group),
+        comments_after_group: into_owned_comments(comments_after_group),
+      },
+      Type2::Array {
+        group,
+        span,
+        comments_before_group,
+        comments_after_group,
+      } => Type2::Array {
+        group: group.into_owned(),
+        span,
+        comments_before_group: into_owned_comments(comments_before_group),
+        comments_after_group: into_owned_comments(comments_after_group),
+      },
+      Type2::Unwrap {
+        ident,
+        generic_args,
+        span,
+        comments,
+      } => Type2::Unwrap {
+        ident: ident.into_owned(),
+        generic_args: generic_args.map(GenericArgs::into_owned),
+        span,
+        comments: into_owned_comments(comments),
+      },
+      Type2::ChoiceFromInlineGroup {
+        group,
+        span,
+        comments,
+        comments_before_group,
+        comments_after_group,
+      } => Type2::ChoiceFromInlineGroup {
+        group: group.into_owned(),
+        span,
+        comments: into_owned_comments(comments),
+        comments_before_group: into_owned_comments(comments_before_group),
+        comments_after_group: into_owned_comments(comments_after_group),
+      },
+      Type2::ChoiceFromGroup {
+        ident,
+        generic_args,
+        span,
+        comments,
+      } => Type2::ChoiceFromGroup {
+        ident: ident.into_owned(),
+        generic_args: generic_args.map(GenericArgs::into_owned),
+        span,
+        comments: into_owned_comments(comments),
+      },
+      Type2::TaggedData {
+        tag,
+        t,
+        span,
+        comments_before_type,
+        comments_after_type,
+      } => Type2::TaggedData {
+        tag: tag.map(HeadNumber::into_owned),
+        t: t.into_owned(),
+        span,
+        comments_before_type: into_owned_comments(comments_before_type),
+        comments_after_type: into_owned_comments(comments_after_type),
+      },
+      Type2::DataMajorType {
+        mt,
+        constraint,
+        span,
+      } => Type2::DataMajorType {
+        mt,
+        constraint: constraint.map(HeadNumber::into_owned),
+        span,
+      },
+      Type2::Any(span) => Type2::Any(span),
+    }
+  }
+}
+
+impl<'a> Group<'a> {
+  /// Converts into a group which owns all of its text and byte strings
+  pub fn into_owned(self) -> Group<'static> {
+    Group {
+      group_choices: self
+        .group_choices
+        .into_iter()
+        .map(GroupChoice::into_owned)
+        .collect(),
+      span: self.span,
+    }
+  }
+}
+
+impl<'a> GroupChoice<'a> {
+  /// Converts into a group choice which owns all of its text and byte strings
+  pub fn into_owned(self) -> GroupChoice<'static> {
+    GroupChoice {
+      group_entries: self
+        .group_entries
+        .into_iter()
+        .map(|(ge, oc)| {
+          (
+            ge.into_owned(),
+            OptionalComma {
+              optional_comma: oc.optional_comma,
+              trailing_comments: into_owned_comments(oc.trailing_comments),
+            },
+          )
+        })
+        .collect(),
+      span: self.span,
+      comments_before_grpchoice: into_owned_comments(self.comments_before_grpchoice),
+    }
+  }
+}
+
+impl<'a> GroupEntry<'a> {
+  /// Converts into a group entry which owns all of its text and byte strings
+  pub fn into_owned(self) -> GroupEntry<'static> {
+    match self {
+      GroupEntry::ValueMemberKey {
+        ge,
+        span,
+        leading_comments,
+        trailing_comments,
+      } => GroupEntry::ValueMemberKey {
+        ge: Box::new(ValueMemberKeyEntry {
+          occur: ge.occur.map(Occurrence::into_owned),
+          member_key: ge.member_key.map(MemberKey::into_owned),
+          entry_type: ge.entry_type.into_owned(),
+        }),
+        span,
+        leading_comments: into_owned_comments(leading_comments),
+        trailing_comments: into_owned_comments(trailing_comments),
+      },
+      GroupEntry::TypeGroupname {
+        ge,
+        span,
+        leading_comments,
+        trailing_comments,
+      } => GroupEntry::TypeGroupname {
+        ge: TypeGroupnameEntry {
+          occur: ge.occur.map(Occurrence::into_owned),
+          name: ge.name.into_owned(),
+          generic_args: ge.generic_args.map(GenericArgs::into_owned),
+        },
+        span,
+        leading_comments: into_owned_comments(leading_comments),
+        trailing_comments: into_owned_comments(trailing_comments),
+      },
+      GroupEntry::InlineGroup {
+        occur,
+        group,
+        span,
+        comments_before_group,
+        comments_after_group,
+      } => GroupEntry::InlineGroup {
+        occur: occur.map(Occurrence::into_owned),
+        group: group.into_owned(),
+        span,
+        comments_before_group: into_owned_comments(comments_before_group),
+        comments_after_group: into_owned_comments(comments_after_group),
+      },
+    }
+  }
+}
+
+impl<'a> Occurrence<'a> {
+  /// Converts into an occurrence indicator which owns its comments
+  pub fn into_owned(self) -> Occurrence<'static> {
+    Occurrence {
+      occur: self.occur,
+      comments: into_owned_comments(self.comments),
+    }
+  }
+}
+
+impl<'a> MemberKey<'a> {
+  /// Converts into a member key which owns all of its text and byte strings
+  pub fn into_owned(self) -> MemberKey<'static> {
+    match self {
+      MemberKey::Type1 {
+        t1,
+        is_cut,
+        span,
+        comments_before_cut,
+        comments_after_cut,
+        comments_after_arrowmap,
+      } => MemberKey::Type1 {
+        t1: Box::new(t1.into_owned()),
+        is_cut,
+        span,
+        comments_before_cut: into_owned_comments(comments_before_cut),
+        comments_after_cut: into_owned_comments(comments_after_cut),
+        comments_after_arrowmap: into_owned_comments(comments_after_arrowmap),
+      },
+      MemberKey::Bareword {
+        ident,
+        span,
+        comments,
+        comments_after_colon,
+      } => MemberKey::Bareword {
+        ident: ident.into_owned(),
+        span,
+        comments: into_owned_comments(comments),
+        comments_after_colon: into_owned_comments(comments_after_colon),
+      },
+      MemberKey::Value {
+        value,
+        span,
+        comments,
+        comments_after_colon,
+      } => MemberKey::Value {
+        value: value.into_owned(),
+        span,
+        comments: into_owned_comments(comments),
+        comments_after_colon: into_owned_comments(comments_after_colon),
+      },
+      MemberKey::NonMemberKey {
+        non_member_key,
+        comments_before_type_or_group,
+        comments_after_type_or_group,
+      } => MemberKey::NonMemberKey {
+        non_member_key: match non_member_key {
+          NonMemberKey::Group(g) => NonMemberKey::Group(g.into_owned()),
+          NonMemberKey::Type(t) => NonMemberKey::Type(t.into_owned()),
+        },
+        comments_before_type_or_group: into_owned_comments(comments_before_type_or_group),
+        comments_after_type_or_group: into_owned_comments(comments_after_type_or_group),
+      },
+    }
+  }
+}
+
 #[cfg(test)]
 #[allow(unused_imports)]
 mod tests {
@@ -2320,6 +3009,20 @@ mod tests {
     )
   }
 
+  #[test]
+  fn verify_float_output() {
+    for (value, expected) in [(1000.0, "1000.0"), (1.5e-7, "1.5e-7"), (-0.25, "-0.25")].iter() {
+      assert_eq!(
+        Type2::FloatValue {
+          value: *value,
+          span: (0, 0, 0),
+        }
+        .to_string(),
+        expected.to_string()
+      )
+    }
+  }
+
   #[test]
   fn verify_group_output() {
     assert_eq!(
@@ -2408,4 +3111,42 @@ mod tests {
       " key1: \"value1\", key2: \"value2\", ".to_string()
     )
   }
+
+  #[test]
+  #[cfg(feature = "std")]
+  fn verify_head_number_output() {
+    use crate::parser::cddl_from_str;
+
+    let input = "a = #6.<1000..1999>(tstr)\n\nb = #6.32(tstr) / #1.2\n\nc = #3.<lengths> / #7\n\nlengths = 1 / 2\n";
+
+    let mut lexer = crate::lexer_from_str(input);
+    let cddl = cddl_from_str(&mut lexer, input, false).unwrap();
+
+    assert_eq!(cddl.to_string(), input);
+  }
+
+  #[test]
+  #[cfg(all(feature = "ast-serde", feature = "std"))]
+  fn verify_serde_roundtrip() {
+    use crate::parser::cddl_from_str;
+
+    let input = r#"; Device description
+device = {
+  id: bstr .size 16, ; Unique identifier
+  ? name: tstr,
+  * $$device-extension,
+}
+$$device-extension //= (location: [lat: float, lon: float])
+"#;
+
+    let mut lexer = crate::lexer_from_str(input);
+    let cddl = cddl_from_str(&mut lexer, input, false).unwrap();
+
+    let json = serde_json::to_string(&cddl).unwrap();
+    assert!(json.contains(r#""span":["#));
+
+    let deserialized: CDDL<'static> = serde_json::from_str(&json).unwrap();
+    assert_eq!(deserialized, cddl);
+    assert_eq!(deserialized.to_string(), cddl.to_string());
+  }
 }
@@ -1,7 +1,11 @@
 #[macro_use]
 extern crate clap;
 
-use cddl::{cddl_from_str, lexer_from_str, validate_json_from_str};
+use cddl::{
+  cddl_from_str,
+  formatter::{format_cddl, CommaStyle, FormatOptions},
+  lexer_from_str, validate_json_from_str,
+};
 use clap::{App, AppSettings, SubCommand};
 use codespan_reporting::term::termcolor::{
   Color, ColorChoice, ColorSpec, StandardStream, WriteColor,
@@ -23,7 +27,15 @@ fn main() -> Result<(), Box<dyn Error>> {
                     .subcommand(SubCommand::with_name("validate")
                                 .about("validate JSON against CDDL definition")
                                 .arg_from_usage("-c --cddl=<FILE> 'CDDL input file'")
-                                .arg_from_usage("-j --json=<FILE> 'JSON input file"));
+                                .arg_from_usage("-j --json=<FILE> 'JSON input file"))
+                    .subcommand(SubCommand::with_name("fmt")
+                                .about("formats CDDL in place")
+                                .arg_from_usage("-c --cddl=<FILE> 'CDDL input file'")
+                                .arg_from_usage("--check 'Exits with an error if the file is not formatted instead of rewriting it'")
+                                .arg_from_usage("--indent=[WIDTH] 'Number of spaces per level of indentation'")
+                                .arg_from_usage("--max-width=[WIDTH] 'Maximum line width'")
+                                .arg_from_usage("--leading-commas 'Places commas at the start of group entries split across lines'")
+                                .arg_from_usage("--no-align 'Disables aligning member keys and comments'"));
 
   let matches = app.get_matches();
 
@@ -46,6 +58,52 @@ fn main() -> Result<(), Box<dyn Error>> {
     }
   }
 
+  if let Some(matches) = matches.subcommand_matches("fmt") {
+    if let Some(c) = matches.value_of("cddl") {
+      let mut options = FormatOptions::default();
+
+      if let Some(indent) = matches.value_of("indent") {
+        options.indent_width = indent.parse()?;
+      }
+
+      if let Some(max_width) = matches.value_of("max-width") {
+        options.max_width = max_width.parse()?;
+      }
+
+      if matches.is_present("leading-commas") {
+        options.comma_style = CommaStyle::Leading;
+      }
+
+      if matches.is_present("no-align") {
+        options.align_member_keys = false;
+        options.align_comments = false;
+      }
+
+      let file_content = fs::read_to_string(c)?;
+      let formatted = format_cddl(&file_content, &options)?;
+
+      if !matches.is_present("check") {
+        if formatted != file_content {
+          fs::write(c, formatted)?;
+        }
+
+        return Ok(());
+      }
+
+      if formatted != file_content {
+        let mut stderr = StandardStream::stderr(ColorChoice::Auto);
+        stderr.set_color(ColorSpec::new().set_fg(Some(Color::Red)))?;
+        writeln!(&mut stderr, "{} is not formatted", c)?;
+
+        std::process::exit(1);
+      }
+
+      let mut stdout = StandardStream::stdout(ColorChoice::Auto);
+      stdout.set_color(ColorSpec::new().set_fg(Some(Color::Green)))?;
+      writeln!(&mut stdout, "{} is formatted", c)?;
+    }
+  }
+
   if let Some(matches) = matches.subcommand_matches("compile-json") {
     if let Some(c) = matches.value_of("json") {
       let file = std::fs::File::open(c)?;
@@ -7,10 +7,13 @@ use serde::Serialize;
 use alloc::string::String;
 
 #[cfg_attr(target_arch = "wasm32", derive(Serialize))]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ErrorMsg {
-  short: String,
-  extended: Option<String>,
+  /// Short error message
+  pub short: String,
+  /// Optional extended error message with further details on how to resolve
+  /// the error
+  pub extended: Option<String>,
 }
 
 impl fmt::Display for ErrorMsg {
@@ -19,7 +22,27 @@ impl fmt::Display for ErrorMsg {
   }
 }
 
-#[derive(Debug, Copy, Clone)]
+/// Severity of an error
+#[cfg_attr(target_arch = "wasm32", derive(Serialize))]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Severity {
+  /// Error which prevents the CDDL document from being used
+  Error,
+  /// Valid CDDL which is likely unintended, e.g. unreachable rules
+  Warning,
+}
+
+impl fmt::Display for Severity {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> fmt::Result {
+    match self {
+      Severity::Error => write!(f, "error"),
+      Severity::Warning => write!(f, "warning"),
+    }
+  }
+}
+
+#[cfg_attr(target_arch = "wasm32", derive(Serialize))]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum MsgType {
   // Parser
   DuplicateRuleIdentifier,
@@ -43,6 +66,16 @@ pub enum MsgType {
   IncompleteRuleEntry,
   TypeSocketNamesMustBeTypeAugmentations,
   GroupSocketNamesMustBeGroupAugmentations,
+  AmbiguousTypeOrGroupRule,
+  ConflictingTypeAndGroupRule,
+
+  // Semantic analysis
+  UndefinedIdentifier,
+  GenericArgumentCountMismatch,
+  NonProductiveRule,
+  UnusedGenericParameter,
+  UnreachableRule,
+  UnpluggedSocket,
 
   // Lexer
   UnableToAdvanceToken,
@@ -53,6 +86,20 @@ pub enum MsgType {
   EmptyTextStringLiteral,
   InvalidByteStringLiteralCharacter,
   EmptyByteStringLiteral,
+  InvalidHexFloat,
+  InvalidLiteral,
+}
+
+impl MsgType {
+  /// Severity of the error
+  pub fn severity(self) -> Severity {
+    match self {
+      MsgType::UnusedGenericParameter | MsgType::UnreachableRule | MsgType::UnpluggedSocket => {
+        Severity::Warning
+      }
+      _ => Severity::Error,
+    }
+  }
 }
 
 impl From<MsgType> for ErrorMsg {
@@ -60,71 +107,71 @@ impl From<MsgType> for ErrorMsg {
     match mt {
       MsgType::DuplicateRuleIdentifier => ErrorMsg {
         short: "rule with the same identifier is already defined".into(),
-        extended: None,
+        extended: Some("use '/=' or '//=' to add type or group choices to an existing rule".into()),
       },
       MsgType::InvalidRuleIdentifier => ErrorMsg {
         short: "expected rule identifier followed by an assignment token '=', '/=' or '//='".into(),
-        extended: None,
+        extended: Some("rule names start with a letter, '@', '_' or '$' and continue with letters, digits, '@', '_', '$', or '-' and '.' between them, e.g. 'my-rule = tstr'".into()),
       },
       MsgType::MissingAssignmentToken => ErrorMsg {
         short: "expected assignment token '=', '/=' or '//=' after rule identifier".into(),
-        extended: None,
+        extended: Some("use '=' to define a rule, '/=' to add type choices to it or '//=' to add group choices to it".into()),
       },
       MsgType::InvalidGenericSyntax => ErrorMsg {
         short: "generic parameters should be between angle brackets '<' and '>' and separated by a comma ','".into(),
-        extended: None,
+        extended: Some("declare generic parameters after the rule name, e.g. 'message<t, v> = { type: t, value: v }', and pass arguments after a reference to it, e.g. 'message<\"reboot\", uint>'".into()),
       },
       MsgType::MissingGenericClosingDelimiter => ErrorMsg {
         short: "missing closing '>'".into(),
-        extended: None,
+        extended: Some("close generic parameters and arguments with '>', e.g. 'pair<tstr, int>'".into()),
       },
       MsgType::InvalidGenericIdentifier => ErrorMsg {
         short: "generic parameters must be named identifiers".into(),
-        extended: None,
+        extended: Some("generic parameters are names that arguments are substituted for, e.g. 'pair<k, v> = [k, v]'. literals can only be given as arguments".into()),
       },
       MsgType::InvalidUnwrapSyntax => ErrorMsg {
         short: "invalid unwrap syntax".into(),
-        extended: None,
+        extended: Some("the unwrap operator '~' must be followed by the name of a rule defining a map, an array or tagged data, e.g. '~header'".into()),
       },
       MsgType::InvalidGroupToChoiceEnumSyntax => ErrorMsg {
         short: "invalid group to choice enumeration syntax".into(),
-        extended: None,
+        extended: Some("the '&' operator must be followed by a group in parentheses or the name of a group rule, e.g. '&(red: 1, green: 2)' or '&colors'".into()),
       },
       MsgType::InvalidTagSyntax => ErrorMsg {
         short: "invalid tag syntax".into(),
-        extended: None,
+        extended: Some("tagged data is written '#6.<tag>(<type>)', e.g. '#6.32(tstr)', and major types as '#<major>' or '#<major>.<info>', e.g. '#0' or '#7.25'".into()),
       },
       MsgType::MissingGroupEntryMemberKey => ErrorMsg {
         short: "missing group entry member key".into(),
-        extended: None,
+        extended: Some("member keys are followed by ':' or '=>', e.g. 'name: tstr' or 'tstr => int'".into()),
       },
       MsgType::MissingGroupEntry => ErrorMsg {
         short: "missing group entry".into(),
-        extended: None,
+        extended: Some("an entry is expected after an occurrence indicator or member key, e.g. '? name: tstr'".into()),
       },
       MsgType::InvalidGroupEntrySyntax => ErrorMsg {
         short: "invalid group entry syntax".into(),
-        extended: None,
+        extended: Some("group entries are a type, optionally preceded by an occurrence indicator and a member key, e.g. '* tstr => int', the name of a group or a group in parentheses. separate entries with ','".into()),
       },
       MsgType::MissingClosingDelimiter => ErrorMsg {
         short: "missing closing delimiter".into(),
-        extended: None,
+        extended: Some("every '{', '[' and '(' must be closed by a matching '}', ']' or ')'. check for an unclosed delimiter before this point".into()),
       },
       MsgType::MissingClosingParend => ErrorMsg {
         short: "missing closing parend ')'".into(),
-        extended: None,
+        extended: Some("every '(' must be closed by a matching ')', e.g. '(tstr / int)'".into()),
       },
       MsgType::InvalidMemberKeyArrowMapSyntax => ErrorMsg {
         short: "invalid memberkey. missing '=>'".into(),
-        extended: None,
+        extended: Some("member keys given as types must be followed by '=>', e.g. 'tstr => int'. ':' may only follow barewords and literal values".into()),
       },
       MsgType::InvalidMemberKeySyntax => ErrorMsg {
         short: "invalid memberkey. missing '=>' or ':'".into(),
-        extended: None,
+        extended: Some("use ':' after barewords and literal values, e.g. 'name: tstr', and '=>' after types, e.g. 'tstr => int'".into()),
       },
       MsgType::InvalidOccurrenceSyntax => ErrorMsg {
         short: "invalid occurrence indicator syntax".into(),
-        extended: None,
+        extended: Some("occurrence indicators are '?', '*', '+' or '<n>*<m>' with optional bounds, e.g. '1*3 tstr'".into()),
       },
       MsgType::UnableToAdvanceToken => ErrorMsg {
         short: "unable to advance to the next token".into(),
@@ -132,19 +179,19 @@ impl From<MsgType> for ErrorMsg {
       },
       MsgType::InvalidControlOperator => ErrorMsg {
         short: "invalid control operator".into(),
-        extended: None,
+        extended: Some("control operators follow a type and precede their controller, e.g. 'tstr .size 10'. operators other than those of RFC 8610, RFC 9165 and RFC 9741 must be registered as custom controls with the lexer".into()),
       },
       MsgType::InvalidCharacter => ErrorMsg {
         short: "invalid character".into(),
-        extended: None,
+        extended: Some("check for characters that are not part of the CDDL syntax, e.g. typographic quotes copied from a document".into()),
       },
       MsgType::InvalidEscapeCharacter => ErrorMsg {
         short: "invalid escape character".into(),
-        extended: None,
+        extended: Some("text strings support the escapes '\\\"', '\\\\', '\\/', '\\b', '\\f', '\\n', '\\r', '\\t' and '\\uXXXX'".into()),
       },
       MsgType::InvalidTextStringLiteralCharacter => ErrorMsg {
         short: "invalid character in text string literal. expected closing \"".into(),
-        extended: None,
+        extended: Some("close the text string with '\"' and escape quotes inside it as '\\\"'".into()),
       },
       MsgType::EmptyTextStringLiteral => ErrorMsg {
         short: "empty text string literal".into(),
@@ -152,27 +199,67 @@ impl From<MsgType> for ErrorMsg {
       },
       MsgType::InvalidByteStringLiteralCharacter => ErrorMsg {
         short: "invalid character in byte string literal. expected closing '".into(),
-        extended: None,
+        extended: Some("close the byte string with \"'\" and escape quotes inside it as \"\\'\". prefix it with 'h' or 'b64' for hex or base64 encoded bytes, e.g. h'0102'".into()),
       },
       MsgType::EmptyByteStringLiteral => ErrorMsg {
         short: "empty byte string literal".into(),
         extended: None,
       },
+      MsgType::InvalidHexFloat => ErrorMsg {
+        short: "invalid hexfloat literal. expected binary exponent, e.g. 0x1.8p1".into(),
+        extended: None,
+      },
+      MsgType::InvalidLiteral => ErrorMsg {
+        short: "unable to decode literal value".into(),
+        extended: None,
+      },
       MsgType::NoRulesDefined => ErrorMsg {
         short: "you must have at least one rule defined".into(),
-        extended: None,
+        extended: Some("a CDDL document consists of rules such as 'root = { name: tstr }'. the first rule is the root that data is validated against".into()),
       },
       MsgType::IncompleteRuleEntry => ErrorMsg {
         short: "missing rule entry after assignment".into(),
-        extended: None,
+        extended: Some("add a type or group after the assignment token, e.g. 'name = tstr'".into()),
       },
       MsgType::TypeSocketNamesMustBeTypeAugmentations => ErrorMsg {
         short: "all plugs for type socket names must be augmentations using '/='".into(),
-        extended: None,
+        extended: Some("names starting with '$' are type sockets, which are only extended with '/=', e.g. '$ext /= int'".into()),
       },
       MsgType::GroupSocketNamesMustBeGroupAugmentations => ErrorMsg {
         short: "all plugs for group socket names must be augmentations using '//='".into(),
-        extended: None,
+        extended: Some("names starting with '$$' are group sockets, which are only extended with '//=', e.g. '$$ext //= (name: tstr)'".into()),
+      },
+      MsgType::AmbiguousTypeOrGroupRule => ErrorMsg {
+        short: "unable to determine if rule is a type or group. it only refers to rules which refer back to it".into(),
+        extended: Some("define at least one rule of the cycle as a type, e.g. a map or an array, or as a group, e.g. with a member key".into()),
+      },
+      MsgType::ConflictingTypeAndGroupRule => ErrorMsg {
+        short: "rule with the same identifier is already defined as a different kind. a name cannot be both a type and a group".into(),
+        extended: Some("rename one of the rules, or extend the existing rule with '/=' if it is a type and '//=' if it is a group".into()),
+      },
+      MsgType::UndefinedIdentifier => ErrorMsg {
+        short: "identifier is not defined by any rule or the standard prelude".into(),
+        extended: Some("check the spelling or define a rule with this name. names are case-sensitive".into()),
+      },
+      MsgType::GenericArgumentCountMismatch => ErrorMsg {
+        short: "number of generic arguments does not match the number of generic parameters of the referenced rule".into(),
+        extended: Some("pass one argument for each generic parameter, e.g. 'pair<tstr, int>' for 'pair<k, v> = [k, v]'".into()),
+      },
+      MsgType::NonProductiveRule => ErrorMsg {
+        short: "rule can never be satisfied. every choice refers back to the rule without a base case".into(),
+        extended: Some("recursive rules must offer at least one choice that does not refer back to the rule, e.g. by making the recursive entry optional".into()),
+      },
+      MsgType::UnusedGenericParameter => ErrorMsg {
+        short: "generic parameter is never used".into(),
+        extended: Some("remove the parameter or refer to it in the definition of the rule".into()),
+      },
+      MsgType::UnreachableRule => ErrorMsg {
+        short: "rule is not reachable from the root rule".into(),
+        extended: Some("the rule is not referred to by the root rule, directly or through other rules. remove it or refer to it".into()),
+      },
+      MsgType::UnpluggedSocket => ErrorMsg {
+        short: "socket is never plugged. it will not match anything".into(),
+        extended: Some("a type socket with no plugs matches no values and a group socket with no plugs is an empty group. add a rule using '/=' or '//=' to extend it".into()),
       },
     }
   }
@@ -0,0 +1,1269 @@
+use super::{
+  ast::*,
+  error::MsgType::*,
+  lexer::{self, Lexer},
+  parser::{Error, Parser, ParserError, Result},
+  token::Token,
+};
+
+#[cfg(not(feature = "std"))]
+use alloc::{
+  string::{String, ToString},
+  vec::Vec,
+};
+
+/// Placement of the commas separating group entries which are split across
+/// lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommaStyle {
+  /// Each entry but the last is followed by a comma
+  Trailing,
+  /// Each entry but the first is preceded by a comma
+  Leading,
+}
+
+/// Options controlling the layout produced by `format_cddl()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatOptions {
+  /// Number of spaces per level of indentation
+  pub indent_width: usize,
+  /// Maximum line width. Groups and type choices which don't fit are split
+  /// across lines
+  pub max_width: usize,
+  /// Whether the types of consecutive group entries are aligned following
+  /// their member keys
+  pub align_member_keys: bool,
+  /// Whether consecutive comments trailing code at the same indentation are
+  /// aligned
+  pub align_comments: bool,
+  /// Placement of commas between group entries split across lines
+  pub comma_style: CommaStyle,
+}
+
+impl Default for FormatOptions {
+  fn default() -> Self {
+    FormatOptions {
+      indent_width: 2,
+      max_width: 80,
+      align_member_keys: true,
+      align_comments: true,
+      comma_style: CommaStyle::Trailing,
+    }
+  }
+}
+
+/// Formats CDDL according to the given options. Every comment is kept, and
+/// formatting already formatted CDDL leaves it unchanged. Rules only need to
+/// be syntactically valid, so definitions which rely on rules defined
+/// elsewhere, e.g. modules, can be formatted as well
+///
+/// # Arguments
+///
+/// * `input` - A string slice with the CDDL text input
+/// * `options` - Layout options
+///
+/// # Example
+///
+/// ```
+/// use cddl::formatter::{format_cddl, FormatOptions};
+///
+/// let input = "person={name:tstr,age:uint}  ; a person";
+/// let formatted = format_cddl(input, &FormatOptions::default()).unwrap();
+///
+/// assert_eq!(formatted, "person = { name: tstr, age: uint } ; a person\n");
+/// ```
+pub fn format_cddl(input: &str, options: &FormatOptions) -> Result<String> {
+  let mut lexer = Lexer::new(input);
+  let mut p = Parser::new(lexer.iter(), input)?;
+  let partial = p.parse_cddl_partial();
+
+  if !partial.skipped.is_empty() || partial.diagnostics.errors.iter().any(is_syntax_error) {
+    return Err(Error::DIAGNOSTICS(partial.diagnostics));
+  }
+
+  let (tokens, trailing_comments) = layout_tokens(input)?;
+
+  let mut printer = Printer::new(options, &tokens);
+  printer.cddl(&partial.cddl, &trailing_comments);
+  let output = printer.finish();
+
+  // The AST drives the layout while the tokens supply the text, so check that
+  // both stayed in step
+  if !is_equivalent(input, &output) {
+    return Err(Error::CDDL(
+      "formatting failed to preserve the tokens and comments of the input".into(),
+    ));
+  }
+
+  Ok(output)
+}
+
+// Errors which don't affect how rules are laid out. Such rules are still
+// formatted, since they may rely on rules defined in other files
+fn is_syntax_error(error: &ParserError) -> bool {
+  !matches!(
+    error.msg_type,
+    AmbiguousTypeOrGroupRule
+      | ConflictingTypeAndGroupRule
+      | UndefinedIdentifier
+      | GenericArgumentCountMismatch
+      | NonProductiveRule
+  )
+}
+
+// Comment along with whether a blank line precedes it
+#[derive(Debug)]
+struct Comment<'a> {
+  text: &'a str,
+  blank_before: bool,
+}
+
+// Token other than a comma or comment, along with its surrounding comments.
+// Commas are written according to the comma style instead, so any comments
+// around them move to the neighboring tokens
+#[derive(Debug)]
+struct LayoutToken<'a> {
+  text: &'a str,
+  // Comments on their own lines before the token
+  leading: Vec<Comment<'a>>,
+  // Comment following the token on the same line
+  trailing: Option<&'a str>,
+  // Whether a blank line precedes the token, following any leading comments
+  blank_before: bool,
+}
+
+impl<'a> LayoutToken<'a> {
+  // Whether a blank line precedes the token along with its leading comments
+  fn starts_after_blank_line(&self) -> bool {
+    self
+      .leading
+      .first()
+      .map(|c| c.blank_before)
+      .unwrap_or(self.blank_before)
+  }
+}
+
+// Splits the input into tokens with attached comments, along with the comments
+// which follow the last token on their own lines. The text of each token is
+// taken verbatim from the input
+fn layout_tokens(input: &str) -> Result<(Vec<LayoutToken<'_>>, Vec<Comment<'_>>)> {
+  let mut lexer = Lexer::new(input);
+  let mut items = Vec::new();
+
+  for item in lexer.iter() {
+    let (position, token) = item.map_err(Error::LEXER)?;
+
+    match token {
+      Token::EOF => break,
+      Token::NEWLINE => continue,
+      _ => items.push((position.range.0, token_kind(&token))),
+    }
+  }
+
+  let mut tokens: Vec<LayoutToken> = Vec::new();
+  let mut leading = Vec::new();
+  let mut previous_end = None;
+
+  for (idx, (start, kind)) in items.iter().enumerate() {
+    // Token positions don't always cover the full token, so each token is
+    // taken to extend up to the next one
+    let end = items
+      .get(idx + 1)
+      .map(|i| i.0)
+      .unwrap_or_else(|| input.len());
+    let text = input[*start..end].trim_end();
+
+    let newlines = previous_end.map(|pe| input[pe..*start].matches('\n').count());
+    let blank_before = matches!(newlines, Some(n) if n > 1);
+    previous_end = Some(*start + text.len());
+
+    match kind {
+      TokenKind::Comma => (),
+      TokenKind::Comment => match tokens.last_mut() {
+        Some(t) if newlines == Some(0) && leading.is_empty() && t.trailing.is_none() => {
+          t.trailing = Some(text)
+        }
+        _ => leading.push(Comment { text, blank_before }),
+      },
+      TokenKind::Other => tokens.push(LayoutToken {
+        text,
+        leading: core::mem::take(&mut leading),
+        trailing: None,
+        blank_before,
+      }),
+    }
+  }
+
+  Ok((tokens, leading))
+}
+
+enum TokenKind {
+  Comma,
+  Comment,
+  Other,
+}
+
+fn token_kind(token: &Token) -> TokenKind {
+  match token {
+    Token::COMMA => TokenKind::Comma,
+    Token::COMMENT(_) => TokenKind::Comment,
+    _ => TokenKind::Other,
+  }
+}
+
+// Whether both inputs consist of the same tokens and comments, disregarding
+// whitespace and the commas separating group entries
+fn is_equivalent(a: &str, b: &str) -> bool {
+  let is_significant =
+    |item: &lexer::Item| !matches!(item, Ok((_, Token::COMMA)) | Ok((_, Token::NEWLINE)));
+
+  let mut lexer_a = Lexer::new(a);
+  let mut lexer_b = Lexer::new(b);
+  let mut tokens_a = lexer_a.iter().filter(is_significant);
+  let mut tokens_b = lexer_b.iter().filter(is_significant);
+
+  loop {
+    match (tokens_a.next(), tokens_b.next()) {
+      (Some(Ok((_, Token::EOF))), Some(Ok((_, Token::EOF)))) => return true,
+      (Some(Ok((_, Token::COMMENT(ca)))), Some(Ok((_, Token::COMMENT(cb)))))
+        if ca.trim_end() == cb.trim_end() => {}
+      (Some(Ok((_, ta))), Some(Ok((_, tb)))) if ta == tb => {}
+      _ => return false,
+    }
+  }
+}
+
+// Layout of a line of output, used to align member keys and comments once all
+// lines are written
+#[derive(Debug, Default, Clone, Copy)]
+struct Line {
+  // Indentation of the block the line belongs to
+  indent: usize,
+  // Offset of the end of the member key beginning the line
+  key_end: Option<usize>,
+  // Offset of the comment trailing the line
+  comment_start: Option<usize>,
+}
+
+// Writes the rules of the AST using the text and comments of the tokens they
+// were parsed from. Constructs are written on a single line when they fit and
+// don't contain any comments, otherwise they're split across lines
+struct Printer<'a, 'b> {
+  options: &'b FormatOptions,
+  tokens: &'b [LayoutToken<'a>],
+  // Index of the next token to write
+  cursor: usize,
+  // Index of the last token whose leading comments were written
+  leading_written: Option<usize>,
+  output: String,
+  // Offset of the current line in the output
+  line_start: usize,
+  // Column at which the output begins. Only non-zero when trying out a flat
+  // layout in the middle of a line
+  start_column: usize,
+  // Indentation of the current block
+  indent: usize,
+  lines: Vec<Line>,
+  // Comment trailing the last token written, written once the line ends
+  trailing_comment: Option<&'a str>,
+  // Whether everything must be written on the current line
+  is_flat: bool,
+  // Whether a flat layout turned out to be impossible
+  is_broken: bool,
+}
+
+impl<'a, 'b> Printer<'a, 'b> {
+  fn new(options: &'b FormatOptions, tokens: &'b [LayoutToken<'a>]) -> Self {
+    Printer {
+      options,
+      tokens,
+      cursor: 0,
+      leading_written: None,
+      output: String::new(),
+      line_start: 0,
+      start_column: 0,
+      indent: 0,
+      lines: vec![Line::default()],
+      trailing_comment: None,
+      is_flat: false,
+      is_broken: false,
+    }
+  }
+
+  fn finish(mut self) -> String {
+    if self.options.align_member_keys {
+      align_lines(&mut self.lines, &mut self.output, |l| &mut l.key_end);
+    }
+
+    if self.options.align_comments {
+      align_lines(&mut self.lines, &mut self.output, |l| &mut l.comment_start);
+    }
+
+    let mut output = self.output.trim_end().to_string();
+    output.push('\n');
+
+    output
+  }
+
+  fn column(&self) -> usize {
+    self.start_column + self.output[self.line_start..].chars().count()
+  }
+
+  fn is_at_line_start(&self) -> bool {
+    self.output[self.line_start..].trim_start().is_empty()
+  }
+
+  fn continuation_indent(&self) -> usize {
+    self.indent + self.options.indent_width
+  }
+
+  // Whether a blank line precedes the next token in the input
+  fn has_blank_before(&self) -> bool {
+    matches!(self.tokens.get(self.cursor), Some(t) if t.starts_after_blank_line())
+  }
+
+  // Ends the current line, after writing any trailing comment, and indents the
+  // next one. A line containing nothing but indentation is reused
+  fn line_break(&mut self, indent: usize, blank_line: bool) {
+    if self.is_flat {
+      self.is_broken = true;
+      return;
+    }
+
+    if let Some(comment) = self.trailing_comment.take() {
+      let code_end = self.output.trim_end().len();
+      self.output.truncate(code_end);
+
+      if let Some(line) = self.lines.last_mut() {
+        line.comment_start = Some(code_end - self.line_start);
+      }
+
+      self.output.push(' ');
+      self.output.push_str(comment);
+    }
+
+    if self.is_at_line_start() {
+      self.output.truncate(self.line_start);
+
+      if blank_line && self.line_start > 0 && !self.output.ends_with("\n\n") {
+        self.new_line();
+      }
+    } else {
+      let code_end = self.output.trim_end().len();
+      self.output.truncate(code_end);
+      self.new_line();
+
+      if blank_line {
+        self.new_line();
+      }
+    }
+
+    if let Some(line) = self.lines.last_mut() {
+      line.indent = indent;
+    }
+
+    for _ in 0..indent {
+      self.output.push(' ');
+    }
+  }
+
+  fn new_line(&mut self) {
+    self.output.push('\n');
+    self.line_start = self.output.len();
+    self.lines.push(Line::default());
+  }
+
+  // Only byte string literals span several lines, which are written as they
+  // are
+  fn write(&mut self, text: &str) {
+    let mut lines = text.split('\n');
+
+    if let Some(first) = lines.next() {
+      self.output.push_str(first);
+    }
+
+    for line in lines {
+      if self.is_flat {
+        self.is_broken = true;
+      }
+
+      self.new_line();
+      self.output.push_str(line);
+    }
+  }
+
+  fn space(&mut self) {
+    if self.trailing_comment.is_some() {
+      self.line_break(self.continuation_indent(), false);
+    } else {
+      self.output.push(' ');
+    }
+  }
+
+  // Writes the comments preceding the next token on their own lines, unless
+  // they were already written
+  fn leading_comments(&mut self) {
+    let tokens = self.tokens;
+
+    let token = match tokens.get(self.cursor) {
+      Some(t) if !t.leading.is_empty() && self.leading_written != Some(self.cursor) => t,
+      _ => return,
+    };
+
+    if self.is_flat {
+      self.is_broken = true;
+      return;
+    }
+
+    if !self.is_at_line_start() {
+      self.line_break(self.continuation_indent(), false);
+    }
+
+    let indent = self.lines.last().map(|l| l.indent).unwrap_or_default();
+
+    for (idx, comment) in token.leading.iter().enumerate() {
+      if idx > 0 {
+        self.line_break(indent, comment.blank_before);
+      }
+
+      self.write(comment.text);
+    }
+
+    self.line_break(indent, token.blank_before);
+    self.leading_written = Some(self.cursor);
+  }
+
+  fn token(&mut self) {
+    self.leading_comments();
+
+    if self.trailing_comment.is_some() {
+      self.line_break(self.continuation_indent(), false);
+    }
+
+    let tokens = self.tokens;
+
+    if let Some(token) = tokens.get(self.cursor) {
+      self.write(token.text);
+      self.trailing_comment = token.trailing;
+      self.cursor += 1;
+    }
+  }
+
+  // Writes output using `f` if it fits on the current line, leaving `reserved`
+  // columns to spare, and doesn't require any line breaks. Returns whether it
+  // did
+  fn try_flat(&mut self, reserved: usize, f: impl FnOnce(&mut Self)) -> bool {
+    if self.is_flat {
+      f(self);
+      return !self.is_broken;
+    }
+
+    let mut p = Printer {
+      options: self.options,
+      tokens: self.tokens,
+      cursor: self.cursor,
+      leading_written: self.leading_written,
+      output: String::new(),
+      line_start: 0,
+      start_column: self.column(),
+      indent: self.indent,
+      lines: vec![Line::default()],
+      trailing_comment: self.trailing_comment,
+      is_flat: true,
+      is_broken: false,
+    };
+
+    f(&mut p);
+
+    if p.is_broken || p.column() + reserved > self.options.max_width {
+      return false;
+    }
+
+    self.output.push_str(&p.output);
+    self.cursor = p.cursor;
+    self.leading_written = p.leading_written;
+    self.trailing_comment = p.trailing_comment;
+
+    true
+  }
+
+  fn cddl(&mut self, cddl: &CDDL, trailing_comments: &[Comment]) {
+    for (idx, rule) in cddl.rules.iter().enumerate() {
+      if idx > 0 {
+        let blank_line = self.has_blank_before();
+        self.line_break(0, blank_line);
+      }
+
+      self.leading_comments();
+      self.rule(rule);
+    }
+
+    for comment in trailing_comments {
+      self.line_break(0, comment.blank_before);
+      self.write(comment.text);
+    }
+
+    self.line_break(0, false);
+  }
+
+  fn rule(&mut self, rule: &Rule) {
+    match rule {
+      Rule::Type { rule, .. } => {
+        self.token();
+
+        if let Some(gp) = &rule.generic_params {
+          self.generic_params(gp);
+        }
+
+        self.space();
+        self.token();
+        self.space();
+        self.type_(&rule.value);
+      }
+      Rule::Group { rule, .. } => {
+        self.token();
+
+        if let Some(gp) = &rule.generic_params {
+          self.generic_params(gp);
+        }
+
+        self.space();
+        self.token();
+        self.space();
+        self.group_entry(&rule.entry, false);
+      }
+    }
+  }
+
+  fn generic_params(&mut self, gp: &GenericParams) {
+    self.token();
+
+    for idx in 0..gp.params.len() {
+      if idx > 0 {
+        self.write(",");
+        self.space();
+      }
+
+      self.token();
+    }
+
+    self.token();
+  }
+
+  fn generic_args(&mut self, ga: &GenericArgs) {
+    self.token();
+
+    for (idx, arg) in ga.args.iter().enumerate() {
+      if idx > 0 {
+        self.write(",");
+        self.space();
+      }
+
+      self.type1(&arg.arg);
+    }
+
+    self.token();
+  }
+
+  // Type choices which don't fit on a single line are each written on their
+  // own line
+  fn type_(&mut self, t: &Type) {
+    let (first, rest) = match t.type_choices.split_first() {
+      Some(choices) => choices,
+      None => return,
+    };
+
+    if rest.is_empty() {
+      return self.type1(&first.type1);
+    }
+
+    let is_flat = self.try_flat(0, |p| {
+      p.type1(&first.type1);
+
+      for tc in rest {
+        p.space();
+        p.token();
+        p.space();
+        p.type1(&tc.type1);
+      }
+    });
+
+    if is_flat || self.is_flat {
+      return;
+    }
+
+    self.type1(&first.type1);
+
+    let indent = self.indent;
+    self.indent = self.continuation_indent();
+
+    for tc in rest {
+      let blank_line = self.has_blank_before();
+      self.line_break(self.indent, blank_line);
+      self.leading_comments();
+      self.token();
+      self.space();
+      self.type1(&tc.type1);
+    }
+
+    self.indent = indent;
+  }
+
+  fn type1(&mut self, t1: &Type1) {
+    let is_integer_range = matches!(&t1.operator, Some(Operator {
+      operator: RangeCtlOp::RangeOp { .. },
+      type2,
+      ..
+    }) if is_integer(&t1.type2) && is_integer(type2) && self.is_decimal(0) && self.is_decimal(2));
+
+    self.type2(&t1.type2);
+
+    if let Some(o) = &t1.operator {
+      // Integer ranges are written without spaces, as in `0..10`
+      if is_integer_range {
+        self.token();
+      } else {
+        self.space();
+        self.token();
+        self.space();
+      }
+
+      self.type2(&o.type2);
+    }
+  }
+
+  // Whether the token `offset` tokens ahead is a plain decimal integer
+  fn is_decimal(&self, offset: usize) -> bool {
+    matches!(self.tokens.get(self.cursor + offset), Some(t)
+      if t.text.trim_start_matches('-').bytes().all(|b| b.is_ascii_digit()))
+  }
+
+  fn type2(&mut self, t2: &Type2) {
+    match t2 {
+      Type2::Typename { generic_args, .. } => {
+        self.token();
+
+        if let Some(ga) = generic_args {
+          self.generic_args(ga);
+        }
+      }
+      Type2::Unwrap { generic_args, .. } | Type2::ChoiceFromGroup { generic_args, .. } => {
+        self.token();
+        self.token();
+
+        if let Some(ga) = generic_args {
+          self.generic_args(ga);
+        }
+      }
+      Type2::ParenthesizedType { pt, .. } => {
+        self.token();
+        self.type_(pt);
+        self.token();
+      }
+      Type2::TaggedData { tag, t, .. } => {
+        self.token();
+        if let Some(HeadNumber::Type(tag)) = tag {
+          self.head_number_type(tag);
+        }
+        self.token();
+        self.type_(t);
+        self.token();
+      }
+      Type2::DataMajorType {
+        constraint: Some(HeadNumber::Type(constraint)),
+        ..
+      } => {
+        self.token();
+        self.head_number_type(constraint);
+      }
+      Type2::Map { group, .. } | Type2::Array { group, .. } => self.group_block(group),
+      Type2::ChoiceFromInlineGroup { group, .. } => {
+        self.token();
+        self.group_block(group);
+      }
+      _ => self.token(),
+    }
+  }
+
+  // Writes the type given between '<' and '>' for a tag number or major type
+  // argument
+  fn head_number_type(&mut self, t: &Type) {
+    self.token();
+    self.type_(t);
+    self.token();
+  }
+
+  // Writes a group along with its enclosing delimiters. Groups which don't fit
+  // on a single line are written with each group entry on its own line, while
+  // group choices are separated by lines beginning with `//`
+  fn group_block(&mut self, group: &Group) {
+    let is_empty = group
+      .group_choices
+      .iter()
+      .all(|gc| gc.group_entries.is_empty());
+
+    if is_empty && group.group_choices.len() <= 1 {
+      self.token();
+      self.token();
+
+      return;
+    }
+
+    let is_flat = self.try_flat(0, |p| {
+      p.token();
+      p.space();
+      p.group(group);
+      p.space();
+      p.token();
+    });
+
+    if is_flat || self.is_flat {
+      return;
+    }
+
+    self.token();
+
+    let indent = self.indent;
+    self.indent = self.continuation_indent();
+
+    let has_group_choices = group.group_choices.len() > 1;
+
+    for (idx, gc) in group.group_choices.iter().enumerate() {
+      if idx > 0 {
+        let blank_line = self.has_blank_before();
+        self.line_break(self.indent, blank_line);
+        self.leading_comments();
+        self.token();
+
+        if gc.group_entries.is_empty()
+          || self.try_flat(0, |p| {
+            p.space();
+            p.group_choice(gc);
+          })
+        {
+          continue;
+        }
+      } else if has_group_choices {
+        self.line_break(self.indent, false);
+        self.leading_comments();
+
+        if self.try_flat(0, |p| p.group_choice(gc)) {
+          continue;
+        }
+      }
+
+      self.group_entries(gc);
+    }
+
+    self.indent = indent;
+    self.line_break(indent, false);
+    self.token();
+  }
+
+  fn group(&mut self, group: &Group) {
+    let mut needs_space = false;
+
+    for (idx, gc) in group.group_choices.iter().enumerate() {
+      if idx > 0 {
+        if needs_space {
+          self.space();
+        }
+
+        self.token();
+        needs_space = true;
+      }
+
+      if !gc.group_entries.is_empty() {
+        if needs_space {
+          self.space();
+        }
+
+        self.group_choice(gc);
+        needs_space = true;
+      }
+    }
+  }
+
+  fn group_choice(&mut self, gc: &GroupChoice) {
+    for (idx, (entry, _)) in gc.group_entries.iter().enumerate() {
+      if idx > 0 {
+        self.write(",");
+        self.space();
+      }
+
+      self.group_entry(entry, false);
+    }
+  }
+
+  // Writes each group entry on its own line
+  fn group_entries(&mut self, gc: &GroupChoice) {
+    let is_leading_comma = self.options.comma_style == CommaStyle::Leading;
+
+    for (idx, (entry, _)) in gc.group_entries.iter().enumerate() {
+      let blank_line = idx > 0 && self.has_blank_before();
+      self.line_break(self.indent, blank_line);
+      self.leading_comments();
+
+      if is_leading_comma {
+        self.write(if idx == 0 { "  " } else { ", " });
+      }
+
+      self.group_entry(entry, true);
+
+      if !is_leading_comma && idx + 1 < gc.group_entries.len() {
+        self.write(",");
+      }
+    }
+  }
+
+  fn group_entry(&mut self, entry: &GroupEntry, is_line_start: bool) {
+    match entry {
+      GroupEntry::ValueMemberKey { ge, .. } => {
+        if let Some(o) = &ge.occur {
+          self.occurrence(o);
+          self.space();
+        }
+
+        let parens = self.unparsed_parens(vmke_leading_parens(ge));
+
+        for _ in 0..parens {
+          self.token();
+        }
+
+        if let Some(mk) = &ge.member_key {
+          let line_count = self.lines.len();
+
+          self.member_key(mk);
+
+          if is_line_start && parens == 0 && line_count == self.lines.len() {
+            self.key_end();
+          }
+
+          self.space();
+        }
+
+        self.type_(&ge.entry_type);
+
+        for _ in 0..parens {
+          self.token();
+        }
+      }
+      GroupEntry::TypeGroupname { ge, .. } => {
+        if let Some(o) = &ge.occur {
+          self.occurrence(o);
+          self.space();
+        }
+
+        let parens = self.unparsed_parens(0);
+
+        for _ in 0..parens {
+          self.token();
+        }
+
+        self.token();
+
+        if let Some(ga) = &ge.generic_args {
+          self.generic_args(ga);
+        }
+
+        for _ in 0..parens {
+          self.token();
+        }
+      }
+      GroupEntry::InlineGroup { occur, group, .. } => {
+        if let Some(o) = occur {
+          self.occurrence(o);
+          self.space();
+        }
+
+        self.group_block(group);
+      }
+    }
+  }
+
+  // Number of parentheses enclosing the next group entry which aren't part of
+  // the AST. The parser doesn't keep those around types in group entries, e.g.
+  // `( tstr )`
+  fn unparsed_parens(&self, ast_parens: usize) -> usize {
+    let parens = self.tokens[self.cursor..]
+      .iter()
+      .take_while(|t| t.text == "(")
+      .count();
+
+    parens.saturating_sub(ast_parens)
+  }
+
+  fn key_end(&mut self) {
+    if self.is_flat {
+      return;
+    }
+
+    let key_end = self.output.len() - self.line_start;
+
+    if let Some(line) = self.lines.last_mut() {
+      line.key_end = Some(key_end);
+    }
+  }
+
+  fn occurrence(&mut self, o: &Occurrence) {
+    if let Occur::Exact { lower, upper, .. } = &o.occur {
+      if lower.is_some() {
+        self.token();
+      }
+
+      self.token();
+
+      if upper.is_some() {
+        self.token();
+      }
+    } else {
+      self.token();
+    }
+  }
+
+  fn member_key(&mut self, mk: &MemberKey) {
+    match mk {
+      MemberKey::Type1 { t1, is_cut, .. } => {
+        self.type1(t1);
+        self.space();
+
+        if *is_cut {
+          self.token();
+          self.space();
+        }
+
+        self.token();
+      }
+      MemberKey::Bareword { .. } | MemberKey::Value { .. } => {
+        self.token();
+        self.token();
+      }
+      MemberKey::NonMemberKey {
+        non_member_key: NonMemberKey::Group(group),
+        ..
+      } => self.group(group),
+      MemberKey::NonMemberKey {
+        non_member_key: NonMemberKey::Type(t),
+        ..
+      } => self.type_(t),
+    }
+  }
+}
+
+fn is_integer(t2: &Type2) -> bool {
+  matches!(t2, Type2::IntValue { .. } | Type2::UintValue { .. })
+}
+
+// Number of parentheses the AST of a value group entry begins with
+fn vmke_leading_parens(ge: &ValueMemberKeyEntry) -> usize {
+  match &ge.member_key {
+    Some(MemberKey::Type1 { t1, .. }) => type2_leading_parens(&t1.type2),
+    Some(_) => 0,
+    None => ge
+      .entry_type
+      .type_choices
+      .first()
+      .map(|tc| type2_leading_parens(&tc.type1.type2))
+      .unwrap_or_default(),
+  }
+}
+
+fn type2_leading_parens(t2: &Type2) -> usize {
+  match t2 {
+    Type2::ParenthesizedType { pt, .. } => {
+      1 + pt
+        .type_choices
+        .first()
+        .map(|tc| type2_leading_parens(&tc.type1.type2))
+        .unwrap_or_default()
+    }
+    _ => 0,
+  }
+}
+
+// Pads consecutive lines at the same indentation so that the offsets selected
+// by `offset` line up
+fn align_lines(
+  lines: &mut [Line],
+  output: &mut String,
+  offset: fn(&mut Line) -> &mut Option<usize>,
+) {
+  let mut text = output.split('\n').map(String::from).collect::<Vec<_>>();
+
+  let mut idx = 0;
+  while idx < lines.len() {
+    let indent = lines[idx].indent;
+    let run_end = (idx..lines.len())
+      .find(|i| offset(&mut lines[*i]).is_none() || lines[*i].indent != indent)
+      .unwrap_or(lines.len());
+
+    if run_end == idx {
+      idx += 1;
+      continue;
+    }
+
+    let width = |text: &[String], lines: &mut [Line], i: usize| {
+      offset(&mut lines[i]).map(|o| text[i][..o].chars().count())
+    };
+
+    let max_width = (idx..run_end)
+      .filter_map(|i| width(&text, lines, i))
+      .max()
+      .unwrap_or_default();
+
+    for i in idx..run_end {
+      let (at, pad) = match (*offset(&mut lines[i]), width(&text, lines, i)) {
+        (Some(at), Some(w)) => (at, max_width - w),
+        _ => continue,
+      };
+
+      text[i].insert_str(at, &" ".repeat(pad));
+
+      // Later offsets in the line move along with the padding
+      let line = &mut lines[i];
+      for o in line.key_end.iter_mut().chain(line.comment_start.iter_mut()) {
+        if *o > at {
+          *o += pad;
+        }
+      }
+    }
+
+    idx = run_end;
+  }
+
+  *output = text.join("\n");
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use indoc::indoc;
+  use pretty_assertions::assert_eq;
+
+  fn format(input: &str) -> String {
+    format_with(input, &FormatOptions::default())
+  }
+
+  fn format_with(input: &str, options: &FormatOptions) -> String {
+    let formatted = format_cddl(input, options).unwrap();
+    assert_eq!(format_cddl(&formatted, options).unwrap(), formatted);
+
+    formatted
+  }
+
+  #[test]
+  fn verify_default_layout() {
+    let input = indoc!(
+      r#"
+        reputon={rater:text,rating:float16,?conf:float16,
+        * text=>any}
+        header=(0..10,[*uint])
+        range=(1..max)
+        "#
+    );
+
+    assert_eq!(
+      format(input),
+      indoc!(
+        r#"
+          reputon = { rater: text, rating: float16, ? conf: float16, * text => any }
+          header = ( 0..10, [ * uint ] )
+          range = (1 .. max)
+        "#
+      )
+    );
+  }
+
+  #[test]
+  fn verify_groups_exceeding_max_width_are_split() {
+    let input = indoc!(
+      r#"
+        message = { id: uint, sender: tstr, recipients: [ + tstr ], body: tstr / bstr }
+
+        color = "red" / "green" / "blue"
+        "#
+    );
+
+    let options = FormatOptions {
+      max_width: 30,
+      ..Default::default()
+    };
+
+    assert_eq!(
+      format_with(input, &options),
+      indoc!(
+        r#"
+          message = {
+            id:         uint,
+            sender:     tstr,
+            recipients: [ + tstr ],
+            body:       tstr / bstr
+          }
+
+          color = "red"
+            / "green"
+            / "blue"
+        "#
+      )
+    );
+  }
+
+  #[test]
+  fn verify_leading_commas() {
+    let input = "message = { id: uint, sender: tstr, body: tstr }";
+
+    let options = FormatOptions {
+      max_width: 20,
+      comma_style: CommaStyle::Leading,
+      ..Default::default()
+    };
+
+    assert_eq!(
+      format_with(input, &options),
+      indoc!(
+        r#"
+          message = {
+              id:     uint
+            , sender: tstr
+            , body:   tstr
+          }
+        "#
+      )
+    );
+  }
+
+  #[test]
+  fn verify_alignment_can_be_disabled() {
+    let input = indoc!(
+      r#"
+        header = (
+          id: uint, ; identifier
+          timestamp: tdate, ; creation time
+        )
+        "#
+    );
+
+    assert_eq!(
+      format(input),
+      indoc!(
+        r#"
+          header = (
+            id:        uint, ; identifier
+            timestamp: tdate ; creation time
+          )
+        "#
+      )
+    );
+
+    let options = FormatOptions {
+      align_member_keys: false,
+      align_comments: false,
+      ..Default::default()
+    };
+
+    assert_eq!(
+      format_with(input, &options),
+      indoc!(
+        r#"
+          header = (
+            id: uint, ; identifier
+            timestamp: tdate ; creation time
+          )
+        "#
+      )
+    );
+  }
+
+  #[test]
+  fn verify_comments_are_preserved() {
+    let input = indoc!(
+      r#"
+        ; leading comment
+
+        ; rule comment
+        rule = [ ; opening
+          ; entry comment
+          ( ; nested
+            a: int
+          ),
+          b: ; after key
+            tstr
+        ] ; trailing
+
+        ; final comment
+        "#
+    );
+
+    assert_eq!(
+      format(input),
+      indoc!(
+        r#"
+          ; leading comment
+
+          ; rule comment
+          rule = [ ; opening
+            ; entry comment
+            ( ; nested
+              a: int
+            ),
+            b: ; after key
+              tstr
+          ] ; trailing
+
+          ; final comment
+        "#
+      )
+    );
+  }
+
+  #[test]
+  fn verify_blank_lines_between_rules_are_kept() {
+    let input = "a = int\n\n\n\nb = tstr\nc = bstr\n";
+
+    assert_eq!(format(input), "a = int\n\nb = tstr\nc = bstr\n");
+  }
+
+  #[test]
+  fn verify_group_choices() {
+    let input = "address = [ 0, keyhash // 1, scripthash ; script\n // 2 ]";
+
+    assert_eq!(
+      format(input),
+      indoc!(
+        r#"
+          address = [
+            0, keyhash
+            // 1, scripthash ; script
+            // 2
+          ]
+        "#
+      )
+    );
+  }
+
+  #[test]
+  fn verify_multiline_byte_strings_are_kept() {
+    let input = "key = {   k: h'01 ; first\n    02', ; key\n  longer-key: 1, ; other\n}\n";
+
+    assert_eq!(
+      format(input),
+      indoc!(
+        r#"
+          key = {
+            k: h'01 ; first
+              02', ; key
+            longer-key: 1 ; other
+          }
+        "#
+      )
+    );
+  }
+
+  #[test]
+  fn verify_syntax_errors_are_reported() {
+    assert!(matches!(
+      format_cddl("rule = { a: }", &FormatOptions::default()),
+      Err(Error::DIAGNOSTICS(_))
+    ));
+  }
+}
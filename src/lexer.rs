@@ -62,9 +62,11 @@ impl Default for Position {
 /// Lexer error
 #[derive(Debug)]
 pub struct LexerError {
-  error_type: LexerErrorType,
+  /// Error type
+  pub error_type: LexerErrorType,
   input: String,
-  position: Position,
+  /// Error position
+  pub position: Position,
 }
 
 /// Various error types emitted by the lexer
@@ -256,6 +258,9 @@ pub struct Lexer<'a> {
   multipeek: itertools::MultiPeek<CharIndices<'a>>,
   /// Lexer position in input
   pub position: Position,
+  // Names of user-defined control operators accepted in addition to the
+  // standard ones
+  custom_controls: Vec<String>,
 }
 
 /// Iterator over a lexer
@@ -298,9 +303,29 @@ impl<'a> Lexer<'a> {
         range: (0, 0),
         index: 0,
       },
+      custom_controls: Vec::new(),
     }
   }
 
+  /// Accepts the given user-defined control operator names, including the
+  /// leading '.', which are otherwise rejected as invalid. They are lexed as
+  /// `Token::CUSTOMCONTROL`
+  ///
+  /// # Example
+  ///
+  /// ```
+  /// use cddl::{lexer::Lexer, token::Token};
+  ///
+  /// let mut l = Lexer::new("tstr .luhn 0").with_custom_controls(&[".luhn"]);
+  /// let tokens = l.iter().map(|t| t.unwrap().1).take(2).collect::<Vec<_>>();
+  ///
+  /// assert_eq!(tokens, vec![Token::TSTR, Token::CUSTOMCONTROL(".luhn")]);
+  /// ```
+  pub fn with_custom_controls<S: AsRef<str>>(mut self, names: &[S]) -> Self {
+    self.custom_controls = names.iter().map(|n| n.as_ref().to_string()).collect();
+    self
+  }
+
   /// Returns an iterator over a lexer
   pub fn iter(&'a mut self) -> LexerIter<'a> {
     LexerIter { l: self }
@@ -461,6 +486,14 @@ impl<'a> Lexer<'a> {
             match self.peek_char() {
               Some(&c) if c.1 == '.' => {
                 let _ = self.read_char()?;
+
+                // Tag number or argument given by a type
+                if let Some(&(_, '<')) = self.peek_char() {
+                  self.position.range = (token_offset, self.position.index + 1);
+
+                  return Ok((self.position, Token::TYPEDTAG(t as u8)));
+                }
+
                 let (idx, _) = self.read_char()?;
 
                 self.position.range = (token_offset, self.position.index + 1);
@@ -490,7 +523,7 @@ impl<'a> Lexer<'a> {
 
           Ok((
             self.position,
-            Token::VALUE(Value::BYTE(ByteValue::UTF8(bsv.as_bytes().into()))),
+            Token::VALUE(Value::BYTE(ByteValue::UTF8(bsv))),
           ))
         }
         (idx, '.') => {
@@ -514,8 +547,16 @@ impl<'a> Lexer<'a> {
               return Ok((self.position, Token::RANGEOP(true)));
             } else if is_ealpha(c.1) {
               // Controlop
-              let ctrlop =
-                token::lookup_control_from_str(&self.read_identifier(idx)?).ok_or_else(|| {
+              let ident = self.read_identifier(idx)?;
+              let ctrlop = token::lookup_control_from_str(ident)
+                .or_else(|| {
+                  if self.custom_controls.iter().any(|c| c == ident) {
+                    Some(Token::CUSTOMCONTROL(ident))
+                  } else {
+                    None
+                  }
+                })
+                .ok_or_else(|| {
                   self.position.range = (token_offset, self.position.index + 1);
 
                   LexerError::from((self.str_input, self.position, InvalidControlOperator))
@@ -539,8 +580,8 @@ impl<'a> Lexer<'a> {
                   let (idx, _) = self.read_char()?;
 
                   // Ensure that the byte string has been properly encoded.
-                  let b = self.read_prefixed_byte_string(idx)?;
-                  let mut buf = [0u8; 1024];
+                  let b = self.read_prefixed_byte_string(idx, true)?;
+                  let mut buf = vec![0u8; b.len() / 2];
                   return base16::decode_slice(&b[..], &mut buf)
                     .map_err(|e| (self.str_input, self.position, e).into())
                     .map(|_| {
@@ -567,8 +608,11 @@ impl<'a> Lexer<'a> {
 
                           // Ensure that the byte string has been properly
                           // encoded
-                          let bs = self.read_prefixed_byte_string(idx)?;
-                          let mut buf = [0u8; 1024];
+                          let bs = self.read_prefixed_byte_string(idx, false)?;
+                          // usize::div_ceil is newer than the minimum
+                          // supported Rust version
+                          #[allow(clippy::manual_div_ceil)]
+                          let mut buf = vec![0u8; (bs.len() + 3) / 4 * 3];
                           return base64::decode_config_slice(&bs, base64::URL_SAFE, &mut buf)
                             .map_err(|e| (self.str_input, self.position, e).into())
                             .map(|_| {
@@ -633,28 +677,36 @@ impl<'a> Lexer<'a> {
     Ok(&self.str_input[idx..=end_idx])
   }
 
-  fn read_text_value(&mut self, idx: usize) -> Result<&'a str> {
-    while let Some(&(_, ch)) = self.peek_char() {
+  fn read_text_value(&mut self, idx: usize) -> Result<Cow<'a, str>> {
+    let input = self.str_input;
+    // Only allocated once an escape sequence is found
+    let mut text: Option<String> = None;
+
+    while let Some(&(i, ch)) = self.peek_char() {
       match ch {
         // SCHAR
-        '\x20'..='\x21' | '\x23'..='\x5b' | '\x5d'..='\x7e' | '\u{0128}'..='\u{10FFFD}' => {
+        '\x20'..='\x21' | '\x23'..='\x5b' | '\x5d'..='\x7e' | '\u{00A0}'..='\u{10FFFD}' => {
           let _ = self.read_char()?;
+          if let Some(text) = &mut text {
+            text.push(ch);
+          }
         }
         // SESC
         '\\' => {
-          let _ = self.read_char();
-          if let Some(&(_, ch)) = self.peek_char() {
-            match ch {
-              '\x20'..='\x7e' | '\u{0128}'..='\u{10FFFD}' => {
-                let _ = self.read_char()?;
-              }
-              _ => return Err((self.str_input, self.position, InvalidEscapeCharacter).into()),
-            }
-          }
+          let _ = self.read_char()?;
+          let c = self.read_escape(false)?;
+          text
+            .get_or_insert_with(|| input[idx + 1..i].to_string())
+            .push(c);
         }
         // Closing "
         '\x22' => {
-          return Ok(&self.str_input[idx + 1..self.read_char()?.0]);
+          let end = self.read_char()?.0;
+
+          return Ok(match text {
+            Some(text) => text.into(),
+            None => input[idx + 1..end].into(),
+          });
         }
         _ => {
           return Err(
@@ -672,15 +724,49 @@ impl<'a> Lexer<'a> {
     Err((self.str_input, self.position, EmptyTextStringLiteral).into())
   }
 
-  fn read_byte_string(&mut self, idx: usize) -> Result<&'a str> {
-    while let Some(&(_, ch)) = self.peek_char() {
+  fn read_byte_string(&mut self, idx: usize) -> Result<Cow<'a, [u8]>> {
+    let input = self.str_input;
+    // Only allocated once an escape sequence is found
+    let mut bytes: Option<Vec<u8>> = None;
+
+    while let Some(&(i, ch)) = self.peek_char() {
       match ch {
-        // BCHAR
-        '\x20'..='\x26' | '\x28'..='\x5b' | '\x5d'..='\u{10FFFD}' => {
-          let _ = self.read_char();
+        // BCHAR and line breaks, which RFC 9682 allows to be either LF or
+        // CRLF
+        '\x20'..='\x26'
+        | '\x28'..='\x5b'
+        | '\x5d'..='\x7e'
+        | '\u{00A0}'..='\u{10FFFD}'
+        | '\x0a' => {
+          let _ = self.read_char()?;
+          if let Some(bytes) = &mut bytes {
+            bytes.extend_from_slice(ch.encode_utf8(&mut [0; 4]).as_bytes());
+          }
+        }
+        // CR of a CRLF, whose LF is read as part of the next character
+        '\x0d' if matches!(self.str_input[i + 1..].chars().next(), Some('\x0a')) => {
+          let _ = self.read_char()?;
+          if let Some(bytes) = &mut bytes {
+            bytes.push(b'\r');
+          }
+        }
+        // SESC
+        '\\' => {
+          let _ = self.read_char()?;
+          let c = self.read_escape(true)?;
+          bytes
+            .get_or_insert_with(|| input.as_bytes()[idx..i].to_vec())
+            .extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
         }
         // Closing '
-        '\x27' => return Ok(&self.str_input[idx..self.read_char()?.0]),
+        '\x27' => {
+          let end = self.read_char()?.0;
+
+          return Ok(match bytes {
+            Some(bytes) => bytes.into(),
+            None => input.as_bytes()[idx..end].into(),
+          });
+        }
         _ => {
           return Err(
             (
@@ -697,59 +783,86 @@ impl<'a> Lexer<'a> {
     Err((self.str_input, self.position, EmptyByteStringLiteral).into())
   }
 
-  fn read_prefixed_byte_string(&mut self, idx: usize) -> Result<Cow<'a, [u8]>> {
-    let mut has_whitespace = false;
+  /// Reads the encoded text of a byte string prefixed by 'h' or 'b64', leaving
+  /// out whitespace and comments. Each character is checked against the
+  /// encoding as it is read, so that a missing closing ' is reported at the
+  /// first character that can't belong to the byte string
+  fn read_prefixed_byte_string(&mut self, idx: usize, is_base16: bool) -> Result<Cow<'a, [u8]>> {
+    let input = self.str_input;
+    let is_encoding_char = |c: char| {
+      if is_base16 {
+        c.is_ascii_hexdigit()
+      } else {
+        c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '=')
+      }
+    };
+    // Only allocated once the encoded text differs from the input
+    let mut bytes: Option<Vec<u8>> = None;
 
-    while let Some(&(_, ch)) = self.peek_char() {
+    while let Some(&(i, ch)) = self.peek_char() {
       match ch {
-        // BCHAR
-        '\x20'..='\x26' | '\x28'..='\x5b' | '\x5d'..='\u{10FFFD}' => {
-          let _ = self.read_char();
+        // Closing '
+        '\x27' => {
+          let end = self.read_char()?.0;
+
+          return Ok(match bytes {
+            Some(bytes) => bytes.into(),
+            None => input.as_bytes()[idx..end].into(),
+          });
         }
-        // SESC
-        '\\' => {
-          let _ = self.read_char();
-          if let Some(&(_, ch)) = self.peek_char() {
-            match ch {
-              '\x20'..='\x7e' | '\u{0128}'..='\u{10FFFD}' => {
-                let _ = self.read_char()?;
-              }
-              _ => return Err((self.str_input, self.position, InvalidEscapeCharacter).into()),
+        // Whitespace, including CRLF, is ignored
+        _ if ch.is_ascii_whitespace() => {
+          let _ = self.read_char()?;
+          bytes.get_or_insert_with(|| input.as_bytes()[idx..i].to_vec());
+        }
+        // Comments run to the end of the line
+        ';' => {
+          bytes.get_or_insert_with(|| input.as_bytes()[idx..i].to_vec());
+          while let Some(&(_, ch)) = self.peek_char() {
+            if ch == '\n' {
+              break;
             }
+
+            let _ = self.read_char()?;
           }
         }
-        // Closing '
-        '\x27' => {
-          // Whitespace is ignored for prefixed byte strings and requires allocation
-          if has_whitespace {
-            return Ok(
-              self.str_input[idx..self.read_char()?.0]
-                .to_string()
-                .replace(" ", "")
-                .into_bytes()
-                .into(),
-            );
+        // SESC
+        '\\' => {
+          let _ = self.read_char()?;
+          let c = self.read_escape(true)?;
+          if !is_encoding_char(c) {
+            self.position.range = (i, self.position.index + 1);
+            return Err(self.invalid_encoding_char(c, is_base16));
           }
 
-          return Ok(self.str_input[idx..self.read_char()?.0].as_bytes().into());
+          bytes
+            .get_or_insert_with(|| input.as_bytes()[idx..i].to_vec())
+            .push(c as u8);
+        }
+        _ if is_encoding_char(ch) => {
+          let _ = self.read_char()?;
+          if let Some(bytes) = &mut bytes {
+            bytes.push(ch as u8);
+          }
         }
-        // CRLF
         _ => {
-          // TODO: if user forgets closing "'", but another "'" is found later
-          // in the string, the error emitted here can be confusing
-          if ch.is_ascii_whitespace() {
-            has_whitespace = true;
-            let _ = self.read_char()?;
-          } else {
-            return Err(
-              (
-                self.str_input,
-                self.position,
-                InvalidByteStringLiteralCharacter,
-              )
-                .into(),
-            );
+          let _ = self.read_char()?;
+          self.position.range = (i, i + ch.len_utf8());
+          let error = self.invalid_encoding_char(ch, is_base16);
+
+          // Lexing resumes after the closing ' if it is on the same line, as
+          // it is otherwise most likely missing
+          if let Some(end) = input[i..].lines().next().and_then(|l| l.find('\'')) {
+            while let Some(&(j, _)) = self.peek_char() {
+              if j > i + end {
+                break;
+              }
+
+              let _ = self.read_char()?;
+            }
           }
+
+          return Err(error);
         }
       }
     }
@@ -757,6 +870,104 @@ impl<'a> Lexer<'a> {
     Err((self.str_input, self.position, EmptyByteStringLiteral).into())
   }
 
+  fn invalid_encoding_char(&self, ch: char, is_base16: bool) -> LexerError {
+    let msg = format!(
+      "invalid character {:?} in byte string literal. expected closing '",
+      ch
+    );
+
+    LexerError {
+      error_type: if is_base16 {
+        LexerErrorType::BASE16(msg)
+      } else {
+        LexerErrorType::BASE64(msg)
+      },
+      input: self.str_input.to_string(),
+      position: self.position,
+    }
+  }
+
+  /// Decodes the escape sequence following a '\' (SESC), as updated by RFC
+  /// 9682. An escaped ' is only allowed in byte strings
+  fn read_escape(&mut self, is_byte_string: bool) -> Result<char> {
+    let (i, ch) = self.read_char()?;
+
+    let c = match ch {
+      '"' | '/' | '\\' => Some(ch),
+      '\'' if is_byte_string => Some(ch),
+      'b' => Some('\x08'),
+      'f' => Some('\x0c'),
+      'n' => Some('\n'),
+      'r' => Some('\r'),
+      't' => Some('\t'),
+      'u' => self.read_hexchar()?,
+      _ => None,
+    };
+
+    c.ok_or_else(|| {
+      // Include the '\'
+      self.position.range = (i - 1, self.position.index + 1);
+
+      (self.str_input, self.position, InvalidEscapeCharacter).into()
+    })
+  }
+
+  /// Reads the Unicode scalar value of a "\u" escape, given either by up to six
+  /// hex digits in braces, e.g. "\u{1F600}", or by four hex digits, with
+  /// characters outside of the Basic Multilingual Plane given as a surrogate
+  /// pair of escapes, e.g. "\uD83D\uDE00". `None` if the escape is malformed or
+  /// denotes a lone surrogate
+  fn read_hexchar(&mut self) -> Result<Option<char>> {
+    if let Some(&(_, '{')) = self.peek_char() {
+      let _ = self.read_char()?;
+
+      let mut scalar = 0;
+      let mut has_digits = false;
+      loop {
+        match self.read_char()?.1 {
+          '}' if has_digits => return Ok(char::from_u32(scalar)),
+          c => match c.to_digit(16) {
+            // Leading zeros are allowed, so only the value is bounded
+            Some(d) if scalar <= 0x10FFFF => {
+              scalar = scalar * 16 + d;
+              has_digits = true;
+            }
+            _ => return Ok(None),
+          },
+        }
+      }
+    }
+
+    match self.read_hex4()? {
+      Some(high @ 0xD800..=0xDBFF) => {
+        // A high surrogate must be followed by an escaped low surrogate
+        if self.read_char()?.1 != '\\' || self.read_char()?.1 != 'u' {
+          return Ok(None);
+        }
+
+        match self.read_hex4()? {
+          Some(low @ 0xDC00..=0xDFFF) => Ok(char::from_u32(
+            0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00),
+          )),
+          _ => Ok(None),
+        }
+      }
+      Some(scalar) => Ok(char::from_u32(scalar)),
+      None => Ok(None),
+    }
+  }
+
+  fn read_hex4(&mut self) -> Result<Option<u32>> {
+    let mut scalar = 0;
+    for _ in 0..4 {
+      match self.read_char()?.1.to_digit(16) {
+        Some(d) => scalar = scalar * 16 + d,
+        None => return Ok(None),
+      }
+    }
+
+    Ok(Some(scalar))
+  }
   fn read_comment(&mut self, idx: usize) -> Result<&'a str> {
     let mut comment_char = (idx, char::default());
 
@@ -800,47 +1011,188 @@ impl<'a> Lexer<'a> {
       idx = self.read_char()?.0;
     }
 
-    let (end_idx, i) = self.read_number(idx)?;
+    // Hexadecimal and binary prefixed numbers
+    if self.str_input.as_bytes()[idx] == b'0' {
+      self.multipeek.reset_peek();
+      if let Some(&c) = self.multipeek.peek() {
+        match c.1 {
+          'x' | 'X' => {
+            if let Some(&c) = self.multipeek.peek() {
+              if c.1.is_ascii_hexdigit() {
+                return self.read_hexfloat_or_int(is_signed);
+              }
+            }
+          }
+          'b' | 'B' => {
+            if let Some(&c) = self.multipeek.peek() {
+              if c.1 == '0' || c.1 == '1' {
+                let _ = self.read_char()?;
+                let (digits_idx, _) = self.read_char()?;
+                let end_idx = self.read_digits(digits_idx, 2)?;
+
+                return self.prefixed_int_from_str(digits_idx, end_idx, 2, is_signed);
+              }
+            }
+          }
+          _ => (),
+        }
+      }
+    }
+
+    let mut end_idx = self.read_digits(idx, 10)?;
+    let mut is_float = false;
 
+    // Fraction
+    self.multipeek.reset_peek();
     if let Some(&c) = self.multipeek.peek() {
       if c.1 == '.' {
         if let Some(&c) = self.multipeek.peek() {
           if is_digit(c.1) {
             let _ = self.read_char()?;
-            let (fraction_idx, _) = self.read_number(c.0)?;
+            let (fraction_idx, _) = self.read_char()?;
+            end_idx = self.read_digits(fraction_idx, 10)?;
+            is_float = true;
+          }
+        }
+      }
+    }
 
-            if is_signed {
-              return Ok(Token::VALUE(Value::FLOAT(
-                lexical::parse::<f64>(&self.str_input[signed_idx..=fraction_idx].as_bytes())
-                  .map_err(|e| LexerError::from((self.str_input, self.position, e)))?,
-              )));
+    // Exponent
+    if let Some(exponent_end_idx) = self.read_exponent(&['e', 'E'])? {
+      end_idx = exponent_end_idx;
+      is_float = true;
+    }
+
+    let start_idx = if is_signed { signed_idx } else { idx };
+
+    if is_float {
+      return Ok(Token::VALUE(Value::FLOAT(
+        lexical::parse::<f64>(&self.str_input.as_bytes()[start_idx..=end_idx])
+          .map_err(|e| LexerError::from((self.str_input, self.position, e)))?,
+      )));
+    }
+
+    if is_signed {
+      return Ok(Token::VALUE(Value::INT(
+        self.str_input[signed_idx..=end_idx]
+          .parse()
+          .map_err(|e| LexerError::from((self.str_input, self.position, e)))?,
+      )));
+    }
+
+    Ok(Token::VALUE(Value::UINT(
+      self.str_input[idx..=end_idx]
+        .parse()
+        .map_err(|e| LexerError::from((self.str_input, self.position, e)))?,
+    )))
+  }
+
+  // Reads the remainder of a "0x" prefixed number, which is either a hex
+  // integer or a hexfloat with a mandatory binary exponent (e.g. 0x1.8p1)
+  fn read_hexfloat_or_int(&mut self, is_signed: bool) -> Result<Token<'a>> {
+    let _ = self.read_char()?;
+    let (digits_idx, _) = self.read_char()?;
+    let mut end_idx = self.read_digits(digits_idx, 16)?;
+    let mut has_fraction = false;
+
+    self.multipeek.reset_peek();
+    if let Some(&c) = self.multipeek.peek() {
+      if c.1 == '.' {
+        if let Some(&c) = self.multipeek.peek() {
+          if c.1.is_ascii_hexdigit() {
+            let _ = self.read_char()?;
+            let (fraction_idx, _) = self.read_char()?;
+            end_idx = self.read_digits(fraction_idx, 16)?;
+            has_fraction = true;
+          }
+        }
+      }
+    }
+
+    if let Some(exponent_end_idx) = self.read_exponent(&['p', 'P'])? {
+      let f = hexfloat_from_str(&self.str_input[digits_idx..=exponent_end_idx])
+        .map_err(|e| LexerError::from((self.str_input, self.position, e)))?;
+
+      return Ok(Token::VALUE(Value::FLOAT(if is_signed { -f } else { f })));
+    }
+
+    if has_fraction {
+      return Err((self.str_input, self.position, InvalidHexFloat).into());
+    }
+
+    self.prefixed_int_from_str(digits_idx, end_idx, 16, is_signed)
+  }
+
+  // Reads an optional exponent introduced by one of the given markers,
+  // returning the index of its last digit
+  fn read_exponent(&mut self, markers: &[char]) -> Result<Option<usize>> {
+    self.multipeek.reset_peek();
+
+    if let Some(&c) = self.multipeek.peek() {
+      if markers.contains(&c.1) {
+        let mut c = self.multipeek.peek().copied();
+        let has_sign = matches!(c, Some((_, '+')) | Some((_, '-')));
+        if has_sign {
+          c = self.multipeek.peek().copied();
+        }
+
+        if let Some((_, ch)) = c {
+          if is_digit(ch) {
+            let _ = self.read_char()?;
+            if has_sign {
+              let _ = self.read_char()?;
             }
+            let (exponent_idx, _) = self.read_char()?;
 
-            return Ok(Token::VALUE(Value::FLOAT(
-              lexical::parse::<f64>(&self.str_input[idx..=fraction_idx].as_bytes())
-                .map_err(|e| LexerError::from((self.str_input, self.position, e)))?,
-            )));
+            return Ok(Some(self.read_digits(exponent_idx, 10)?));
           }
         }
       }
     }
 
+    Ok(None)
+  }
+
+  fn prefixed_int_from_str(
+    &self,
+    digits_idx: usize,
+    end_idx: usize,
+    radix: u32,
+    is_signed: bool,
+  ) -> Result<Token<'a>> {
+    let digits = &self.str_input[digits_idx..=end_idx];
+
     if is_signed {
       return Ok(Token::VALUE(Value::INT(
-        self.str_input[signed_idx..=end_idx]
-          .parse()
+        -i128::from_str_radix(digits, radix)
           .map_err(|e| LexerError::from((self.str_input, self.position, e)))?,
       )));
     }
 
-    Ok(Token::VALUE(Value::UINT(i)))
+    Ok(Token::VALUE(Value::UINT(
+      u64::from_str_radix(digits, radix)
+        .map_err(|e| LexerError::from((self.str_input, self.position, e)))?,
+    )))
+  }
+
+  fn read_number(&mut self, idx: usize) -> Result<(usize, u64)> {
+    let end_index = self.read_digits(idx, 10)?;
+
+    Ok((
+      end_index,
+      self.str_input[idx..=end_index]
+        .parse()
+        .map_err(|e| LexerError::from((self.str_input, self.position, e)))?,
+    ))
   }
 
-  fn read_number(&mut self, idx: usize) -> Result<(usize, usize)> {
+  // Advances past the digits in the given radix that follow the already read
+  // character at idx, returning the index of the last digit
+  fn read_digits(&mut self, idx: usize, radix: u32) -> Result<usize> {
     let mut end_index = idx;
 
     while let Some(&c) = self.peek_char() {
-      if is_digit(c.1) {
+      if c.1.is_digit(radix) {
         let (ei, _) = self.read_char()?;
 
         end_index = ei;
@@ -849,12 +1201,7 @@ impl<'a> Lexer<'a> {
       }
     }
 
-    Ok((
-      end_index,
-      self.str_input[idx..=end_index]
-        .parse()
-        .map_err(|e| LexerError::from((self.str_input, self.position, e)))?,
-    ))
+    Ok(end_index)
   }
 
   fn peek_char(&mut self) -> Option<&(usize, char)> {
@@ -870,6 +1217,39 @@ fn is_digit(ch: char) -> bool {
   ch.is_digit(10)
 }
 
+/// Converts the digits of a hexfloat literal following the "0x" prefix (e.g.
+/// "1.8p1") into a float
+fn hexfloat_from_str(s: &str) -> result::Result<f64, num::ParseIntError> {
+  let (mantissa, exponent) = s.split_at(s.find(&['p', 'P'][..]).unwrap_or(s.len()));
+  let mut exponent = exponent.get(1..).unwrap_or("0").parse::<i32>()?;
+
+  let mut value = 0f64;
+  let mut after_point = false;
+  for c in mantissa.chars() {
+    if c == '.' {
+      after_point = true;
+    } else if let Some(d) = c.to_digit(16) {
+      value = value * 16f64 + d as f64;
+      if after_point {
+        exponent = exponent.saturating_sub(4);
+      }
+    }
+  }
+
+  // Scale in steps so that large mantissas with very small exponents (and vice
+  // versa) don't prematurely overflow or underflow
+  while exponent > 1000 {
+    value *= 2f64.powi(1000);
+    exponent -= 1000;
+  }
+  while exponent < -1000 {
+    value *= 2f64.powi(-1000);
+    exponent += 1000;
+  }
+
+  Ok(value * 2f64.powi(exponent))
+}
+
 #[cfg(test)]
 mod tests {
   use super::{
@@ -1104,6 +1484,49 @@ mod tests {
     Ok(())
   }
 
+  #[test]
+  fn verify_numeric_literals() -> Result<()> {
+    let input = r#"1e3 1.5e-7 -2.5E+2 0x1.8p1 -0x1p-2 0x1F 0b101 -0x10 1e3..1e4"#;
+
+    let mut l = Lexer::new(input);
+
+    let expected_tokens = [
+      VALUE(Value::FLOAT(1000.0)),
+      VALUE(Value::FLOAT(1.5e-7)),
+      VALUE(Value::FLOAT(-250.0)),
+      VALUE(Value::FLOAT(3.0)),
+      VALUE(Value::FLOAT(-0.25)),
+      VALUE(Value::UINT(31)),
+      VALUE(Value::UINT(5)),
+      VALUE(Value::INT(-16)),
+      VALUE(Value::FLOAT(1000.0)),
+      RANGEOP(true),
+      VALUE(Value::FLOAT(10000.0)),
+    ];
+
+    for expected_tok in expected_tokens.iter() {
+      assert_eq!(expected_tok, &l.next_token()?.1)
+    }
+
+    assert!(Lexer::new("0x1.8").next_token().is_err());
+
+    let input = r#"18446744073709551615 -18446744073709551616 #6.18446744073709551615"#;
+
+    let mut l = Lexer::new(input);
+
+    let expected_tokens = [
+      VALUE(Value::UINT(u64::MAX)),
+      VALUE(Value::INT(-18446744073709551616)),
+      TAG((Some(6), Some(u64::MAX))),
+    ];
+
+    for expected_tok in expected_tokens.iter() {
+      assert_eq!(expected_tok, &l.next_token()?.1)
+    }
+
+    Ok(())
+  }
+
   #[test]
   fn verify_controlop() -> Result<()> {
     let input = r#".size"#;
@@ -1116,6 +1539,19 @@ mod tests {
     Ok(())
   }
 
+  #[test]
+  fn verify_base10_controlop() -> Result<()> {
+    for input in [".base10", ".decimal"].iter() {
+      let mut l = Lexer::new(input);
+
+      assert_eq!(Token::BASE10, l.next_token()?.1);
+    }
+
+    assert_eq!(Token::BASE10.to_string(), ".base10");
+
+    Ok(())
+  }
+
   #[test]
   fn verify_range() -> Result<()> {
     let input = r#"100.5..150.5"#;
@@ -1136,6 +1572,82 @@ mod tests {
     Ok(())
   }
 
+  #[test]
+  fn verify_string_escapes() -> Result<()> {
+    for (input, expected) in [
+      (r#""a\"b\\c\/d""#, Value::TEXT("a\"b\\c/d".into())),
+      (r#""\b\f\n\r\t""#, Value::TEXT("\x08\x0c\n\r\t".into())),
+      (r#""caf\u00e9 \u{1F600}""#, Value::TEXT("café 😀".into())),
+      (r#""\uD83D\uDE00 \u{0000041}""#, Value::TEXT("😀 A".into())),
+      (r#""é\u00E9""#, Value::TEXT("éé".into())),
+      (
+        r#"'it\'s é'"#,
+        Value::BYTE(ByteValue::UTF8("it's é".as_bytes().into())),
+      ),
+      (
+        "'a\r\nb\nc'",
+        Value::BYTE(ByteValue::UTF8(b"a\r\nb\nc".as_ref().into())),
+      ),
+      (
+        "h'01 02 ; first bytes\n  0a0b'",
+        Value::BYTE(ByteValue::B16(b"01020a0b".as_ref().into())),
+      ),
+      (
+        "b64'AQID ; comment\n BAU='",
+        Value::BYTE(ByteValue::B64(b"AQIDBAU=".as_ref().into())),
+      ),
+    ]
+    .iter()
+    {
+      assert_eq!(Lexer::new(input).next_token()?.1, VALUE(expected.clone()));
+    }
+
+    for input in [
+      r#""\x""#,
+      r#""\'""#,
+      r#""\uD83D""#,
+      r#""\uDE00""#,
+      r#""\uD83DA""#,
+      r#""\u{110000}""#,
+      r#""\u{}""#,
+      r#""\u12""#,
+      "h'01 0z'",
+      "h'010'",
+      "h'01\nb = 2",
+      "'a\rb'",
+      "b64'AQ+/'",
+    ]
+    .iter()
+    {
+      assert!(Lexer::new(input).next_token().is_err(), "{}", input);
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn verify_escaped_string_display() -> Result<()> {
+    for input in [r#""a\"b\\c\n""#, r#"'it\'s'"#].iter() {
+      assert_eq!(Lexer::new(input).next_token()?.1.to_string(), *input);
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn verify_nonascii_strings() -> Result<()> {
+    // NONASCII starts at U+00A0, leaving out the C1 control characters
+    for input in ["\"a\u{00A0}b\"", "'a\u{00A0}b'"].iter() {
+      assert!(Lexer::new(input).next_token().is_ok());
+    }
+
+    for input in ["\"a\u{0085}b\"", "'a\u{0085}b'"].iter() {
+      assert!(Lexer::new(input).next_token().is_err());
+    }
+
+    Ok(())
+  }
+
   #[test]
   fn verify_lexer_diagnostic() -> Result<()> {
     let input = r#"myrule = number .asdf 10"#;
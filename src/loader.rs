@@ -0,0 +1,682 @@
+use super::{
+  ast::CDDL,
+  lexer::Lexer,
+  parser::{self, Parser, ParserError},
+  semantic,
+  token::{self, Token},
+};
+
+use std::{
+  error, fmt, fs, io,
+  path::{Path, PathBuf},
+};
+
+/// Alias for `Result` with an error of type `loader::Error`
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Loader errors
+#[derive(Debug)]
+pub enum Error {
+  /// Error reading a CDDL file
+  IO(PathBuf, io::Error),
+  /// Directive which is neither a valid `;# include` nor `;# import`
+  InvalidDirective {
+    /// File containing the directive
+    path: PathBuf,
+    /// Line of the directive
+    line: usize,
+  },
+  /// Module which can't be found relative to the importing file or on the
+  /// search path
+  ModuleNotFound {
+    /// File containing the directive
+    path: PathBuf,
+    /// Line of the directive
+    line: usize,
+    /// Name of the module
+    name: String,
+  },
+  /// Module which directly or indirectly includes or imports itself
+  ImportCycle {
+    /// File containing the directive
+    path: PathBuf,
+    /// Line of the directive
+    line: usize,
+    /// Name of the module
+    name: String,
+  },
+  /// Errors parsing the merged CDDL, followed by any warnings, located in
+  /// their original files
+  Diagnostics(Vec<ModuleDiagnostic>),
+  /// Other error parsing the merged CDDL
+  CDDLParsing(parser::Error),
+}
+
+impl fmt::Display for Error {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Error::IO(path, e) => write!(f, "{}: {}", path.display(), e),
+      Error::InvalidDirective { path, line } => write!(
+        f,
+        "{}:{}: expected \";# include <module> [as <prefix>]\" or \";# import <module> [as <prefix>]\"",
+        path.display(),
+        line
+      ),
+      Error::ModuleNotFound { path, line, name } => write!(
+        f,
+        "{}:{}: module \"{}\" not found",
+        path.display(),
+        line,
+        name
+      ),
+      Error::ImportCycle { path, line, name } => write!(
+        f,
+        "{}:{}: module \"{}\" includes or imports itself",
+        path.display(),
+        line,
+        name
+      ),
+      Error::Diagnostics(diagnostics) => {
+        let mut errors = String::new();
+        for d in diagnostics.iter() {
+          errors.push_str(&format!("{}\n", d));
+        }
+
+        write!(f, "{}", errors)
+      }
+      Error::CDDLParsing(e) => write!(f, "{}", e),
+    }
+  }
+}
+
+impl error::Error for Error {
+  fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+    match self {
+      Error::IO(_, e) => Some(e),
+      Error::CDDLParsing(e) => Some(e),
+      _ => None,
+    }
+  }
+}
+
+/// Parser error or warning located in the file it originated from
+#[derive(Debug, Clone)]
+pub struct ModuleDiagnostic {
+  /// File the error or warning originated from
+  pub path: PathBuf,
+  /// Line within the file
+  pub line: usize,
+  /// Parser error or warning. Its position refers to the merged input
+  pub error: ParserError,
+}
+
+impl fmt::Display for ModuleDiagnostic {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(
+      f,
+      "{}:{}: {}: {}",
+      self.path.display(),
+      self.line,
+      self.error.severity,
+      self.error.msg
+    )
+  }
+}
+
+/// Loads CDDL definitions split across multiple files, following the
+/// `;# include` and `;# import` directives of the CDDL modules draft
+/// (draft-ietf-cbor-cddl-modules):
+///
+/// - `;# include <module>` includes every rule of the module
+/// - `;# import <module>` includes only the rules of the module which the
+///   importing file references but doesn't define, along with the rules they
+///   depend on, and any plugs for sockets the importing file uses
+///
+/// Either may be followed by `as <prefix>`, in which case the rules of the
+/// module are renamed to `<prefix>.<name>` and must be referenced as such.
+/// Sockets aren't renamed, so that modules can extend each other's sockets
+/// regardless of prefixes.
+///
+/// Modules are named without their `.cddl` extension and are looked up
+/// relative to the importing file, then in each directory of the search path
+/// in turn
+///
+/// # Example
+///
+/// ```no_run
+/// use cddl::{lexer_from_str, loader::Loader};
+///
+/// let mut loader = Loader::default();
+/// loader.add_search_path("specs/common");
+///
+/// let module = loader.load("specs/cwt.cddl").unwrap();
+/// let mut lexer = lexer_from_str(module.input());
+/// let (cddl, warnings) = module.parse(&mut lexer).unwrap();
+///
+/// for warning in warnings.iter() {
+///   eprintln!("{}", warning);
+/// }
+///
+/// for rule in cddl.rules.iter() {
+///   println!("{} from {:?}", rule.name(), module.locate(rule.span().0));
+/// }
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct Loader {
+  search_path: Vec<PathBuf>,
+}
+
+/// CDDL definitions loaded from a root file and the modules it includes or
+/// imports, merged into a single input
+#[derive(Debug, Clone)]
+pub struct Module {
+  files: Vec<PathBuf>,
+  chunks: Vec<Chunk>,
+  input: String,
+}
+
+// Portion of a file, usually a single rule along with any comments following
+// it, which is merged into a module
+#[derive(Debug, Clone)]
+struct Chunk {
+  // Index of the file in Module::files
+  file: usize,
+  // Byte offset within the file
+  offset: usize,
+  // Line within the file
+  line: usize,
+  // Name of the rule defined by the chunk. None for the leading comments of
+  // the root file and for input which failed to parse, both of which are
+  // always kept
+  name: Option<String>,
+  // Text of the chunk, with any prefixes applied
+  text: String,
+}
+
+enum DirectiveKind {
+  Include,
+  Import,
+}
+
+struct Directive {
+  kind: DirectiveKind,
+  name: String,
+  prefix: Option<String>,
+  line: usize,
+}
+
+impl Loader {
+  /// Appends a directory to the search path used to find modules
+  pub fn add_search_path<P: AsRef<Path>>(&mut self, path: P) -> &mut Self {
+    self.search_path.push(path.as_ref().to_path_buf());
+
+    self
+  }
+
+  /// Loads the given CDDL file along with all the modules it includes or
+  /// imports, directly or indirectly
+  pub fn load<P: AsRef<Path>>(&self, path: P) -> Result<Module> {
+    let mut files = Vec::new();
+    let mut stack = Vec::new();
+
+    let chunks = self.load_file(path.as_ref(), &mut files, &mut stack, true)?;
+
+    let mut input = String::new();
+    let mut merged = Vec::with_capacity(chunks.len());
+    for chunk in chunks.into_iter() {
+      let offset = input.len();
+      input.push_str(&chunk.text);
+
+      // Each chunk's offset is rewritten to refer to the merged input
+      merged.push(Chunk { offset, ..chunk });
+    }
+
+    Ok(Module {
+      files,
+      chunks: merged,
+      input,
+    })
+  }
+
+  fn load_file(
+    &self,
+    path: &Path,
+    files: &mut Vec<PathBuf>,
+    stack: &mut Vec<PathBuf>,
+    is_root: bool,
+  ) -> Result<Vec<Chunk>> {
+    let text = fs::read_to_string(path).map_err(|e| Error::IO(path.to_path_buf(), e))?;
+    let canonical = fs::canonicalize(path).map_err(|e| Error::IO(path.to_path_buf(), e))?;
+
+    let file = match files.iter().position(|f| *f == canonical) {
+      Some(idx) => idx,
+      None => {
+        files.push(canonical.clone());
+        files.len() - 1
+      }
+    };
+
+    let directives = directives(&text).map_err(|line| Error::InvalidDirective {
+      path: path.to_path_buf(),
+      line,
+    })?;
+
+    let mut chunks = file_chunks(file, &text, is_root);
+
+    stack.push(canonical);
+
+    for directive in directives.into_iter() {
+      let module_path =
+        self
+          .resolve(path, &directive.name)
+          .ok_or_else(|| Error::ModuleNotFound {
+            path: path.to_path_buf(),
+            line: directive.line,
+            name: directive.name.clone(),
+          })?;
+
+      let is_cycle = fs::canonicalize(&module_path)
+        .map(|p| stack.contains(&p))
+        .unwrap_or(false);
+      if is_cycle {
+        return Err(Error::ImportCycle {
+          path: path.to_path_buf(),
+          line: directive.line,
+          name: directive.name,
+        });
+      }
+
+      let mut module_chunks = self.load_file(&module_path, files, stack, false)?;
+
+      if let Some(prefix) = &directive.prefix {
+        apply_prefix(&mut module_chunks, prefix);
+      }
+
+      if let DirectiveKind::Import = directive.kind {
+        module_chunks = imported_chunks(&chunks, module_chunks);
+      }
+
+      for chunk in module_chunks.into_iter() {
+        // Modules included by more than one file are only merged once
+        let is_duplicate = chunks
+          .iter()
+          .any(|c| c.file == chunk.file && c.offset == chunk.offset && c.text == chunk.text);
+
+        if !is_duplicate {
+          chunks.push(chunk);
+        }
+      }
+    }
+
+    stack.pop();
+
+    Ok(chunks)
+  }
+
+  fn resolve(&self, importing_path: &Path, name: &str) -> Option<PathBuf> {
+    let file_name = if name.ends_with(".cddl") {
+      name.to_string()
+    } else {
+      format!("{}.cddl", name)
+    };
+
+    importing_path
+      .parent()
+      .into_iter()
+      .chain(self.search_path.iter().map(|p| p.as_path()))
+      .map(|dir| dir.join(&file_name))
+      .find(|p| p.is_file())
+  }
+}
+
+impl Module {
+  /// Merged input of all loaded files, which the spans of the parsed AST refer
+  /// to. Use `locate()` to map a span back to its original file
+  pub fn input(&self) -> &str {
+    &self.input
+  }
+
+  /// Canonical paths of all loaded files, beginning with the root file
+  pub fn files(&self) -> &[PathBuf] {
+    &self.files
+  }
+
+  /// Returns the file and line that the given byte offset into the merged
+  /// input originated from
+  pub fn locate(&self, offset: usize) -> Option<(&Path, usize)> {
+    let chunk = self.chunks.iter().rev().find(|c| c.offset <= offset)?;
+    let end = offset.min(self.input.len());
+    let line = chunk.line + self.input[chunk.offset..end].matches('\n').count();
+
+    Some((self.files[chunk.file].as_path(), line))
+  }
+
+  /// Parses the merged input into a single `CDDL` AST using a lexer created
+  /// from `input()`, along with the parser warnings, e.g. for references to
+  /// rules which none of the loaded files define. Errors and warnings are
+  /// located in the files they originated from
+  pub fn parse<'a>(
+    &'a self,
+    lexer: &'a mut Lexer<'a>,
+  ) -> Result<(CDDL<'a>, Vec<ModuleDiagnostic>)> {
+    let mut p = Parser::new(lexer.iter(), &self.input).map_err(|e| self.parser_error(e))?;
+
+    match p.parse_cddl() {
+      Ok(cddl) => Ok((cddl, self.diagnostics(&p.warnings))),
+      Err(parser::Error::PARSER) => {
+        let mut diagnostics = self.diagnostics(&p.errors);
+        diagnostics.append(&mut self.diagnostics(&p.warnings));
+
+        Err(Error::Diagnostics(diagnostics))
+      }
+      Err(e) => Err(self.parser_error(e)),
+    }
+  }
+
+  fn parser_error(&self, e: parser::Error) -> Error {
+    match e {
+      parser::Error::LEXER(le) => Error::Diagnostics(vec![self.diagnostic(ParserError::from(le))]),
+      _ => Error::CDDLParsing(e),
+    }
+  }
+
+  fn diagnostics(&self, errors: &[ParserError]) -> Vec<ModuleDiagnostic> {
+    errors.iter().map(|e| self.diagnostic(e.clone())).collect()
+  }
+
+  fn diagnostic(&self, error: ParserError) -> ModuleDiagnostic {
+    let (path, line) = self
+      .locate(error.position.range.0)
+      .map(|(path, line)| (path.to_path_buf(), line))
+      .unwrap_or_default();
+
+    ModuleDiagnostic { path, line, error }
+  }
+}
+
+// Parses the `;# include` and `;# import` directives of a file. Returns the
+// line of the first invalid directive on error
+fn directives(text: &str) -> std::result::Result<Vec<Directive>, usize> {
+  let mut directives = Vec::new();
+
+  for (idx, line) in text.lines().enumerate() {
+    let directive = match line.strip_prefix(";#") {
+      Some(directive) => directive,
+      None => continue,
+    };
+
+    let words = directive.split_whitespace().collect::<Vec<_>>();
+    let kind = match words.first() {
+      Some(&"include") => DirectiveKind::Include,
+      Some(&"import") => DirectiveKind::Import,
+      // Other directives are left to other tools
+      _ => continue,
+    };
+
+    let prefix = match words.len() {
+      2 => None,
+      4 if words[2] == "as" => Some(words[3].to_string()),
+      _ => return Err(idx + 1),
+    };
+
+    directives.push(Directive {
+      kind,
+      name: words[1].to_string(),
+      prefix,
+      line: idx + 1,
+    });
+  }
+
+  Ok(directives)
+}
+
+// Splits a file into chunks at the beginning of each rule, or of input which
+// failed to parse
+fn file_chunks(file: usize, text: &str, is_root: bool) -> Vec<Chunk> {
+  let mut lexer = Lexer::new(text);
+  let mut p = Parser::new_tolerant(lexer.iter(), text);
+  let partial = p.parse_cddl_partial();
+
+  let mut starts = partial
+    .cddl
+    .rules
+    .iter()
+    .map(|r| (r.span().0, Some(r.name())))
+    .chain(partial.skipped.iter().map(|s| (s.0, None)))
+    .collect::<Vec<_>>();
+  starts.sort_by_key(|(offset, _)| *offset);
+
+  let mut chunks = Vec::new();
+
+  if is_root {
+    let end = starts.first().map_or(text.len(), |(offset, _)| *offset);
+    if end > 0 {
+      chunks.push(chunk(file, text, 0, end, None));
+    }
+  }
+
+  for (idx, (offset, name)) in starts.iter().enumerate() {
+    let end = starts
+      .get(idx + 1)
+      .map_or(text.len(), |(offset, _)| *offset);
+
+    chunks.push(chunk(file, text, *offset, end, name.clone()));
+  }
+
+  chunks
+}
+
+fn chunk(file: usize, text: &str, offset: usize, end: usize, name: Option<String>) -> Chunk {
+  let mut chunk_text = text[offset..end].to_string();
+  if !chunk_text.ends_with('\n') {
+    chunk_text.push('\n');
+  }
+
+  Chunk {
+    file,
+    offset,
+    line: text[..offset].matches('\n').count() + 1,
+    name,
+    text: chunk_text,
+  }
+}
+
+// Renames every rule defined by the given chunks to `<prefix>.<name>`, along
+// with all references to them. Sockets and bareword member keys are left as is
+fn apply_prefix(chunks: &mut [Chunk], prefix: &str) {
+  let defined = chunks
+    .iter()
+    .filter_map(|c| c.name.clone())
+    .filter(|name| !name.starts_with('$'))
+    .collect::<Vec<_>>();
+
+  for chunk in chunks.iter_mut() {
+    if let Some(name) = &chunk.name {
+      if defined.contains(name) {
+        chunk.name = Some(format!("{}.{}", prefix, name));
+      }
+    }
+
+    let mut lexer = Lexer::new(&chunk.text);
+    let tokens = lexer
+      .iter()
+      .take_while(|t| t.is_ok())
+      .filter_map(|t| t.ok())
+      .take_while(|(_, t)| *t != Token::EOF)
+      .filter(|(_, t)| !matches!(t, Token::NEWLINE | Token::COMMENT(_)))
+      .collect::<Vec<_>>();
+
+    let mut renamed = String::with_capacity(chunk.text.len());
+    let mut last = 0;
+
+    for (idx, (position, t)) in tokens.iter().enumerate() {
+      let is_member_key = matches!(tokens.get(idx + 1), Some((_, Token::COLON)));
+
+      if let Token::IDENT((ident, None)) = t {
+        if !is_member_key && defined.iter().any(|d| d == ident) {
+          renamed.push_str(&chunk.text[last..position.range.0]);
+          renamed.push_str(&format!("{}.{}", prefix, ident));
+          last = position.range.1;
+        }
+      }
+    }
+
+    renamed.push_str(&chunk.text[last..]);
+    chunk.text = renamed;
+  }
+}
+
+// Selects the chunks of a module which the importing chunks need. These are
+// the rules referenced by, but not defined in, the importing chunks, plugs for
+// any sockets they use, and all rules either depend on
+fn imported_chunks(importing: &[Chunk], module: Vec<Chunk>) -> Vec<Chunk> {
+  let importing_text = importing
+    .iter()
+    .map(|c| c.text.as_str())
+    .collect::<String>();
+  let mut lexer = Lexer::new(&importing_text);
+  let importing_cddl = Parser::new_tolerant(lexer.iter(), &importing_text)
+    .parse_cddl_partial()
+    .cddl;
+
+  let defined = importing_cddl
+    .rules
+    .iter()
+    .map(|r| r.name())
+    .collect::<Vec<_>>();
+  let mut wanted = defined
+    .iter()
+    .filter(|name| name.starts_with('$'))
+    .cloned()
+    .collect::<Vec<_>>();
+
+  for name in defined.iter() {
+    for referenced in semantic::referenced_rule_names(&importing_cddl, name) {
+      let is_wanted = referenced.starts_with('$')
+        || (!defined.contains(&referenced)
+          && token::lookup_ident(&referenced)
+            .in_standard_prelude()
+            .is_none());
+
+      if is_wanted && !wanted.contains(&referenced) {
+        wanted.push(referenced);
+      }
+    }
+  }
+
+  let module_text = module.iter().map(|c| c.text.as_str()).collect::<String>();
+  let mut lexer = Lexer::new(&module_text);
+  let module_cddl = Parser::new_tolerant(lexer.iter(), &module_text)
+    .parse_cddl_partial()
+    .cddl;
+
+  let needed = semantic::reachable_rule_names(&module_cddl, wanted);
+
+  module
+    .into_iter()
+    .filter(|c| c.name.iter().all(|name| needed.contains(name)))
+    .collect()
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+  use super::*;
+  use crate::error::MsgType;
+
+  const FIXTURES: &str = "tests/fixtures/modules";
+
+  fn load(name: &str) -> Result<Module> {
+    let mut loader = Loader::default();
+    loader.add_search_path(Path::new(FIXTURES).join("common"));
+
+    loader.load(Path::new(FIXTURES).join(name))
+  }
+
+  #[test]
+  fn verify_include_and_import() {
+    let module = load("token.cddl").unwrap();
+    let mut lexer = Lexer::new(module.input());
+    let (cddl, warnings) = module.parse(&mut lexer).unwrap();
+    assert!(warnings.is_empty());
+
+    let names = cddl.rules.iter().map(|r| r.name()).collect::<Vec<_>>();
+    assert_eq!(
+      names,
+      vec![
+        "token",
+        "$claim-key",
+        "header",
+        "$claim-key",
+        "$claim-key",
+        "COSE.Key",
+        "COSE.label",
+        "COSE.values",
+        "timestamp",
+        "$claim-key",
+      ]
+    );
+
+    let (path, line) = module.locate(cddl.rules[4].span().0).unwrap();
+    assert!(path.ends_with("claims.cddl"));
+    assert_eq!(line, 4);
+
+    let (path, line) = module.locate(cddl.rules[9].span().0).unwrap();
+    assert!(path.ends_with("common/time.cddl"));
+    assert_eq!(line, 4);
+
+    // Unreferenced rules of imported modules aren't merged
+    assert!(!names.iter().any(|n| n == "COSE.unused" || n == "duration"));
+  }
+
+  #[test]
+  fn verify_located_diagnostics() {
+    let module = load("broken.cddl").unwrap();
+    let mut lexer = Lexer::new(module.input());
+    match module.parse(&mut lexer) {
+      Err(Error::Diagnostics(diagnostics)) => {
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].path.ends_with("invalid.cddl"));
+        assert_eq!(diagnostics[0].line, 3);
+      }
+      r => panic!("expected located diagnostics, got {:?}", r),
+    }
+
+    match load("missing.cddl") {
+      Err(Error::ModuleNotFound { line, name, .. }) => {
+        assert_eq!(line, 1);
+        assert_eq!(name, "nonexistent");
+      }
+      r => panic!("expected missing module error, got {:?}", r),
+    }
+
+    assert!(matches!(load("cycle.cddl"), Err(Error::ImportCycle { .. })));
+  }
+
+  #[test]
+  fn verify_located_warnings() {
+    let module = load("envelope.cddl").unwrap();
+    let mut lexer = Lexer::new(module.input());
+    let (_, warnings) = module.parse(&mut lexer).unwrap();
+
+    // Only prefixed names refer to the rules of a module imported with a
+    // prefix
+    let located = warnings
+      .iter()
+      .map(|w| {
+        (
+          w.path.file_name().unwrap().to_str().unwrap(),
+          w.line,
+          w.error.msg_type,
+        )
+      })
+      .collect::<Vec<_>>();
+    assert_eq!(
+      located,
+      vec![
+        ("envelope.cddl", 5, MsgType::UndefinedIdentifier),
+        ("envelope.cddl", 6, MsgType::UndefinedIdentifier),
+      ]
+    );
+  }
+}
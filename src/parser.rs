@@ -3,8 +3,10 @@ use super::{
   error::{
     ErrorMsg,
     MsgType::{self, *},
+    Severity,
   },
-  lexer::{self, Lexer, LexerError, Position},
+  lexer::{self, Lexer, LexerError, LexerErrorType, Position},
+  prelude, semantic,
   token::{self, SocketPlug, Token},
 };
 #[cfg(feature = "std")]
@@ -18,7 +20,7 @@ use codespan_reporting::{
 #[cfg(feature = "std")]
 use std::borrow::Cow;
 
-use std::{cmp::Ordering, fmt, mem, result};
+use std::{cmp::Ordering, convert::TryFrom, fmt, mem, result};
 
 #[cfg(not(feature = "std"))]
 use alloc::{
@@ -51,6 +53,21 @@ where
   parser_position: Position,
   /// Vec of collected parsing errors
   pub errors: Vec<ParserError>,
+  /// Vec of collected warnings. Warnings, e.g. unreachable rules, don't cause
+  /// parsing to fail
+  pub warnings: Vec<ParserError>,
+  // Whether lexer errors are collected rather than aborting the parse
+  is_tolerant: bool,
+  // Spans of the input discarded because the lexer failed to read them
+  unreadable: Vec<Span>,
+  // Whether the input is a prelude, whose rules may redefine the names of the
+  // standard prelude
+  is_prelude: bool,
+  // Whether undefined identifiers and generic argument count mismatches are
+  // reported as errors rather than warnings
+  is_strict: bool,
+  // Prelude whose rules are merged with the parsed rules
+  prelude: Option<&'a CDDL<'a>>,
 }
 
 /// Parsing error types
@@ -64,14 +81,92 @@ pub enum Error {
   LEXER(LexerError),
   /// Regex error
   REGEX(regex::Error),
+  /// Parsing failed with the given errors and warnings. Returned by
+  /// `cddl_from_str()`
+  DIAGNOSTICS(Diagnostics),
+}
+
+/// Best-effort result of `Parser::parse_cddl_partial()`
+#[derive(Debug)]
+pub struct PartialCDDL<'a> {
+  /// Rules which were parsed successfully
+  pub cddl: CDDL<'a>,
+  /// Spans of the input skipped while recovering from errors, in order. Each
+  /// either begins where a rule failed to parse and ends where the next rule
+  /// begins, or covers input the lexer failed to read
+  pub skipped: Vec<Span>,
+  /// Errors and warnings collected while parsing
+  pub diagnostics: Diagnostics,
+}
+
+/// Errors and warnings collected while parsing a CDDL document
+#[derive(Debug)]
+pub struct Diagnostics {
+  /// Errors which prevented the document from being parsed
+  pub errors: Vec<ParserError>,
+  /// Warnings about valid, but likely unintended, definitions
+  pub warnings: Vec<ParserError>,
+  // Errors and warnings as rendered by codespan-reporting
+  report: String,
+}
+
+impl fmt::Display for Diagnostics {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", self.report)
+  }
 }
 
 /// Parser error information and position
 #[cfg_attr(target_arch = "wasm32", derive(Serialize))]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ParserError {
-  position: Position,
-  msg: ErrorMsg,
+  /// Error position
+  pub position: Position,
+  /// Error type
+  pub msg_type: MsgType,
+  /// Short and extended error messages
+  pub msg: ErrorMsg,
+  /// Whether the error prevents parsing from succeeding
+  pub severity: Severity,
+}
+
+impl ParserError {
+  fn new(position: Position, msg_type: MsgType) -> Self {
+    ParserError {
+      position,
+      msg_type,
+      msg: msg_type.into(),
+      severity: msg_type.severity(),
+    }
+  }
+}
+
+impl From<LexerError> for ParserError {
+  fn from(e: LexerError) -> Self {
+    let extended = match e.error_type {
+      LexerErrorType::LEXER(msg_type) => return ParserError::new(e.position, msg_type),
+      LexerErrorType::UTF8(utf8e) => utf8e.to_string(),
+      LexerErrorType::BASE16(b16e) => b16e,
+      LexerErrorType::BASE64(b64e) => b64e,
+      LexerErrorType::PARSEINT(pie) => pie.to_string(),
+      LexerErrorType::PARSEFLOAT(pfe) => format!("{:?}", pfe),
+    };
+
+    let mut error = ParserError::new(e.position, InvalidLiteral);
+    error.msg.extended = Some(extended);
+
+    error
+  }
+}
+
+impl fmt::Display for ParserError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(
+      f,
+      "{} at line {}: {}",
+      self.severity, self.position.line, self.msg
+    )
+  }
 }
 
 impl fmt::Display for Error {
@@ -81,6 +176,7 @@ impl fmt::Display for Error {
       Error::PARSER => write!(f, "Parser error"),
       Error::LEXER(e) => write!(f, "{}", e),
       Error::REGEX(e) => write!(f, "{}", e),
+      Error::DIAGNOSTICS(d) => write!(f, "{}", d),
     }
   }
 }
@@ -113,21 +209,117 @@ where
   /// let p = Parser::new(Lexer::new(input).iter(), input);
   /// ```
   pub fn new(tokens: I, str_input: &'a str) -> Result<Parser<I>> {
-    let mut p = Parser {
+    let mut p = Parser::with_tolerance(tokens, str_input, false);
+
+    p.next_token()?;
+    p.next_token()?;
+
+    Ok(p)
+  }
+
+  /// Create a new `Parser` which, rather than aborting on the first lexer
+  /// error, collects lexer errors along with parser errors and skips the
+  /// offending input. Intended for use with `parse_cddl_partial()` by editors
+  /// and linters working on incomplete documents
+  ///
+  /// # Example
+  ///
+  /// ```
+  /// use cddl::parser::Parser;
+  /// use cddl::lexer::Lexer;
+  ///
+  /// let input = r#"mycddl = ( int / float ) ^"#;
+  /// let mut lexer = Lexer::new(input);
+  /// let mut p = Parser::new_tolerant(lexer.iter(), input);
+  /// let partial = p.parse_cddl_partial();
+  ///
+  /// assert_eq!(partial.cddl.rules.len(), 1);
+  /// assert_eq!(partial.diagnostics.errors.len(), 1);
+  /// ```
+  pub fn new_tolerant(tokens: I, str_input: &'a str) -> Parser<'a, I> {
+    let mut p = Parser::with_tolerance(tokens, str_input, true);
+
+    // Lexer errors are collected when tolerant, so these never fail
+    let _ = p.next_token();
+    let _ = p.next_token();
+
+    p
+  }
+
+  fn with_tolerance(tokens: I, str_input: &'a str, is_tolerant: bool) -> Parser<'a, I> {
+    Parser {
       tokens,
       str_input,
       cur_token: Token::EOF,
       peek_token: Token::EOF,
       errors: Vec::default(),
+      warnings: Vec::default(),
       lexer_position: Position::default(),
       peek_lexer_position: Position::default(),
       parser_position: Position::default(),
-    };
+      is_tolerant,
+      unreadable: Vec::default(),
+      is_prelude: false,
+      is_strict: false,
+      prelude: None,
+    }
+  }
 
-    p.next_token()?;
-    p.next_token()?;
+  /// Merges the rules of a prelude extending the standard prelude, e.g. with
+  /// the IANA registered tags used by a domain, with the parsed rules. Names
+  /// defined by the prelude can be referenced as if they were defined by the
+  /// input, which can in turn redefine them. Preludes can be parsed with
+  /// `prelude::prelude_from_str()`
+  ///
+  /// # Example
+  ///
+  /// ```
+  /// use cddl::{lexer::Lexer, parser::Parser, prelude::prelude_from_str};
+  ///
+  /// let prelude_input = r#"cose-sign1 = #6.18([* any])"#;
+  /// let mut prelude_lexer = Lexer::new(prelude_input);
+  /// let prelude = prelude_from_str(&mut prelude_lexer, prelude_input).unwrap();
+  ///
+  /// let input = r#"message = { payload: cose-sign1 }"#;
+  /// let mut lexer = Lexer::new(input);
+  /// let cddl = Parser::new(lexer.iter(), input)
+  ///   .unwrap()
+  ///   .with_prelude(&prelude)
+  ///   .parse_cddl()
+  ///   .unwrap();
+  ///
+  /// assert_eq!(cddl.rules.len(), 2);
+  /// ```
+  pub fn with_prelude(mut self, prelude: &'a CDDL<'a>) -> Self {
+    self.prelude = Some(prelude);
+    self
+  }
 
-    Ok(p)
+  /// Reports undefined identifiers and generic rules referenced with the wrong
+  /// number of arguments as errors, failing the parse. By default they are
+  /// reported as warnings, so that documents relying on rules defined
+  /// elsewhere keep parsing
+  ///
+  /// # Example
+  ///
+  /// ```
+  /// use cddl::{lexer::Lexer, parser::Parser};
+  ///
+  /// let input = r#"message = { payload: cose-sign1 }"#;
+  ///
+  /// let mut lexer = Lexer::new(input);
+  /// assert!(Parser::new(lexer.iter(), input).unwrap().parse_cddl().is_ok());
+  ///
+  /// let mut lexer = Lexer::new(input);
+  /// assert!(Parser::new(lexer.iter(), input)
+  ///   .unwrap()
+  ///   .with_semantic_errors()
+  ///   .parse_cddl()
+  ///   .is_err());
+  /// ```
+  pub fn with_semantic_errors(mut self) -> Self {
+    self.is_strict = true;
+    self
   }
 
   /// Print parser errors if there are any. Used with the `Error::PARSER`
@@ -173,7 +365,8 @@ where
 
     let diagnostic = Diagnostic::error()
       .with_message("parser errors")
-      .with_labels(labels);
+      .with_labels(labels)
+      .with_notes(extended_notes(&self.errors));
 
     let config = term::Config::default();
 
@@ -231,7 +424,8 @@ where
 
     let diagnostic = Diagnostic::error()
       .with_message("parser errors")
-      .with_labels(labels);
+      .with_labels(labels)
+      .with_notes(extended_notes(&self.errors));
 
     let config = term::Config::default();
 
@@ -243,19 +437,198 @@ where
     Some(String::from_utf8(buffer).ok()?)
   }
 
+  /// Print parser warnings if there are any. Warnings are collected by
+  /// `parse_cddl()` whether or not it succeeds
+  ///
+  /// # Arguments
+  ///
+  /// * `to_stderr` - When true, outputs formatted warnings to stderr
+  ///
+  /// # Example
+  ///
+  /// ```
+  /// use cddl::parser::Parser;
+  /// use cddl::lexer::Lexer;
+  ///
+  /// let input = r#"mycddl = ( int / float )
+  /// unused = tstr"#;
+  /// if let Ok(mut p) = Parser::new(Lexer::new(input).iter(), input) {
+  ///   if p.parse_cddl().is_ok() {
+  ///     let _ = p.report_warnings(true);
+  ///   }
+  /// }
+  /// ```
+  #[cfg(feature = "std")]
+  pub fn report_warnings(
+    &self,
+    to_stderr: bool,
+  ) -> std::result::Result<Option<String>, Box<dyn std::error::Error>> {
+    if self.warnings.is_empty() {
+      return Ok(None);
+    }
+
+    let mut files = SimpleFiles::new();
+
+    let file_id = files.add("input", self.str_input);
+
+    let mut labels = Vec::new();
+    for warning in self.warnings.iter() {
+      labels.push(
+        Label::primary(file_id, warning.position.range.0..warning.position.range.1)
+          .with_message(warning.msg.to_string()),
+      );
+    }
+
+    let diagnostic = Diagnostic::warning()
+      .with_message("parser warnings")
+      .with_labels(labels)
+      .with_notes(extended_notes(&self.warnings));
+
+    let config = term::Config::default();
+
+    if to_stderr {
+      let writer = StandardStream::stderr(ColorChoice::Auto);
+      term::emit(&mut writer.lock(), &config, &files, &diagnostic)?;
+
+      return Ok(None);
+    }
+
+    let mut buffer = Vec::new();
+    let mut writer = term::termcolor::NoColor::new(&mut buffer);
+
+    term::emit(&mut writer, &config, &files, &diagnostic)?;
+
+    Ok(Some(String::from_utf8(buffer)?))
+  }
+
+  /// Print parser warnings if there are any. Warnings are collected by
+  /// `parse_cddl()` whether or not it succeeds
+  ///
+  /// # Example
+  ///
+  /// ```
+  /// use cddl::parser::Parser;
+  /// use cddl::lexer::Lexer;
+  ///
+  /// let input = r#"mycddl = ( int / float )
+  /// unused = tstr"#;
+  /// if let Ok(mut p) = Parser::new(Lexer::new(input).iter(), input) {
+  ///   if p.parse_cddl().is_ok() {
+  ///     let _ = p.report_warnings();
+  ///   }
+  /// }
+  /// ```
+  #[cfg(not(feature = "std"))]
+  pub fn report_warnings(&self) -> Option<String> {
+    if self.warnings.is_empty() {
+      return None;
+    }
+
+    let mut files = SimpleFiles::new();
+
+    let file_id = files.add("input", self.str_input);
+
+    let mut labels = Vec::new();
+    for warning in self.warnings.iter() {
+      labels.push(
+        Label::primary(file_id, warning.position.range.0..warning.position.range.1)
+          .with_message(warning.msg.to_string()),
+      );
+    }
+
+    let diagnostic = Diagnostic::warning()
+      .with_message("parser warnings")
+      .with_labels(labels)
+      .with_notes(extended_notes(&self.warnings));
+
+    let config = term::Config::default();
+
+    let mut buffer = Vec::new();
+    let mut writer = term::termcolor::NoColor::new(&mut buffer);
+
+    term::emit(&mut writer, &config, &files, &diagnostic).ok()?;
+
+    Some(String::from_utf8(buffer).ok()?)
+  }
+
+  /// Errors and warnings collected by `parse_cddl()`, along with their
+  /// rendered report
+  pub fn diagnostics(&self) -> Diagnostics {
+    #[cfg(feature = "std")]
+    let reports = vec![
+      self.report_errors(false).ok().flatten(),
+      self.report_warnings(false).ok().flatten(),
+    ];
+    #[cfg(not(feature = "std"))]
+    let reports = vec![self.report_errors(), self.report_warnings()];
+
+    Diagnostics {
+      errors: self.errors.clone(),
+      warnings: self.warnings.clone(),
+      report: reports.into_iter().flatten().collect(),
+    }
+  }
+
   fn next_token(&mut self) -> Result<()> {
     mem::swap(&mut self.cur_token, &mut self.peek_token);
     mem::swap(&mut self.lexer_position, &mut self.peek_lexer_position);
 
-    if let Some(next_token) = self.tokens.next() {
-      let nt = next_token.map_err(Error::LEXER)?;
-      self.peek_token = nt.1;
-      self.peek_lexer_position = nt.0;
+    // Beginning and line of the input discarded due to lexer errors, which ends
+    // where the next token begins
+    let mut unreadable = None;
+
+    for next_token in self.tokens.by_ref() {
+      match next_token {
+        Ok(nt) => {
+          if let Some((begin, line)) = unreadable.take() {
+            self.skip_unreadable(begin, nt.0.range.0, line);
+          }
+
+          self.peek_token = nt.1;
+          self.peek_lexer_position = nt.0;
+        }
+        Err(e) if self.is_tolerant => {
+          unreadable.get_or_insert((self.lexer_position.range.1, e.position.line));
+
+          // The lexer consumes the offending input before returning an error.
+          // Should it ever fail twice at the same spot, treat the rest of the
+          // input as unreadable rather than looping forever
+          let is_stuck = matches!(self.errors.last(), Some(last)
+            if last.position.range == e.position.range && last.position.index == e.position.index);
+
+          self.errors.push(ParserError::from(e));
+
+          if !is_stuck {
+            continue;
+          }
+
+          if let Some((begin, line)) = unreadable.take() {
+            self.skip_unreadable(begin, self.str_input.len(), line);
+          }
+
+          self.peek_token = Token::EOF;
+        }
+        Err(e) => return Err(Error::LEXER(e)),
+      }
+
+      break;
     }
 
     Ok(())
   }
 
+  // Records the given span of input the lexer failed to read, without the
+  // surrounding whitespace
+  fn skip_unreadable(&mut self, begin: usize, end: usize, line: usize) {
+    let text = &self.str_input[begin..end];
+    let trimmed = text.trim();
+
+    if !trimmed.is_empty() {
+      let begin = begin + (text.len() - text.trim_start().len());
+      self.unreadable.push((begin, begin + trimmed.len(), line));
+    }
+  }
+
   fn advance_to_next_rule(&mut self) -> Result<()> {
     let mut is_possible_rule = false;
 
@@ -275,42 +648,118 @@ where
   }
 
   fn collect_comments(&mut self) -> Result<Option<Comments<'a>>> {
-    #[cfg_attr(not(feature = "lsp"), allow(unused_mut))]
     let mut comments: Option<Comments> = None;
 
-    while let Token::COMMENT(_comment) = self.cur_token {
-      #[cfg(feature = "lsp")]
-      comments.get_or_insert(Comments::default()).0.push(_comment);
+    while let Token::COMMENT(comment) = self.cur_token {
+      comments
+        .get_or_insert(Comments::default())
+        .0
+        .push(comment.into());
 
       self.next_token()?;
     }
 
     while let Token::NEWLINE = self.cur_token {
-      #[cfg(feature = "lsp")]
-      comments.get_or_insert(Comments::default()).0.push("\n");
+      comments
+        .get_or_insert(Comments::default())
+        .0
+        .push("\n".into());
 
       self.next_token()?;
     }
 
     if let Token::COMMENT(_) = self.cur_token {
       if let Some(c) = self.collect_comments()? {
-        #[cfg_attr(not(feature = "lsp"), allow(unused_mut))]
-        for comment in c.0.iter() {
+        for comment in c.0.into_iter() {
           comments.get_or_insert(Comments::default()).0.push(comment);
         }
       }
     }
 
+    // Line breaks alone aren't worth keeping
+    if let Some(c) = &comments {
+      if c.0.iter().all(|c| c == "\n") {
+        return Ok(None);
+      }
+    }
+
     Ok(comments)
   }
 
   /// Parses into a `CDDL` AST
   pub fn parse_cddl(&mut self) -> Result<CDDL<'a>> {
+    let (c, _) = self.parse_rules()?;
+
+    if !self.errors.is_empty() {
+      return Err(Error::PARSER);
+    }
+
+    Ok(c)
+  }
+
+  /// Parses a prelude into a `CDDL` AST. Unlike a CDDL definition, the rules of
+  /// a prelude may redefine the names of the standard prelude and aren't
+  /// reported when unreachable
+  pub fn parse_prelude(&mut self) -> Result<CDDL<'a>> {
+    self.is_prelude = true;
+
+    self.parse_cddl()
+  }
+
+  /// Parses into a best-effort `CDDL` AST. Rather than failing on the first
+  /// error, rules which fail to parse are skipped and parsing resumes at the
+  /// next rule. The returned AST contains every rule which did parse, along
+  /// with the skipped regions of the input and all collected diagnostics.
+  /// Lexer errors are also collected rather than aborting parsing. Use
+  /// `Parser::new_tolerant()` to also recover from lexer errors in the first
+  /// tokens of the input.
+  ///
+  /// Semantic analysis only runs when there are no syntax errors, since rules
+  /// which failed to parse would otherwise be reported as undefined
+  ///
+  /// # Example
+  ///
+  /// ```
+  /// use cddl::parser::Parser;
+  /// use cddl::lexer::Lexer;
+  ///
+  /// let input = r#"a = int
+  /// b = { x: }
+  /// c = tstr"#;
+  /// let mut lexer = Lexer::new(input);
+  /// let mut p = Parser::new_tolerant(lexer.iter(), input);
+  /// let partial = p.parse_cddl_partial();
+  ///
+  /// assert_eq!(partial.cddl.rules.len(), 2);
+  /// assert_eq!(partial.skipped.len(), 1);
+  /// assert!(!partial.diagnostics.errors.is_empty());
+  /// ```
+  pub fn parse_cddl_partial(&mut self) -> PartialCDDL<'a> {
+    self.is_tolerant = true;
+
+    // Lexer errors are collected when tolerant, so this never fails
+    let (cddl, skipped) = self.parse_rules().unwrap_or_default();
+
+    PartialCDDL {
+      cddl,
+      skipped,
+      diagnostics: self.diagnostics(),
+    }
+  }
+
+  // Parses all rules, returning them along with the spans of any input skipped
+  // while recovering from errors. Resolves type and group rules and, if there
+  // are no errors, runs semantic analysis
+  fn parse_rules(&mut self) -> Result<(CDDL<'a>, Vec<Span>)> {
     let mut c = CDDL::default();
+    let mut skipped = Vec::new();
 
     c.comments = self.collect_comments()?;
 
     while self.cur_token != Token::EOF {
+      let begin_rule_range = self.lexer_position.range.0;
+      let begin_rule_line = self.lexer_position.line;
+
       match self.parse_rule() {
         Ok(r) => {
           let rule_exists =
@@ -319,10 +768,10 @@ where
             self.parser_position.range = (r.span().0, r.span().1);
             self.parser_position.line = r.span().2;
 
-            self.errors.push(ParserError {
-              position: self.parser_position,
-              msg: DuplicateRuleIdentifier.into(),
-            });
+            self.errors.push(ParserError::new(
+              self.parser_position,
+              DuplicateRuleIdentifier,
+            ));
 
             continue;
           }
@@ -333,39 +782,174 @@ where
           if !self.cur_token_is(Token::EOF) {
             self.advance_to_next_rule()?;
           }
+
+          let end_rule_range = if self.cur_token_is(Token::EOF) {
+            self.str_input.len()
+          } else {
+            self.lexer_position.range.0
+          };
+
+          skipped.push((begin_rule_range, end_rule_range, begin_rule_line));
         }
         _ => continue,
       }
     }
 
-    // TODO: implement second pass over parenthesized type rules whose contents
-    // are Type2::Typename, and if the identifier refers to another group rule
-    // per the match rules in Appendix C, refactor rule into a group rule:
-    //
-    // "A rule defines a name for a type expression (production "type") or for a
-    // group expression (production "grpent"), with the intention that the
-    // semantics does not change when the name is replaced by its (parenthesized
-    // if needed) definition.  Note that whether the name defined by a rule
-    // stands for a type or a group isn't always determined by syntax alone:
-    // e.g., "a = b" can make "a" a type if "b" is a type, or a group if "b" is
-    // a group.  More subtly, in "a = (b)", "a" may be used as a type if "b" is
-    // a type, or as a group both when "b" is a group and when "b" is a type (a
-    // good convention to make the latter case stand out to the human reader is
-    // to write "a = (b,)")."
+    skipped.append(&mut self.unreadable);
+    skipped.sort_by_key(|s| s.0);
+
+    attach_comments(&mut c);
+    self.resolve_rule_kinds(&mut c);
+
     if !self.errors.is_empty() {
-      return Err(Error::PARSER);
+      return Ok((c, skipped));
     }
 
     if c.rules.is_empty() {
-      self.errors.push(ParserError {
-        position: self.parser_position,
-        msg: NoRulesDefined.into(),
-      });
+      self
+        .errors
+        .push(ParserError::new(self.parser_position, NoRulesDefined));
 
-      return Err(Error::PARSER);
+      return Ok((c, skipped));
     }
 
-    Ok(c)
+    self.analyze(&c);
+
+    if let Some(prelude) = self.prelude {
+      prelude::merge(&mut c, prelude);
+    }
+
+    Ok((c, skipped))
+  }
+
+  /// Runs semantic analysis over the parsed rules. Non-productive rules are
+  /// collected as errors, as are undefined identifiers and generic argument
+  /// count mismatches with `with_semantic_errors()`. Everything else is
+  /// collected as warnings
+  fn analyze(&mut self, c: &CDDL<'a>) {
+    let issues = match self.prelude {
+      Some(prelude) => semantic::analyze_with_prelude(c, prelude),
+      None => semantic::analyze(c),
+    };
+
+    for issue in issues {
+      // The rules of a prelude are meant to be referenced by other definitions
+      if self.is_prelude && issue.msg == UnreachableRule {
+        continue;
+      }
+
+      self.parser_position.range = (issue.span.0, issue.span.1);
+      self.parser_position.line = issue.span.2;
+
+      let mut error = ParserError::new(self.parser_position, issue.msg);
+      if !self.is_strict {
+        if let UndefinedIdentifier | GenericArgumentCountMismatch = issue.msg {
+          error.severity = Severity::Warning;
+        }
+      }
+
+      match error.severity {
+        Severity::Error => self.errors.push(error),
+        Severity::Warning => self.warnings.push(error),
+      }
+    }
+  }
+
+  /// Second pass over the parsed rules which, per Appendix C, turns type rules
+  /// whose value is a bare (possibly parenthesized) name referring to a group
+  /// into group rules:
+  ///
+  /// "A rule defines a name for a type expression (production "type") or for a
+  /// group expression (production "grpent"), with the intention that the
+  /// semantics does not change when the name is replaced by its (parenthesized
+  /// if needed) definition.  Note that whether the name defined by a rule
+  /// stands for a type or a group isn't always determined by syntax alone:
+  /// e.g., "a = b" can make "a" a type if "b" is a type, or a group if "b" is
+  /// a group.  More subtly, in "a = (b)", "a" may be used as a type if "b" is
+  /// a type, or as a group both when "b" is a group and when "b" is a type (a
+  /// good convention to make the latter case stand out to the human reader is
+  /// to write "a = (b,)")."
+  ///
+  /// Rules whose kind cannot be determined because they only ever refer to one
+  /// another, and rule names defined as both a type and a group, are reported
+  /// as errors
+  fn resolve_rule_kinds(&mut self, c: &mut CDDL<'a>) {
+    let error_count = self.errors.len();
+
+    let kinds = c
+      .rules
+      .iter()
+      .map(|r| rule_kind(&c.rules, r, &mut Vec::new()))
+      .collect::<Vec<_>>();
+
+    for (idx, kind) in kinds.iter().enumerate() {
+      let rule = &c.rules[idx];
+
+      let msg = match kind {
+        None => Some(AmbiguousTypeOrGroupRule),
+        Some(kind) => {
+          // The first rule with a given name determines its kind. Subsequent
+          // choice alternates must agree
+          let first_kind = c
+            .rules
+            .iter()
+            .position(|r| r.name() == rule.name())
+            .and_then(|first_idx| kinds[first_idx]);
+
+          if matches!(first_kind, Some(fk) if fk != *kind)
+            || (*kind == RuleKind::Group
+              && matches!(rule, Rule::Type { rule, .. } if rule.is_type_choice_alternate))
+          {
+            Some(ConflictingTypeAndGroupRule)
+          } else {
+            None
+          }
+        }
+      };
+
+      if let Some(msg) = msg {
+        self.parser_position.range = (rule.span().0, rule.span().1);
+        self.parser_position.line = rule.span().2;
+
+        self
+          .errors
+          .push(ParserError::new(self.parser_position, msg));
+      }
+    }
+
+    if self.errors.len() != error_count {
+      return;
+    }
+
+    for (rule, kind) in c.rules.iter_mut().zip(kinds) {
+      if kind != Some(RuleKind::Group) {
+        continue;
+      }
+
+      if let Rule::Type {
+        rule: tr,
+        comments_before_rule,
+        comments_after_rule,
+        span,
+      } = rule
+      {
+        if let Some(entry) = group_entry_from_type(&tr.value) {
+          *rule = Rule::Group {
+            rule: Box::from(GroupRule {
+              name: tr.name.clone(),
+              generic_params: tr.generic_params.take(),
+              is_group_choice_alternate: false,
+              entry,
+              comments_before_assigng: tr.comments_before_assignt.take(),
+              comments_after_assigng: tr.comments_after_assignt.take(),
+            }),
+            comments_before_rule: comments_before_rule.take(),
+            comments_after_rule: comments_after_rule.take(),
+            span: *span,
+          };
+        }
+      }
+    }
   }
 
   fn parse_rule(&mut self) -> Result<Rule<'a>> {
@@ -373,16 +957,17 @@ where
     let begin_rule_line = self.lexer_position.line;
     let begin_rule_col = self.lexer_position.column;
 
-    let ident = match &self.cur_token {
-      Token::IDENT(i) => self.identifier_from_ident_token(*i),
+    let ident = match (&self.cur_token, self.cur_token.in_standard_prelude()) {
+      (Token::IDENT(i), _) => self.identifier_from_ident_token(*i),
+      (_, Some(name)) if self.is_prelude => self.identifier_from_ident_token((name, None)),
       _ => {
         self.parser_position.range = self.lexer_position.range;
         self.parser_position.line = self.lexer_position.line;
 
-        self.errors.push(ParserError {
-          position: self.parser_position,
-          msg: InvalidRuleIdentifier.into(),
-        });
+        self.errors.push(ParserError::new(
+          self.parser_position,
+          InvalidRuleIdentifier,
+        ));
 
         return Err(Error::PARSER);
       }
@@ -405,10 +990,10 @@ where
       self.parser_position.range = (begin_rule_range, self.lexer_position.range.1);
       self.parser_position.line = self.lexer_position.line;
 
-      self.errors.push(ParserError {
-        position: self.parser_position,
-        msg: MsgType::MissingAssignmentToken.into(),
-      });
+      self.errors.push(ParserError::new(
+        self.parser_position,
+        MsgType::MissingAssignmentToken,
+      ));
 
       return Err(Error::PARSER);
     }
@@ -428,10 +1013,10 @@ where
           self.parser_position.range = (begin_rule_range, self.lexer_position.range.1);
           self.parser_position.line = self.lexer_position.line;
 
-          self.errors.push(ParserError {
-            position: self.parser_position,
-            msg: MsgType::TypeSocketNamesMustBeTypeAugmentations.into(),
-          });
+          self.errors.push(ParserError::new(
+            self.parser_position,
+            MsgType::TypeSocketNamesMustBeTypeAugmentations,
+          ));
 
           return Err(Error::PARSER);
         }
@@ -439,10 +1024,10 @@ where
           self.parser_position.range = (begin_rule_range, self.lexer_position.range.1);
           self.parser_position.line = self.lexer_position.line;
 
-          self.errors.push(ParserError {
-            position: self.parser_position,
-            msg: MsgType::GroupSocketNamesMustBeGroupAugmentations.into(),
-          });
+          self.errors.push(ParserError::new(
+            self.parser_position,
+            MsgType::GroupSocketNamesMustBeGroupAugmentations,
+          ));
 
           return Err(Error::PARSER);
         }
@@ -477,6 +1062,7 @@ where
               comments_before_assigng: comments_before_assign,
               comments_after_assigng: comments_after_assign,
             }),
+            comments_before_rule: None,
             comments_after_rule,
             span,
           })
@@ -504,6 +1090,7 @@ where
               comments_before_assignt: comments_before_assign,
               comments_after_assignt: comments_after_assign,
             },
+            comments_before_rule: None,
             comments_after_rule,
             span,
           })
@@ -563,6 +1150,7 @@ where
                             comments_before_assignt: comments_before_assign,
                             comments_after_assignt: comments_after_assign,
                           },
+                          comments_before_rule: None,
                           comments_after_rule,
                           span: (begin_rule_range, end_rule_range, begin_rule_line),
                         });
@@ -584,6 +1172,7 @@ where
             comments_before_assigng: comments_before_assign,
             comments_after_assigng: comments_after_assign,
           }),
+          comments_before_rule: None,
           comments_after_rule,
           span: (begin_rule_range, end_rule_range, begin_rule_line),
         })
@@ -601,15 +1190,15 @@ where
           || self.cur_token_is(Token::TCHOICEALT)
           || self.cur_token_is(Token::GCHOICEALT)
         {
-          self.errors.push(ParserError {
-            position: Position {
+          self.errors.push(ParserError::new(
+            Position {
               line: begin_rule_line,
               column: begin_rule_col,
               range: (ident.span.0, ident.span.1),
               index: self.parser_position.range.0,
             },
-            msg: IncompleteRuleEntry.into(),
-          });
+            IncompleteRuleEntry,
+          ));
 
           return Err(Error::PARSER);
         }
@@ -629,6 +1218,7 @@ where
             comments_before_assignt: comments_before_assign,
             comments_after_assignt: comments_after_assign,
           },
+          comments_before_rule: None,
           comments_after_rule,
           span,
         })
@@ -666,10 +1256,9 @@ where
             self.parser_position.range = (begin_range + 1, self.peek_lexer_position.range.0);
             self.parser_position.line = self.lexer_position.line;
 
-            self.errors.push(ParserError {
-              position: self.parser_position,
-              msg: InvalidGenericSyntax.into(),
-            });
+            self
+              .errors
+              .push(ParserError::new(self.parser_position, InvalidGenericSyntax));
 
             return Err(Error::PARSER);
           }
@@ -679,10 +1268,10 @@ where
           self.parser_position.range = (self.lexer_position.range.0, self.lexer_position.range.1);
           self.parser_position.line = self.lexer_position.line;
 
-          self.errors.push(ParserError {
-            position: self.parser_position,
-            msg: InvalidGenericIdentifier.into(),
-          });
+          self.errors.push(ParserError::new(
+            self.parser_position,
+            InvalidGenericIdentifier,
+          ));
 
           return Err(Error::PARSER);
         }
@@ -690,10 +1279,9 @@ where
           self.parser_position.range = (begin_range, self.lexer_position.range.0);
           self.parser_position.line = self.lexer_position.line;
 
-          self.errors.push(ParserError {
-            position: self.parser_position,
-            msg: InvalidGenericSyntax.into(),
-          });
+          self
+            .errors
+            .push(ParserError::new(self.parser_position, InvalidGenericSyntax));
 
           return Err(Error::PARSER);
         }
@@ -743,10 +1331,10 @@ where
       }
 
       if self.cur_token_is(Token::EOF) {
-        self.errors.push(ParserError {
-          position: self.parser_position,
-          msg: MissingGenericClosingDelimiter.into(),
-        });
+        self.errors.push(ParserError::new(
+          self.parser_position,
+          MissingGenericClosingDelimiter,
+        ));
 
         return Err(Error::PARSER);
       }
@@ -766,6 +1354,34 @@ where
     Ok(generic_args)
   }
 
+  // Parses the type given between '<' and '>' as the tag number or argument of
+  // a data item, e.g. the '<1000..1999>' of '#6.<1000..1999>(any)'. The '>' is
+  // left as the current token
+  fn parse_head_number_type(&mut self) -> Result<Type<'a>> {
+    self.next_token()?;
+    if !self.cur_token_is(Token::LANGLEBRACKET) {
+      self
+        .errors
+        .push(ParserError::new(self.parser_position, InvalidTagSyntax));
+
+      return Err(Error::PARSER);
+    }
+
+    self.next_token()?;
+
+    let t = self.parse_type(None)?;
+
+    if !self.cur_token_is(Token::RANGLEBRACKET) {
+      self
+        .errors
+        .push(ParserError::new(self.parser_position, InvalidTagSyntax));
+
+      return Err(Error::PARSER);
+    }
+
+    Ok(t)
+  }
+
   fn parse_type(&mut self, parenthesized_type: Option<Type2<'a>>) -> Result<Type<'a>> {
     self.parser_position.range = self.lexer_position.range;
     self.parser_position.line = self.lexer_position.line;
@@ -857,11 +1473,22 @@ where
           span,
         })
       }
+      Token::CUSTOMCONTROL(ctrl) => {
+        span.0 = self.lexer_position.range.0;
+
+        Some(RangeCtlOp::CtlOp {
+          ctrl: (*ctrl).into(),
+          span,
+        })
+      }
       _ => match token::control_str_from_token(&self.cur_token) {
         Some(ctrl) => {
           span.0 = self.lexer_position.range.0;
 
-          Some(RangeCtlOp::CtlOp { ctrl, span })
+          Some(RangeCtlOp::CtlOp {
+            ctrl: ctrl.into(),
+            span,
+          })
         }
         None => None,
       },
@@ -918,7 +1545,10 @@ where
         );
 
         match value {
-          token::Value::TEXT(t) => Ok(Type2::TextValue { value: t, span }),
+          token::Value::TEXT(t) => Ok(Type2::TextValue {
+            value: t.clone(),
+            span,
+          }),
           token::Value::INT(i) => Ok(Type2::IntValue { value: *i, span }),
           token::Value::UINT(ui) => Ok(Type2::UintValue { value: *ui, span }),
           token::Value::FLOAT(f) => Ok(Type2::FloatValue { value: *f, span }),
@@ -1123,10 +1753,9 @@ where
           });
         }
 
-        self.errors.push(ParserError {
-          position: self.parser_position,
-          msg: InvalidUnwrapSyntax.into(),
-        });
+        self
+          .errors
+          .push(ParserError::new(self.parser_position, InvalidUnwrapSyntax));
 
         Err(Error::PARSER)
       }
@@ -1196,36 +1825,44 @@ where
             })
           }
           _ => {
-            self.errors.push(ParserError {
-              position: self.parser_position,
-              msg: InvalidGroupToChoiceEnumSyntax.into(),
-            });
+            self.errors.push(ParserError::new(
+              self.parser_position,
+              InvalidGroupToChoiceEnumSyntax,
+            ));
             Err(Error::PARSER)
           }
         }
       }
 
-      // # 6 ["." uint] ( type )
-      // # DIGIT ["." uint]   ; major/ai
-      // #                    ; any
+      // # 6 ["." head-number] ( type )
+      // # DIGIT ["." head-number]   ; major/ai
+      // #                           ; any
       // Token::TAG(tag) => match tag {
       //   Tag::DATA(data) => Ok(Type2::TaggedData(data.clone())),
       //   Tag::MAJORTYPE(mt) => Ok(Type2::DataMajorType(*mt)),
       //   Tag::ANY => Ok(Type2::Any),
       // },
-      Token::TAG(t) => {
+      Token::TAG(_) | Token::TYPEDTAG(_) => {
         let begin_type2_range = self.lexer_position.range.0;
         let begin_type2_line = self.lexer_position.line;
 
-        match *t {
+        let t = match self.cur_token {
+          Token::TYPEDTAG(mt) => (
+            Some(mt),
+            Some(HeadNumber::Type(self.parse_head_number_type()?)),
+          ),
+          Token::TAG((mt, n)) => (mt, n.map(HeadNumber::Uint)),
+          _ => (None, None),
+        };
+
+        match t {
           // Tagged data item containing the given type as the tagged value
           (Some(6), tag) => {
             self.next_token()?;
             if !self.cur_token_is(Token::LPAREN) {
-              self.errors.push(ParserError {
-                position: self.parser_position,
-                msg: InvalidTagSyntax.into(),
-              });
+              self
+                .errors
+                .push(ParserError::new(self.parser_position, InvalidTagSyntax));
 
               return Err(Error::PARSER);
             }
@@ -1239,10 +1876,9 @@ where
             let comments_after_type = self.collect_comments()?;
 
             if !self.cur_token_is(Token::RPAREN) {
-              self.errors.push(ParserError {
-                position: self.parser_position,
-                msg: InvalidTagSyntax.into(),
-              });
+              self
+                .errors
+                .push(ParserError::new(self.parser_position, InvalidTagSyntax));
 
               return Err(Error::PARSER);
             }
@@ -1297,10 +1933,10 @@ where
           self.parser_position.range = self.lexer_position.range;
 
           if self.cur_token_is(Token::COLON) || self.cur_token_is(Token::ARROWMAP) {
-            self.errors.push(ParserError {
-              position: self.parser_position,
-              msg: MissingGroupEntryMemberKey.into(),
-            });
+            self.errors.push(ParserError::new(
+              self.parser_position,
+              MissingGroupEntryMemberKey,
+            ));
 
             return Err(Error::PARSER);
           }
@@ -1309,18 +1945,17 @@ where
             || self.cur_token_is(Token::RBRACKET)
             || self.cur_token_is(Token::RPAREN)
           {
-            self.errors.push(ParserError {
-              position: self.parser_position,
-              msg: MissingGroupEntry.into(),
-            });
+            self
+              .errors
+              .push(ParserError::new(self.parser_position, MissingGroupEntry));
 
             return Err(Error::PARSER);
           }
 
-          self.errors.push(ParserError {
-            position: self.parser_position,
-            msg: InvalidGroupEntrySyntax.into(),
-          });
+          self.errors.push(ParserError::new(
+            self.parser_position,
+            InvalidGroupEntrySyntax,
+          ));
 
           Err(Error::PARSER)
         }
@@ -1362,10 +1997,10 @@ where
 
     if let Some(cd) = closing_delimiter.as_ref() {
       if cd != &self.cur_token {
-        self.errors.push(ParserError {
-          position: self.lexer_position,
-          msg: MissingClosingDelimiter.into(),
-        });
+        self.errors.push(ParserError::new(
+          self.lexer_position,
+          MissingClosingDelimiter,
+        ));
 
         return Err(Error::PARSER);
       }
@@ -1393,7 +2028,9 @@ where
       grpchoice.span.0 = self.lexer_position.range.0;
     };
 
-    grpchoice.comments_before_grpchoice = self.collect_comments()?;
+    if grpchoice.comments_before_grpchoice.is_none() {
+      grpchoice.comments_before_grpchoice = self.collect_comments()?;
+    }
 
     // TODO: The logic in this while loop is quite messy. Need to figure out a
     // better way to advance the token when parsing the entries in a group
@@ -1492,10 +2129,9 @@ where
       let comments_after_group = self.collect_comments()?;
 
       if !self.cur_token_is(Token::RPAREN) {
-        self.errors.push(ParserError {
-          position: self.lexer_position,
-          msg: MissingClosingParend.into(),
-        });
+        self
+          .errors
+          .push(ParserError::new(self.lexer_position, MissingClosingParend));
         return Err(Error::PARSER);
       }
 
@@ -1691,10 +2327,10 @@ where
       let comments_after_cut = self.collect_comments()?;
 
       if !self.cur_token_is(Token::ARROWMAP) {
-        self.errors.push(ParserError {
-          position: self.lexer_position,
-          msg: InvalidMemberKeyArrowMapSyntax.into(),
-        });
+        self.errors.push(ParserError::new(
+          self.lexer_position,
+          InvalidMemberKeyArrowMapSyntax,
+        ));
         return Err(Error::PARSER);
       }
 
@@ -1838,10 +2474,10 @@ where
           let comments_after_cut = self.collect_comments()?;
 
           if !self.cur_token_is(Token::ARROWMAP) {
-            self.errors.push(ParserError {
-              position: self.lexer_position,
-              msg: InvalidMemberKeyArrowMapSyntax.into(),
-            });
+            self.errors.push(ParserError::new(
+              self.lexer_position,
+              InvalidMemberKeyArrowMapSyntax,
+            ));
             return Err(Error::PARSER);
           }
 
@@ -1867,10 +2503,10 @@ where
           let comments = self.collect_comments()?;
 
           if !self.cur_token_is(Token::ARROWMAP) && !self.cur_token_is(Token::COLON) {
-            self.errors.push(ParserError {
-              position: self.lexer_position,
-              msg: InvalidMemberKeySyntax.into(),
-            });
+            self.errors.push(ParserError::new(
+              self.lexer_position,
+              InvalidMemberKeySyntax,
+            ));
             return Err(Error::PARSER);
           }
 
@@ -1922,7 +2558,6 @@ where
             has_group_entries = true;
           }
 
-          // TODO: parse nested comments
           if self.cur_token_is(Token::LPAREN) {
             nested_parend_count += 1;
           }
@@ -1944,13 +2579,22 @@ where
 
           self.next_token()?;
 
-          comments_after_type_or_group = self.collect_comments()?;
+          if closing_parend {
+            comments_after_type_or_group = self.collect_comments()?;
+          } else {
+            // Nested comments are handed to the parser of the parenthesized
+            // tokens below so they end up attached to the nested type or group
+            while let Token::COMMENT(_) | Token::NEWLINE = self.cur_token {
+              tokens.push(Ok((self.lexer_position, self.cur_token.clone())));
+
+              self.next_token()?;
+            }
+          }
 
           if self.cur_token_is(Token::EOF) {
-            self.errors.push(ParserError {
-              position: self.lexer_position,
-              msg: MissingClosingParend.into(),
-            });
+            self
+              .errors
+              .push(ParserError::new(self.lexer_position, MissingClosingParend));
 
             return Err(Error::PARSER);
           }
@@ -2000,10 +2644,10 @@ where
           let comments_after_cut = self.collect_comments()?;
 
           if !self.cur_token_is(Token::ARROWMAP) {
-            self.errors.push(ParserError {
-              position: self.lexer_position,
-              msg: InvalidMemberKeyArrowMapSyntax.into(),
-            });
+            self.errors.push(ParserError::new(
+              self.lexer_position,
+              InvalidMemberKeyArrowMapSyntax,
+            ));
             return Err(Error::PARSER);
           }
 
@@ -2108,10 +2752,10 @@ where
           let comments_after_cut = self.collect_comments()?;
 
           if !self.cur_token_is(Token::ARROWMAP) {
-            self.errors.push(ParserError {
-              position: self.lexer_position,
-              msg: InvalidMemberKeyArrowMapSyntax.into(),
-            });
+            self.errors.push(ParserError::new(
+              self.lexer_position,
+              InvalidMemberKeyArrowMapSyntax,
+            ));
             return Err(Error::PARSER);
           }
 
@@ -2223,7 +2867,7 @@ where
 
           Occur::Exact {
             lower: None,
-            upper: Some(*u),
+            upper: Some(usize::try_from(*u).unwrap_or(usize::MAX)),
             span: (
               self.parser_position.range.0,
               self.parser_position.range.1,
@@ -2253,7 +2897,7 @@ where
       Token::VALUE(_) => {
         let lower = if let Token::VALUE(value) = &self.cur_token {
           if let token::Value::UINT(li) = *value {
-            Some(li)
+            Some(usize::try_from(li).unwrap_or(usize::MAX))
           } else {
             None
           }
@@ -2266,10 +2910,10 @@ where
             return Ok(None);
           }
 
-          self.errors.push(ParserError {
-            position: self.lexer_position,
-            msg: InvalidOccurrenceSyntax.into(),
-          });
+          self.errors.push(ParserError::new(
+            self.lexer_position,
+            InvalidOccurrenceSyntax,
+          ));
 
           return Err(Error::PARSER);
         }
@@ -2286,7 +2930,7 @@ where
 
             self.next_token()?;
 
-            Some(ui)
+            Some(usize::try_from(ui).unwrap_or(usize::MAX))
           } else {
             None
           }
@@ -2335,7 +2979,7 @@ where
     ident: (&'a str, Option<token::SocketPlug>),
   ) -> Identifier<'a> {
     Identifier {
-      ident: ident.0,
+      ident: ident.0.into(),
       socket: ident.1,
       span: (
         self.lexer_position.range.0,
@@ -2346,6 +2990,350 @@ where
   }
 }
 
+// Extended messages of the given errors, without duplicates, to be rendered as
+// diagnostic notes
+fn extended_notes(errors: &[ParserError]) -> Vec<String> {
+  let mut notes: Vec<String> = Vec::new();
+
+  for extended in errors.iter().filter_map(|e| e.msg.extended.as_ref()) {
+    if !notes.contains(extended) {
+      notes.push(extended.clone());
+    }
+  }
+
+  notes
+}
+
+/// Whether a rule defines a type or a group
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RuleKind {
+  Type,
+  Group,
+}
+
+// If the type consists of nothing but a single (possibly parenthesized) name,
+// returns the name along with its generic arguments
+fn bare_typename<'a, 'b>(
+  t: &'b Type<'a>,
+) -> Option<(&'b Identifier<'a>, Option<&'b GenericArgs<'a>>)> {
+  if t.type_choices.len() != 1 || t.type_choices[0].type1.operator.is_some() {
+    return None;
+  }
+
+  match &t.type_choices[0].type1.type2 {
+    Type2::Typename {
+      ident,
+      generic_args,
+      ..
+    } => Some((ident, generic_args.as_ref())),
+    Type2::ParenthesizedType { pt, .. } => bare_typename(pt),
+    _ => None,
+  }
+}
+
+// Determines the kind of a rule, following bare names to the rules they refer
+// to. Returns `None` if the kind can't be determined because the names only
+// refer back to one another
+fn rule_kind(rules: &[Rule], rule: &Rule, visited: &mut Vec<String>) -> Option<RuleKind> {
+  match rule {
+    Rule::Group { .. } => Some(RuleKind::Group),
+    Rule::Type { rule, .. } => match bare_typename(&rule.value) {
+      Some((ident, _)) => {
+        let is_generic_param = rule
+          .generic_params
+          .iter()
+          .flat_map(|gp| gp.params.iter())
+          .any(|p| p.param == *ident);
+
+        if is_generic_param {
+          return Some(RuleKind::Type);
+        }
+
+        ident_kind(rules, ident, visited)
+      }
+      None => Some(RuleKind::Type),
+    },
+  }
+}
+
+// Determines whether a referenced name is a type or a group
+fn ident_kind(rules: &[Rule], ident: &Identifier, visited: &mut Vec<String>) -> Option<RuleKind> {
+  match ident.socket {
+    Some(SocketPlug::GROUP) => return Some(RuleKind::Group),
+    Some(SocketPlug::TYPE) => return Some(RuleKind::Type),
+    None => (),
+  }
+
+  let name = ident.to_string();
+
+  // Names from the standard prelude and undefined names are taken to be types
+  let rule = match rules.iter().find(|r| r.name() == name) {
+    Some(rule) => rule,
+    None => return Some(RuleKind::Type),
+  };
+
+  if visited.contains(&name) {
+    return None;
+  }
+
+  visited.push(name);
+  let kind = rule_kind(rules, rule, visited);
+  visited.pop();
+
+  kind
+}
+
+// Converts a type consisting of a bare name into the equivalent group entry,
+// retaining the parentheses of "a = (b)" as an inline group
+fn group_entry_from_type<'a>(t: &Type<'a>) -> Option<GroupEntry<'a>> {
+  let tc = t.type_choices.first()?;
+
+  match &tc.type1.type2 {
+    Type2::Typename {
+      ident,
+      generic_args,
+      span,
+    } => Some(GroupEntry::TypeGroupname {
+      ge: TypeGroupnameEntry {
+        occur: None,
+        name: ident.clone(),
+        generic_args: generic_args.clone(),
+      },
+      span: *span,
+      leading_comments: tc.comments_before_type.clone(),
+      trailing_comments: tc.comments_after_type.clone(),
+    }),
+    Type2::ParenthesizedType {
+      pt,
+      span,
+      comments_before_type,
+      comments_after_type,
+    } => Some(GroupEntry::InlineGroup {
+      occur: None,
+      group: Group {
+        group_choices: vec![GroupChoice {
+          group_entries: vec![(group_entry_from_type(pt)?, OptionalComma::default())],
+          span: pt.span,
+          comments_before_grpchoice: None,
+        }],
+        span: *span,
+      },
+      span: *span,
+      comments_before_group: comments_before_type.clone(),
+      comments_after_group: comments_after_type.clone(),
+    }),
+    _ => None,
+  }
+}
+
+// Comments are collected following the token they come after, so comments on
+// lines of their own end up attached to whatever precedes them. Moves them to
+// the rule, group entry or type choice following them instead, which is the
+// one they usually describe
+fn attach_comments(c: &mut CDDL) {
+  let mut leading = c.comments.take();
+  let rule_count = c.rules.len();
+
+  for (idx, rule) in c.rules.iter_mut().enumerate() {
+    let (comments_before_rule, comments_after_rule) = match rule {
+      Rule::Type {
+        rule,
+        comments_before_rule,
+        comments_after_rule,
+        ..
+      } => {
+        attach_type_comments(&mut rule.value);
+
+        (comments_before_rule, comments_after_rule)
+      }
+      Rule::Group {
+        rule,
+        comments_before_rule,
+        comments_after_rule,
+        ..
+      } => {
+        attach_group_entry_comments(&mut rule.entry);
+
+        (comments_before_rule, comments_after_rule)
+      }
+    };
+
+    *comments_before_rule = join_comments(leading.take(), comments_before_rule.take());
+
+    // Comments following the last rule don't describe anything, so they stay
+    // where they are
+    if idx + 1 < rule_count {
+      leading = take_own_line_comments(comments_after_rule);
+    }
+  }
+
+  c.comments = leading;
+}
+
+fn attach_type_comments(t: &mut Type) {
+  let mut leading = None;
+  let choice_count = t.type_choices.len();
+
+  for (idx, tc) in t.type_choices.iter_mut().enumerate() {
+    tc.comments_before_type = join_comments(leading.take(), tc.comments_before_type.take());
+
+    attach_type2_comments(&mut tc.type1.type2);
+    if let Some(o) = &mut tc.type1.operator {
+      attach_type2_comments(&mut o.type2);
+    }
+
+    if idx + 1 < choice_count {
+      leading = join_comments(
+        take_own_line_comments(&mut tc.type1.comments_after_type),
+        take_own_line_comments(&mut tc.comments_after_type),
+      );
+    }
+  }
+}
+
+fn attach_type2_comments(t2: &mut Type2) {
+  match t2 {
+    Type2::ParenthesizedType { pt: t, .. } | Type2::TaggedData { t, .. } => attach_type_comments(t),
+    Type2::Map {
+      group,
+      comments_before_group,
+      ..
+    }
+    | Type2::Array {
+      group,
+      comments_before_group,
+      ..
+    }
+    | Type2::ChoiceFromInlineGroup {
+      group,
+      comments_before_group,
+      ..
+    } => attach_group_comments(group, comments_before_group),
+    _ => (),
+  }
+}
+
+fn attach_group_entry_comments(entry: &mut GroupEntry) {
+  match entry {
+    GroupEntry::ValueMemberKey { ge, .. } => {
+      if let Some(MemberKey::Type1 { t1, .. }) = &mut ge.member_key {
+        attach_type2_comments(&mut t1.type2);
+      }
+
+      attach_type_comments(&mut ge.entry_type);
+    }
+    GroupEntry::InlineGroup {
+      group,
+      comments_before_group,
+      ..
+    } => attach_group_comments(group, comments_before_group),
+    GroupEntry::TypeGroupname { .. } => (),
+  }
+}
+
+// Comments on lines of their own are moved to the entries following them. Inline
+// groups are skipped since their leading comments follow the opening
+// parenthesis
+fn attach_group_comments<'a>(
+  group: &mut Group<'a>,
+  comments_before_group: &mut Option<Comments<'a>>,
+) {
+  let describes_entry = |entry: Option<&(GroupEntry, OptionalComma)>| {
+    matches!(
+      entry,
+      Some((GroupEntry::ValueMemberKey { .. }, _)) | Some((GroupEntry::TypeGroupname { .. }, _))
+    )
+  };
+
+  for (idx, gc) in group.group_choices.iter_mut().enumerate() {
+    let mut leading = None;
+
+    if describes_entry(gc.group_entries.first()) {
+      leading = take_own_line_comments(&mut gc.comments_before_grpchoice);
+
+      if idx == 0 {
+        leading = join_comments(take_own_line_comments(comments_before_group), leading);
+      }
+    }
+
+    for entry_idx in 0..gc.group_entries.len() {
+      let describes_next_entry = describes_entry(gc.group_entries.get(entry_idx + 1));
+      let (entry, comma) = &mut gc.group_entries[entry_idx];
+
+      attach_group_entry_comments(entry);
+
+      match entry {
+        GroupEntry::ValueMemberKey {
+          leading_comments,
+          trailing_comments,
+          ..
+        }
+        | GroupEntry::TypeGroupname {
+          leading_comments,
+          trailing_comments,
+          ..
+        } => {
+          *leading_comments = join_comments(leading.take(), leading_comments.take());
+
+          if describes_next_entry {
+            leading = take_own_line_comments(trailing_comments);
+          }
+        }
+        GroupEntry::InlineGroup {
+          comments_after_group,
+          ..
+        } => {
+          if describes_next_entry {
+            leading = take_own_line_comments(comments_after_group);
+          }
+        }
+      }
+
+      if describes_next_entry {
+        leading = join_comments(
+          leading,
+          take_own_line_comments(&mut comma.trailing_comments),
+        );
+      }
+    }
+  }
+}
+
+// Splits off the comments on lines of their own, i.e. all but a first comment
+// which follows the preceding token on the same line
+fn take_own_line_comments<'a>(comments: &mut Option<Comments<'a>>) -> Option<Comments<'a>> {
+  let c = comments.as_mut()?;
+  let at = match c.0.first() {
+    Some(first) if first != "\n" => 1,
+    _ => 0,
+  };
+
+  if c.0[at..].iter().all(|c| c == "\n") {
+    return None;
+  }
+
+  let own_line_comments = c.0.split_off(at);
+  if c.0.is_empty() {
+    *comments = None;
+  }
+
+  Some(Comments(own_line_comments))
+}
+
+fn join_comments<'a>(
+  first: Option<Comments<'a>>,
+  second: Option<Comments<'a>>,
+) -> Option<Comments<'a>> {
+  match (first, second) {
+    (Some(mut first), Some(second)) => {
+      first.0.extend(second.0);
+
+      Some(first)
+    }
+    (first, second) => first.or(second),
+  }
+}
+
 /// Returns a `ast::CDDL` from a `&str`
 ///
 /// # Arguments
@@ -2353,7 +3341,7 @@ where
 /// * `lexer` - A mutable reference to a `lexer::Lexer`. Can be created from
 ///   `cddl::lexer_from_str()`
 /// * `input` - A string slice with the CDDL text input
-/// * `print_stderr` - When true, print any errors to stderr
+/// * `print_stderr` - When true, print any errors or warnings to stderr
 ///
 /// # Example
 ///
@@ -2368,29 +3356,103 @@ pub fn cddl_from_str<'a>(
   lexer: &'a mut Lexer<'a>,
   input: &'a str,
   print_stderr: bool,
-) -> std::result::Result<CDDL<'a>, String> {
-  match Parser::new(lexer.iter(), input).map_err(|e| e.to_string()) {
-    Ok(mut p) => match p.parse_cddl() {
-      Ok(c) => Ok(c),
-      Err(Error::PARSER) => {
-        let e = if print_stderr {
-          p.report_errors(true)
-        } else {
-          p.report_errors(false)
-        };
+) -> Result<CDDL<'a>> {
+  parse_cddl_reporting(Parser::new(lexer.iter(), input)?, print_stderr)
+}
 
-        if let Ok(Some(e)) = e {
-          return Err(e);
-        }
+/// Returns a `ast::CDDL` from a `&str`, with the rules of a prelude extending
+/// the standard prelude merged with those of the input. See
+/// `Parser::with_prelude()`
+///
+/// # Arguments
+///
+/// * `lexer` - A mutable reference to a `lexer::Lexer`. Can be created from
+///   `cddl::lexer_from_str()`
+/// * `input` - A string slice with the CDDL text input
+/// * `prelude` - A prelude parsed with `prelude::prelude_from_str()`
+/// * `print_stderr` - When true, print any errors or warnings to stderr
+///
+/// # Example
+///
+/// ```
+/// use cddl::{lexer_from_str, parser::cddl_from_str_with_prelude, prelude::prelude_from_str};
+///
+/// let prelude_input = r#"cose-sign1 = #6.18([* any])"#;
+/// let mut prelude_lexer = lexer_from_str(prelude_input);
+/// let prelude = prelude_from_str(&mut prelude_lexer, prelude_input).unwrap();
+///
+/// let input = r#"message = { payload: cose-sign1 }"#;
+/// assert!(cddl_from_str_with_prelude(&mut lexer_from_str(input), input, &prelude, true).is_ok());
+/// ```
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(feature = "std")]
+pub fn cddl_from_str_with_prelude<'a>(
+  lexer: &'a mut Lexer<'a>,
+  input: &'a str,
+  prelude: &'a CDDL<'a>,
+  print_stderr: bool,
+) -> Result<CDDL<'a>> {
+  parse_cddl_reporting(
+    Parser::new(lexer.iter(), input)?.with_prelude(prelude),
+    print_stderr,
+  )
+}
+
+// Parses into a `CDDL` AST, returning any errors as diagnostics and optionally
+// printing them along with any warnings to stderr
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(feature = "std")]
+fn parse_cddl_reporting<'a, I>(mut p: Parser<'a, I>, print_stderr: bool) -> Result<CDDL<'a>>
+where
+  I: Iterator<Item = lexer::Item<'a>>,
+{
+  match p.parse_cddl() {
+    Ok(c) => {
+      if print_stderr {
+        let _ = p.report_warnings(true);
+      }
 
-        Err(Error::PARSER.to_string())
+      Ok(c)
+    }
+    Err(Error::PARSER) => {
+      if print_stderr {
+        let _ = p.report_errors(true);
+        let _ = p.report_warnings(true);
       }
-      Err(e) => Err(e.to_string()),
-    },
+
+      Err(Error::DIAGNOSTICS(p.diagnostics()))
+    }
     Err(e) => Err(e),
   }
 }
 
+/// Returns an owned `ast::CDDL` from a `String`. Unlike `cddl_from_str()`, the
+/// returned AST doesn't borrow from the input, so it can outlive it, be stored
+/// in long-lived structs and be shared across threads
+///
+/// # Arguments
+///
+/// * `input` - A string with the CDDL text input
+/// * `print_stderr` - When true, print any errors or warnings to stderr
+///
+/// # Example
+///
+/// ```
+/// use cddl::parser::cddl_from_string;
+///
+/// let cddl = cddl_from_string(String::from("myrule = int"), true).unwrap();
+/// let handle = std::thread::spawn(move || cddl.rules.len());
+///
+/// assert_eq!(handle.join().unwrap(), 1);
+/// ```
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(feature = "std")]
+pub fn cddl_from_string(input: String, print_stderr: bool) -> Result<CDDL<'static>> {
+  let mut lexer = Lexer::new(&input);
+
+  cddl_from_str(&mut lexer, &input, print_stderr).map(CDDL::into_owned)
+}
+
 /// Returns a `ast::CDDL` from a `&str`
 ///
 /// # Arguments
@@ -2410,26 +3472,39 @@ pub fn cddl_from_str<'a>(
 /// ```
 #[cfg(not(target_arch = "wasm32"))]
 #[cfg(not(feature = "std"))]
-pub fn cddl_from_str<'a>(
-  lexer: &'a mut Lexer<'a>,
-  input: &'a str,
-) -> std::result::Result<CDDL<'a>, String> {
-  match Parser::new(lexer.iter(), input).map_err(|e| e.to_string()) {
-    Ok(mut p) => match p.parse_cddl() {
-      Ok(c) => Ok(c),
-      Err(Error::PARSER) => {
-        if let Some(e) = p.report_errors() {
-          return Err(e);
-        }
+pub fn cddl_from_str<'a>(lexer: &'a mut Lexer<'a>, input: &'a str) -> Result<CDDL<'a>> {
+  let mut p = Parser::new(lexer.iter(), input)?;
 
-        Err(Error::PARSER.to_string())
-      }
-      Err(e) => Err(e.to_string()),
-    },
+  match p.parse_cddl() {
+    Ok(c) => Ok(c),
+    Err(Error::PARSER) => Err(Error::DIAGNOSTICS(p.diagnostics())),
     Err(e) => Err(e),
   }
 }
 
+/// Returns an owned `ast::CDDL` from a `String`. Unlike `cddl_from_str()`, the
+/// returned AST doesn't borrow from the input, so it can outlive it and be
+/// stored in long-lived structs
+///
+/// # Arguments
+///
+/// * `input` - A string with the CDDL text input
+///
+/// # Example
+///
+/// ```
+/// use cddl::parser::cddl_from_string;
+///
+/// let _ = cddl_from_string(String::from("myrule = int"));
+/// ```
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(not(feature = "std"))]
+pub fn cddl_from_string(input: String) -> Result<CDDL<'static>> {
+  let mut lexer = Lexer::new(&input);
+
+  cddl_from_str(&mut lexer, &input).map(CDDL::into_owned)
+}
+
 /// Returns a `ast::CDDL` wrapped in `JsValue` from a `&str`
 ///
 /// # Arguments
@@ -2474,18 +3549,11 @@ pub fn cddl_from_str(input: &str) -> result::Result<JsValue, JsValue> {
 #[wasm_bindgen]
 /// Formats cddl from input string
 pub fn format_cddl_from_str(input: &str) -> result::Result<String, JsValue> {
-  match Parser::new(Lexer::new(input).iter(), input) {
-    Ok(mut p) => match p.parse_cddl() {
-      Ok(c) => Ok(c.to_string()),
-      Err(Error::PARSER) => {
-        if !p.errors.is_empty() {
-          return Err(JsValue::from_serde(&p.errors).map_err(|e| JsValue::from(e.to_string()))?);
-        }
-
-        Err(JsValue::from(Error::PARSER.to_string()))
-      }
-      Err(e) => Err(JsValue::from(e.to_string())),
-    },
+  match super::formatter::format_cddl(input, &super::formatter::FormatOptions::default()) {
+    Ok(formatted) => Ok(formatted),
+    Err(Error::DIAGNOSTICS(d)) => {
+      Err(JsValue::from_serde(&d.errors).map_err(|e| JsValue::from(e.to_string()))?)
+    }
     Err(e) => Err(JsValue::from(e.to_string())),
   }
 }
@@ -2528,6 +3596,8 @@ mod tests {
                   │
                 2 │ a = b
                   │ ^^^^^ rule with the same identifier is already defined
+                  │
+                  = use '/=' or '//=' to add type or group choices to an existing rule
 
               "#
             )
@@ -2558,6 +3628,388 @@ mod tests {
     }
   }
 
+  #[test]
+  fn verify_rule_kind_resolution() -> Result<()> {
+    let input = indoc!(
+      r#"
+        a = b
+        b = (c: int)
+        d = (a)
+        e = (tstr / int)
+        f<t> = t
+        g = $$h
+      "#
+    );
+
+    let mut l = Lexer::new(input);
+    let c = Parser::new(l.iter(), input)?.parse_cddl()?;
+
+    assert!(matches!(
+      &c.rules[0],
+      Rule::Group { rule, .. }
+        if matches!(&rule.entry, GroupEntry::TypeGroupname { ge, .. } if ge.name.ident == "b")
+    ));
+    assert!(matches!(
+      &c.rules[2],
+      Rule::Group { rule, .. } if matches!(rule.entry, GroupEntry::InlineGroup { .. })
+    ));
+    assert!(matches!(&c.rules[3], Rule::Type { .. }));
+    assert!(matches!(&c.rules[4], Rule::Type { .. }));
+    assert!(matches!(&c.rules[5], Rule::Group { .. }));
+
+    assert_eq!(c.rules[0].to_string(), "a = b");
+    assert_eq!(c.rules[2].to_string(), "d = ( a )");
+
+    Ok(())
+  }
+
+  #[test]
+  fn verify_rule_kind_diagnostic() -> Result<()> {
+    let input = indoc!(
+      r#"
+        a = b
+        b = a
+        c = int
+        c //= (d: int)
+      "#
+    );
+
+    match Parser::new(Lexer::new(input).iter(), input) {
+      Ok(mut p) => match p.parse_cddl() {
+        Ok(_) => Ok(()),
+        #[cfg(feature = "std")]
+        Err(Error::PARSER) if !p.errors.is_empty() => {
+          let e = p.report_errors(false).unwrap().unwrap();
+
+          #[cfg(feature = "std")]
+          println!("{}", e);
+
+          assert_eq!(
+            e,
+            indoc!(
+              r#"
+                error: parser errors
+                  ┌─ input:1:1
+                  │
+                1 │ a = b
+                  │ ^^^^^ unable to determine if rule is a type or group. it only refers to rules which refer back to it
+                2 │ b = a
+                  │ ^^^^^ unable to determine if rule is a type or group. it only refers to rules which refer back to it
+                3 │ c = int
+                4 │ c //= (d: int)
+                  │ ^^^^^^^^^^^^^^ rule with the same identifier is already defined as a different kind. a name cannot be both a type and a group
+                  │
+                  = define at least one rule of the cycle as a type, e.g. a map or an array, or as a group, e.g. with a member key
+                  = rename one of the rules, or extend the existing rule with '/=' if it is a type and '//=' if it is a group
+
+              "#
+            )
+          );
+          Ok(())
+        }
+        Err(e) => Err(e),
+      },
+      Err(e) => Err(e),
+    }
+  }
+
+  #[test]
+  fn verify_semantic_diagnostic() -> Result<()> {
+    let input = indoc!(
+      r#"
+        root = [* entry]
+        entry = tstr / intt
+        entry /= pair<int>
+        pair<k, v> = [k, v]
+      "#
+    );
+
+    // Undefined identifiers and argument count mismatches are only warnings by
+    // default
+    let mut l = Lexer::new(input);
+    let mut p = Parser::new(l.iter(), input)?;
+    assert!(p.parse_cddl().is_ok());
+    assert_eq!(
+      p.warnings.iter().map(|w| w.msg_type).collect::<Vec<_>>(),
+      vec![UndefinedIdentifier, GenericArgumentCountMismatch]
+    );
+
+    match Parser::new(Lexer::new(input).iter(), input).map(Parser::with_semantic_errors) {
+      Ok(mut p) => match p.parse_cddl() {
+        Ok(_) => Ok(()),
+        #[cfg(feature = "std")]
+        Err(Error::PARSER) if !p.errors.is_empty() => {
+          let e = p.report_errors(false).unwrap().unwrap();
+
+          #[cfg(feature = "std")]
+          println!("{}", e);
+
+          assert_eq!(
+            e,
+            indoc!(
+              r#"
+                error: parser errors
+                  ┌─ input:2:16
+                  │
+                2 │ entry = tstr / intt
+                  │                ^^^^ identifier is not defined by any rule or the standard prelude
+                3 │ entry /= pair<int>
+                  │              ^^^^^ number of generic arguments does not match the number of generic parameters of the referenced rule
+                  │
+                  = check the spelling or define a rule with this name. names are case-sensitive
+                  = pass one argument for each generic parameter, e.g. 'pair<tstr, int>' for 'pair<k, v> = [k, v]'
+
+              "#
+            )
+          );
+          Ok(())
+        }
+        Err(e) => Err(e),
+      },
+      Err(e) => Err(e),
+    }
+  }
+
+  #[test]
+  fn verify_non_productive_rule_diagnostic() -> Result<()> {
+    let input = indoc!(
+      r#"
+        root = [tree, inf, g-root]
+        tree = int / [* tree]
+        inf = [inf-entry]
+        inf-entry = { next: inf }
+        g-root = { g }
+        g = (a: int, g)
+      "#
+    );
+
+    match Parser::new(Lexer::new(input).iter(), input) {
+      Ok(mut p) => match p.parse_cddl() {
+        Ok(_) => Ok(()),
+        #[cfg(feature = "std")]
+        Err(Error::PARSER) if !p.errors.is_empty() => {
+          let e = p.report_errors(false).unwrap().unwrap();
+
+          #[cfg(feature = "std")]
+          println!("{}", e);
+
+          assert_eq!(
+            e,
+            indoc!(
+              r#"
+                error: parser errors
+                  ┌─ input:3:1
+                  │
+                3 │ inf = [inf-entry]
+                  │ ^^^^^^^^^^^^^^^^^ rule can never be satisfied. every choice refers back to the rule without a base case
+                4 │ inf-entry = { next: inf }
+                  │ ^^^^^^^^^^^^^^^^^^^^^^^^^ rule can never be satisfied. every choice refers back to the rule without a base case
+                5 │ g-root = { g }
+                6 │ g = (a: int, g)
+                  │ ^^^^^^^^^^^^^^^ rule can never be satisfied. every choice refers back to the rule without a base case
+                  │
+                  = recursive rules must offer at least one choice that does not refer back to the rule, e.g. by making the recursive entry optional
+
+              "#
+            )
+          );
+          Ok(())
+        }
+        Err(e) => Err(e),
+      },
+      Err(e) => Err(e),
+    }
+  }
+
+  #[test]
+  fn verify_non_productive_generic_rule() -> Result<()> {
+    // Substituting the argument gives "b = [b]", which has no base case
+    let input = indoc!(
+      r#"
+        b = a<b>
+        a<t> = [t]
+      "#
+    );
+
+    let mut l = Lexer::new(input);
+    let mut p = Parser::new(l.iter(), input)?;
+    assert!(p.parse_cddl().is_err());
+    assert_eq!(
+      p.errors.iter().map(|e| e.msg_type).collect::<Vec<_>>(),
+      vec![NonProductiveRule]
+    );
+
+    let input = indoc!(
+      r#"
+        b = a<b> / int
+        a<t> = [t]
+      "#
+    );
+
+    let mut l = Lexer::new(input);
+    assert!(Parser::new(l.iter(), input)?.parse_cddl().is_ok());
+
+    Ok(())
+  }
+
+  #[cfg(feature = "std")]
+  #[test]
+  fn verify_structured_diagnostics() {
+    let input = indoc!(
+      r#"
+        a = 1234
+        a = b
+        b = tstr
+        c<t> = int
+      "#
+    );
+
+    let mut l = Lexer::new(input);
+    match cddl_from_str(&mut l, input, false) {
+      Err(Error::DIAGNOSTICS(d)) => {
+        assert_eq!(d.errors.len(), 1);
+        assert_eq!(d.errors[0].msg_type, DuplicateRuleIdentifier);
+        assert_eq!(d.errors[0].severity, Severity::Error);
+        assert_eq!(d.errors[0].position.line, 2);
+        assert_eq!(d.errors[0].position.range, (9, 14));
+        assert!(d.errors[0].msg.extended.is_some());
+
+        assert!(d.warnings.is_empty());
+        assert!(d.to_string().starts_with("error: parser errors"));
+      }
+      _ => panic!("expected parsing to fail with diagnostics"),
+    }
+
+    let input = indoc!(
+      r#"
+        a = 1234
+        b<t> = tstr
+      "#
+    );
+
+    let mut l = Lexer::new(input);
+    let mut p = Parser::new(l.iter(), input).unwrap();
+    assert!(p.parse_cddl().is_ok());
+
+    let d = p.diagnostics();
+    assert!(d.errors.is_empty());
+    assert_eq!(
+      d.warnings
+        .iter()
+        .map(|w| (w.msg_type, w.severity))
+        .collect::<Vec<_>>(),
+      vec![
+        (UnusedGenericParameter, Severity::Warning),
+        (UnreachableRule, Severity::Warning),
+      ]
+    );
+  }
+
+  #[test]
+  #[cfg(feature = "std")]
+  fn verify_owned_cddl() {
+    fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+    let input = indoc!(
+      r#"
+        ; Comment
+        thing = {
+          "text": tstr .regexp "^[a-z]+$",
+          bytes: h'0102' / 'abc',
+          ? tagged: #6.32(tstr),
+          * label => [* int],
+        }
+        label = "a" / "b"
+      "#
+    );
+
+    let mut l = Lexer::new(input);
+    let borrowed = Parser::new(l.iter(), input).unwrap().parse_cddl().unwrap();
+
+    let owned = cddl_from_string(input.to_string(), false).unwrap();
+    assert_send_sync(&owned);
+
+    let owned = std::thread::spawn(move || owned).join().unwrap();
+    assert_eq!(owned, borrowed);
+    assert_eq!(owned.to_string(), borrowed.to_string());
+  }
+
+  #[test]
+  fn verify_partial_parse() {
+    let input = indoc!(
+      r#"
+        a = { x: b, y: c }
+        b = { x: }
+        c = tstr
+        d = [ * ]
+        e = bool
+      "#
+    );
+
+    let mut l = Lexer::new(input);
+    let mut p = Parser::new(l.iter(), input).unwrap();
+    let partial = p.parse_cddl_partial();
+
+    assert_eq!(
+      partial
+        .cddl
+        .rules
+        .iter()
+        .map(|r| r.name())
+        .collect::<Vec<_>>(),
+      vec!["a", "c", "e"]
+    );
+    assert_eq!(partial.skipped.len(), 2);
+    assert_eq!(
+      &input[partial.skipped[0].0..partial.skipped[0].1],
+      "b = { x: }\n"
+    );
+    assert_eq!(partial.skipped[0].2, 2);
+    assert_eq!(
+      &input[partial.skipped[1].0..partial.skipped[1].1],
+      "d = [ * ]\n"
+    );
+    assert!(!partial.diagnostics.errors.is_empty());
+    assert!(partial
+      .diagnostics
+      .errors
+      .iter()
+      .all(|e| e.severity == Severity::Error));
+  }
+
+  #[test]
+  fn verify_partial_parse_lexer_errors() {
+    let input = indoc!(
+      r#"
+        a = int
+        b = [ h'zz' ]
+        c = tstr
+      "#
+    );
+
+    let mut l = Lexer::new(input);
+    let mut p = Parser::new_tolerant(l.iter(), input);
+    let partial = p.parse_cddl_partial();
+
+    assert_eq!(
+      partial
+        .cddl
+        .rules
+        .iter()
+        .map(|r| r.name())
+        .collect::<Vec<_>>(),
+      vec!["a", "b", "c"]
+    );
+    assert_eq!(partial.skipped.len(), 1);
+    assert_eq!(&input[partial.skipped[0].0..partial.skipped[0].1], "h'zz'");
+    assert_eq!(partial.skipped[0].2, 2);
+    assert!(partial
+      .diagnostics
+      .errors
+      .iter()
+      .any(|e| e.msg_type == InvalidLiteral && e.msg.extended.is_some()));
+  }
+
   #[test]
   fn verify_genericparams() -> Result<()> {
     let input = r#"<t, v>"#;
@@ -2618,6 +4070,8 @@ mod tests {
                 │
               1 │ <1, 2>
                 │  ^ generic parameters must be named identifiers
+                │
+                = generic parameters are names that arguments are substituted for, e.g. 'pair<k, v> = [k, v]'. literals can only be given as arguments
 
               "#
             )
@@ -2684,6 +4138,9 @@ mod tests {
                 4 │ rulec = rulee
                 5 │ rulec = rulee2
                   │ ^^^^^^^^^^^^^^ rule with the same identifier is already defined
+                  │
+                  = declare generic parameters after the rule name, e.g. 'message<t, v> = { type: t, value: v }', and pass arguments after a reference to it, e.g. 'message<"reboot", uint>'
+                  = use '/=' or '//=' to add type or group choices to an existing rule
 
               "#
             )
@@ -2953,7 +4410,7 @@ mod tests {
         },
         operator: Some(Operator {
           operator: RangeCtlOp::CtlOp {
-            ctrl: ".lt",
+            ctrl: ".lt".into(),
             span: (7, 10, 1),
           },
           type2: Type2::Typename {
@@ -3021,7 +4478,7 @@ mod tests {
         },
         operator: Some(Operator {
           operator: RangeCtlOp::CtlOp {
-            ctrl: ".eq",
+            ctrl: ".eq".into(),
             span: (16, 19, 1),
           },
           type2: Type2::TextValue {
@@ -3129,7 +4586,7 @@ mod tests {
         span: (0, 0, 0),
       },
       Type2::TaggedData {
-        tag: Some(997),
+        tag: Some(HeadNumber::Uint(997)),
         t: Type {
           type_choices: vec![TypeChoice {
             type1: Type1 {
@@ -4172,7 +5629,7 @@ mod tests {
                 TypeChoice {
                   type1: Type1 {
                     type2: Type2::TextValue {
-                      value: "mytype1",
+                      value: "mytype1".into(),
                       span: (2, 11, 1),
                     },
                     operator: None,
@@ -4186,7 +5643,7 @@ mod tests {
                   type1: Type1 {
                     type2: Type2::Typename {
                       ident: Identifier {
-                        ident: "int",
+                        ident: "int".into(),
                         span: (14, 17, 1),
                         socket: None,
                       },
@@ -4319,4 +5776,150 @@ mod tests {
 
     Ok(())
   }
+
+  #[test]
+  fn verify_comments_attach_to_following_rule() -> Result<()> {
+    let input = indoc!(
+      r#"
+        ; header
+
+        ; first rule
+        a = int ; trailing a
+        ; second rule
+        b = tstr
+      "#
+    );
+
+    let mut l = Lexer::new(input);
+    let cddl = Parser::new(l.iter(), input)?.parse_cddl()?;
+
+    assert_eq!(cddl.comments, None);
+    assert!(matches!(&cddl.rules[0], Rule::Type {
+      comments_before_rule: Some(Comments(comments)),
+      comments_after_rule: Some(Comments(trailing)),
+      ..
+    } if comments == &[" header", "\n", " first rule"] && trailing == &[" trailing a"]));
+    assert!(matches!(&cddl.rules[1], Rule::Type {
+      comments_before_rule: Some(Comments(comments)),
+      comments_after_rule: None,
+      ..
+    } if comments == &[" second rule"]));
+
+    Ok(())
+  }
+
+  #[test]
+  fn verify_doc_comments() -> Result<()> {
+    let input = indoc!(
+      r#"
+        ; unrelated
+
+        ; A reputon
+        reputon = {
+          ; who rated it
+          rater: text, ; name of the rater
+          rating: float16 ; the rating
+          ; separated by a blank line
+
+          ? conf: float16
+          ( a: int // b: int )
+          ; nested key
+          nested: [ ; not a key
+            ; inner key
+            inner: uint
+          ]
+        }
+
+        untouched = int / ; second choice
+          tstr
+      "#
+    );
+
+    let mut l = Lexer::new(input);
+    let cddl = Parser::new(l.iter(), input)?.parse_cddl()?;
+
+    let docs = cddl
+      .doc_comments()
+      .into_iter()
+      .map(|d| (d.rule.name(), d.member_key.map(|mk| mk.to_string()), d.text))
+      .collect::<Vec<_>>();
+
+    assert_eq!(
+      docs,
+      vec![
+        ("reputon".to_string(), None, "A reputon".to_string()),
+        (
+          "reputon".to_string(),
+          Some("rater:".to_string()),
+          "who rated it\nname of the rater".to_string()
+        ),
+        (
+          "reputon".to_string(),
+          Some("rating:".to_string()),
+          "the rating".to_string()
+        ),
+        (
+          "reputon".to_string(),
+          Some("nested:".to_string()),
+          "nested key".to_string()
+        ),
+        (
+          "reputon".to_string(),
+          Some("inner:".to_string()),
+          "inner key".to_string()
+        ),
+      ]
+    );
+
+    assert_eq!(cddl.rules[1].doc_comment(), None);
+
+    Ok(())
+  }
+
+  #[test]
+  fn verify_prelude() -> Result<()> {
+    let prelude_input = indoc!(
+      r#"
+        tdate = #6.0(tstr .regexp "[0-9]{4}-[0-9]{2}-[0-9]{2}")
+        cose-sign1 = #6.18([* any])
+        unix-time = uint
+      "#
+    );
+
+    let mut l = Lexer::new(prelude_input);
+    let prelude = Parser::new(l.iter(), prelude_input)?.parse_prelude()?;
+    assert_eq!(prelude.rules.len(), 3);
+
+    let input = indoc!(
+      r#"
+        message = { issued: tdate, payload: cose-sign1, expires: unix-time }
+        unix-time = uint .le 4102444800
+      "#
+    );
+
+    let mut l = Lexer::new(input);
+    let mut p = Parser::new(l.iter(), input)?.with_prelude(&prelude);
+    let cddl = p.parse_cddl()?;
+
+    assert!(p.warnings.is_empty());
+    assert_eq!(
+      cddl.rules.iter().map(|r| r.name()).collect::<Vec<_>>(),
+      vec!["message", "unix-time", "tdate", "cose-sign1"]
+    );
+
+    // Names of the standard prelude can only be redefined by a prelude, and
+    // names defined by a prelude are undefined without it
+    let input = "message = tdate\ntdate = tstr";
+    let mut l = Lexer::new(input);
+    assert!(Parser::new(l.iter(), input)?.parse_cddl().is_err());
+
+    let input = "message = cose-sign1";
+    let mut l = Lexer::new(input);
+    assert!(Parser::new(l.iter(), input)?
+      .with_semantic_errors()
+      .parse_cddl()
+      .is_err());
+
+    Ok(())
+  }
 }
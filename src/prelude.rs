@@ -0,0 +1,155 @@
+use super::{
+  ast::CDDL,
+  lexer::Lexer,
+  parser::{Error, Parser, Result},
+};
+use lazy_static::lazy_static;
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+/// The standard prelude from Appendix D of RFC 8610, which is implicitly
+/// included in every CDDL definition
+pub const STANDARD_PRELUDE: &str = r#"any = #
+
+uint = #0
+nint = #1
+int = uint / nint
+
+bstr = #2
+bytes = bstr
+tstr = #3
+text = tstr
+
+tdate = #6.0(tstr)
+time = #6.1(number)
+number = int / float
+biguint = #6.2(bstr)
+bignint = #6.3(bstr)
+bigint = biguint / bignint
+integer = int / bigint
+unsigned = uint / biguint
+decfrac = #6.4([e10: int, m: integer])
+bigfloat = #6.5([e2: int, m: integer])
+eb64url = #6.21(any)
+eb64legacy = #6.22(any)
+eb16 = #6.23(any)
+encoded-cbor = #6.24(bstr)
+uri = #6.32(tstr)
+b64url = #6.33(tstr)
+b64legacy = #6.34(tstr)
+regexp = #6.35(tstr)
+mime-message = #6.36(tstr)
+cbor-any = #6.55799(any)
+
+float16 = #7.25
+float32 = #7.26
+float64 = #7.27
+float16-32 = float16 / float32
+float32-64 = float32 / float64
+float = float16-32 / float64
+
+false = #7.20
+true = #7.21
+bool = false / true
+nil = #7.22
+null = nil
+undefined = #7.23
+"#;
+
+lazy_static! {
+  // Standard prelude, parsed once
+  static ref STANDARD_PRELUDE_AST: CDDL<'static> = {
+    let mut lexer = Lexer::new(STANDARD_PRELUDE);
+
+    prelude_from_str(&mut lexer, STANDARD_PRELUDE)
+      .map(CDDL::into_owned)
+      .expect("the standard prelude is well-formed")
+  };
+}
+
+/// Returns the standard prelude as a CDDL AST, which is parsed on first use
+///
+/// # Example
+///
+/// ```
+/// use cddl::prelude::standard_prelude;
+///
+/// let prelude = standard_prelude();
+/// assert!(prelude.rules.iter().any(|r| r.name() == "tdate"));
+/// ```
+pub fn standard_prelude() -> &'static CDDL<'static> {
+  &STANDARD_PRELUDE_AST
+}
+
+/// Returns a prelude extending the standard prelude, e.g. with the IANA
+/// registered tags used by a domain, as a CDDL AST. Unlike a CDDL definition,
+/// a prelude may redefine the names of the standard prelude and none of its
+/// rules are expected to be reachable. Use `Parser::with_prelude()` to merge it
+/// with a CDDL definition
+///
+/// # Arguments
+///
+/// * `lexer` - A mutable reference to a `lexer::Lexer`. Can be created from
+///   `cddl::lexer_from_str()`
+/// * `input` - A string slice with the prelude text input
+///
+/// # Example
+///
+/// ```
+/// use cddl::{lexer_from_str, prelude::prelude_from_str};
+///
+/// let input = r#"cose-sign1 = #6.18([* any])"#;
+///
+/// assert!(prelude_from_str(&mut lexer_from_str(input), input).is_ok());
+/// ```
+pub fn prelude_from_str<'a>(lexer: &'a mut Lexer<'a>, input: &'a str) -> Result<CDDL<'a>> {
+  let mut p = Parser::new(lexer.iter(), input)?;
+
+  match p.parse_prelude() {
+    Ok(c) => Ok(c),
+    Err(Error::PARSER) => Err(Error::DIAGNOSTICS(p.diagnostics())),
+    Err(e) => Err(e),
+  }
+}
+
+/// Appends the rules of a prelude to a CDDL definition, other than those whose
+/// name is already defined by the definition. Choice alternates of the prelude
+/// are appended unless the definition redefines the name outright
+pub(crate) fn merge<'a>(cddl: &mut CDDL<'a>, prelude: &CDDL<'a>) {
+  let defined = cddl
+    .rules
+    .iter()
+    .filter(|r| !r.is_choice_alternate())
+    .map(|r| r.name())
+    .collect::<Vec<_>>();
+
+  for rule in prelude.rules.iter() {
+    if !defined.contains(&rule.name()) {
+      cddl.rules.push(rule.clone());
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{ast::Rule, token};
+
+  #[test]
+  fn verify_standard_prelude_names() {
+    let prelude = standard_prelude();
+
+    assert_eq!(prelude.rules.len(), 40);
+
+    for rule in prelude.rules.iter() {
+      let name = rule.name();
+
+      assert!(matches!(rule, Rule::Type { .. }), "{}", name);
+      assert_eq!(
+        token::lookup_ident(&name).in_standard_prelude(),
+        Some(name.as_str())
+      );
+    }
+  }
+}
@@ -0,0 +1,681 @@
+use super::{
+  ast::*,
+  error::MsgType::{self, *},
+  token,
+};
+
+#[cfg(not(feature = "std"))]
+use alloc::{
+  collections::BTreeMap,
+  string::{String, ToString},
+  vec,
+  vec::Vec,
+};
+#[cfg(feature = "std")]
+use std::collections::BTreeMap;
+
+/// Problem found in a syntactically valid CDDL definition
+#[derive(Debug, Clone, Copy)]
+pub struct Issue {
+  /// Span of the offending rule, identifier or generic parameter
+  pub span: Span,
+  /// Issue type. Its severity distinguishes errors from warnings, which
+  /// describe definitions that are valid but likely unintended, e.g. rules that
+  /// are never used
+  pub msg: MsgType,
+}
+
+// Name referenced from within a rule, along with any generic arguments
+struct Reference<'a, 'b> {
+  ident: &'b Identifier<'a>,
+  generic_args: Option<&'b GenericArgs<'a>>,
+}
+
+/// Checks a parsed CDDL definition for problems that can't be detected by
+/// syntax alone. The following are reported as errors:
+///
+/// - identifiers which are neither defined by a rule, a generic parameter nor
+///   the standard prelude
+/// - generic rules referenced with the wrong number of generic arguments
+/// - rules which can never be satisfied (see `non_productive_rules()`)
+///
+/// and the following as warnings:
+///
+/// - generic parameters which are never used
+/// - rules which can't be reached from the root (first) rule
+/// - sockets which are referenced but never plugged
+///
+/// # Example
+///
+/// ```
+/// use cddl::{error::Severity, lexer_from_str, parser::cddl_from_str, semantic::analyze};
+///
+/// let input = r#"myrule = int
+/// unused = tstr"#;
+/// let mut lexer = lexer_from_str(input);
+/// let cddl = cddl_from_str(&mut lexer, input, false).unwrap();
+///
+/// let issues = analyze(&cddl);
+/// assert_eq!(issues.len(), 1);
+/// assert_eq!(issues[0].msg.severity(), Severity::Warning);
+/// ```
+pub fn analyze(cddl: &CDDL) -> Vec<Issue> {
+  analyze_rules(cddl, &[])
+}
+
+/// Checks a parsed CDDL definition for the same problems as `analyze()`, with
+/// the names defined by a prelude extending the standard prelude taken to be
+/// defined
+pub fn analyze_with_prelude(cddl: &CDDL, prelude: &CDDL) -> Vec<Issue> {
+  analyze_rules(cddl, &prelude.rules)
+}
+
+fn analyze_rules(cddl: &CDDL, prelude_rules: &[Rule]) -> Vec<Issue> {
+  let mut issues = Vec::new();
+
+  for rule in cddl.rules.iter() {
+    let generic_params = rule_generic_params(rule);
+    let references = rule_references(rule);
+
+    for reference in references.iter() {
+      if is_generic_param(generic_params, reference.ident) {
+        continue;
+      }
+
+      let name = reference.ident.to_string();
+
+      match cddl
+        .rules
+        .iter()
+        .chain(prelude_rules)
+        .find(|r| r.name() == name)
+      {
+        Some(referenced_rule) => {
+          let expected = rule_generic_params(referenced_rule).map_or(0, |gp| gp.params.len());
+          let supplied = reference.generic_args.map_or(0, |ga| ga.args.len());
+
+          if expected != supplied {
+            let span = reference
+              .generic_args
+              .map_or(reference.ident.span, |ga| ga.span);
+
+            issues.push(Issue {
+              span,
+              msg: GenericArgumentCountMismatch,
+            });
+          }
+        }
+        None if reference.ident.socket.is_some() => issues.push(Issue {
+          span: reference.ident.span,
+          msg: UnpluggedSocket,
+        }),
+        None => {
+          let in_prelude = token::lookup_ident(&reference.ident.ident)
+            .in_standard_prelude()
+            .is_some();
+
+          if !in_prelude {
+            issues.push(Issue {
+              span: reference.ident.span,
+              msg: UndefinedIdentifier,
+            });
+          }
+        }
+      }
+    }
+
+    if let Some(gp) = generic_params {
+      for param in gp.params.iter() {
+        if !references.iter().any(|r| *r.ident == param.param) {
+          issues.push(Issue {
+            span: param.param.span,
+            msg: UnusedGenericParameter,
+          });
+        }
+      }
+    }
+  }
+
+  for rule in non_productive_rules(cddl) {
+    issues.push(Issue {
+      span: rule.span(),
+      msg: NonProductiveRule,
+    });
+  }
+
+  // The first rule is the root of the definition per section 2
+  let root = cddl.rules.first().map(|r| r.name());
+  let reachable = reachable_rule_names(cddl, root.into_iter().collect());
+
+  for (idx, rule) in cddl.rules.iter().enumerate() {
+    let name = rule.name();
+
+    // Only the first of a rule's choice alternates is reported
+    let is_first = cddl.rules[..idx].iter().all(|r| r.name() != name);
+
+    if is_first && !reachable.contains(&name) {
+      issues.push(Issue {
+        span: rule.span(),
+        msg: UnreachableRule,
+      });
+    }
+  }
+
+  issues
+}
+
+/// Returns the recursive rules which can never be satisfied by a finite data
+/// item. Recursive rules are legal, but only if at least one of the choices
+/// they offer eventually bottoms out, e.g. `a = [* a]` is satisfied by `[]`
+/// whereas `a = [a]` or `g = (int, g)` only ever refer back to themselves.
+/// Rules which merely refer to such a cycle aren't returned, and only the
+/// first of a rule's choice alternates is returned.
+///
+/// Validating against such rules never terminates, so validators use this to
+/// refuse them up front
+///
+/// # Example
+///
+/// ```
+/// use cddl::{lexer_from_str, parser::cddl_from_str, semantic::non_productive_rules};
+///
+/// let input = r#"tree = int / [* tree]"#;
+/// let mut lexer = lexer_from_str(input);
+/// let cddl = cddl_from_str(&mut lexer, input, false).unwrap();
+///
+/// assert!(non_productive_rules(&cddl).is_empty());
+/// ```
+pub fn non_productive_rules<'a, 'b>(cddl: &'b CDDL<'a>) -> Vec<&'b Rule<'a>> {
+  let names = RuleNames::new(cddl);
+
+  // Whether the rules with each name are known to be satisfiable. Names are
+  // marked until a fixed point is reached
+  let mut productive = vec![false; names.rules.len()];
+
+  loop {
+    let mut changed = false;
+
+    for (idx, rules) in names.rules.iter().enumerate() {
+      if !productive[idx]
+        && rules
+          .iter()
+          .any(|rule| rule_is_productive(&names, rule, &productive))
+      {
+        productive[idx] = true;
+        changed = true;
+      }
+    }
+
+    if !changed {
+      break;
+    }
+  }
+
+  names
+    .rules
+    .iter()
+    .zip(productive)
+    .filter(|(_, is_productive)| !is_productive)
+    .map(|(rules, _)| rules[0])
+    .filter(|rule| {
+      let name = rule.name();
+
+      reachable_rule_names(cddl, referenced_rule_names(cddl, &name)).contains(&name)
+    })
+    .collect()
+}
+
+// Rules grouped by name, including choice alternates, in order of definition
+struct RuleNames<'a, 'b> {
+  indices: BTreeMap<(&'static str, &'b str), usize>,
+  rules: Vec<Vec<&'b Rule<'a>>>,
+}
+
+impl<'a, 'b> RuleNames<'a, 'b> {
+  fn new(cddl: &'b CDDL<'a>) -> Self {
+    let mut names = RuleNames {
+      indices: BTreeMap::new(),
+      rules: Vec::new(),
+    };
+
+    for rule in cddl.rules.iter() {
+      let next_idx = names.rules.len();
+      let idx = *names
+        .indices
+        .entry(ident_key(rule_ident(rule)))
+        .or_insert(next_idx);
+
+      if idx == next_idx {
+        names.rules.push(Vec::new());
+      }
+      names.rules[idx].push(rule);
+    }
+
+    names
+  }
+
+  fn index(&self, ident: &'b Identifier<'a>) -> Option<usize> {
+    self.indices.get(&ident_key(ident)).copied()
+  }
+}
+
+fn ident_key<'a, 'b>(ident: &'b Identifier<'a>) -> (&'static str, &'b str) {
+  (ident.socket_prefix(), &ident.ident)
+}
+
+fn rule_ident<'a, 'b>(rule: &'b Rule<'a>) -> &'b Identifier<'a> {
+  match rule {
+    Rule::Type { rule, .. } => &rule.name,
+    Rule::Group { rule, .. } => &rule.name,
+  }
+}
+
+// Context for determining whether a given rule is productive
+struct Productivity<'a, 'b, 'c> {
+  names: &'c RuleNames<'a, 'b>,
+  generic_params: Option<&'b GenericParams<'a>>,
+  productive: &'c [bool],
+  // Whether the generic arguments substituted for the generic parameters are
+  // productive. Parameters without an argument are assumed to be
+  args: Vec<bool>,
+  // Generic rules whose arguments are being substituted, which are only
+  // substituted once to bound the recursion
+  expanding: Vec<usize>,
+}
+
+fn rule_is_productive<'a, 'b>(
+  names: &RuleNames<'a, 'b>,
+  rule: &'b Rule<'a>,
+  productive: &[bool],
+) -> bool {
+  Productivity {
+    names,
+    generic_params: rule_generic_params(rule),
+    productive,
+    args: Vec::new(),
+    expanding: Vec::new(),
+  }
+  .is_rule_productive(rule)
+}
+
+impl<'a, 'b, 'c> Productivity<'a, 'b, 'c> {
+  fn is_rule_productive(&self, rule: &'b Rule<'a>) -> bool {
+    match rule {
+      Rule::Type { rule, .. } => self.is_type_productive(&rule.value),
+      Rule::Group { rule, .. } => self.is_group_entry_productive(&rule.entry),
+    }
+  }
+
+  // Names from the standard prelude, undefined names and unplugged sockets
+  // don't contribute to a cycle. A generic parameter is productive if its
+  // argument is, so the arguments of a reference to a generic rule are
+  // substituted into the rule, e.g. `b = a<b>` with `a<t> = [t]` is `b = [b]`
+  fn is_name_productive(
+    &self,
+    ident: &'b Identifier<'a>,
+    generic_args: Option<&'b GenericArgs<'a>>,
+  ) -> bool {
+    if let Some(idx) = generic_param_index(self.generic_params, ident) {
+      return self.args.get(idx).copied().unwrap_or(true);
+    }
+
+    let idx = match self.names.index(ident) {
+      Some(idx) => idx,
+      None => return true,
+    };
+
+    if let Some(generic_args) = generic_args {
+      if !self.expanding.contains(&idx) {
+        let args = generic_args
+          .args
+          .iter()
+          .map(|arg| self.is_type1_productive(&arg.arg))
+          .collect::<Vec<_>>();
+        let mut expanding = self.expanding.clone();
+        expanding.push(idx);
+
+        return self.names.rules[idx].iter().any(|r| {
+          Productivity {
+            names: self.names,
+            generic_params: rule_generic_params(r),
+            productive: self.productive,
+            args: args.clone(),
+            expanding: expanding.clone(),
+          }
+          .is_rule_productive(r)
+        });
+      }
+    }
+
+    self.productive[idx]
+  }
+
+  fn is_type_productive(&self, t: &'b Type<'a>) -> bool {
+    t.type_choices
+      .iter()
+      .any(|tc| self.is_type1_productive(&tc.type1))
+  }
+
+  fn is_type1_productive(&self, t1: &'b Type1<'a>) -> bool {
+    self.is_type2_productive(&t1.type2)
+      && t1
+        .operator
+        .iter()
+        .all(|o| self.is_type2_productive(&o.type2))
+  }
+
+  fn is_type2_productive(&self, t2: &'b Type2<'a>) -> bool {
+    match t2 {
+      Type2::Typename {
+        ident,
+        generic_args,
+        ..
+      }
+      | Type2::Unwrap {
+        ident,
+        generic_args,
+        ..
+      }
+      | Type2::ChoiceFromGroup {
+        ident,
+        generic_args,
+        ..
+      } => self.is_name_productive(ident, generic_args.as_ref()),
+      Type2::ParenthesizedType { pt, .. } => self.is_type_productive(pt),
+      Type2::Map { group, .. }
+      | Type2::Array { group, .. }
+      | Type2::ChoiceFromInlineGroup { group, .. } => self.is_group_productive(group),
+      Type2::TaggedData {
+        tag: Some(HeadNumber::Type(tag)),
+        t,
+        ..
+      } => self.is_type_productive(tag) && self.is_type_productive(t),
+      Type2::TaggedData { t, .. } => self.is_type_productive(t),
+      Type2::DataMajorType {
+        constraint: Some(HeadNumber::Type(constraint)),
+        ..
+      } => self.is_type_productive(constraint),
+      _ => true,
+    }
+  }
+
+  fn is_group_productive(&self, g: &'b Group<'a>) -> bool {
+    g.group_choices.iter().any(|gc| {
+      gc.group_entries
+        .iter()
+        .all(|(ge, _)| self.is_group_entry_productive(ge))
+    })
+  }
+
+  fn is_group_entry_productive(&self, ge: &'b GroupEntry<'a>) -> bool {
+    match ge {
+      GroupEntry::ValueMemberKey { ge, .. } => {
+        if allows_zero_occurrences(ge.occur.as_ref()) {
+          return true;
+        }
+
+        let is_key_productive = match &ge.member_key {
+          Some(MemberKey::Type1 { t1, .. }) => self.is_type1_productive(t1),
+          _ => true,
+        };
+
+        is_key_productive && self.is_type_productive(&ge.entry_type)
+      }
+      GroupEntry::TypeGroupname { ge, .. } => {
+        allows_zero_occurrences(ge.occur.as_ref())
+          || self.is_name_productive(&ge.name, ge.generic_args.as_ref())
+      }
+      GroupEntry::InlineGroup { occur, group, .. } => {
+        allows_zero_occurrences(occur.as_ref()) || self.is_group_productive(group)
+      }
+    }
+  }
+}
+
+fn allows_zero_occurrences(occur: Option<&Occurrence>) -> bool {
+  matches!(
+    occur.map(|o| &o.occur),
+    Some(Occur::Optional(_))
+      | Some(Occur::ZeroOrMore(_))
+      | Some(Occur::Exact { lower: None, .. })
+      | Some(Occur::Exact { lower: Some(0), .. })
+  )
+}
+
+// Names of all rules that can be reached by following references from the
+// given names, including the given names themselves
+pub(crate) fn reachable_rule_names(cddl: &CDDL, mut reachable: Vec<String>) -> Vec<String> {
+  let mut idx = 0;
+  while idx < reachable.len() {
+    for referenced in referenced_rule_names(cddl, &reachable[idx]) {
+      if !reachable.contains(&referenced) {
+        reachable.push(referenced);
+      }
+    }
+
+    idx += 1;
+  }
+
+  reachable
+}
+
+// Names directly referenced by the rule with the given name, including all of
+// its choice alternates
+pub(crate) fn referenced_rule_names(cddl: &CDDL, name: &str) -> Vec<String> {
+  let mut referenced = Vec::new();
+
+  for rule in cddl.rules.iter().filter(|r| r.name() == name) {
+    let generic_params = rule_generic_params(rule);
+
+    for reference in rule_references(rule) {
+      if !is_generic_param(generic_params, reference.ident) {
+        referenced.push(reference.ident.to_string());
+      }
+    }
+  }
+
+  referenced
+}
+
+fn rule_generic_params<'a, 'b>(rule: &'b Rule<'a>) -> Option<&'b GenericParams<'a>> {
+  match rule {
+    Rule::Type { rule, .. } => rule.generic_params.as_ref(),
+    Rule::Group { rule, .. } => rule.generic_params.as_ref(),
+  }
+}
+
+fn generic_param_index(
+  generic_params: Option<&GenericParams>,
+  ident: &Identifier,
+) -> Option<usize> {
+  generic_params?
+    .params
+    .iter()
+    .position(|gp| gp.param == *ident)
+}
+
+fn is_generic_param(generic_params: Option<&GenericParams>, ident: &Identifier) -> bool {
+  generic_param_index(generic_params, ident).is_some()
+}
+
+fn rule_references<'a, 'b>(rule: &'b Rule<'a>) -> Vec<Reference<'a, 'b>> {
+  let mut references = Vec::new();
+
+  match rule {
+    Rule::Type { rule, .. } => type_references(&rule.value, &mut references),
+    Rule::Group { rule, .. } => group_entry_references(&rule.entry, &mut references),
+  }
+
+  references
+}
+
+fn type_references<'a, 'b>(t: &'b Type<'a>, references: &mut Vec<Reference<'a, 'b>>) {
+  for tc in t.type_choices.iter() {
+    type1_references(&tc.type1, references);
+  }
+}
+
+fn type1_references<'a, 'b>(t1: &'b Type1<'a>, references: &mut Vec<Reference<'a, 'b>>) {
+  type2_references(&t1.type2, references);
+
+  if let Some(o) = &t1.operator {
+    type2_references(&o.type2, references);
+  }
+}
+
+fn type2_references<'a, 'b>(t2: &'b Type2<'a>, references: &mut Vec<Reference<'a, 'b>>) {
+  match t2 {
+    Type2::Typename {
+      ident,
+      generic_args,
+      ..
+    }
+    | Type2::Unwrap {
+      ident,
+      generic_args,
+      ..
+    }
+    | Type2::ChoiceFromGroup {
+      ident,
+      generic_args,
+      ..
+    } => name_references(ident, generic_args.as_ref(), references),
+    Type2::ParenthesizedType { pt, .. } => type_references(pt, references),
+    Type2::Map { group, .. }
+    | Type2::Array { group, .. }
+    | Type2::ChoiceFromInlineGroup { group, .. } => group_references(group, references),
+    Type2::TaggedData { tag, t, .. } => {
+      if let Some(HeadNumber::Type(tag)) = tag {
+        type_references(tag, references);
+      }
+
+      type_references(t, references)
+    }
+    Type2::DataMajorType {
+      constraint: Some(HeadNumber::Type(constraint)),
+      ..
+    } => type_references(constraint, references),
+    _ => (),
+  }
+}
+
+fn name_references<'a, 'b>(
+  ident: &'b Identifier<'a>,
+  generic_args: Option<&'b GenericArgs<'a>>,
+  references: &mut Vec<Reference<'a, 'b>>,
+) {
+  references.push(Reference {
+    ident,
+    generic_args,
+  });
+
+  if let Some(ga) = generic_args {
+    for arg in ga.args.iter() {
+      type1_references(&arg.arg, references);
+    }
+  }
+}
+
+fn group_references<'a, 'b>(g: &'b Group<'a>, references: &mut Vec<Reference<'a, 'b>>) {
+  for gc in g.group_choices.iter() {
+    for (ge, _) in gc.group_entries.iter() {
+      group_entry_references(ge, references);
+    }
+  }
+}
+
+fn group_entry_references<'a, 'b>(ge: &'b GroupEntry<'a>, references: &mut Vec<Reference<'a, 'b>>) {
+  match ge {
+    GroupEntry::ValueMemberKey { ge, .. } => {
+      match &ge.member_key {
+        Some(MemberKey::Type1 { t1, .. }) => type1_references(t1, references),
+        Some(MemberKey::NonMemberKey {
+          non_member_key: NonMemberKey::Type(t),
+          ..
+        }) => type_references(t, references),
+        Some(MemberKey::NonMemberKey {
+          non_member_key: NonMemberKey::Group(g),
+          ..
+        }) => group_references(g, references),
+        _ => (),
+      }
+
+      type_references(&ge.entry_type, references);
+    }
+    GroupEntry::TypeGroupname { ge, .. } => {
+      name_references(&ge.name, ge.generic_args.as_ref(), references)
+    }
+    GroupEntry::InlineGroup { group, .. } => group_references(group, references),
+  }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+  use super::{
+    super::{error::Severity, lexer::Lexer, parser::Parser},
+    *,
+  };
+  use indoc::indoc;
+
+  fn issues(input: &str) -> Vec<(String, bool)> {
+    let mut l = Lexer::new(input);
+    let c = Parser::new(l.iter(), input).unwrap().parse_cddl().unwrap();
+
+    analyze(&c)
+      .into_iter()
+      .map(|i| {
+        (
+          input[i.span.0..i.span.1].to_string(),
+          i.msg.severity() == Severity::Warning,
+        )
+      })
+      .collect()
+  }
+
+  #[test]
+  fn verify_productive_recursion() {
+    let input = indoc!(
+      r#"
+        root = [tree, list, nested, g-root, generic]
+        tree = leaf / [* tree]
+        leaf = int
+        list = [int, ? list]
+        nested = { ? next: nested }
+        g-root = { g }
+        g = (a: int, 0*1 g)
+        generic = [opt-list<generic>, choice<generic>]
+        opt-list<t> = [* t]
+        choice<t> = int / [t]
+      "#
+    );
+
+    let mut l = Lexer::new(input);
+    let c = Parser::new(l.iter(), input).unwrap().parse_cddl().unwrap();
+
+    assert!(non_productive_rules(&c).is_empty());
+  }
+
+  #[test]
+  fn verify_warnings() {
+    let input = indoc!(
+      r#"
+        root = { * tstr => $value, ? ext: $$ext, msg: message<int, tstr> }
+        message<t, v> = [t]
+        $$ext //= (a: int)
+        unused = int
+        also-unused /= tstr
+      "#
+    );
+
+    assert_eq!(
+      issues(input),
+      vec![
+        ("$value".to_string(), true),
+        ("v".to_string(), true),
+        ("unused = int".to_string(), true),
+        ("also-unused /= tstr".to_string(), true),
+      ]
+    );
+  }
+}
@@ -3,11 +3,11 @@ use std::{convert::TryFrom, fmt};
 #[cfg(feature = "std")]
 use std::borrow::Cow;
 
-#[cfg(target_arch = "wasm32")]
+#[cfg(any(target_arch = "wasm32", feature = "ast-serde"))]
 use serde::{Deserialize, Serialize};
 
 #[cfg(not(feature = "std"))]
-use alloc::{borrow::Cow, string::String};
+use alloc::{borrow::Cow, string::String, vec, vec::Vec};
 
 /// Token which represents a valid CDDL character or sequence
 #[derive(PartialEq, Debug, Clone)]
@@ -22,7 +22,10 @@ pub enum Token<'a> {
   /// Value
   VALUE(Value<'a>),
   /// CBOR tag '#'
-  TAG((Option<u8>, Option<usize>)),
+  TAG((Option<u8>, Option<u64>)),
+  /// CBOR tag or major type whose tag number or argument is given by the type
+  /// that follows between '<' and '>', e.g. '#6.<'
+  TYPEDTAG(u8),
 
   // Operators
   /// Assignment operator '='
@@ -87,7 +90,8 @@ pub enum Token<'a> {
   SIZE,
   /// .bits control operator
   BITS,
-  /// .regexp control operator
+  /// .regexp control operator. Previously lexed as `REGEXP`, which is now
+  /// only the `regexp` type from the standard prelude
   CREGEXP,
   /// .cbor control operator
   CBOR,
@@ -115,6 +119,70 @@ pub enum Token<'a> {
   /// Proposed control extension to support Perl-Compatible Regular Expressions
   /// (PCREs). See https://tools.ietf.org/html/rfc8610#section-3.8.3.2s
   PCRE,
+  /// .plus control operator. Numeric addition of the target and controller.
+  /// See https://tools.ietf.org/html/rfc9165#section-2.1
+  PLUS,
+  /// .cat control operator. Concatenation of text or byte strings. See
+  /// https://tools.ietf.org/html/rfc9165#section-2.2
+  CAT,
+  /// .det control operator. Concatenation of dedented text or byte strings.
+  /// See https://tools.ietf.org/html/rfc9165#section-2.2
+  DET,
+  /// .abnf control operator. Text string described by an ABNF grammar. See
+  /// https://tools.ietf.org/html/rfc9165#section-3
+  ABNF,
+  /// .abnfb control operator. Byte string described by an ABNF grammar. See
+  /// https://tools.ietf.org/html/rfc9165#section-3
+  ABNFB,
+  /// .feature control operator. Detects the use of a feature. See
+  /// https://tools.ietf.org/html/rfc9165#section-4
+  FEATURE,
+  /// .b64u control operator. Text string carrying base64url encoded bytes without
+  /// padding. See https://tools.ietf.org/html/rfc9741
+  B64U,
+  /// .b64u-sloppy control operator. Text string carrying base64url encoded bytes,
+  /// ignoring unused bits. See https://tools.ietf.org/html/rfc9741
+  B64USLOPPY,
+  /// .b64c control operator. Text string carrying base64 encoded bytes with
+  /// padding. See https://tools.ietf.org/html/rfc9741
+  B64C,
+  /// .b64c-sloppy control operator. Text string carrying base64 encoded bytes,
+  /// ignoring unused bits. See https://tools.ietf.org/html/rfc9741
+  B64CSLOPPY,
+  /// .b32 control operator. Text string carrying base32 encoded bytes. See
+  /// https://tools.ietf.org/html/rfc9741
+  B32,
+  /// .h32 control operator. Text string carrying base32hex encoded bytes. See
+  /// https://tools.ietf.org/html/rfc9741
+  H32,
+  /// .b45 control operator. Text string carrying base45 encoded bytes. See
+  /// https://tools.ietf.org/html/rfc9741
+  B45,
+  /// .hex control operator. Text string carrying base16 encoded bytes. See
+  /// https://tools.ietf.org/html/rfc9741
+  HEX,
+  /// .hexlc control operator. Text string carrying base16 encoded bytes in
+  /// lowercase. See https://tools.ietf.org/html/rfc9741
+  HEXLC,
+  /// .hexuc control operator. Text string carrying base16 encoded bytes in
+  /// uppercase. See https://tools.ietf.org/html/rfc9741
+  HEXUC,
+  /// .base10 control operator. Text string carrying a decimal integer. Also
+  /// lexed from `.decimal`, its name in earlier drafts. See
+  /// https://tools.ietf.org/html/rfc9741
+  BASE10,
+  /// .printf control operator. Text string formatted from values by a format
+  /// string. See https://tools.ietf.org/html/rfc9741
+  PRINTF,
+  /// .json control operator. Text string carrying a JSON encoded data item. See
+  /// https://tools.ietf.org/html/rfc9741
+  JSON,
+  /// .join control operator. Text or byte string joined from the entries of an
+  /// array. See https://tools.ietf.org/html/rfc9741
+  JOIN,
+  /// User-defined control operator, including the leading '.', accepted by a
+  /// `Lexer` configured with `with_custom_controls`
+  CUSTOMCONTROL(&'a str),
 
   /// group to choice enumeration '&'
   GTOCHOICE,
@@ -192,7 +260,7 @@ pub enum Token<'a> {
   B64URL,
   /// b64legacy
   B64LEGACY,
-  /// regexp
+  /// regexp. The `.regexp` control operator is lexed as `CREGEXP`
   REGEXP,
   /// mime-message
   MIMEMESSAGE,
@@ -269,9 +337,9 @@ pub enum RangeValue<'a> {
   /// Identifier with optional socket/plug prefix
   IDENT((&'a str, Option<SocketPlug>)),
   /// Integer
-  INT(isize),
+  INT(i128),
   /// Unsigned integer
-  UINT(usize),
+  UINT(u64),
   /// Float
   FLOAT(f64),
 }
@@ -310,25 +378,27 @@ impl<'a> fmt::Display for RangeValue<'a> {
       RangeValue::IDENT(ident) => write!(f, "{}", ident.0),
       RangeValue::INT(i) => write!(f, "{}", i),
       RangeValue::UINT(i) => write!(f, "{}", i),
-      RangeValue::FLOAT(fl) => write!(f, "{}", fl),
+      RangeValue::FLOAT(fl) => write!(f, "{:?}", fl),
     }
   }
 }
 
 /// Literal value
-// TODO: support hexfloat and exponent
-#[cfg_attr(target_arch = "wasm32", derive(Serialize, Deserialize))]
+#[cfg_attr(
+  any(target_arch = "wasm32", feature = "ast-serde"),
+  derive(Serialize, Deserialize)
+)]
 #[derive(Debug, PartialEq, Clone)]
 pub enum Value<'a> {
   /// Integer value
-  INT(isize),
+  INT(i128),
   /// Unsigned integer value
-  UINT(usize),
+  UINT(u64),
   /// Float value
   FLOAT(f64),
   /// Text value
   #[cfg_attr(target_arch = "wasm32", serde(borrow))]
-  TEXT(&'a str),
+  TEXT(Cow<'a, str>),
   /// Byte value
   #[cfg_attr(target_arch = "wasm32", serde(borrow))]
   BYTE(ByteValue<'a>),
@@ -338,9 +408,9 @@ pub enum Value<'a> {
 #[derive(Debug, PartialEq)]
 pub enum Numeric {
   /// Integer
-  INT(isize),
+  INT(i128),
   /// Unsigned integer
-  UINT(usize),
+  UINT(u64),
   /// Float
   FLOAT(f64),
 }
@@ -348,23 +418,65 @@ pub enum Numeric {
 impl<'a> fmt::Display for Value<'a> {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     match self {
-      Value::TEXT(text) => write!(f, "\"{}\"", text),
+      Value::TEXT(text) => {
+        write!(f, "\"")?;
+        write_escaped(f, text, '"')?;
+        write!(f, "\"")
+      }
       Value::INT(i) => write!(f, "{}", i),
       Value::UINT(ui) => write!(f, "{}", ui),
-      Value::FLOAT(float) => write!(f, "{}", float),
+      // Debug formatting retains the fractional part or exponent so that the
+      // output is lexed as a float again
+      Value::FLOAT(float) => write!(f, "{:?}", float),
       Value::BYTE(bv) => write!(f, "{}", bv),
     }
   }
 }
 
+/// Writes the content of a text or byte string literal delimited by `quote`,
+/// escaping the characters which can't appear in it as they are
+pub(crate) fn write_escaped(f: &mut fmt::Formatter, text: &str, quote: char) -> fmt::Result {
+  for c in text.chars() {
+    match c {
+      '\\' => write!(f, "\\\\")?,
+      '\x08' => write!(f, "\\b")?,
+      '\x0c' => write!(f, "\\f")?,
+      '\n' => write!(f, "\\n")?,
+      '\r' => write!(f, "\\r")?,
+      '\t' => write!(f, "\\t")?,
+      _ if c == quote => write!(f, "\\{}", c)?,
+      _ if c.is_control() => write!(f, "\\u{:04X}", c as u32)?,
+      _ => write!(f, "{}", c)?,
+    }
+  }
+
+  Ok(())
+}
+
+impl<'a> Value<'a> {
+  /// Converts into a value which owns its text or bytes
+  pub fn into_owned(self) -> Value<'static> {
+    match self {
+      Value::INT(i) => Value::INT(i),
+      Value::UINT(ui) => Value::UINT(ui),
+      Value::FLOAT(f) => Value::FLOAT(f),
+      Value::TEXT(t) => Value::TEXT(Cow::Owned(t.into_owned())),
+      Value::BYTE(b) => Value::BYTE(b.into_owned()),
+    }
+  }
+}
+
 impl<'a> From<&'static str> for Value<'a> {
   fn from(value: &'static str) -> Self {
-    Value::TEXT(value)
+    Value::TEXT(value.into())
   }
 }
 
 /// Byte string values
-#[cfg_attr(target_arch = "wasm32", derive(Serialize, Deserialize))]
+#[cfg_attr(
+  any(target_arch = "wasm32", feature = "ast-serde"),
+  derive(Serialize, Deserialize)
+)]
 #[derive(Debug, PartialEq, Clone)]
 pub enum ByteValue<'a> {
   /// Unprefixed byte string value
@@ -375,10 +487,62 @@ pub enum ByteValue<'a> {
   B64(Cow<'a, [u8]>),
 }
 
+impl<'a> ByteValue<'a> {
+  /// Converts into a byte string value which owns its bytes
+  pub fn into_owned(self) -> ByteValue<'static> {
+    match self {
+      ByteValue::UTF8(b) => ByteValue::UTF8(Cow::Owned(b.into_owned())),
+      ByteValue::B16(b) => ByteValue::B16(Cow::Owned(b.into_owned())),
+      ByteValue::B64(b) => ByteValue::B64(Cow::Owned(b.into_owned())),
+    }
+  }
+
+  /// Returns the bytes denoted by the byte string value. Prefixed byte strings
+  /// hold their encoded text and are decoded here, ignoring whitespace and
+  /// accepting either base64 alphabet with or without padding. Returns `None`
+  /// if the encoded text is malformed
+  pub fn to_bytes(&self) -> Option<Cow<'_, [u8]>> {
+    match self {
+      ByteValue::UTF8(b) => Some(Cow::Borrowed(b.as_ref())),
+      ByteValue::B16(b) => {
+        let text = b
+          .iter()
+          .filter(|c| !c.is_ascii_whitespace())
+          .copied()
+          .collect::<Vec<_>>();
+        let mut buf = vec![0u8; text.len() / 2];
+        base16::decode_slice(&text, &mut buf).ok()?;
+
+        Some(Cow::Owned(buf))
+      }
+      ByteValue::B64(b) => {
+        let text = b
+          .iter()
+          .filter(|c| !c.is_ascii_whitespace() && **c != b'=')
+          .map(|c| match c {
+            b'+' => b'-',
+            b'/' => b'_',
+            c => *c,
+          })
+          .collect::<Vec<_>>();
+        let mut buf = vec![0u8; text.len() * 3 / 4 + 3];
+        let len = base64::decode_config_slice(&text, base64::URL_SAFE_NO_PAD, &mut buf).ok()?;
+        buf.truncate(len);
+
+        Some(Cow::Owned(buf))
+      }
+    }
+  }
+}
+
 impl<'a> fmt::Display for ByteValue<'a> {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     match self {
-      ByteValue::UTF8(b) => write!(f, "'{}'", std::str::from_utf8(b).map_err(|_| fmt::Error)?),
+      ByteValue::UTF8(b) => {
+        write!(f, "'")?;
+        write_escaped(f, std::str::from_utf8(b).map_err(|_| fmt::Error)?, '\'')?;
+        write!(f, "'")
+      }
       ByteValue::B16(b) => write!(
         f,
         "h'{}'",
@@ -398,7 +562,10 @@ impl<'a> fmt::Display for ByteValue<'a> {
 }
 
 /// Socket/plug prefix
-#[cfg_attr(target_arch = "wasm32", derive(Serialize, Deserialize))]
+#[cfg_attr(
+  any(target_arch = "wasm32", feature = "ast-serde"),
+  derive(Serialize, Deserialize)
+)]
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum SocketPlug {
   /// Type socket `$`
@@ -473,7 +640,7 @@ impl<'a> fmt::Display for Token<'a> {
       Token::ARROWMAP => write!(f, "=>"),
       Token::SIZE => write!(f, ".size"),
       Token::BITS => write!(f, ".bits"),
-      Token::REGEXP => write!(f, ".regexp"),
+      Token::CREGEXP => write!(f, ".regexp"),
       Token::PCRE => write!(f, ".pcre"),
       Token::CBOR => write!(f, ".cbor"),
       Token::CBORSEQ => write!(f, ".cborseq"),
@@ -486,6 +653,27 @@ impl<'a> fmt::Display for Token<'a> {
       Token::EQ => write!(f, ".eq"),
       Token::NE => write!(f, ".ne"),
       Token::DEFAULT => write!(f, ".default"),
+      Token::PLUS => write!(f, ".plus"),
+      Token::CAT => write!(f, ".cat"),
+      Token::DET => write!(f, ".det"),
+      Token::ABNF => write!(f, ".abnf"),
+      Token::ABNFB => write!(f, ".abnfb"),
+      Token::FEATURE => write!(f, ".feature"),
+      Token::B64U => write!(f, ".b64u"),
+      Token::B64USLOPPY => write!(f, ".b64u-sloppy"),
+      Token::B64C => write!(f, ".b64c"),
+      Token::B64CSLOPPY => write!(f, ".b64c-sloppy"),
+      Token::B32 => write!(f, ".b32"),
+      Token::H32 => write!(f, ".h32"),
+      Token::B45 => write!(f, ".b45"),
+      Token::HEX => write!(f, ".hex"),
+      Token::HEXLC => write!(f, ".hexlc"),
+      Token::HEXUC => write!(f, ".hexuc"),
+      Token::BASE10 => write!(f, ".base10"),
+      Token::PRINTF => write!(f, ".printf"),
+      Token::JSON => write!(f, ".json"),
+      Token::JOIN => write!(f, ".join"),
+      Token::CUSTOMCONTROL(ctrl) => write!(f, "{}", ctrl),
       Token::NUMBER => write!(f, "number"),
       Token::BSTR => write!(f, "bstr"),
       Token::BYTES => write!(f, "bytes"),
@@ -521,6 +709,7 @@ impl<'a> fmt::Display for Token<'a> {
         }
         write!(f, "#")
       }
+      Token::TYPEDTAG(mt) => write!(f, "#{}.", mt),
       _ => write!(f, ""),
     }
   }
@@ -543,7 +732,7 @@ pub fn lookup_control_from_str<'a>(ident: &str) -> Option<Token<'a>> {
   match ident {
     ".size" => Some(Token::SIZE),
     ".bits" => Some(Token::BITS),
-    ".regexp" => Some(Token::REGEXP),
+    ".regexp" => Some(Token::CREGEXP),
     ".cbor" => Some(Token::CBOR),
     ".cborseq" => Some(Token::CBORSEQ),
     ".within" => Some(Token::WITHIN),
@@ -556,6 +745,26 @@ pub fn lookup_control_from_str<'a>(ident: &str) -> Option<Token<'a>> {
     ".ne" => Some(Token::NE),
     ".default" => Some(Token::DEFAULT),
     ".pcre" => Some(Token::PCRE),
+    ".plus" => Some(Token::PLUS),
+    ".cat" => Some(Token::CAT),
+    ".det" => Some(Token::DET),
+    ".abnf" => Some(Token::ABNF),
+    ".abnfb" => Some(Token::ABNFB),
+    ".feature" => Some(Token::FEATURE),
+    ".b64u" => Some(Token::B64U),
+    ".b64u-sloppy" => Some(Token::B64USLOPPY),
+    ".b64c" => Some(Token::B64C),
+    ".b64c-sloppy" => Some(Token::B64CSLOPPY),
+    ".b32" => Some(Token::B32),
+    ".h32" => Some(Token::H32),
+    ".b45" => Some(Token::B45),
+    ".hex" => Some(Token::HEX),
+    ".hexlc" => Some(Token::HEXLC),
+    ".hexuc" => Some(Token::HEXUC),
+    ".base10" | ".decimal" => Some(Token::BASE10),
+    ".printf" => Some(Token::PRINTF),
+    ".json" => Some(Token::JSON),
+    ".join" => Some(Token::JOIN),
     _ => None,
   }
 }
@@ -578,7 +787,7 @@ pub fn control_str_from_token(t: &Token) -> Option<&'static str> {
   match t {
     Token::SIZE => Some(".size"),
     Token::BITS => Some(".bits"),
-    Token::REGEXP => Some(".regexp"),
+    Token::CREGEXP => Some(".regexp"),
     Token::CBOR => Some(".cbor"),
     Token::CBORSEQ => Some(".cborseq"),
     Token::WITHIN => Some(".within"),
@@ -591,6 +800,26 @@ pub fn control_str_from_token(t: &Token) -> Option<&'static str> {
     Token::NE => Some(".ne"),
     Token::DEFAULT => Some(".default"),
     Token::PCRE => Some(".pcre"),
+    Token::PLUS => Some(".plus"),
+    Token::CAT => Some(".cat"),
+    Token::DET => Some(".det"),
+    Token::ABNF => Some(".abnf"),
+    Token::ABNFB => Some(".abnfb"),
+    Token::FEATURE => Some(".feature"),
+    Token::B64U => Some(".b64u"),
+    Token::B64USLOPPY => Some(".b64u-sloppy"),
+    Token::B64C => Some(".b64c"),
+    Token::B64CSLOPPY => Some(".b64c-sloppy"),
+    Token::B32 => Some(".b32"),
+    Token::H32 => Some(".h32"),
+    Token::B45 => Some(".b45"),
+    Token::HEX => Some(".hex"),
+    Token::HEXLC => Some(".hexlc"),
+    Token::HEXUC => Some(".hexuc"),
+    Token::BASE10 => Some(".base10"),
+    Token::PRINTF => Some(".printf"),
+    Token::JSON => Some(".json"),
+    Token::JOIN => Some(".join"),
     _ => None,
   }
 }
@@ -634,6 +863,7 @@ pub fn lookup_ident(ident: &str) -> Token {
     "number" => Token::NUMBER,
     "biguint" => Token::BIGUINT,
     "bignint" => Token::BIGNINT,
+    "bigint" => Token::BIGINT,
     "integer" => Token::INTEGER,
     "unsigned" => Token::UNSIGNED,
     "decfrac" => Token::DECFRAC,
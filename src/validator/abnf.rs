@@ -0,0 +1,582 @@
+//! Matching of text and byte strings against ABNF grammars ([RFC
+//! 5234](https://tools.ietf.org/html/rfc5234), including the case-sensitive
+//! strings of [RFC 7405](https://tools.ietf.org/html/rfc7405)) for the `.abnf`
+//! and `.abnfb` control operators
+
+use lazy_static::lazy_static;
+use std::collections::{BTreeSet, HashMap};
+
+/// Core rules every grammar may refer to without defining them
+const CORE_RULES: &str = r#"ALPHA = %x41-5A / %x61-7A
+BIT = "0" / "1"
+CHAR = %x01-7F
+CR = %x0D
+CRLF = CR LF
+CTL = %x00-1F / %x7F
+DIGIT = %x30-39
+DQUOTE = %x22
+HEXDIG = DIGIT / "A" / "B" / "C" / "D" / "E" / "F"
+HTAB = %x09
+LF = %x0A
+LWSP = *(WSP / CRLF WSP)
+OCTET = %x00-FF
+SP = %x20
+VCHAR = %x21-7E
+WSP = SP / HTAB
+"#;
+
+lazy_static! {
+  // Core rules, parsed once
+  static ref CORE: Grammar = Grammar::core();
+}
+
+/// Limit on nested rule references, reached by left recursive grammars
+const MAX_DEPTH: usize = 512;
+
+type Alternation = Vec<Concatenation>;
+type Concatenation = Vec<Repetition>;
+
+#[derive(Debug, Clone)]
+struct Repetition {
+  min: usize,
+  max: Option<usize>,
+  element: Element,
+}
+
+#[derive(Debug, Clone)]
+enum Element {
+  Rule(String),
+  Group(Alternation),
+  Text {
+    value: Vec<char>,
+    case_sensitive: bool,
+  },
+  Range(u32, u32),
+  Sequence(Vec<u32>),
+}
+
+/// A parsed ABNF grammar
+#[derive(Debug, Clone)]
+pub struct Grammar {
+  start: String,
+  rules: HashMap<String, Alternation>,
+}
+
+impl Grammar {
+  /// Parses an ABNF grammar. The start rule is the first rule defined, or the
+  /// rule named by a first line that consists of a rule name alone, as
+  /// composed by e.g. `"start" .det rules`
+  pub fn parse(grammar: &str) -> Result<Grammar, String> {
+    let mut start = None;
+    let mut rules: HashMap<String, Alternation> = HashMap::new();
+
+    for (idx, rule) in rule_definitions(grammar).into_iter().enumerate() {
+      let (name, elements) = match rule.find('=') {
+        Some(pos) => (rule[..pos].trim(), &rule[pos + 1..]),
+        None if idx == 0 && is_rulename(rule.trim()) => {
+          start = Some(rule.trim().to_lowercase());
+          continue;
+        }
+        None => return Err(format!("expected rule definition, got \"{}\"", rule.trim())),
+      };
+
+      if !is_rulename(name) {
+        return Err(format!("invalid rule name \"{}\"", name));
+      }
+
+      // Incremental alternatives
+      let (is_incremental, elements) = match elements.strip_prefix('/') {
+        Some(elements) => (true, elements),
+        None => (false, elements),
+      };
+
+      let mut parser = ElementParser {
+        input: elements.chars().collect(),
+        pos: 0,
+      };
+      let alternation = parser.parse()?;
+
+      let name = name.to_lowercase();
+      start.get_or_insert_with(|| name.clone());
+      match rules.get_mut(&name) {
+        Some(existing) if is_incremental => existing.extend(alternation),
+        Some(_) => return Err(format!("rule \"{}\" is defined more than once", name)),
+        None if is_incremental => {
+          return Err(format!(
+            "incremental alternative for undefined rule \"{}\"",
+            name
+          ))
+        }
+        None => {
+          rules.insert(name, alternation);
+        }
+      }
+    }
+
+    match start {
+      Some(start) => Ok(Grammar { start, rules }),
+      None => Err("ABNF grammar has no rules".to_string()),
+    }
+  }
+
+  /// Returns whether the text matches the start rule of the grammar
+  pub fn matches_text(&self, text: &str) -> Result<bool, String> {
+    self.matches(&text.chars().map(u32::from).collect::<Vec<_>>())
+  }
+
+  /// Returns whether the bytes match the start rule of the grammar
+  pub fn matches_bytes(&self, bytes: &[u8]) -> Result<bool, String> {
+    self.matches(&bytes.iter().map(|b| u32::from(*b)).collect::<Vec<_>>())
+  }
+
+  fn matches(&self, input: &[u32]) -> Result<bool, String> {
+    let matcher = Matcher {
+      grammar: self,
+      core: &CORE,
+      input,
+    };
+
+    Ok(matcher.rule(&self.start, 0, 0)?.contains(&input.len()))
+  }
+
+  fn core() -> Grammar {
+    let mut rules = HashMap::new();
+    for rule in rule_definitions(CORE_RULES) {
+      if let Some(pos) = rule.find('=') {
+        let mut parser = ElementParser {
+          input: rule[pos + 1..].chars().collect(),
+          pos: 0,
+        };
+        if let Ok(alternation) = parser.parse() {
+          rules.insert(rule[..pos].trim().to_lowercase(), alternation);
+        }
+      }
+    }
+
+    Grammar {
+      start: String::new(),
+      rules,
+    }
+  }
+}
+
+fn is_rulename(name: &str) -> bool {
+  let mut chars = name.chars();
+  matches!(chars.next(), Some(c) if c.is_ascii_alphabetic())
+    && chars.all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+/// Splits a grammar into the text of its rule definitions, with comments
+/// removed and continuation lines joined. Rules start at the indentation of
+/// the first line, and lines indented further continue the previous rule
+fn rule_definitions(grammar: &str) -> Vec<String> {
+  let mut rules: Vec<String> = Vec::new();
+  let mut indentation = None;
+
+  for line in grammar.lines() {
+    let line = strip_comment(line);
+    if line.trim().is_empty() {
+      continue;
+    }
+
+    let line_indentation = line.len() - line.trim_start().len();
+    match rules.last_mut() {
+      Some(rule) if Some(line_indentation) > indentation => {
+        rule.push(' ');
+        rule.push_str(line.trim());
+      }
+      _ => {
+        indentation.get_or_insert(line_indentation);
+        rules.push(line.trim().to_string());
+      }
+    }
+  }
+
+  rules
+}
+
+fn strip_comment(line: &str) -> &str {
+  let mut closing = None;
+  for (idx, c) in line.char_indices() {
+    match (closing, c) {
+      (None, '"') => closing = Some('"'),
+      (None, '<') => closing = Some('>'),
+      (None, ';') => return &line[..idx],
+      (Some(close), c) if c == close => closing = None,
+      _ => (),
+    }
+  }
+
+  line
+}
+
+struct ElementParser {
+  input: Vec<char>,
+  pos: usize,
+}
+
+impl ElementParser {
+  fn parse(&mut self) -> Result<Alternation, String> {
+    let alternation = self.alternation()?;
+    self.skip_whitespace();
+
+    match self.peek() {
+      None => Ok(alternation),
+      Some(c) => Err(format!("unexpected character '{}' in ABNF rule", c)),
+    }
+  }
+
+  fn peek(&self) -> Option<char> {
+    self.input.get(self.pos).copied()
+  }
+
+  fn skip_whitespace(&mut self) {
+    while matches!(self.peek(), Some(c) if c.is_whitespace()) {
+      self.pos += 1;
+    }
+  }
+
+  fn alternation(&mut self) -> Result<Alternation, String> {
+    let mut alternation = vec![self.concatenation()?];
+    loop {
+      self.skip_whitespace();
+      if self.peek() != Some('/') {
+        return Ok(alternation);
+      }
+      self.pos += 1;
+      alternation.push(self.concatenation()?);
+    }
+  }
+
+  fn concatenation(&mut self) -> Result<Concatenation, String> {
+    let mut concatenation = Vec::new();
+    loop {
+      self.skip_whitespace();
+      match self.peek() {
+        None | Some('/') | Some(')') | Some(']') => break,
+        _ => concatenation.push(self.repetition()?),
+      }
+    }
+
+    if concatenation.is_empty() {
+      return Err("expected ABNF element".to_string());
+    }
+
+    Ok(concatenation)
+  }
+
+  fn repetition(&mut self) -> Result<Repetition, String> {
+    let min = self.number(10);
+    let (min, max) = if self.peek() == Some('*') {
+      self.pos += 1;
+      (min.unwrap_or(0), self.number(10))
+    } else if let Some(n) = min {
+      (n, Some(n))
+    } else {
+      (1, Some(1))
+    };
+
+    let to_usize = |n: u32| n as usize;
+    Ok(Repetition {
+      min: to_usize(min),
+      max: max.map(to_usize),
+      element: self.element()?,
+    })
+  }
+
+  fn number(&mut self, radix: u32) -> Option<u32> {
+    let start = self.pos;
+    while matches!(self.peek(), Some(c) if c.is_digit(radix)) {
+      self.pos += 1;
+    }
+
+    let digits = self.input[start..self.pos].iter().collect::<String>();
+    u32::from_str_radix(&digits, radix).ok()
+  }
+
+  fn element(&mut self) -> Result<Element, String> {
+    match self.peek() {
+      Some('(') | Some('[') => {
+        let is_option = self.peek() == Some('[');
+        self.pos += 1;
+        let alternation = self.alternation()?;
+        self.skip_whitespace();
+
+        match (self.peek(), is_option) {
+          (Some(')'), false) => {
+            self.pos += 1;
+            Ok(Element::Group(alternation))
+          }
+          (Some(']'), true) => {
+            self.pos += 1;
+            Ok(Element::Group(vec![vec![Repetition {
+              min: 0,
+              max: Some(1),
+              element: Element::Group(alternation),
+            }]]))
+          }
+          _ => Err("unterminated ABNF group or option".to_string()),
+        }
+      }
+      Some('"') => self.text(false),
+      Some('%') => {
+        self.pos += 1;
+        let radix = match self.peek().map(|c| c.to_ascii_lowercase()) {
+          Some('x') => 16,
+          Some('d') => 10,
+          Some('b') => 2,
+          Some('s') => {
+            self.pos += 1;
+            return self.text(true);
+          }
+          Some('i') => {
+            self.pos += 1;
+            return self.text(false);
+          }
+          _ => return Err("expected %x, %d, %b, %s or %i in ABNF".to_string()),
+        };
+        self.pos += 1;
+
+        let first = self
+          .number(radix)
+          .ok_or_else(|| "expected numeric value in ABNF".to_string())?;
+        match self.peek() {
+          Some('-') => {
+            self.pos += 1;
+            let last = self
+              .number(radix)
+              .ok_or_else(|| "expected end of numeric range in ABNF".to_string())?;
+            Ok(Element::Range(first, last))
+          }
+          Some('.') => {
+            let mut values = vec![first];
+            while self.peek() == Some('.') {
+              self.pos += 1;
+              values.push(
+                self
+                  .number(radix)
+                  .ok_or_else(|| "expected numeric value in ABNF".to_string())?,
+              );
+            }
+            Ok(Element::Sequence(values))
+          }
+          _ => Ok(Element::Sequence(vec![first])),
+        }
+      }
+      Some('<') => Err("prose values in ABNF can't be matched".to_string()),
+      Some(c) if c.is_ascii_alphabetic() => {
+        let start = self.pos;
+        while matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric() || c == '-') {
+          self.pos += 1;
+        }
+
+        Ok(Element::Rule(
+          self.input[start..self.pos]
+            .iter()
+            .collect::<String>()
+            .to_lowercase(),
+        ))
+      }
+      Some(c) => Err(format!("unexpected character '{}' in ABNF rule", c)),
+      None => Err("expected ABNF element".to_string()),
+    }
+  }
+
+  fn text(&mut self, case_sensitive: bool) -> Result<Element, String> {
+    if self.peek() != Some('"') {
+      return Err("expected quoted string in ABNF".to_string());
+    }
+    self.pos += 1;
+
+    let start = self.pos;
+    while self.peek() != Some('"') {
+      if self.peek().is_none() {
+        return Err("unterminated quoted string in ABNF".to_string());
+      }
+      self.pos += 1;
+    }
+    let value = self.input[start..self.pos].to_vec();
+    self.pos += 1;
+
+    Ok(Element::Text {
+      value,
+      case_sensitive,
+    })
+  }
+}
+
+/// Computes the positions at which a match of each element can end, starting
+/// from a given position
+struct Matcher<'a> {
+  grammar: &'a Grammar,
+  core: &'a Grammar,
+  input: &'a [u32],
+}
+
+impl<'a> Matcher<'a> {
+  fn rule(&self, name: &str, pos: usize, depth: usize) -> Result<Vec<usize>, String> {
+    if depth > MAX_DEPTH {
+      return Err(format!(
+        "ABNF rule \"{}\" is nested too deeply, possibly due to left recursion",
+        name
+      ));
+    }
+
+    match self
+      .grammar
+      .rules
+      .get(name)
+      .or_else(|| self.core.rules.get(name))
+    {
+      Some(alternation) => self.alternation(alternation, pos, depth + 1),
+      None => Err(format!("ABNF rule \"{}\" is not defined", name)),
+    }
+  }
+
+  fn alternation(
+    &self,
+    alternation: &[Concatenation],
+    pos: usize,
+    depth: usize,
+  ) -> Result<Vec<usize>, String> {
+    let mut ends = Vec::new();
+    for concatenation in alternation.iter() {
+      let mut positions = vec![pos];
+      for repetition in concatenation.iter() {
+        let mut next = Vec::new();
+        for p in positions.iter() {
+          next.extend(self.repetition(repetition, *p, depth)?);
+        }
+        positions = dedup(next);
+        if positions.is_empty() {
+          break;
+        }
+      }
+      ends.extend(positions);
+    }
+
+    Ok(dedup(ends))
+  }
+
+  fn repetition(
+    &self,
+    repetition: &Repetition,
+    pos: usize,
+    depth: usize,
+  ) -> Result<Vec<usize>, String> {
+    let mut ends = BTreeSet::new();
+    if repetition.min == 0 {
+      ends.insert(pos);
+    }
+
+    let mut positions = vec![pos];
+    let mut count = 0;
+    while !positions.is_empty() && !matches!(repetition.max, Some(max) if count >= max) {
+      let mut next = Vec::new();
+      for p in positions.iter() {
+        next.extend(self.element(&repetition.element, *p, depth)?);
+      }
+      next = dedup(next);
+      count += 1;
+
+      // Once the minimum is met, only positions that haven't been reached
+      // before can lead to further matches
+      if count >= repetition.min {
+        next.retain(|p| ends.insert(*p));
+      } else if next == positions {
+        // Empty matches repeat indefinitely
+        count = repetition.min - 1;
+      }
+      positions = next;
+    }
+
+    Ok(ends.into_iter().collect())
+  }
+
+  fn element(&self, element: &Element, pos: usize, depth: usize) -> Result<Vec<usize>, String> {
+    let input = &self.input[pos..];
+
+    Ok(match element {
+      Element::Rule(name) => return self.rule(name, pos, depth),
+      Element::Group(alternation) => return self.alternation(alternation, pos, depth),
+      Element::Text {
+        value,
+        case_sensitive,
+      } => {
+        let is_match = input.len() >= value.len()
+          && value.iter().zip(input.iter()).all(|(expected, actual)| {
+            match std::char::from_u32(*actual) {
+              Some(actual) if *case_sensitive => actual == *expected,
+              Some(actual) => actual.eq_ignore_ascii_case(expected),
+              None => false,
+            }
+          });
+
+        if is_match {
+          vec![pos + value.len()]
+        } else {
+          Vec::new()
+        }
+      }
+      Element::Range(first, last) => match input.first() {
+        Some(c) if c >= first && c <= last => vec![pos + 1],
+        _ => Vec::new(),
+      },
+      Element::Sequence(values) => {
+        if input.starts_with(values) {
+          vec![pos + values.len()]
+        } else {
+          Vec::new()
+        }
+      }
+    })
+  }
+}
+
+fn dedup(mut positions: Vec<usize>) -> Vec<usize> {
+  positions.sort_unstable();
+  positions.dedup();
+  positions
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn verify_abnf_matching() -> Result<(), String> {
+    let grammar = Grammar::parse(
+      r#"
+        date = year "-" month "-" day ; RFC 3339 full-date
+        year = 4DIGIT
+        month = %x30 %x31-39 / %x31 %x30-32
+        day = 2DIGIT
+      "#,
+    )?;
+
+    assert!(grammar.matches_text("2021-12-31")?);
+    assert!(!grammar.matches_text("2021-13-31")?);
+    assert!(!grammar.matches_text("2021-12-31T00:00:00")?);
+
+    let grammar = Grammar::parse("word = 1*ALPHA\nword =/ %s\"Id\" *3DIGIT")?;
+    assert!(grammar.matches_text("Hello")?);
+    assert!(grammar.matches_text("Id123")?);
+    assert!(!grammar.matches_text("id123")?);
+    assert!(!grammar.matches_text("Id1234")?);
+
+    // A rule name alone on the first line selects the start rule
+    let grammar = Grammar::parse(
+      "roid\noid = 1*arc\nroid = *arc\narc = [nlsb] %x00-7f\nnlsb = %x81-ff *%x80-ff",
+    )?;
+    assert!(grammar.matches_bytes(&[])?);
+    assert!(grammar.matches_bytes(&[0x2b, 0x81, 0x00])?);
+    assert!(!grammar.matches_bytes(&[0x81])?);
+
+    assert!(Grammar::parse("a = b\nb = a \"x\"")?
+      .matches_text("x")
+      .is_err());
+    assert!(Grammar::parse("a = <prose>").is_err());
+
+    Ok(())
+  }
+}
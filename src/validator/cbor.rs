@@ -2,12 +2,14 @@
 
 use crate::{
   ast::*,
+  error::{ErrorMsg, MsgType},
+  semantic,
   token::{self, Token},
   visitor::{self, *},
 };
 use chrono::{TimeZone, Utc};
 use serde_cbor::Value;
-use std::{convert::TryFrom, fmt};
+use std::{collections::BTreeSet, convert::TryFrom, fmt, rc::Rc};
 
 use super::*;
 
@@ -22,7 +24,7 @@ pub enum Error {
   /// cbor parsing error
   CBORParsing(serde_cbor::Error),
   /// CDDL parsing error
-  CDDLParsing(String),
+  CDDLParsing(crate::parser::Error),
 }
 
 impl fmt::Display for Error {
@@ -45,6 +47,7 @@ impl std::error::Error for Error {
   fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
     match self {
       Error::CBORParsing(error) => Some(error),
+      Error::CDDLParsing(error) => Some(error),
       _ => None,
     }
   }
@@ -107,16 +110,17 @@ impl ValidationError {
       reason,
       is_multi_type_choice: cv.is_multi_type_choice,
       is_group_to_choice_enum: cv.is_group_to_choice_enum,
-      type_group_name_entry: cv.type_group_name_entry.map(|e| e.to_string()),
+      type_group_name_entry: cv.type_group_name_entry.clone(),
       is_multi_group_choice: cv.is_multi_group_choice,
     }
   }
 }
 
 /// cbor validator type
+#[derive(Clone)]
 pub struct CBORValidator<'a> {
   cddl: &'a CDDL<'a>,
-  cbor: Value,
+  cbor: &'a Value,
   errors: Vec<ValidationError>,
   cddl_location: String,
   cbor_location: String,
@@ -125,7 +129,7 @@ pub struct CBORValidator<'a> {
   // Current group entry index detected in current state of AST evaluation
   group_entry_idx: Option<usize>,
   // cbor object value hoisted from previous state of AST evaluation
-  object_value: Option<Value>,
+  object_value: Option<&'a Value>,
   // Is member key detected in current state of AST evaluation
   is_member_key: bool,
   // Is a cut detected in current state of AST evaluation
@@ -134,9 +138,10 @@ pub struct CBORValidator<'a> {
   cut_value: Option<Type1<'a>>,
   // Validate the generic rule given by str ident in current state of AST
   // evaluation
-  eval_generic_rule: Option<&'a str>,
-  // Aggregation of generic rules
-  generic_rules: Vec<GenericRule<'a>>,
+  eval_generic_rule: Option<String>,
+  // Aggregation of generic rules, shared with the validators of nested values
+  // until either adds to them
+  generic_rules: Rc<Vec<GenericRule<'a>>>,
   // Control operator token detected in current state of AST evaluation
   ctrl: Option<token::Token<'a>>,
   // Is a group to choice enumeration detected in current state of AST
@@ -148,26 +153,25 @@ pub struct CBORValidator<'a> {
   is_multi_group_choice: bool,
   // Type/group name entry detected in current state of AST evaluation. Used
   // only for providing more verbose error messages
-  type_group_name_entry: Option<&'a str>,
+  type_group_name_entry: Option<String>,
   // Whether or not to advance to the next group entry if member key validation
   // fails as detected during the current state of AST evaluation
   advance_to_next_entry: bool,
   is_ctrl_map_equality: bool,
   entry_counts: Option<Vec<EntryCount>>,
   validated_keys: Option<Vec<Value>>,
-  values_to_validate: Option<Vec<Value>>,
-}
-
-#[derive(Clone, Debug)]
-struct GenericRule<'a> {
-  name: &'a str,
-  params: Vec<&'a str>,
-  args: Vec<Type1<'a>>,
+  values_to_validate: Option<Vec<&'a Value>>,
+  // Names of features detected by the .feature control operator
+  features: BTreeSet<String>,
+  // Registry of user-defined control operators
+  controls: Option<&'a dyn ControlRegistry>,
+  // Schema the CDDL was compiled into ahead of validation, if any
+  schema: Option<&'a CompiledSchema>,
 }
 
 impl<'a> CBORValidator<'a> {
   /// New cborValidation from CDDL AST and cbor value
-  pub fn new(cddl: &'a CDDL<'a>, cbor: Value) -> Self {
+  pub fn new(cddl: &'a CDDL<'a>, cbor: &'a Value) -> Self {
     CBORValidator {
       cddl,
       cbor,
@@ -181,7 +185,7 @@ impl<'a> CBORValidator<'a> {
       is_cut_present: false,
       cut_value: None,
       eval_generic_rule: None,
-      generic_rules: Vec::new(),
+      generic_rules: Rc::default(),
       ctrl: None,
       is_group_to_choice_enum: false,
       is_multi_type_choice: false,
@@ -192,11 +196,79 @@ impl<'a> CBORValidator<'a> {
       entry_counts: None,
       validated_keys: None,
       values_to_validate: None,
+      features: BTreeSet::new(),
+      controls: None,
+      schema: None,
+    }
+  }
+
+  /// New CBORValidator from a compiled schema and cbor value
+  pub(crate) fn from_schema(schema: &'a CompiledSchema, cbor: &'a Value) -> Self {
+    let mut validator = CBORValidator::new(schema.cddl(), cbor);
+    validator.schema = Some(schema);
+    validator
+  }
+
+  /// Evaluates the user-defined control operators of the given registry. The
+  /// CDDL must have been parsed by a `Lexer` accepting the registered names
+  pub fn with_controls(mut self, controls: &'a dyn ControlRegistry) -> Self {
+    self.controls = Some(controls);
+    self
+  }
+
+  /// Names of the features detected by the `.feature` control operator during
+  /// validation
+  pub fn features(&self) -> &BTreeSet<String> {
+    &self.features
+  }
+
+  /// Validates a cbor value against a type rather than the root rule, as
+  /// needed for data decoded while validating other formats
+  pub(crate) fn validate_type2(
+    cddl: &'a CDDL<'a>,
+    cbor: &'a Value,
+    t2: &Type2<'a>,
+    controls: Option<&'a dyn ControlRegistry>,
+    generic_rules: &Rc<Vec<GenericRule<'a>>>,
+    eval_generic_rule: Option<String>,
+  ) -> Vec<ValidationError> {
+    let mut cv = CBORValidator::new(cddl, cbor);
+    cv.controls = controls;
+    cv.generic_rules = Rc::clone(generic_rules);
+    cv.eval_generic_rule = eval_generic_rule;
+    if let Err(e) = cv.visit_type2(t2) {
+      cv.errors.push(e);
     }
+
+    cv.errors
   }
 
   /// Validate
   pub fn validate(&mut self) -> std::result::Result<(), Error> {
+    // Rules that can never be satisfied would otherwise be followed endlessly.
+    // Compiled schemas are checked once when compiled
+    if self.schema.is_none() {
+      for rule in semantic::non_productive_rules(self.cddl) {
+        self.add_error(format!(
+          "rule \"{}\": {}",
+          rule.name(),
+          ErrorMsg::from(MsgType::NonProductiveRule)
+        ));
+      }
+
+      if !self.errors.is_empty() {
+        return Err(Error::Validation(self.errors.clone()));
+      }
+    }
+
+    self.validate_unchecked()
+  }
+
+  /// Validate without first checking the CDDL for rules that can never be
+  /// satisfied. The parser refuses such rules, so this skips repeating the
+  /// check for CDDL parsed by `cddl_from_str()` and validated many times.
+  /// Validating against a non-productive rule never terminates
+  pub fn validate_unchecked(&mut self) -> std::result::Result<(), Error> {
     for r in self.cddl.rules.iter() {
       // First type rule is root
       if let Rule::Type { rule, .. } = r {
@@ -216,6 +288,40 @@ impl<'a> CBORValidator<'a> {
     Ok(())
   }
 
+  /// Returns whether a tag number or major type argument matches the type
+  /// given for it by a '#6.<type>' or '#mt.<type>' expression
+  fn is_head_number_match(
+    &self,
+    t: &Type<'a>,
+    n: u64,
+  ) -> std::result::Result<bool, ValidationError> {
+    let n = Value::Integer(i128::from(n));
+    let mut cv = self.nested(&n);
+    cv.visit_type(t)?;
+
+    Ok(cv.errors.is_empty())
+  }
+
+  // Validator for a value nested within or derived from the one being
+  // validated, sharing the CDDL, generic rules and registries
+  fn nested<'b>(&self, cbor: &'b Value) -> CBORValidator<'b>
+  where
+    'a: 'b,
+  {
+    let mut cv = CBORValidator::new(self.cddl, cbor);
+    cv.controls = self.controls;
+    cv.schema = self.schema;
+    cv.generic_rules = Rc::clone(&self.generic_rules);
+    cv.eval_generic_rule = self.eval_generic_rule.clone();
+    cv
+  }
+
+  // Collects the errors and detected features of a nested validator
+  fn merge_nested(&mut self, nested: &mut CBORValidator) {
+    self.errors.append(&mut nested.errors);
+    self.features.append(&mut nested.features);
+  }
+
   fn add_error(&mut self, reason: String) {
     self.errors.push(ValidationError {
       reason,
@@ -224,7 +330,7 @@ impl<'a> CBORValidator<'a> {
       is_multi_type_choice: self.is_multi_type_choice,
       is_multi_group_choice: self.is_multi_group_choice,
       is_group_to_choice_enum: self.is_group_to_choice_enum,
-      type_group_name_entry: self.type_group_name_entry.map(|e| e.to_string()),
+      type_group_name_entry: self.type_group_name_entry.clone(),
     });
   }
 }
@@ -232,16 +338,23 @@ impl<'a> CBORValidator<'a> {
 impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
   fn visit_type_rule(&mut self, tr: &TypeRule<'a>) -> visitor::Result<ValidationError> {
     if let Some(gp) = &tr.generic_params {
-      if let Some(gr) = self
-        .generic_rules
+      if let Some(gr) = Rc::make_mut(&mut self.generic_rules)
         .iter_mut()
         .find(|r| r.name == tr.name.ident)
       {
-        gr.params = gp.params.iter().map(|p| p.param.ident).collect();
+        gr.params = gp
+          .params
+          .iter()
+          .map(|p| p.param.ident.to_string())
+          .collect();
       } else {
-        self.generic_rules.push(GenericRule {
-          name: tr.name.ident,
-          params: gp.params.iter().map(|p| p.param.ident).collect(),
+        Rc::make_mut(&mut self.generic_rules).push(GenericRule {
+          name: tr.name.ident.to_string(),
+          params: gp
+            .params
+            .iter()
+            .map(|p| p.param.ident.to_string())
+            .collect(),
           args: vec![],
         });
       }
@@ -266,16 +379,23 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
 
   fn visit_group_rule(&mut self, gr: &GroupRule<'a>) -> visitor::Result<ValidationError> {
     if let Some(gp) = &gr.generic_params {
-      if let Some(gr) = self
-        .generic_rules
+      if let Some(gr) = Rc::make_mut(&mut self.generic_rules)
         .iter_mut()
         .find(|r| r.name == gr.name.ident)
       {
-        gr.params = gp.params.iter().map(|p| p.param.ident).collect();
+        gr.params = gp
+          .params
+          .iter()
+          .map(|p| p.param.ident.to_string())
+          .collect();
       } else {
-        self.generic_rules.push(GenericRule {
-          name: gr.name.ident,
-          params: gp.params.iter().map(|p| p.param.ident).collect(),
+        Rc::make_mut(&mut self.generic_rules).push(GenericRule {
+          name: gr.name.ident.to_string(),
+          params: gp
+            .params
+            .iter()
+            .map(|p| p.param.ident.to_string())
+            .collect(),
           args: vec![],
         });
       }
@@ -332,7 +452,7 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
     // Map equality/inequality validation
     if self.is_ctrl_map_equality {
       if let Some(t) = &self.ctrl {
-        if let Value::Map(m) = &self.cbor {
+        if let Value::Map(m) = self.cbor {
           let mut entry_counts = Vec::new();
           for gc in g.group_choices.iter() {
             let count = entry_counts_from_group_choice(self.cddl, gc);
@@ -432,11 +552,11 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
 
   fn visit_range(
     &mut self,
-    lower: &Type2,
-    upper: &Type2,
+    lower: &Type2<'a>,
+    upper: &Type2<'a>,
     is_inclusive: bool,
   ) -> visitor::Result<ValidationError> {
-    if let Value::Array(a) = &self.cbor {
+    if let Value::Array(a) = self.cbor {
       let allow_empty_array = matches!(self.occurrence.as_ref(), Some(Occur::Optional(_)));
 
       #[allow(unused_assignments)]
@@ -475,29 +595,25 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
 
       if iter_items {
         for (idx, v) in a.iter().enumerate() {
-          let mut cv = CBORValidator::new(self.cddl, v.clone());
-          cv.generic_rules = self.generic_rules.clone();
-          cv.eval_generic_rule = self.eval_generic_rule;
+          let mut cv = self.nested(v);
           cv.is_multi_type_choice = self.is_multi_type_choice;
           cv.cbor_location
             .push_str(&format!("{}/{}", self.cbor_location, idx));
 
           cv.visit_range(lower, upper, is_inclusive)?;
 
-          self.errors.append(&mut cv.errors);
+          self.merge_nested(&mut cv);
         }
       } else if let Some(idx) = self.group_entry_idx.take() {
         if let Some(v) = a.get(idx) {
-          let mut cv = CBORValidator::new(self.cddl, v.clone());
-          cv.generic_rules = self.generic_rules.clone();
-          cv.eval_generic_rule = self.eval_generic_rule;
+          let mut cv = self.nested(v);
           cv.is_multi_type_choice = self.is_multi_type_choice;
           cv.cbor_location
             .push_str(&format!("{}/{}", self.cbor_location, idx));
 
           cv.visit_range(lower, upper, is_inclusive)?;
 
-          self.errors.append(&mut cv.errors);
+          self.merge_nested(&mut cv);
         } else if !allow_empty_array {
           self.add_error(format!("expected array item at index {}", idx));
         }
@@ -526,15 +642,15 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
             )
           };
 
-          match &self.cbor {
+          match self.cbor {
             Value::Integer(i) => {
               if is_inclusive {
-                if *i < *l as i128 || *i > *u as i128 {
+                if *i < *l || *i > *u {
                   self.add_error(error_str);
                 } else {
                   return Ok(());
                 }
-              } else if *i <= *l as i128 || *i >= *u as i128 {
+              } else if *i <= *l || *i >= *u {
                 self.add_error(error_str);
                 return Ok(());
               } else {
@@ -560,15 +676,15 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
             )
           };
 
-          match &self.cbor {
+          match self.cbor {
             Value::Integer(i) => {
               if is_inclusive {
-                if *i < *l as i128 || *i > *u as i128 {
+                if *i < *l || *i > i128::from(*u) {
                   self.add_error(error_str);
                 } else {
                   return Ok(());
                 }
-              } else if *i <= *l as i128 || *i >= *u as i128 {
+              } else if *i <= *l || *i >= i128::from(*u) {
                 self.add_error(error_str);
                 return Ok(());
               } else {
@@ -603,15 +719,15 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
             )
           };
 
-          match &self.cbor {
+          match self.cbor {
             Value::Integer(i) => {
               if is_inclusive {
-                if *i < *l as i128 || *i > *u as i128 {
+                if *i < i128::from(*l) || *i > i128::from(*u) {
                   self.add_error(error_str);
                 } else {
                   return Ok(());
                 }
-              } else if *i <= *l as i128 || *i >= *u as i128 {
+              } else if *i <= i128::from(*l) || *i >= i128::from(*u) {
                 self.add_error(error_str);
                 return Ok(());
               } else {
@@ -620,10 +736,10 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
             }
             Value::Text(s) => match self.ctrl {
               Some(Token::SIZE) => {
-                let len = s.len();
+                let len = s.len() as u64;
                 let s = s.clone();
                 if is_inclusive {
-                  if s.len() < *l || s.len() > *u {
+                  if len < *l || len > *u {
                     self.add_error(format!(
                       "expected \"{}\" string length to be in the range {} <= value <= {}, got {}",
                       s, l, u, len
@@ -632,19 +748,47 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
                   } else {
                     return Ok(());
                   }
-                } else if s.len() <= *l || s.len() >= *u {
+                } else if len < *l || len >= *u {
                   self.add_error(format!(
-                    "expected \"{}\" string length to be in the range {} < value < {}, got {}",
+                    "expected \"{}\" string length to be in the range {} <= value < {}, got {}",
                     s, l, u, len
                   ));
-                  return Ok(());
                 }
+
+                return Ok(());
               }
               _ => {
                 self.add_error("string value cannot be validated against a range without the .size control operator".to_string());
                 return Ok(());
               }
             },
+            Value::Bytes(b) => match self.ctrl {
+              Some(Token::SIZE) => {
+                let len = b.len() as u64;
+                let b = byte_string_literal(b);
+                if is_inclusive {
+                  if len < *l || len > *u {
+                    self.add_error(format!(
+                      "expected {} byte string length to be in the range {} <= value <= {}, got {}",
+                      b, l, u, len
+                    ));
+                  }
+
+                  return Ok(());
+                } else if len < *l || len >= *u {
+                  self.add_error(format!(
+                    "expected {} byte string length to be in the range {} <= value < {}, got {}",
+                    b, l, u, len
+                  ));
+                }
+
+                return Ok(());
+              }
+              _ => {
+                self.add_error("byte string value cannot be validated against a range without the .size control operator".to_string());
+                return Ok(());
+              }
+            },
             _ => {
               self.add_error(error_str);
               return Ok(());
@@ -673,7 +817,7 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
             )
           };
 
-          match &self.cbor {
+          match self.cbor {
             Value::Float(f) => {
               if is_inclusive {
                 if *f < *l as f64 || *f > *u as f64 {
@@ -732,7 +876,7 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
             }
           }
           Type2::Array { group, .. } => {
-            if let Value::Array(_) = &self.cbor {
+            if let Value::Array(_) = self.cbor {
               let mut entry_counts = Vec::new();
               for gc in group.group_choices.iter() {
                 let count = entry_counts_from_group_choice(self.cddl, gc);
@@ -745,7 +889,7 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
             }
           }
           Type2::Map { .. } => {
-            if let Value::Map(_) = &self.cbor {
+            if let Value::Map(_) = self.cbor {
               self.ctrl = t;
               self.is_ctrl_map_equality = true;
               self.visit_type2(controller)?;
@@ -774,7 +918,7 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
             }
           }
           Type2::Array { .. } => {
-            if let Value::Array(_) = &self.cbor {
+            if let Value::Array(_) = self.cbor {
               self.ctrl = t;
               self.visit_type2(controller)?;
               self.ctrl = None;
@@ -782,7 +926,7 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
             }
           }
           Type2::Map { .. } => {
-            if let Value::Map(_) = &self.cbor {
+            if let Value::Map(_) = self.cbor {
               self.ctrl = t;
               self.is_ctrl_map_equality = true;
               self.visit_type2(controller)?;
@@ -818,8 +962,25 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
       t @ Some(Token::SIZE) => match target {
         Type2::Typename { ident, .. }
           if is_ident_string_data_type(self.cddl, ident)
+            || is_ident_byte_string_data_type(self.cddl, ident)
             || is_ident_uint_data_type(self.cddl, ident) =>
         {
+          // The size of a value is only meaningful if it is of the target type
+          let is_target_type = match self.cbor {
+            Value::Text(_) => is_ident_string_data_type(self.cddl, ident),
+            Value::Bytes(_) => is_ident_byte_string_data_type(self.cddl, ident),
+            Value::Integer(_) => is_ident_uint_data_type(self.cddl, ident),
+            _ => true,
+          };
+
+          if !is_target_type {
+            self.add_error(format!(
+              "expected type {} .size {}, got {:?}",
+              ident, controller, self.cbor
+            ));
+            return Ok(());
+          }
+
           self.ctrl = t;
           self.visit_type2(controller)?;
           self.ctrl = None;
@@ -827,7 +988,106 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
         }
         _ => {
           self.add_error(format!(
-            "target for .size must a string or uint data type, got {}",
+            "target for .size must a string, byte string or uint data type, got {}",
+            target
+          ));
+          Ok(())
+        }
+      },
+      t @ Some(Token::CBOR) | t @ Some(Token::CBORSEQ) => match target {
+        Type2::Typename { ident, .. } if is_ident_byte_string_data_type(self.cddl, ident) => {
+          let b = match self.cbor {
+            Value::Bytes(b) => b,
+            _ => {
+              self.add_error(format!(
+                "expected type {} {} {}, got {:?}",
+                ident, ctrl, controller, self.cbor
+              ));
+              return Ok(());
+            }
+          };
+
+          // A CBOR sequence is validated as an array of its data items
+          let embedded = if let Some(Token::CBOR) = t {
+            serde_cbor::from_slice::<Value>(b)
+          } else {
+            serde_cbor::Deserializer::from_slice(b)
+              .into_iter::<Value>()
+              .collect::<std::result::Result<Vec<_>, _>>()
+              .map(Value::Array)
+          };
+
+          match embedded {
+            Ok(embedded) => {
+              let mut cv = self.nested(&embedded);
+              cv.cbor_location.push_str(&self.cbor_location);
+              cv.visit_type2(controller)?;
+
+              self.merge_nested(&mut cv);
+            }
+            Err(e) => self.add_error(format!(
+              "expected byte string containing embedded CBOR ({} {}), decoding error: {}",
+              ctrl, controller, e
+            )),
+          }
+
+          Ok(())
+        }
+        _ => {
+          self.add_error(format!(
+            "target for {} must be a byte string data type, got {}",
+            ctrl, target
+          ));
+          Ok(())
+        }
+      },
+      Some(Token::BITS) => match target {
+        Type2::Typename { ident, .. }
+          if is_ident_byte_string_data_type(self.cddl, ident)
+            || is_ident_uint_data_type(self.cddl, ident) =>
+        {
+          let bits = match self.cbor {
+            Value::Bytes(b) if is_ident_byte_string_data_type(self.cddl, ident) => {
+              set_bits_from_bytes(b)
+            }
+            Value::Integer(i) if *i >= 0 && is_ident_uint_data_type(self.cddl, ident) => {
+              (0..128u64).filter(|bit| *i & (1 << bit) != 0).collect()
+            }
+            _ => {
+              self.add_error(format!(
+                "expected type {} .bits {}, got {:?}",
+                ident, controller, self.cbor
+              ));
+              return Ok(());
+            }
+          };
+
+          // Each set bit must be a bit number admitted by the controller
+          let mut unexpected_bits = Vec::new();
+          for bit in bits.into_iter() {
+            let bit_value = Value::Integer(i128::from(bit));
+            let mut cv = self.nested(&bit_value);
+            cv.visit_type2(controller)?;
+
+            if !cv.errors.is_empty() {
+              unexpected_bits.push(bit.to_string());
+            }
+          }
+
+          if !unexpected_bits.is_empty() {
+            self.add_error(format!(
+              "expected only bits in {} to be set, got unexpected bits {} in {:?}",
+              controller,
+              unexpected_bits.join(", "),
+              self.cbor
+            ));
+          }
+
+          Ok(())
+        }
+        _ => {
+          self.add_error(format!(
+            "target for .bits must be a byte string or uint data type, got {}",
             target
           ));
           Ok(())
@@ -876,7 +1136,234 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
         self.ctrl = None;
         Ok(())
       }
-      t @ Some(Token::REGEXP) | t @ Some(Token::PCRE) => {
+      Some(Token::PLUS) | Some(Token::CAT) | Some(Token::DET) => {
+        match literal_from_control_operator(self.cddl, target, ctrl, controller) {
+          Some(value) => self.visit_value(&value),
+          None => {
+            self.add_error(format!(
+              "cannot compute the value of {} {} {}, expected literal operands",
+              target, ctrl, controller
+            ));
+            Ok(())
+          }
+        }
+      }
+      t @ Some(Token::ABNF) | t @ Some(Token::ABNFB) => match target {
+        Type2::Typename { ident, .. }
+          if (t == Some(Token::ABNF) && is_ident_string_data_type(self.cddl, ident))
+            || (t == Some(Token::ABNFB) && is_ident_byte_string_data_type(self.cddl, ident)) =>
+        {
+          let grammar = match literal_from_type2(self.cddl, controller) {
+            Some(token::Value::TEXT(grammar)) => abnf::Grammar::parse(&grammar),
+            Some(token::Value::BYTE(grammar)) => match grammar.to_bytes() {
+              Some(grammar) => match std::str::from_utf8(&grammar) {
+                Ok(grammar) => abnf::Grammar::parse(grammar),
+                Err(e) => Err(format!("grammar is not valid UTF-8: {}", e)),
+              },
+              None => Err(format!("malformed byte string literal {}", controller)),
+            },
+            _ => Err(format!(
+              "expected text or byte string controller, got {}",
+              controller
+            )),
+          };
+          let grammar = match grammar {
+            Ok(grammar) => grammar,
+            Err(e) => {
+              self.add_error(format!("invalid ABNF grammar for {}: {}", ctrl, e));
+              return Ok(());
+            }
+          };
+
+          let is_match = match self.cbor {
+            Value::Text(text) if t == Some(Token::ABNF) => grammar.matches_text(text),
+            Value::Bytes(bytes) if t == Some(Token::ABNFB) => grammar.matches_bytes(bytes),
+            _ => {
+              self.add_error(format!(
+                "expected type {} {}, got {:?}",
+                ident, ctrl, self.cbor
+              ));
+              return Ok(());
+            }
+          };
+
+          match is_match {
+            Ok(true) => (),
+            Ok(false) => self.add_error(format!(
+              "expected value matching the {} grammar, got {:?}",
+              ctrl, self.cbor
+            )),
+            Err(e) => self.add_error(format!("error matching {} grammar: {}", ctrl, e)),
+          }
+
+          Ok(())
+        }
+        _ => {
+          self.add_error(format!(
+            "target for {} must be a {} data type, got {}",
+            ctrl,
+            if t == Some(Token::ABNF) {
+              "string"
+            } else {
+              "byte string"
+            },
+            target
+          ));
+          Ok(())
+        }
+      },
+      Some(Token::FEATURE) => {
+        let error_count = self.errors.len();
+        self.visit_type2(target)?;
+
+        // The feature is only used if the target is matched
+        if self.errors.len() == error_count {
+          self
+            .features
+            .insert(match literal_from_type2(self.cddl, controller) {
+              Some(token::Value::TEXT(feature)) => feature.into_owned(),
+              _ => controller.to_string(),
+            });
+        }
+
+        Ok(())
+      }
+      Some(t @ Token::B64U)
+      | Some(t @ Token::B64USLOPPY)
+      | Some(t @ Token::B64C)
+      | Some(t @ Token::B64CSLOPPY)
+      | Some(t @ Token::B32)
+      | Some(t @ Token::H32)
+      | Some(t @ Token::B45)
+      | Some(t @ Token::HEX)
+      | Some(t @ Token::HEXLC)
+      | Some(t @ Token::HEXUC)
+      | Some(t @ Token::BASE10)
+      | Some(t @ Token::PRINTF)
+      | Some(t @ Token::JSON) => match target {
+        Type2::Typename { ident, .. } if is_ident_string_data_type(self.cddl, ident) => {
+          let text = match self.cbor {
+            Value::Text(text) => text,
+            _ => {
+              self.add_error(format!(
+                "expected type {} {} {}, got {:?}",
+                ident, ctrl, controller, self.cbor
+              ));
+              return Ok(());
+            }
+          };
+
+          let decoded = match t {
+            Token::BASE10 => encoding::decode_decimal(text).map(token_value_into_cbor_value),
+            // The format string is the first element of the decoded array
+            Token::PRINTF => match printf_format(self.cddl, controller) {
+              Some(format) => match self.schema.and_then(|schema| schema.printf_format(&format)) {
+                Some(compiled) => compiled.values(text),
+                None => encoding::printf_values(&format, text),
+              }
+              .map(|values| {
+                Value::Array(
+                  std::iter::once(Value::Text(format))
+                    .chain(values.into_iter().map(token_value_into_cbor_value))
+                    .collect(),
+                )
+              }),
+              None => Err(format!(
+                "expected array controller starting with a format string, got {}",
+                controller
+              )),
+            },
+            Token::JSON => serde_json::from_str::<serde_json::Value>(text)
+              .map_err(|e| e.to_string())
+              .and_then(|json| serde_cbor::value::to_value(json).map_err(|e| e.to_string())),
+            _ => encoding::decode_bytes(&t, text).map(Value::Bytes),
+          };
+
+          match decoded {
+            Ok(decoded) => {
+              let mut cv = self.nested(&decoded);
+              cv.cbor_location.push_str(&self.cbor_location);
+              cv.visit_type2(controller)?;
+
+              self.merge_nested(&mut cv);
+            }
+            Err(e) => self.add_error(format!(
+              "expected type {} {} {}, decoding error: {}",
+              ident, ctrl, controller, e
+            )),
+          }
+
+          Ok(())
+        }
+        _ => {
+          self.add_error(format!(
+            "target for {} must be a string data type, got {}",
+            ctrl, target
+          ));
+          Ok(())
+        }
+      },
+      Some(Token::JOIN) => match target {
+        Type2::Typename { ident, .. }
+          if is_ident_string_data_type(self.cddl, ident)
+            || is_ident_byte_string_data_type(self.cddl, ident) =>
+        {
+          let (value, is_text) = match self.cbor {
+            Value::Text(text) if is_ident_string_data_type(self.cddl, ident) => {
+              (text.as_bytes(), true)
+            }
+            Value::Bytes(bytes) if is_ident_byte_string_data_type(self.cddl, ident) => {
+              (bytes.as_slice(), false)
+            }
+            _ => {
+              self.add_error(format!(
+                "expected type {} .join {}, got {:?}",
+                ident, controller, self.cbor
+              ));
+              return Ok(());
+            }
+          };
+
+          let entries = match join_entries(self.cddl, controller) {
+            Some(entries) => entries,
+            None => {
+              self.add_error(format!(
+                "controller for .join must be an array without occurrence indicators, got {}",
+                controller
+              ));
+              return Ok(());
+            }
+          };
+
+          let is_match = encoding::join_matches(&entries, value, is_text, &mut |t2, part| {
+            let part = if is_text {
+              Value::Text(String::from_utf8_lossy(part).into_owned())
+            } else {
+              Value::Bytes(part.to_vec())
+            };
+
+            let mut cv = self.nested(&part);
+            cv.visit_type2(t2).is_ok() && cv.errors.is_empty()
+          });
+
+          if !is_match {
+            self.add_error(format!(
+              "expected type {} .join {}, got {:?}",
+              ident, controller, self.cbor
+            ));
+          }
+
+          Ok(())
+        }
+        _ => {
+          self.add_error(format!(
+            "target for .join must be a string or byte string data type, got {}",
+            target
+          ));
+          Ok(())
+        }
+      },
+      t @ Some(Token::CREGEXP) | t @ Some(Token::PCRE) => {
         self.ctrl = t;
         match target {
           Type2::Typename { ident, .. } if is_ident_string_data_type(self.cddl, ident) => {
@@ -897,33 +1384,63 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
 
         Ok(())
       }
-      _ => {
-        self.add_error(format!("unsupported control operator {}", ctrl));
-        Ok(())
-      }
+      _ => match self.controls {
+        Some(controls) if controls.contains(ctrl) => {
+          let error_count = self.errors.len();
+          self.visit_type2(target)?;
+
+          // The control is only evaluated for values matching the target
+          if self.errors.len() == error_count {
+            match controls.validate_cbor(ctrl, target, controller, self.cbor) {
+              Some(Ok(())) => {}
+              Some(Err(reason)) => self.add_error(format!(
+                "expected type {} {} {}: {}",
+                target, ctrl, controller, reason
+              )),
+              None => self.add_error(format!(
+                "control operator {} is not supported for CBOR",
+                ctrl
+              )),
+            }
+          }
+
+          Ok(())
+        }
+        _ => {
+          self.add_error(format!("unsupported control operator {}", ctrl));
+          Ok(())
+        }
+      },
     }
   }
 
   fn visit_type2(&mut self, t2: &Type2<'a>) -> visitor::Result<ValidationError> {
     match t2 {
-      Type2::TextValue { value, .. } => self.visit_value(&token::Value::TEXT(value)),
-      Type2::Map { group, .. } => match &self.cbor {
+      Type2::TextValue { value, .. } => self.visit_value(&token::Value::TEXT(value.clone())),
+      Type2::UTF8ByteString { value, .. } => {
+        self.visit_value(&token::Value::BYTE(ByteValue::UTF8(value.clone())))
+      }
+      Type2::B16ByteString { value, .. } => {
+        self.visit_value(&token::Value::BYTE(ByteValue::B16(value.clone())))
+      }
+      Type2::B64ByteString { value, .. } => {
+        self.visit_value(&token::Value::BYTE(ByteValue::B64(value.clone())))
+      }
+      Type2::Map { group, .. } => match self.cbor {
         Value::Map(m) => {
           if self.is_member_key {
             let current_location = self.cbor_location.clone();
 
             for (k, v) in m.iter() {
-              let mut cv = CBORValidator::new(self.cddl, k.clone());
-              cv.generic_rules = self.generic_rules.clone();
-              cv.eval_generic_rule = self.eval_generic_rule;
+              let mut cv = self.nested(k);
               cv.is_multi_type_choice = self.is_multi_type_choice;
               cv.is_multi_group_choice = self.is_multi_group_choice;
               cv.cbor_location.push_str(&self.cbor_location);
-              cv.type_group_name_entry = self.type_group_name_entry;
+              cv.type_group_name_entry = self.type_group_name_entry.clone();
               cv.visit_type2(t2)?;
 
               if cv.errors.is_empty() {
-                self.object_value = Some(v.clone());
+                self.object_value = Some(v);
                 self
                   .validated_keys
                   .get_or_insert(vec![k.clone()])
@@ -932,21 +1449,19 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
                 return Ok(());
               }
 
-              self.errors.append(&mut cv.errors);
+              self.merge_nested(&mut cv);
             }
 
             return Ok(());
           }
 
-          let m = m.keys().cloned().collect::<Vec<_>>();
-
           self.visit_group(group)?;
 
           // If extra map entries are detected, return validation error
           if self.values_to_validate.is_none() {
-            for k in m.into_iter() {
+            for k in m.keys() {
               if let Some(keys) = &self.validated_keys {
-                if !keys.contains(&k) {
+                if !keys.contains(k) {
                   self.add_error(format!("unexpected key {:?}", k));
                 }
               }
@@ -1001,29 +1516,25 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
 
           if iter_items {
             for (idx, v) in a.iter().enumerate() {
-              let mut cv = CBORValidator::new(self.cddl, v.clone());
-              cv.generic_rules = self.generic_rules.clone();
-              cv.eval_generic_rule = self.eval_generic_rule;
+              let mut cv = self.nested(v);
               cv.is_multi_type_choice = self.is_multi_type_choice;
               cv.cbor_location
                 .push_str(&format!("{}/{}", self.cbor_location, idx));
 
               cv.visit_group(group)?;
 
-              self.errors.append(&mut cv.errors);
+              self.merge_nested(&mut cv);
             }
           } else if let Some(idx) = self.group_entry_idx.take() {
             if let Some(v) = a.get(idx) {
-              let mut cv = CBORValidator::new(self.cddl, v.clone());
-              cv.generic_rules = self.generic_rules.clone();
-              cv.eval_generic_rule = self.eval_generic_rule;
+              let mut cv = self.nested(v);
               cv.is_multi_type_choice = self.is_multi_type_choice;
               cv.cbor_location
                 .push_str(&format!("{}/{}", self.cbor_location, idx));
 
               cv.visit_group(group)?;
 
-              self.errors.append(&mut cv.errors);
+              self.merge_nested(&mut cv);
             } else if !allow_empty_array {
               self.add_error(format!("expected map object {} at index {}", group, idx));
             }
@@ -1041,7 +1552,7 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
           Ok(())
         }
       },
-      Type2::Array { group, .. } => match &self.cbor {
+      Type2::Array { group, .. } => match self.cbor {
         Value::Array(a) => {
           if group.group_choices.len() == 1
             && group.group_choices[0].group_entries.is_empty()
@@ -1075,18 +1586,16 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
           self.entry_counts = Some(entry_counts);
 
           for (k, v) in m.iter() {
-            let mut cv = CBORValidator::new(self.cddl, k.clone());
-            cv.generic_rules = self.generic_rules.clone();
+            let mut cv = self.nested(k);
             cv.entry_counts = self.entry_counts.clone();
-            cv.eval_generic_rule = self.eval_generic_rule;
             cv.is_multi_type_choice = self.is_multi_type_choice;
             cv.is_multi_group_choice = self.is_multi_group_choice;
             cv.cbor_location.push_str(&self.cbor_location);
-            cv.type_group_name_entry = self.type_group_name_entry;
+            cv.type_group_name_entry = self.type_group_name_entry.clone();
             cv.visit_type2(t2)?;
 
             if cv.errors.is_empty() {
-              self.object_value = Some(v.clone());
+              self.object_value = Some(v);
               self
                 .validated_keys
                 .get_or_insert(vec![k.clone()])
@@ -1095,7 +1604,7 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
               return Ok(());
             }
 
-            self.errors.append(&mut cv.errors);
+            self.merge_nested(&mut cv);
           }
 
           self.entry_counts = None;
@@ -1114,8 +1623,7 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
       } => {
         if let Some(ga) = generic_args {
           if let Some(rule) = rule_from_ident(self.cddl, ident) {
-            if let Some(gr) = self
-              .generic_rules
+            if let Some(gr) = Rc::make_mut(&mut self.generic_rules)
               .iter_mut()
               .find(|gr| gr.name == ident.ident)
             {
@@ -1123,21 +1631,20 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
                 gr.args.push((*arg.arg).clone());
               }
             } else if let Some(params) = generic_params_from_rule(rule) {
-              self.generic_rules.push(GenericRule {
-                name: ident.ident,
+              Rc::make_mut(&mut self.generic_rules).push(GenericRule {
+                name: ident.ident.to_string(),
                 params,
                 args: ga.args.iter().cloned().map(|arg| *arg.arg).collect(),
               });
             }
 
-            let mut cv = CBORValidator::new(self.cddl, self.cbor.clone());
-            cv.generic_rules = self.generic_rules.clone();
-            cv.eval_generic_rule = Some(ident.ident);
+            let mut cv = self.nested(self.cbor);
+            cv.eval_generic_rule = Some(ident.ident.to_string());
             cv.is_group_to_choice_enum = true;
             cv.is_multi_type_choice = self.is_multi_type_choice;
             cv.visit_rule(rule)?;
 
-            self.errors.append(&mut cv.errors);
+            self.merge_nested(&mut cv);
 
             return Ok(());
           }
@@ -1170,8 +1677,7 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
       } => {
         if let Some(ga) = generic_args {
           if let Some(rule) = rule_from_ident(self.cddl, ident) {
-            if let Some(gr) = self
-              .generic_rules
+            if let Some(gr) = Rc::make_mut(&mut self.generic_rules)
               .iter_mut()
               .find(|gr| gr.name == ident.ident)
             {
@@ -1179,20 +1685,19 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
                 gr.args.push((*arg.arg).clone());
               }
             } else if let Some(params) = generic_params_from_rule(rule) {
-              self.generic_rules.push(GenericRule {
-                name: ident.ident,
+              Rc::make_mut(&mut self.generic_rules).push(GenericRule {
+                name: ident.ident.to_string(),
                 params,
                 args: ga.args.iter().cloned().map(|arg| *arg.arg).collect(),
               });
             }
 
-            let mut cv = CBORValidator::new(self.cddl, self.cbor.clone());
-            cv.generic_rules = self.generic_rules.clone();
-            cv.eval_generic_rule = Some(ident.ident);
+            let mut cv = self.nested(self.cbor);
+            cv.eval_generic_rule = Some(ident.ident.to_string());
             cv.is_multi_type_choice = self.is_multi_type_choice;
             cv.visit_rule(rule)?;
 
-            self.errors.append(&mut cv.errors);
+            self.merge_nested(&mut cv);
 
             return Ok(());
           }
@@ -1209,14 +1714,9 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
         generic_args,
         ..
       } => {
-        if let Some(tag) = tag_from_token(&lookup_ident(ident.ident)) {
-          return self.visit_type2(&tag);
-        }
-
         if let Some(ga) = generic_args {
           if let Some(rule) = unwrap_rule_from_ident(self.cddl, ident) {
-            if let Some(gr) = self
-              .generic_rules
+            if let Some(gr) = Rc::make_mut(&mut self.generic_rules)
               .iter_mut()
               .find(|gr| gr.name == ident.ident)
             {
@@ -1224,20 +1724,19 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
                 gr.args.push((*arg.arg).clone());
               }
             } else if let Some(params) = generic_params_from_rule(rule) {
-              self.generic_rules.push(GenericRule {
-                name: ident.ident,
+              Rc::make_mut(&mut self.generic_rules).push(GenericRule {
+                name: ident.ident.to_string(),
                 params,
                 args: ga.args.iter().cloned().map(|arg| *arg.arg).collect(),
               });
             }
 
-            let mut cv = CBORValidator::new(self.cddl, self.cbor.clone());
-            cv.generic_rules = self.generic_rules.clone();
-            cv.eval_generic_rule = Some(ident.ident);
+            let mut cv = self.nested(self.cbor);
+            cv.eval_generic_rule = Some(ident.ident.to_string());
             cv.is_multi_type_choice = self.is_multi_type_choice;
             cv.visit_rule(rule)?;
 
-            self.errors.append(&mut cv.errors);
+            self.merge_nested(&mut cv);
 
             return Ok(());
           }
@@ -1247,6 +1746,10 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
           return self.visit_rule(rule);
         }
 
+        if let Some(Rule::Type { rule, .. }) = unwrap_rule_from_ident(standard_prelude(), ident) {
+          return self.visit_type(&rule.value);
+        }
+
         self.add_error(format!(
           "cannot unwrap identifier {}, rule not found",
           ident
@@ -1254,10 +1757,15 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
 
         Ok(())
       }
-      Type2::TaggedData { tag, t, .. } => match &self.cbor {
+      Type2::TaggedData { tag, t, .. } => match self.cbor {
         Value::Tag(actual_tag, value) => {
           if let Some(tag) = tag {
-            if *tag as u64 != *actual_tag {
+            let is_match = match tag {
+              HeadNumber::Uint(tag) => *tag == *actual_tag,
+              HeadNumber::Type(tag) => self.is_head_number_match(tag, *actual_tag)?,
+            };
+
+            if !is_match {
               self.add_error(format!(
                 "expected tagged data #6.{}({}), got {:?}",
                 tag, t, self.cbor
@@ -1272,16 +1780,25 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
             return Ok(());
           }
 
-          let mut cv = CBORValidator::new(self.cddl, value.as_ref().clone());
-          cv.generic_rules = self.generic_rules.clone();
-          cv.eval_generic_rule = self.eval_generic_rule;
+          // Tag 24 always encloses a well-formed, encoded CBOR data item
+          if let (24, Value::Bytes(b)) = (*actual_tag, value.as_ref()) {
+            if let Err(e) = serde_cbor::from_slice::<Value>(b) {
+              self.add_error(format!(
+                "expected encoded CBOR data item in tagged data #6.24, decoding error: {}",
+                e
+              ));
+              return Ok(());
+            }
+          }
+
+          let mut cv = self.nested(value);
           cv.is_multi_type_choice = self.is_multi_type_choice;
           cv.is_multi_group_choice = self.is_multi_group_choice;
           cv.cbor_location.push_str(&self.cbor_location);
-          cv.type_group_name_entry = self.type_group_name_entry;
+          cv.type_group_name_entry = self.type_group_name_entry.clone();
           cv.visit_type(&t)?;
 
-          self.errors.append(&mut cv.errors);
+          self.merge_nested(&mut cv);
           Ok(())
         }
         _ => {
@@ -1300,173 +1817,232 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
           Ok(())
         }
       },
-      Type2::DataMajorType { mt, constraint, .. } => match &self.cbor {
-        Value::Integer(i) => {
-          match mt {
-            0u8 => match constraint {
-              Some(c) if *i == *c as i128 && *i >= 0i128 => return Ok(()),
-              Some(c) => {
-                self.add_error(format!(
-                  "expected uint data type with constraint {} (#{}.{}), got {:?}",
-                  c, mt, c, self.cbor
-                ));
+      Type2::DataMajorType {
+        mt,
+        constraint: Some(HeadNumber::Type(constraint)),
+        ..
+      } => {
+        if let Some((actual_mt, arguments)) = major_type_and_arguments(self.cbor) {
+          if actual_mt == *mt {
+            for argument in arguments {
+              if self.is_head_number_match(constraint, argument)? {
                 return Ok(());
               }
-              _ => {
-                if i.is_negative() {
+            }
+          }
+        }
+
+        self.add_error(format!(
+          "expected major type #{}.<{}>, got {:?}",
+          mt, constraint, self.cbor
+        ));
+
+        Ok(())
+      }
+      Type2::DataMajorType { mt, constraint, .. } => {
+        let constraint = &match constraint {
+          Some(HeadNumber::Uint(c)) => Some(*c),
+          _ => None,
+        };
+
+        match self.cbor {
+          Value::Integer(i) => {
+            match mt {
+              0u8 => match constraint {
+                Some(c) if *i == i128::from(*c) && *i >= 0i128 => return Ok(()),
+                Some(c) => {
                   self.add_error(format!(
-                    "expected uint data type (#{}), got {:?}",
-                    mt, self.cbor
+                    "expected uint data type with constraint {} (#{}.{}), got {:?}",
+                    c, mt, c, self.cbor
                   ));
                   return Ok(());
                 }
-              }
-            },
-            1u8 => match constraint {
-              Some(c) if *i == 0i128 - *c as i128 => return Ok(()),
-              Some(c) => {
-                self.add_error(format!(
-                  "expected nint type with constraint {} (#{}.{}), got {:?}",
-                  c, mt, c, self.cbor
-                ));
-                return Ok(());
-              }
-              _ => {
-                if *i >= 0i128 {
+                _ => {
+                  if i.is_negative() {
+                    self.add_error(format!(
+                      "expected uint data type (#{}), got {:?}",
+                      mt, self.cbor
+                    ));
+                    return Ok(());
+                  }
+                }
+              },
+              1u8 => match constraint {
+                Some(c) if *i == -1 - i128::from(*c) => return Ok(()),
+                Some(c) => {
                   self.add_error(format!(
-                    "expected nint data type (#{}), got {:?}",
-                    mt, self.cbor
+                    "expected nint type with constraint {} (#{}.{}), got {:?}",
+                    c, mt, c, self.cbor
                   ));
                   return Ok(());
                 }
-              }
-            },
-            _ => self.add_error(format!(
-              "expected major type {} with constraint {:?}, got {:?}",
-              mt, constraint, self.cbor
-            )),
-          }
-
-          Ok(())
-        }
-        Value::Bytes(b) => {
-          match mt {
-            2u8 => match constraint {
-              Some(c) if *c == b.len() => return Ok(()),
-              Some(c) => self.add_error(format!(
-                "expected byte string type with constraint {} (#{}.{}), got {:?}",
-                c, mt, c, self.cbor
+                _ => {
+                  if *i >= 0i128 {
+                    self.add_error(format!(
+                      "expected nint data type (#{}), got {:?}",
+                      mt, self.cbor
+                    ));
+                    return Ok(());
+                  }
+                }
+              },
+              _ => self.add_error(format!(
+                "expected major type {} with constraint {:?}, got {:?}",
+                mt, constraint, self.cbor
               )),
-              _ => return Ok(()),
-            },
-            _ => self.add_error(format!(
-              "expected major type {} with constraint {:?}, got {:?}",
-              mt, constraint, self.cbor
-            )),
-          }
+            }
 
-          Ok(())
-        }
-        Value::Text(t) => {
-          match mt {
-            3u8 => match constraint {
-              Some(c) if *c == t.len() => return Ok(()),
-              Some(c) => self.add_error(format!(
-                "expected text string type with constraint {} (#{}.{}), got {:?}",
-                c, mt, c, self.cbor
-              )),
-              _ => return Ok(()),
-            },
-            _ => self.add_error(format!(
-              "expected major type {} with constraint {:?}, got {:?}",
-              mt, constraint, self.cbor
-            )),
+            Ok(())
           }
+          Value::Bytes(b) => {
+            match mt {
+              2u8 => match constraint {
+                Some(c) if *c == b.len() as u64 => return Ok(()),
+                Some(c) => self.add_error(format!(
+                  "expected byte string type with constraint {} (#{}.{}), got {:?}",
+                  c, mt, c, self.cbor
+                )),
+                _ => return Ok(()),
+              },
+              _ => self.add_error(format!(
+                "expected major type {} with constraint {:?}, got {:?}",
+                mt, constraint, self.cbor
+              )),
+            }
 
-          Ok(())
-        }
-        Value::Array(a) => {
-          match mt {
-            4u8 => match constraint {
-              Some(c) if *c == a.len() => return Ok(()),
-              Some(c) => self.add_error(format!(
-                "expected array type with constraint {} (#{}.{}), got {:?}",
-                c, mt, c, self.cbor
+            Ok(())
+          }
+          Value::Text(t) => {
+            match mt {
+              3u8 => match constraint {
+                Some(c) if *c == t.len() as u64 => return Ok(()),
+                Some(c) => self.add_error(format!(
+                  "expected text string type with constraint {} (#{}.{}), got {:?}",
+                  c, mt, c, self.cbor
+                )),
+                _ => return Ok(()),
+              },
+              _ => self.add_error(format!(
+                "expected major type {} with constraint {:?}, got {:?}",
+                mt, constraint, self.cbor
               )),
-              _ => return Ok(()),
-            },
-            _ => self.add_error(format!(
-              "expected major type {} with constraint {:?}, got {:?}",
-              mt, constraint, self.cbor
-            )),
+            }
+
+            Ok(())
           }
+          Value::Array(a) => {
+            match mt {
+              4u8 => match constraint {
+                Some(c) if *c == a.len() as u64 => return Ok(()),
+                Some(c) => self.add_error(format!(
+                  "expected array type with constraint {} (#{}.{}), got {:?}",
+                  c, mt, c, self.cbor
+                )),
+                _ => return Ok(()),
+              },
+              _ => self.add_error(format!(
+                "expected major type {} with constraint {:?}, got {:?}",
+                mt, constraint, self.cbor
+              )),
+            }
 
-          Ok(())
-        }
-        Value::Map(m) => {
-          match mt {
-            5u8 => match constraint {
-              Some(c) if *c == m.len() => return Ok(()),
-              Some(c) => self.add_error(format!(
-                "expected map type with constraint {} (#{}.{}), got {:?}",
-                c, mt, c, self.cbor
+            Ok(())
+          }
+          Value::Map(m) => {
+            match mt {
+              5u8 => match constraint {
+                Some(c) if *c == m.len() as u64 => return Ok(()),
+                Some(c) => self.add_error(format!(
+                  "expected map type with constraint {} (#{}.{}), got {:?}",
+                  c, mt, c, self.cbor
+                )),
+                _ => return Ok(()),
+              },
+              _ => self.add_error(format!(
+                "expected major type {} with constraint {:?}, got {:?}",
+                mt, constraint, self.cbor
               )),
-              _ => return Ok(()),
-            },
-            _ => self.add_error(format!(
-              "expected major type {} with constraint {:?}, got {:?}",
-              mt, constraint, self.cbor
-            )),
+            }
+
+            Ok(())
           }
+          Value::Float(f) => {
+            match mt {
+              // The encoded width of a float isn't retained once decoded, so a
+              // width constraint is met by any float that can be represented
+              // losslessly at that width
+              7u8 => match constraint {
+                Some(25) if is_float16_representable(*f) => return Ok(()),
+                Some(26) if is_float32_representable(*f) => return Ok(()),
+                Some(27) | None => return Ok(()),
+                Some(c @ 25) | Some(c @ 26) => self.add_error(format!(
+                  "expected float representable in {} bits (#{}.{}), got {:?}",
+                  if *c == 25 { 16 } else { 32 },
+                  mt,
+                  c,
+                  self.cbor
+                )),
+                Some(c) => self.add_error(format!(
+                  "expected simple value #{}.{}, got {:?}",
+                  mt, c, self.cbor
+                )),
+              },
+              _ => self.add_error(format!(
+                "expected major type {} with constraint {:?}, got {:?}",
+                mt, constraint, self.cbor
+              )),
+            }
 
-          Ok(())
-        }
-        Value::Float(_f) => {
-          match mt {
-            7u8 => match constraint {
-              Some(_c) => unimplemented!(),
-              _ => return Ok(()),
-            },
-            _ => self.add_error(format!(
-              "expected major type {} with constraint {:?}, got {:?}",
-              mt, constraint, self.cbor
-            )),
+            Ok(())
           }
+          Value::Bool(_) | Value::Null => {
+            let simple_value = match self.cbor {
+              Value::Bool(false) => 20,
+              Value::Bool(true) => 21,
+              _ => 22,
+            };
+
+            match mt {
+              7u8 => match constraint {
+                Some(c) if *c == simple_value => return Ok(()),
+                // Undefined is decoded as null, so the two can't be told apart
+                Some(23) if simple_value == 22 => return Ok(()),
+                Some(c) => self.add_error(format!(
+                  "expected simple value #{}.{}, got {:?}",
+                  mt, c, self.cbor
+                )),
+                None => return Ok(()),
+              },
+              _ => self.add_error(format!(
+                "expected major type {} with constraint {:?}, got {:?}",
+                mt, constraint, self.cbor
+              )),
+            }
 
-          Ok(())
-        }
-        _ => {
-          if let Some(constraint) = constraint {
-            self.add_error(format!(
-              "expected major type #{}.{}, got {:?}",
-              mt, constraint, self.cbor
-            ));
-          } else {
-            self.add_error(format!("expected major type #{}, got {:?}", mt, self.cbor));
+            Ok(())
           }
+          _ => {
+            if let Some(constraint) = constraint {
+              self.add_error(format!(
+                "expected major type #{}.{}, got {:?}",
+                mt, constraint, self.cbor
+              ));
+            } else {
+              self.add_error(format!("expected major type #{}, got {:?}", mt, self.cbor));
+            }
 
-          Ok(())
+            Ok(())
+          }
         }
-      },
-      Type2::Any(_) => Ok(()),
-      _ => {
-        self.add_error(format!(
-          "unsupported data type for validating cbor, got {}",
-          t2
-        ));
-        Ok(())
       }
+      Type2::Any(_) => Ok(()),
     }
   }
 
   fn visit_identifier(&mut self, ident: &Identifier<'a>) -> visitor::Result<ValidationError> {
-    if let Some(name) = self.eval_generic_rule {
-      if let Some(gr) = self
-        .generic_rules
-        .iter()
-        .cloned()
-        .find(|gr| gr.name == name)
-      {
+    if let Some(name) = &self.eval_generic_rule {
+      let generic_rules = Rc::clone(&self.generic_rules);
+      if let Some(gr) = generic_rules.iter().find(|gr| gr.name == *name) {
         for (idx, gp) in gr.params.iter().enumerate() {
           if *gp == ident.ident {
             if let Some(arg) = gr.args.get(idx) {
@@ -1481,13 +2057,34 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
       return self.visit_rule(r);
     }
 
+    // Names from the standard prelude are validated against their prelude
+    // definition like any other rule, unless their type is implemented
+    // natively. Tagged data always is, e.g. against tdate = #6.0(tstr)
+    if !is_ident_native_prelude_type(ident) || matches!(self.cbor, Value::Tag(..)) {
+      if let Some(tr) = type_rule_from_ident(standard_prelude(), ident) {
+        return self.visit_type(&tr.value);
+      }
+    }
+
     if is_ident_any_type(self.cddl, ident) {
       return Ok(());
     }
 
-    match &self.cbor {
+    match self.cbor {
       Value::Null if is_ident_null_data_type(self.cddl, ident) => Ok(()),
       Value::Bytes(_) if is_ident_byte_string_data_type(self.cddl, ident) => Ok(()),
+      Value::Tag(2, b)
+        if matches!(b.as_ref(), Value::Bytes(_))
+          && is_ident_biguint_data_type(self.cddl, ident) =>
+      {
+        Ok(())
+      }
+      Value::Tag(3, b)
+        if matches!(b.as_ref(), Value::Bytes(_))
+          && is_ident_bignint_data_type(self.cddl, ident) =>
+      {
+        Ok(())
+      }
       Value::Bool(b) => {
         if is_ident_bool_data_type(self.cddl, ident) {
           return Ok(());
@@ -1610,29 +2207,25 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
 
         if iter_items {
           for (idx, v) in a.iter().enumerate() {
-            let mut cv = CBORValidator::new(self.cddl, v.clone());
-            cv.generic_rules = self.generic_rules.clone();
-            cv.eval_generic_rule = self.eval_generic_rule;
+            let mut cv = self.nested(v);
             cv.is_multi_type_choice = self.is_multi_type_choice;
             cv.cbor_location
               .push_str(&format!("{}/{}", self.cbor_location, idx));
 
             cv.visit_identifier(ident)?;
 
-            self.errors.append(&mut cv.errors);
+            self.merge_nested(&mut cv);
           }
         } else if let Some(idx) = self.group_entry_idx.take() {
           if let Some(v) = a.get(idx) {
-            let mut cv = CBORValidator::new(self.cddl, v.clone());
-            cv.generic_rules = self.generic_rules.clone();
-            cv.eval_generic_rule = self.eval_generic_rule;
+            let mut cv = self.nested(v);
             cv.is_multi_type_choice = self.is_multi_type_choice;
             cv.cbor_location
               .push_str(&format!("{}/{}", self.cbor_location, idx));
 
             cv.visit_identifier(ident)?;
 
-            self.errors.append(&mut cv.errors);
+            self.merge_nested(&mut cv);
           } else if !allow_empty_array {
             self.add_error(format!("expected type {} at index {}", ident, idx));
           }
@@ -1663,7 +2256,7 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
                   if let Some(keys) = &self.validated_keys {
                     if !keys.contains(&k) {
                       if matches!(k, Value::Text(_)) {
-                        Some(v.clone())
+                        Some(v)
                       } else {
                         errors.push(format!("key of type {} required, got {:?}", ident, k));
                         None
@@ -1672,7 +2265,7 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
                       None
                     }
                   } else if matches!(k, Value::Text(_)) {
-                    Some(v.clone())
+                    Some(v)
                   } else {
                     errors.push(format!("key of type {} required, got {:?}", ident, k));
                     None
@@ -1696,7 +2289,7 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
                   if let Some(keys) = &self.validated_keys {
                     if !keys.contains(&k) {
                       if matches!(k, Value::Integer(_)) {
-                        Some(v.clone())
+                        Some(v)
                       } else {
                         errors.push(format!("key of type {} required, got {:?}", ident, k));
                         None
@@ -1705,7 +2298,7 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
                       None
                     }
                   } else if matches!(k, Value::Integer(_)) {
-                    Some(v.clone())
+                    Some(v)
                   } else {
                     errors.push(format!("key of type {} required, got {:?}", ident, k));
                     None
@@ -1729,7 +2322,7 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
                   if let Some(keys) = &self.validated_keys {
                     if !keys.contains(&k) {
                       if matches!(k, Value::Bool(_)) {
-                        Some(v.clone())
+                        Some(v)
                       } else {
                         errors.push(format!("key of type {} required, got {:?}", ident, k));
                         None
@@ -1738,7 +2331,7 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
                       None
                     }
                   } else if matches!(k, Value::Bool(_)) {
-                    Some(v.clone())
+                    Some(v)
                   } else {
                     errors.push(format!("key of type {} required, got {:?}", ident, k));
                     None
@@ -1762,7 +2355,7 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
                   if let Some(keys) = &self.validated_keys {
                     if !keys.contains(&k) {
                       if matches!(k, Value::Bytes(_)) {
-                        Some(v.clone())
+                        Some(v)
                       } else {
                         errors.push(format!("key of type {} required, got {:?}", ident, k));
                         None
@@ -1771,7 +2364,7 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
                       None
                     }
                   } else if matches!(k, Value::Bytes(_)) {
-                    Some(v.clone())
+                    Some(v)
                   } else {
                     errors.push(format!("key of type {} required, got {:?}", ident, k));
                     None
@@ -1795,7 +2388,7 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
                   if let Some(keys) = &self.validated_keys {
                     if !keys.contains(&k) {
                       if matches!(k, Value::Null) {
-                        Some(v.clone())
+                        Some(v)
                       } else {
                         errors.push(format!("key of type {} required, got {:?}", ident, k));
                         None
@@ -1804,7 +2397,7 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
                       None
                     }
                   } else if matches!(k, Value::Null) {
-                    Some(v.clone())
+                    Some(v)
                   } else {
                     errors.push(format!("key of type {} required, got {:?}", ident, k));
                     None
@@ -1828,7 +2421,7 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
                   if let Some(keys) = &self.validated_keys {
                     if !keys.contains(&k) {
                       if matches!(k, Value::Float(_)) {
-                        Some(v.clone())
+                        Some(v)
                       } else {
                         errors.push(format!("key of type {} required, got {:?}", ident, k));
                         None
@@ -1837,7 +2430,7 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
                       None
                     }
                   } else if matches!(k, Value::Float(_)) {
-                    Some(v.clone())
+                    Some(v)
                   } else {
                     errors.push(format!("key of type {} required, got {:?}", ident, k));
                     None
@@ -1861,7 +2454,7 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
               .validated_keys
               .get_or_insert(vec![k.clone()])
               .push(k.clone());
-            self.object_value = Some(v.clone());
+            self.object_value = Some(v);
             self.cbor_location.push_str(&format!("/{:?}", v));
           } else {
             self.add_error(format!("map requires entry key of type {}", ident));
@@ -1876,7 +2469,7 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
               .validated_keys
               .get_or_insert(vec![k.clone()])
               .push(k.clone());
-            self.object_value = Some(v.clone());
+            self.object_value = Some(v);
             self.cbor_location.push_str(&format!("/{:?}", v));
           } else {
             self.add_error(format!("map requires entry key of type {}", ident));
@@ -1890,7 +2483,7 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
               .validated_keys
               .get_or_insert(vec![k.clone()])
               .push(k.clone());
-            self.object_value = Some(v.clone());
+            self.object_value = Some(v);
             self.cbor_location.push_str(&format!("/{:?}", v));
           } else {
             self.add_error(format!("map requires entry key of type {}", ident));
@@ -1904,7 +2497,7 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
               .validated_keys
               .get_or_insert(vec![k.clone()])
               .push(k.clone());
-            self.object_value = Some(v.clone());
+            self.object_value = Some(v);
             self.cbor_location.push_str(&format!("/{:?}", v));
           } else {
             self.add_error(format!("map requires entry key of type {}", ident));
@@ -1918,7 +2511,7 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
               .validated_keys
               .get_or_insert(vec![k.clone()])
               .push(k.clone());
-            self.object_value = Some(v.clone());
+            self.object_value = Some(v);
             self.cbor_location.push_str(&format!("/{:?}", v));
           } else {
             self.add_error(format!("map requires entry key of type {}", ident));
@@ -1932,7 +2525,7 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
               .validated_keys
               .get_or_insert(vec![k.clone()])
               .push(k.clone());
-            self.object_value = Some(v.clone());
+            self.object_value = Some(v);
             self.cbor_location.push_str(&format!("/{:?}", v));
           } else {
             self.add_error(format!("map requires entry key of type {}", ident));
@@ -1940,7 +2533,7 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
           return Ok(());
         }
 
-        self.visit_value(&token::Value::TEXT(ident.ident))
+        self.visit_value(&token::Value::TEXT(ident.ident.clone()))
       }
       _ => {
         if let Some(cut_value) = self.cut_value.take() {
@@ -1979,20 +2572,18 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
       }
     }
 
-    if let Some(values) = &self.values_to_validate {
-      for v in values.iter() {
-        let mut cv = CBORValidator::new(self.cddl, v.clone());
-        cv.generic_rules = self.generic_rules.clone();
-        cv.eval_generic_rule = self.eval_generic_rule;
+    if let Some(values) = self.values_to_validate.clone() {
+      for v in values.into_iter() {
+        let mut cv = self.nested(v);
         cv.is_multi_type_choice = self.is_multi_type_choice;
         cv.is_multi_group_choice = self.is_multi_group_choice;
         cv.cbor_location.push_str(&self.cbor_location);
-        cv.type_group_name_entry = self.type_group_name_entry;
+        cv.type_group_name_entry = self.type_group_name_entry.clone();
         cv.visit_type(&entry.entry_type)?;
 
         self.cbor_location = current_location.clone();
 
-        self.errors.append(&mut cv.errors);
+        self.merge_nested(&mut cv);
         if entry.occur.is_some() {
           self.occurrence = None;
         }
@@ -2002,18 +2593,16 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
     }
 
     if let Some(v) = self.object_value.take() {
-      let mut cv = CBORValidator::new(self.cddl, v);
-      cv.generic_rules = self.generic_rules.clone();
-      cv.eval_generic_rule = self.eval_generic_rule;
+      let mut cv = self.nested(v);
       cv.is_multi_type_choice = self.is_multi_type_choice;
       cv.is_multi_group_choice = self.is_multi_group_choice;
       cv.cbor_location.push_str(&self.cbor_location);
-      cv.type_group_name_entry = self.type_group_name_entry;
+      cv.type_group_name_entry = self.type_group_name_entry.clone();
       cv.visit_type(&entry.entry_type)?;
 
       self.cbor_location = current_location;
 
-      self.errors.append(&mut cv.errors);
+      self.merge_nested(&mut cv);
       if entry.occur.is_some() {
         self.occurrence = None;
       }
@@ -2030,7 +2619,7 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
     &mut self,
     entry: &TypeGroupnameEntry<'a>,
   ) -> visitor::Result<ValidationError> {
-    self.type_group_name_entry = Some(entry.name.ident);
+    self.type_group_name_entry = Some(entry.name.ident.to_string());
     walk_type_groupname_entry(self, entry)?;
     self.type_group_name_entry = None;
 
@@ -2046,16 +2635,16 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
   }
 
   fn visit_value(&mut self, value: &token::Value<'a>) -> visitor::Result<ValidationError> {
-    let error: Option<String> = match &self.cbor {
+    let error: Option<String> = match self.cbor {
       Value::Integer(i) => match value {
         token::Value::INT(v) => match &self.ctrl {
-          Some(Token::NE) if *i != *v as i128 => None,
-          Some(Token::LT) if *i < *v as i128 => None,
-          Some(Token::LE) if *i <= *v as i128 => None,
-          Some(Token::GT) if *i > *v as i128 => None,
-          Some(Token::GE) if *i >= *v as i128 => None,
+          Some(Token::NE) if *i != *v => None,
+          Some(Token::LT) if *i < *v => None,
+          Some(Token::LE) if *i <= *v => None,
+          Some(Token::GT) if *i > *v => None,
+          Some(Token::GE) if *i >= *v => None,
           None => {
-            if *i == *v as i128 {
+            if *i == *v {
               None
             } else {
               Some(format!("expected value {}, got {}", v, i))
@@ -2069,14 +2658,14 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
           )),
         },
         token::Value::UINT(v) => match &self.ctrl {
-          Some(Token::NE) if *i != *v as i128 => None,
-          Some(Token::LT) if *i < *v as i128 => None,
-          Some(Token::LE) if *i <= *v as i128 => None,
-          Some(Token::GT) if *i > *v as i128 => None,
-          Some(Token::GE) if *i >= *v as i128 => None,
-          Some(Token::SIZE) if *i < 256i128.pow(*v as u32) => None,
+          Some(Token::NE) if *i != i128::from(*v) => None,
+          Some(Token::LT) if *i < i128::from(*v) => None,
+          Some(Token::LE) if *i <= i128::from(*v) => None,
+          Some(Token::GT) if *i > i128::from(*v) => None,
+          Some(Token::GE) if *i >= i128::from(*v) => None,
+          Some(Token::SIZE) if *v >= 16 || *i < 256i128.pow(*v as u32) => None,
           None => {
-            if *i == *v as i128 {
+            if *i == i128::from(*v) {
               None
             } else {
               Some(format!("expected value {}, got {}", v, i))
@@ -2094,13 +2683,13 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
       },
       Value::Float(f) => match value {
         token::Value::FLOAT(v) => match &self.ctrl {
-          Some(Token::NE) if (f - *v).abs() > std::f64::EPSILON => None,
+          Some(Token::NE) if !float_eq(*f, *v) => None,
           Some(Token::LT) if *f < *v as f64 => None,
           Some(Token::LE) if *f <= *v as f64 => None,
           Some(Token::GT) if *f > *v as f64 => None,
           Some(Token::GE) if *f >= *v as f64 => None,
           None => {
-            if (f - *v).abs() < std::f64::EPSILON {
+            if float_eq(*f, *v) {
               None
             } else {
               Some(format!("expected value {}, got {}", v, f))
@@ -2124,16 +2713,16 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
               Some(format!("expected {} .ne to \"{}\"", value, s))
             }
           }
-          Some(Token::REGEXP) | Some(Token::PCRE) => {
-            let re = regex::Regex::new(
-              serde_json::from_str::<serde_json::Value>(&format!("\"{}\"", t))
-                .map_err(|e| ValidationError::from_validator(self, e.to_string()))?
-                .as_str()
-                .ok_or_else(|| {
-                  ValidationError::from_validator(self, "malformed regex".to_string())
-                })?,
-            )
-            .map_err(|e| ValidationError::from_validator(self, e.to_string()))?;
+          Some(Token::CREGEXP) | Some(Token::PCRE) => {
+            let compiled;
+            let re = match self.schema.and_then(|schema| schema.regex(t)) {
+              Some(re) => re,
+              None => {
+                compiled =
+                  regex_from_text(t).map_err(|e| ValidationError::from_validator(self, e))?;
+                &compiled
+              }
+            };
 
             if re.is_match(s) {
               None
@@ -2153,7 +2742,7 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
         },
         token::Value::UINT(u) => match &self.ctrl {
           Some(Token::SIZE) => {
-            if s.len() == *u {
+            if s.len() as u64 == *u {
               None
             } else {
               Some(format!("expected \"{}\" .size {}, got {}", s, u, s.len()))
@@ -2161,11 +2750,64 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
           }
           _ => Some(format!("expected {}, got {}", u, s)),
         },
-        token::Value::BYTE(token::ByteValue::UTF8(b)) if s.as_bytes() == b.as_ref() => None,
-        token::Value::BYTE(token::ByteValue::B16(b)) if s.as_bytes() == b.as_ref() => None,
-        token::Value::BYTE(token::ByteValue::B64(b)) if s.as_bytes() == b.as_ref() => None,
         _ => Some(format!("expected {}, got \"{}\"", value, s)),
       },
+      Value::Bytes(b) => match value {
+        token::Value::BYTE(bv) => match bv.to_bytes() {
+          Some(expected) => match &self.ctrl {
+            Some(Token::NE) => {
+              if *b != expected.as_ref() {
+                None
+              } else {
+                Some(format!(
+                  "expected {} .ne to {}",
+                  value,
+                  byte_string_literal(b)
+                ))
+              }
+            }
+            _ => {
+              if *b == expected.as_ref() {
+                None
+              } else if let Some(ctrl) = &self.ctrl {
+                Some(format!(
+                  "expected value {} {}, got {}",
+                  ctrl,
+                  value,
+                  byte_string_literal(b)
+                ))
+              } else {
+                Some(format!(
+                  "expected value {}, got {}",
+                  value,
+                  byte_string_literal(b)
+                ))
+              }
+            }
+          },
+          None => Some(format!("malformed byte string literal {}", value)),
+        },
+        token::Value::UINT(u) => match &self.ctrl {
+          Some(Token::SIZE) => {
+            if b.len() as u64 == *u {
+              None
+            } else {
+              Some(format!(
+                "expected {} .size {}, got {}",
+                byte_string_literal(b),
+                u,
+                b.len()
+              ))
+            }
+          }
+          _ => Some(format!("expected {}, got {}", u, byte_string_literal(b))),
+        },
+        _ => Some(format!(
+          "expected {}, got {}",
+          value,
+          byte_string_literal(b)
+        )),
+      },
       Value::Array(a) => {
         // Member keys are annotation only in an array context
         if self.is_member_key {
@@ -2210,29 +2852,25 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
 
         if iter_items {
           for (idx, v) in a.iter().enumerate() {
-            let mut cv = CBORValidator::new(self.cddl, v.clone());
-            cv.generic_rules = self.generic_rules.clone();
-            cv.eval_generic_rule = self.eval_generic_rule;
+            let mut cv = self.nested(v);
             cv.is_multi_type_choice = self.is_multi_type_choice;
             cv.cbor_location
               .push_str(&format!("{}/{}", self.cbor_location, idx));
 
             cv.visit_value(value)?;
 
-            self.errors.append(&mut cv.errors);
+            self.merge_nested(&mut cv);
           }
         } else if let Some(idx) = self.group_entry_idx.take() {
           if let Some(v) = a.get(idx) {
-            let mut cv = CBORValidator::new(self.cddl, v.clone());
-            cv.generic_rules = self.generic_rules.clone();
-            cv.eval_generic_rule = self.eval_generic_rule;
+            let mut cv = self.nested(v);
             cv.is_multi_type_choice = self.is_multi_type_choice;
             cv.cbor_location
               .push_str(&format!("{}/{}", self.cbor_location, idx));
 
             cv.visit_value(value)?;
 
-            self.errors.append(&mut cv.errors);
+            self.merge_nested(&mut cv);
           } else if !allow_empty_array {
             self.add_error(format!("expected value {} at index {}", value, idx));
           }
@@ -2247,7 +2885,7 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
           self.cut_value = Some(Type1::from(value.clone()));
         }
 
-        if let token::Value::TEXT("any") = value {
+        if matches!(value, token::Value::TEXT(t) if t == "any") {
           return Ok(());
         }
 
@@ -2257,7 +2895,7 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
 
         if let Some(v) = o.get(&k) {
           self.validated_keys.get_or_insert(vec![k.clone()]).push(k);
-          self.object_value = Some(v.clone());
+          self.object_value = Some(v);
           self.cbor_location.push_str(&format!("/{}", value));
 
           None
@@ -2289,17 +2927,105 @@ impl<'a> Visitor<'a, ValidationError> for CBORValidator<'a> {
   }
 }
 
+/// Returns the major type of the data item a value is encoded as, along with
+/// the arguments it may be encoded with. The argument of a float is the
+/// additional information giving its width, and of a simple value its number
+fn major_type_and_arguments(value: &Value) -> Option<(u8, Vec<u64>)> {
+  match value {
+    Value::Integer(i) if *i >= 0 => Some((0, vec![u64::try_from(*i).ok()?])),
+    Value::Integer(i) => Some((1, vec![u64::try_from(-1 - *i).ok()?])),
+    Value::Bytes(b) => Some((2, vec![b.len() as u64])),
+    Value::Text(t) => Some((3, vec![t.len() as u64])),
+    Value::Array(a) => Some((4, vec![a.len() as u64])),
+    Value::Map(m) => Some((5, vec![m.len() as u64])),
+    Value::Tag(tag, _) => Some((6, vec![*tag])),
+    Value::Float(f) => {
+      let mut widths = Vec::new();
+      if is_float16_representable(*f) {
+        widths.push(25);
+      }
+      if is_float32_representable(*f) {
+        widths.push(26);
+      }
+      widths.push(27);
+
+      Some((7, widths))
+    }
+    Value::Bool(false) => Some((7, vec![20])),
+    Value::Bool(true) => Some((7, vec![21])),
+    // Undefined is decoded as null, so the two can't be told apart
+    Value::Null => Some((7, vec![22, 23])),
+    _ => None,
+  }
+}
+
+/// Returns the numbers of the bits set in a byte string, where bit n is found
+/// in byte n / 8 with a value of 2^(n % 8)
+fn set_bits_from_bytes(b: &[u8]) -> Vec<u64> {
+  let mut bits = Vec::new();
+  for (idx, byte) in b.iter().enumerate() {
+    for bit in 0..8u64 {
+      if byte & (1 << bit) != 0 {
+        bits.push(idx as u64 * 8 + bit);
+      }
+    }
+  }
+
+  bits
+}
+
+/// Returns whether a float can be represented without loss by a half precision
+/// float (#7.25)
+pub(crate) fn is_float16_representable(f: f64) -> bool {
+  if !f.is_finite() || f == 0.0 {
+    return true;
+  }
+
+  let f = f.abs();
+  if f > 65504.0 {
+    return false;
+  }
+
+  // Half precision floats carry 10 fraction bits, with subnormals sharing the
+  // smallest normal exponent of -14
+  let exponent = ((f.to_bits() >> 52) & 0x7ff) as i32 - 1023;
+  let step = 2f64.powi(exponent.max(-14) - 10);
+
+  (f / step).fract() == 0.0
+}
+
+/// Returns whether a float can be represented without loss by a single
+/// precision float (#7.26)
+pub(crate) fn is_float32_representable(f: f64) -> bool {
+  f.is_nan() || f64::from(f as f32) == f
+}
+
 /// Converts a CDDL value type to serde_cbor::Value
 pub fn token_value_into_cbor_value(value: token::Value) -> serde_cbor::Value {
   match value {
-    token::Value::UINT(i) => serde_cbor::Value::Integer(i as i128),
-    token::Value::INT(i) => serde_cbor::Value::Integer(i as i128),
+    token::Value::UINT(i) => serde_cbor::Value::Integer(i128::from(i)),
+    token::Value::INT(i) => serde_cbor::Value::Integer(i),
     token::Value::FLOAT(f) => serde_cbor::Value::Float(f),
     token::Value::TEXT(t) => serde_cbor::Value::Text(t.to_string()),
-    token::Value::BYTE(b) => match b {
-      ByteValue::UTF8(b) | ByteValue::B16(b) | ByteValue::B64(b) => {
-        serde_cbor::Value::Bytes(b.into_owned())
-      }
+    token::Value::BYTE(b) => match b.to_bytes() {
+      Some(bytes) => serde_cbor::Value::Bytes(bytes.into_owned()),
+      None => match b {
+        ByteValue::UTF8(b) | ByteValue::B16(b) | ByteValue::B64(b) => {
+          serde_cbor::Value::Bytes(b.into_owned())
+        }
+      },
     },
   }
 }
+
+/// Formats bytes as a base16 encoded CDDL byte string literal for use in error
+/// messages
+fn byte_string_literal(b: &[u8]) -> String {
+  let mut s = String::from("h'");
+  for byte in b.iter() {
+    s.push_str(&format!("{:02x}", byte));
+  }
+  s.push('\'');
+
+  s
+}
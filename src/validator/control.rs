@@ -0,0 +1,145 @@
+use crate::ast::Type2;
+use std::collections::BTreeMap;
+
+/// Callback validating a CBOR value against a user-defined control operator,
+/// given the target and controller types
+pub type CBORControl =
+  Box<dyn Fn(&Type2, &Type2, &serde_cbor::Value) -> Result<(), String> + Send + Sync>;
+
+/// Callback validating a JSON value against a user-defined control operator,
+/// given the target and controller types
+pub type JSONControl =
+  Box<dyn Fn(&Type2, &Type2, &serde_json::Value) -> Result<(), String> + Send + Sync>;
+
+/// Registry of user-defined control operators consulted by the validators for
+/// controls that are not defined by the standard. The registered names must
+/// also be given to `Lexer::with_custom_controls` so that they are accepted
+/// when parsing
+pub trait ControlRegistry {
+  /// Names of the registered control operators, including the leading '.'
+  fn names(&self) -> Vec<&str>;
+
+  /// Whether the named control operator is registered
+  fn contains(&self, ctrl: &str) -> bool {
+    self.names().contains(&ctrl)
+  }
+
+  /// Validates a CBOR value against the named control operator, returning a
+  /// reason on failure. `None` if the control operator does not apply to CBOR
+  fn validate_cbor(
+    &self,
+    _ctrl: &str,
+    _target: &Type2,
+    _controller: &Type2,
+    _value: &serde_cbor::Value,
+  ) -> Option<Result<(), String>> {
+    None
+  }
+
+  /// Validates a JSON value against the named control operator, returning a
+  /// reason on failure. `None` if the control operator does not apply to JSON
+  fn validate_json(
+    &self,
+    _ctrl: &str,
+    _target: &Type2,
+    _controller: &Type2,
+    _value: &serde_json::Value,
+  ) -> Option<Result<(), String>> {
+    None
+  }
+}
+
+/// Control operators registered by name with a callback for each data format
+/// they apply to
+///
+/// # Example
+///
+/// ```
+/// use cddl::validator::{control::CustomControls, validate_json_from_str_with_controls};
+///
+/// let mut controls = CustomControls::new();
+/// controls.register_json(".even", |_, _, value| match value.as_u64() {
+///   Some(n) if n % 2 == 0 => Ok(()),
+///   _ => Err("expected an even number".to_string()),
+/// });
+///
+/// assert!(validate_json_from_str_with_controls("n = uint .even 0", "4", &controls).is_ok());
+/// assert!(validate_json_from_str_with_controls("n = uint .even 0", "5", &controls).is_err());
+/// ```
+#[derive(Default)]
+pub struct CustomControls {
+  cbor: BTreeMap<String, CBORControl>,
+  json: BTreeMap<String, JSONControl>,
+}
+
+impl CustomControls {
+  /// Creates an empty registry
+  pub fn new() -> Self {
+    CustomControls::default()
+  }
+
+  /// Registers the callback used when validating CBOR against the named
+  /// control operator, replacing any previously registered one
+  pub fn register_cbor<F>(&mut self, name: &str, callback: F) -> &mut Self
+  where
+    F: Fn(&Type2, &Type2, &serde_cbor::Value) -> Result<(), String> + Send + Sync + 'static,
+  {
+    self.cbor.insert(name.to_string(), Box::new(callback));
+    self
+  }
+
+  /// Registers the callback used when validating JSON against the named
+  /// control operator, replacing any previously registered one
+  pub fn register_json<F>(&mut self, name: &str, callback: F) -> &mut Self
+  where
+    F: Fn(&Type2, &Type2, &serde_json::Value) -> Result<(), String> + Send + Sync + 'static,
+  {
+    self.json.insert(name.to_string(), Box::new(callback));
+    self
+  }
+}
+
+impl ControlRegistry for CustomControls {
+  fn names(&self) -> Vec<&str> {
+    let mut names = self
+      .cbor
+      .keys()
+      .chain(self.json.keys())
+      .map(String::as_str)
+      .collect::<Vec<_>>();
+    names.sort_unstable();
+    names.dedup();
+
+    names
+  }
+
+  fn contains(&self, ctrl: &str) -> bool {
+    self.cbor.contains_key(ctrl) || self.json.contains_key(ctrl)
+  }
+
+  fn validate_cbor(
+    &self,
+    ctrl: &str,
+    target: &Type2,
+    controller: &Type2,
+    value: &serde_cbor::Value,
+  ) -> Option<Result<(), String>> {
+    self
+      .cbor
+      .get(ctrl)
+      .map(|callback| callback(target, controller, value))
+  }
+
+  fn validate_json(
+    &self,
+    ctrl: &str,
+    target: &Type2,
+    controller: &Type2,
+    value: &serde_json::Value,
+  ) -> Option<Result<(), String>> {
+    self
+      .json
+      .get(ctrl)
+      .map(|callback| callback(target, controller, value))
+  }
+}
@@ -0,0 +1,420 @@
+//! Decoding of text strings that carry encoded data, as described by the
+//! control operators of [RFC 9741](https://tools.ietf.org/html/rfc9741)
+
+use crate::token::{Token, Value};
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::{borrow::Cow, collections::HashSet, convert::TryFrom};
+
+const BASE64_URL: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+const BASE64_CLASSIC: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const BASE32: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+const BASE32_HEX: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUV";
+const BASE45: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
+
+/// Decodes the bytes carried by a text string according to the `.b64u`,
+/// `.b64u-sloppy`, `.b64c`, `.b64c-sloppy`, `.b32`, `.h32`, `.b45`, `.hex`,
+/// `.hexlc` or `.hexuc` control operator
+pub fn decode_bytes(ctrl: &Token, text: &str) -> Result<Vec<u8>, String> {
+  match ctrl {
+    Token::B64U => decode_base(text, BASE64_URL, 6, false, true),
+    Token::B64USLOPPY => decode_base(text, BASE64_URL, 6, false, false),
+    Token::B64C => decode_base(text, BASE64_CLASSIC, 6, true, true),
+    Token::B64CSLOPPY => decode_base(text, BASE64_CLASSIC, 6, true, false),
+    Token::B32 => decode_base(text, BASE32, 5, false, true),
+    Token::H32 => decode_base(text, BASE32_HEX, 5, false, true),
+    Token::B45 => decode_base45(text),
+    Token::HEX => decode_base(&text.to_ascii_uppercase(), BASE32_HEX, 4, false, true),
+    Token::HEXLC if text.bytes().any(|b| b.is_ascii_uppercase()) => {
+      Err("expected lowercase hex digits".to_string())
+    }
+    Token::HEXLC => decode_base(&text.to_ascii_uppercase(), BASE32_HEX, 4, false, true),
+    Token::HEXUC if text.bytes().any(|b| b.is_ascii_lowercase()) => {
+      Err("expected uppercase hex digits".to_string())
+    }
+    Token::HEXUC => decode_base(text, BASE32_HEX, 4, false, true),
+    _ => Err(format!("{} is not a byte string encoding", ctrl)),
+  }
+}
+
+/// Decodes text in a base 16, 32 or 64 alphabet, each character of which
+/// carries the given number of bits. The base 16 alphabet is the prefix of the
+/// base32hex alphabet. Strict decoding requires unused bits to be zero
+fn decode_base(
+  text: &str,
+  alphabet: &[u8],
+  bits: u32,
+  is_padded: bool,
+  is_strict: bool,
+) -> Result<Vec<u8>, String> {
+  let alphabet = &alphabet[..1 << bits];
+  let mut digits = text.as_bytes();
+
+  // Characters per group of whole bytes
+  let group_len = match bits {
+    4 => 2,
+    5 => 8,
+    _ => 4,
+  };
+  if is_padded {
+    if digits
+      .chunks(group_len)
+      .any(|group| group.len() < group_len)
+    {
+      return Err("expected padded encoding".to_string());
+    }
+    while let Some((b'=', rest)) = digits.split_last() {
+      digits = rest;
+    }
+  }
+
+  let mut bytes = Vec::with_capacity(digits.len() * bits as usize / 8);
+  let mut buffer = 0u32;
+  let mut buffered_bits = 0;
+  for d in digits.iter() {
+    let value = alphabet
+      .iter()
+      .position(|a| a == d)
+      .ok_or_else(|| format!("invalid character '{}'", char::from(*d)))?;
+
+    buffer = (buffer << bits) | value as u32;
+    buffered_bits += bits;
+    if buffered_bits >= 8 {
+      buffered_bits -= 8;
+      bytes.push((buffer >> buffered_bits) as u8);
+      buffer &= (1 << buffered_bits) - 1;
+    }
+  }
+
+  // A trailing character that doesn't complete a byte can't be produced by an
+  // encoder
+  if buffered_bits >= bits {
+    return Err("invalid length".to_string());
+  }
+  if is_strict && buffer != 0 {
+    return Err("unused bits are not zero".to_string());
+  }
+
+  Ok(bytes)
+}
+
+/// Decodes base45 text ([RFC 9285](https://tools.ietf.org/html/rfc9285))
+fn decode_base45(text: &str) -> Result<Vec<u8>, String> {
+  let mut values = Vec::with_capacity(text.len());
+  for c in text.bytes() {
+    values.push(
+      BASE45
+        .iter()
+        .position(|a| *a == c)
+        .ok_or_else(|| format!("invalid character '{}'", char::from(c)))? as u32,
+    );
+  }
+
+  let mut bytes = Vec::with_capacity(values.len() * 2 / 3);
+  for chunk in values.chunks(3) {
+    match *chunk {
+      [c, d, e] => {
+        let n = c + d * 45 + e * 45 * 45;
+        let n = u16::try_from(n).map_err(|_| "invalid character triple".to_string())?;
+        bytes.extend_from_slice(&n.to_be_bytes());
+      }
+      [c, d] => {
+        let n = c + d * 45;
+        bytes.push(u8::try_from(n).map_err(|_| "invalid character pair".to_string())?);
+      }
+      _ => return Err("invalid length".to_string()),
+    }
+  }
+
+  Ok(bytes)
+}
+
+/// Decodes the integer represented by text for the `.base10` control operator
+pub fn decode_decimal(text: &str) -> Result<Value<'static>, String> {
+  // Neither leading zeros nor negative zero
+  let digits = text.strip_prefix('-').unwrap_or(text);
+  if digits.is_empty()
+    || !digits.bytes().all(|b| b.is_ascii_digit())
+    || (digits.starts_with('0') && (digits.len() > 1 || digits != text))
+  {
+    return Err(format!("\"{}\" is not a decimal integer", text));
+  }
+
+  let value = text
+    .parse::<i128>()
+    .map_err(|e| format!("\"{}\" is not a decimal integer: {}", text, e))?;
+
+  Ok(match u64::try_from(value) {
+    Ok(ui) => Value::UINT(ui),
+    Err(_) => Value::INT(value),
+  })
+}
+
+lazy_static! {
+  // Conversion specification of a .printf format string
+  static ref CONVERSION: Regex =
+    Regex::new(r"%([-+ #0]*)([0-9]*)(?:\.([0-9]*))?([diuxXobefgEFGsc%])").unwrap();
+}
+
+/// Extracts the values formatted into text by the format string of a
+/// `.printf` control operator. Supports the `d`, `i`, `u`, `x`, `X`, `o`, `b`,
+/// `e`, `f`, `g`, `s` and `c` conversions with flags, widths and precisions
+pub fn printf_values(format: &str, text: &str) -> Result<Vec<Value<'static>>, String> {
+  PrintfFormat::new(format)?.values(text)
+}
+
+/// Format string of a `.printf` control operator, compiled into a regex that
+/// matches the text it formats
+#[derive(Debug)]
+pub struct PrintfFormat {
+  format: String,
+  pattern: Regex,
+  // Conversion and field width of each formatted value
+  kinds: Vec<(char, usize)>,
+}
+
+impl PrintfFormat {
+  /// Compiles a given format string
+  pub fn new(format: &str) -> Result<Self, String> {
+    let mut pattern = String::from("^");
+    let mut kinds = Vec::new();
+    let mut end = 0;
+    for c in CONVERSION.captures_iter(format) {
+      let m = c
+        .get(0)
+        .ok_or_else(|| "invalid format string".to_string())?;
+      pattern.push_str(&regex::escape(&format[end..m.start()]));
+      end = m.end();
+
+      let kind = c[4].chars().next().unwrap_or('%');
+      let value = match kind {
+        '%' => {
+          pattern.push('%');
+          continue;
+        }
+        'd' | 'i' | 'u' => r"[-+ ]?[0-9]+",
+        'x' => r"[-+ ]?(?:0x)?[0-9a-f]+",
+        'X' => r"[-+ ]?(?:0X)?[0-9A-F]+",
+        'o' => r"[-+ ]?[0-7]+",
+        'b' => r"[-+ ]?(?:0b)?[01]+",
+        's' => r".*?",
+        'c' => r".",
+        _ => {
+          r"[-+ ]?(?:[0-9]+(?:\.[0-9]*)?|\.[0-9]+)(?:[eE][-+]?[0-9]+)?|[-+ ]?(?:[iI][nN][fF]|[nN][aA][nN])"
+        }
+      };
+
+      // Fields shorter than their width are padded with spaces
+      pattern.push_str(&format!("( *(?:{}) *)", value));
+      let width = c[2].parse::<usize>().unwrap_or(0);
+      kinds.push((kind, width));
+    }
+    pattern.push_str(&regex::escape(&format[end..]));
+    pattern.push('$');
+
+    Ok(PrintfFormat {
+      format: format.to_string(),
+      pattern: Regex::new(&pattern).map_err(|e| e.to_string())?,
+      kinds,
+    })
+  }
+
+  /// Extracts the values formatted into a given text
+  pub fn values(&self, text: &str) -> Result<Vec<Value<'static>>, String> {
+    let format = &self.format;
+    let captures = self
+      .pattern
+      .captures(text)
+      .ok_or_else(|| format!("\"{}\" does not match format \"{}\"", text, format))?;
+
+    let mut values = Vec::with_capacity(self.kinds.len());
+    for (idx, &(kind, width)) in self.kinds.iter().enumerate() {
+      let field = captures.get(idx + 1).map_or("", |m| m.as_str());
+      if field.chars().count() < width {
+        return Err(format!(
+          "\"{}\" is shorter than the field width {} of format \"{}\"",
+          field, width, format
+        ));
+      }
+
+      let trimmed = field.trim_matches(' ');
+      let value = match kind {
+        's' => Value::TEXT(Cow::Owned(field.to_string())),
+        'c' => Value::TEXT(Cow::Owned(trimmed.to_string())),
+        'd' | 'i' | 'u' | 'x' | 'X' | 'o' | 'b' => {
+          let (is_negative, digits) = match trimmed.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, trimmed.trim_start_matches('+')),
+          };
+          let (radix, digits) = match kind {
+            'x' | 'X' => (16, digits.trim_start_matches("0x").trim_start_matches("0X")),
+            'o' => (8, digits),
+            'b' => (2, digits.trim_start_matches("0b")),
+            _ => (10, digits),
+          };
+
+          let value = i128::from_str_radix(digits, radix).map_err(|e| e.to_string())?;
+          let value = if is_negative { -value } else { value };
+          match u64::try_from(value) {
+            Ok(ui) => Value::UINT(ui),
+            Err(_) => Value::INT(value),
+          }
+        }
+        _ => Value::FLOAT(
+          trimmed
+            .trim_start_matches('+')
+            .parse::<f64>()
+            .map_err(|e| e.to_string())?,
+        ),
+      };
+      values.push(value);
+    }
+
+    Ok(values)
+  }
+}
+
+/// Returns whether a text or byte string can be split into consecutive parts
+/// matching the entries of the array controlling a `.join` control operator.
+/// Entries are either literals, which must appear as is, or types, against
+/// which parts are checked by the given function. Parts of text strings are
+/// split at character boundaries only
+pub fn join_matches<T, F>(
+  entries: &[(Option<Vec<u8>>, T)],
+  value: &[u8],
+  is_text: bool,
+  matches: &mut F,
+) -> bool
+where
+  F: FnMut(&T, &[u8]) -> bool,
+{
+  fn split<T, F>(
+    entries: &[(Option<Vec<u8>>, T)],
+    value: &[u8],
+    is_text: bool,
+    matches: &mut F,
+    idx: usize,
+    pos: usize,
+    failed: &mut HashSet<(usize, usize)>,
+  ) -> bool
+  where
+    F: FnMut(&T, &[u8]) -> bool,
+  {
+    let (literal, t) = match entries.get(idx) {
+      Some(entry) => entry,
+      None => return pos == value.len(),
+    };
+    if failed.contains(&(idx, pos)) {
+      return false;
+    }
+
+    let is_match = match literal {
+      Some(literal) => {
+        value[pos..].starts_with(literal)
+          && split(
+            entries,
+            value,
+            is_text,
+            matches,
+            idx + 1,
+            pos + literal.len(),
+            failed,
+          )
+      }
+      None => {
+        // The last entry takes the remainder
+        let ends = if idx + 1 == entries.len() {
+          value.len()..=value.len()
+        } else {
+          pos..=value.len()
+        };
+
+        ends
+          .filter(|end| !is_text || *end == value.len() || (value[*end] as i8) >= -0x40)
+          .any(|end| {
+            matches(t, &value[pos..end])
+              && split(entries, value, is_text, matches, idx + 1, end, failed)
+          })
+      }
+    };
+
+    if !is_match {
+      failed.insert((idx, pos));
+    }
+
+    is_match
+  }
+
+  split(entries, value, is_text, matches, 0, 0, &mut HashSet::new())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn verify_decode_bytes() {
+    assert_eq!(decode_bytes(&Token::B64U, "AQID"), Ok(vec![1, 2, 3]));
+    assert_eq!(decode_bytes(&Token::B64U, "-_8"), Ok(vec![0xfb, 0xff]));
+    assert!(decode_bytes(&Token::B64U, "-_9").is_err());
+    assert_eq!(
+      decode_bytes(&Token::B64USLOPPY, "-_9"),
+      Ok(vec![0xfb, 0xff])
+    );
+    assert!(decode_bytes(&Token::B64U, "AQI=").is_err());
+    assert_eq!(decode_bytes(&Token::B64C, "+/8="), Ok(vec![0xfb, 0xff]));
+    assert!(decode_bytes(&Token::B64C, "+/8").is_err());
+    assert_eq!(decode_bytes(&Token::B32, "MZXW6"), Ok(b"foo".to_vec()));
+    assert_eq!(decode_bytes(&Token::H32, "CPNMU"), Ok(b"foo".to_vec()));
+    assert!(decode_bytes(&Token::B32, "MZXW6===").is_err());
+    assert_eq!(decode_bytes(&Token::B45, "BB8"), Ok(b"AB".to_vec()));
+    assert_eq!(
+      decode_bytes(&Token::B45, "%69 VD92EX0"),
+      Ok(b"Hello!!".to_vec())
+    );
+    assert!(decode_bytes(&Token::B45, "GGW").is_err());
+    assert_eq!(decode_bytes(&Token::HEX, "0aFf"), Ok(vec![0x0a, 0xff]));
+    assert!(decode_bytes(&Token::HEX, "0aF").is_err());
+    assert!(decode_bytes(&Token::HEXLC, "0aFf").is_err());
+    assert_eq!(decode_bytes(&Token::HEXUC, "0AFF"), Ok(vec![0x0a, 0xff]));
+  }
+
+  #[test]
+  fn verify_decode_decimal() {
+    assert_eq!(decode_decimal("0"), Ok(Value::UINT(0)));
+    assert_eq!(decode_decimal("-42"), Ok(Value::INT(-42)));
+    assert!(decode_decimal("042").is_err());
+    assert!(decode_decimal("-0").is_err());
+    assert!(decode_decimal("+1").is_err());
+    assert!(decode_decimal("").is_err());
+  }
+
+  #[test]
+  fn verify_printf_values() -> Result<(), String> {
+    assert_eq!(printf_values("0x%04x", "0x1267")?, vec![Value::UINT(4711)]);
+    assert!(printf_values("0x%04x", "0x126").is_err());
+    assert_eq!(
+      printf_values("%s: %d%%", "rate: -3%")?,
+      vec![Value::TEXT("rate".into()), Value::INT(-3)]
+    );
+    assert_eq!(printf_values("%.2f", "1.50")?, vec![Value::FLOAT(1.5)]);
+
+    Ok(())
+  }
+
+  #[test]
+  fn verify_join_matches() {
+    let entries = [
+      (Some(b"v".to_vec()), ()),
+      (None, ()),
+      (Some(b".".to_vec()), ()),
+      (None, ()),
+    ];
+    let mut is_digits =
+      |_: &(), part: &[u8]| !part.is_empty() && part.iter().all(u8::is_ascii_digit);
+
+    assert!(join_matches(&entries, b"v1.23", true, &mut is_digits));
+    assert!(!join_matches(&entries, b"v1.", true, &mut is_digits));
+    assert!(!join_matches(&entries, b"1.2", true, &mut is_digits));
+  }
+}
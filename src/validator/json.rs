@@ -2,14 +2,19 @@
 
 use crate::{
   ast::*,
+  error::{ErrorMsg, MsgType},
+  semantic,
   token::{self, Token},
   visitor::{self, *},
 };
 use chrono::{TimeZone, Utc};
 use serde_json::Value;
-use std::{convert::TryFrom, fmt};
+use std::{borrow::Cow, collections::BTreeSet, convert::TryFrom, fmt, rc::Rc};
 
-use super::*;
+use super::{
+  cbor::{is_float16_representable, is_float32_representable},
+  *,
+};
 
 /// JSON validation Result
 pub type Result = std::result::Result<(), Error>;
@@ -22,7 +27,7 @@ pub enum Error {
   /// JSON parsing error
   JSONParsing(serde_json::Error),
   /// CDDL parsing error
-  CDDLParsing(String),
+  CDDLParsing(crate::parser::Error),
 }
 
 impl fmt::Display for Error {
@@ -45,6 +50,7 @@ impl std::error::Error for Error {
   fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
     match self {
       Error::JSONParsing(error) => Some(error),
+      Error::CDDLParsing(error) => Some(error),
       _ => None,
     }
   }
@@ -107,7 +113,7 @@ impl ValidationError {
       reason,
       is_multi_type_choice: jv.is_multi_type_choice,
       is_group_to_choice_enum: jv.is_group_to_choice_enum,
-      type_group_name_entry: jv.type_group_name_entry.map(|e| e.to_string()),
+      type_group_name_entry: jv.type_group_name_entry.clone(),
       is_multi_group_choice: jv.is_multi_group_choice,
     }
   }
@@ -116,7 +122,7 @@ impl ValidationError {
 /// JSON validator type
 pub struct JSONValidator<'a> {
   cddl: &'a CDDL<'a>,
-  json: Value,
+  json: &'a Value,
   errors: Vec<ValidationError>,
   cddl_location: String,
   json_location: String,
@@ -125,18 +131,19 @@ pub struct JSONValidator<'a> {
   // Current group entry index detected in current state of AST evaluation
   group_entry_idx: Option<usize>,
   // JSON object value hoisted from previous state of AST evaluation
-  object_value: Option<Value>,
+  object_value: Option<&'a Value>,
   // Is member key detected in current state of AST evaluation
   is_member_key: bool,
   // Is a cut detected in current state of AST evaluation
   is_cut_present: bool,
   // Str value of cut detected in current state of AST evaluation
-  cut_value: Option<&'a str>,
+  cut_value: Option<Cow<'a, str>>,
   // Validate the generic rule given by str ident in current state of AST
   // evaluation
-  eval_generic_rule: Option<&'a str>,
-  // Aggregation of generic rules
-  generic_rules: Vec<GenericRule<'a>>,
+  eval_generic_rule: Option<String>,
+  // Aggregation of generic rules, shared with the validators of nested values
+  // until either adds to them
+  generic_rules: Rc<Vec<GenericRule<'a>>>,
   // Control operator token detected in current state of AST evaluation
   ctrl: Option<token::Token<'a>>,
   // Is a group to choice enumeration detected in current state of AST
@@ -148,26 +155,25 @@ pub struct JSONValidator<'a> {
   is_multi_group_choice: bool,
   // Type/group name entry detected in current state of AST evaluation. Used
   // only for providing more verbose error messages
-  type_group_name_entry: Option<&'a str>,
+  type_group_name_entry: Option<String>,
   // Whether or not to advance to the next group entry if member key validation
   // fails as detected during the current state of AST evaluation
   advance_to_next_entry: bool,
   is_ctrl_map_equality: bool,
   entry_counts: Option<Vec<EntryCount>>,
   validated_keys: Option<Vec<String>>,
-  values_to_validate: Option<Vec<Value>>,
-}
-
-#[derive(Clone, Debug)]
-struct GenericRule<'a> {
-  name: &'a str,
-  params: Vec<&'a str>,
-  args: Vec<Type1<'a>>,
+  values_to_validate: Option<Vec<&'a Value>>,
+  // Names of features detected by the .feature control operator
+  features: BTreeSet<String>,
+  // Registry of user-defined control operators
+  controls: Option<&'a dyn ControlRegistry>,
+  // Schema the CDDL was compiled into ahead of validation, if any
+  schema: Option<&'a CompiledSchema>,
 }
 
 impl<'a> JSONValidator<'a> {
   /// New JSONValidation from CDDL AST and JSON value
-  pub fn new(cddl: &'a CDDL<'a>, json: Value) -> Self {
+  pub fn new(cddl: &'a CDDL<'a>, json: &'a Value) -> Self {
     JSONValidator {
       cddl,
       json,
@@ -181,7 +187,7 @@ impl<'a> JSONValidator<'a> {
       is_cut_present: false,
       cut_value: None,
       eval_generic_rule: None,
-      generic_rules: Vec::new(),
+      generic_rules: Rc::default(),
       ctrl: None,
       is_group_to_choice_enum: false,
       is_multi_type_choice: false,
@@ -192,11 +198,58 @@ impl<'a> JSONValidator<'a> {
       entry_counts: None,
       validated_keys: None,
       values_to_validate: None,
+      features: BTreeSet::new(),
+      controls: None,
+      schema: None,
     }
   }
 
+  /// New JSONValidator from a compiled schema and JSON value
+  pub(crate) fn from_schema(schema: &'a CompiledSchema, json: &'a Value) -> Self {
+    let mut validator = JSONValidator::new(schema.cddl(), json);
+    validator.schema = Some(schema);
+    validator
+  }
+
+  /// Evaluates the user-defined control operators of the given registry. The
+  /// CDDL must have been parsed by a `Lexer` accepting the registered names
+  pub fn with_controls(mut self, controls: &'a dyn ControlRegistry) -> Self {
+    self.controls = Some(controls);
+    self
+  }
+
+  /// Names of the features detected by the `.feature` control operator during
+  /// validation
+  pub fn features(&self) -> &BTreeSet<String> {
+    &self.features
+  }
+
   /// Validate
   pub fn validate(&mut self) -> std::result::Result<(), Error> {
+    // Rules that can never be satisfied would otherwise be followed endlessly.
+    // Compiled schemas are checked once when compiled
+    if self.schema.is_none() {
+      for rule in semantic::non_productive_rules(self.cddl) {
+        self.add_error(format!(
+          "rule \"{}\": {}",
+          rule.name(),
+          ErrorMsg::from(MsgType::NonProductiveRule)
+        ));
+      }
+
+      if !self.errors.is_empty() {
+        return Err(Error::Validation(self.errors.clone()));
+      }
+    }
+
+    self.validate_unchecked()
+  }
+
+  /// Validate without first checking the CDDL for rules that can never be
+  /// satisfied. The parser refuses such rules, so this skips repeating the
+  /// check for CDDL parsed by `cddl_from_str()` and validated many times.
+  /// Validating against a non-productive rule never terminates
+  pub fn validate_unchecked(&mut self) -> std::result::Result<(), Error> {
     for r in self.cddl.rules.iter() {
       // First type rule is root
       if let Rule::Type { rule, .. } = r {
@@ -216,6 +269,40 @@ impl<'a> JSONValidator<'a> {
     Ok(())
   }
 
+  /// Returns whether a major type argument matches the type given for it by a
+  /// '#mt.<type>' expression
+  fn is_head_number_match(
+    &self,
+    t: &Type<'a>,
+    n: u64,
+  ) -> std::result::Result<bool, ValidationError> {
+    let n = Value::from(n);
+    let mut jv = self.nested(&n);
+    jv.visit_type(t)?;
+
+    Ok(jv.errors.is_empty())
+  }
+
+  // Validator for a value nested within or derived from the one being
+  // validated, sharing the CDDL, generic rules and registries
+  fn nested<'b>(&self, json: &'b Value) -> JSONValidator<'b>
+  where
+    'a: 'b,
+  {
+    let mut jv = JSONValidator::new(self.cddl, json);
+    jv.controls = self.controls;
+    jv.schema = self.schema;
+    jv.generic_rules = Rc::clone(&self.generic_rules);
+    jv.eval_generic_rule = self.eval_generic_rule.clone();
+    jv
+  }
+
+  // Collects the errors and detected features of a nested validator
+  fn merge_nested(&mut self, nested: &mut JSONValidator) {
+    self.errors.append(&mut nested.errors);
+    self.features.append(&mut nested.features);
+  }
+
   fn add_error(&mut self, reason: String) {
     self.errors.push(ValidationError {
       reason,
@@ -224,7 +311,7 @@ impl<'a> JSONValidator<'a> {
       is_multi_type_choice: self.is_multi_type_choice,
       is_multi_group_choice: self.is_multi_group_choice,
       is_group_to_choice_enum: self.is_group_to_choice_enum,
-      type_group_name_entry: self.type_group_name_entry.map(|e| e.to_string()),
+      type_group_name_entry: self.type_group_name_entry.clone(),
     });
   }
 }
@@ -232,16 +319,23 @@ impl<'a> JSONValidator<'a> {
 impl<'a> Visitor<'a, ValidationError> for JSONValidator<'a> {
   fn visit_type_rule(&mut self, tr: &TypeRule<'a>) -> visitor::Result<ValidationError> {
     if let Some(gp) = &tr.generic_params {
-      if let Some(gr) = self
-        .generic_rules
+      if let Some(gr) = Rc::make_mut(&mut self.generic_rules)
         .iter_mut()
         .find(|r| r.name == tr.name.ident)
       {
-        gr.params = gp.params.iter().map(|p| p.param.ident).collect();
+        gr.params = gp
+          .params
+          .iter()
+          .map(|p| p.param.ident.to_string())
+          .collect();
       } else {
-        self.generic_rules.push(GenericRule {
-          name: tr.name.ident,
-          params: gp.params.iter().map(|p| p.param.ident).collect(),
+        Rc::make_mut(&mut self.generic_rules).push(GenericRule {
+          name: tr.name.ident.to_string(),
+          params: gp
+            .params
+            .iter()
+            .map(|p| p.param.ident.to_string())
+            .collect(),
           args: vec![],
         });
       }
@@ -266,16 +360,23 @@ impl<'a> Visitor<'a, ValidationError> for JSONValidator<'a> {
 
   fn visit_group_rule(&mut self, gr: &GroupRule<'a>) -> visitor::Result<ValidationError> {
     if let Some(gp) = &gr.generic_params {
-      if let Some(gr) = self
-        .generic_rules
+      if let Some(gr) = Rc::make_mut(&mut self.generic_rules)
         .iter_mut()
         .find(|r| r.name == gr.name.ident)
       {
-        gr.params = gp.params.iter().map(|p| p.param.ident).collect();
+        gr.params = gp
+          .params
+          .iter()
+          .map(|p| p.param.ident.to_string())
+          .collect();
       } else {
-        self.generic_rules.push(GenericRule {
-          name: gr.name.ident,
-          params: gp.params.iter().map(|p| p.param.ident).collect(),
+        Rc::make_mut(&mut self.generic_rules).push(GenericRule {
+          name: gr.name.ident.to_string(),
+          params: gp
+            .params
+            .iter()
+            .map(|p| p.param.ident.to_string())
+            .collect(),
           args: vec![],
         });
       }
@@ -332,7 +433,7 @@ impl<'a> Visitor<'a, ValidationError> for JSONValidator<'a> {
     // Map equality/inequality validation
     if self.is_ctrl_map_equality {
       if let Some(t) = &self.ctrl {
-        if let Value::Object(o) = &self.json {
+        if let Value::Object(o) = self.json {
           let mut entry_counts = Vec::new();
           for gc in g.group_choices.iter() {
             let count = entry_counts_from_group_choice(self.cddl, gc);
@@ -436,7 +537,7 @@ impl<'a> Visitor<'a, ValidationError> for JSONValidator<'a> {
     upper: &Type2,
     is_inclusive: bool,
   ) -> visitor::Result<ValidationError> {
-    if let Value::Array(a) = &self.json {
+    if let Value::Array(a) = self.json {
       let allow_empty_array = matches!(self.occurrence.as_ref(), Some(Occur::Optional(_)));
 
       #[allow(unused_assignments)]
@@ -475,29 +576,25 @@ impl<'a> Visitor<'a, ValidationError> for JSONValidator<'a> {
 
       if iter_items {
         for (idx, v) in a.iter().enumerate() {
-          let mut jv = JSONValidator::new(self.cddl, v.clone());
-          jv.generic_rules = self.generic_rules.clone();
-          jv.eval_generic_rule = self.eval_generic_rule;
+          let mut jv = self.nested(v);
           jv.is_multi_type_choice = self.is_multi_type_choice;
           jv.json_location
             .push_str(&format!("{}/{}", self.json_location, idx));
 
           jv.visit_range(lower, upper, is_inclusive)?;
 
-          self.errors.append(&mut jv.errors);
+          self.merge_nested(&mut jv);
         }
       } else if let Some(idx) = self.group_entry_idx.take() {
         if let Some(v) = a.get(idx) {
-          let mut jv = JSONValidator::new(self.cddl, v.clone());
-          jv.generic_rules = self.generic_rules.clone();
-          jv.eval_generic_rule = self.eval_generic_rule;
+          let mut jv = self.nested(v);
           jv.is_multi_type_choice = self.is_multi_type_choice;
           jv.json_location
             .push_str(&format!("{}/{}", self.json_location, idx));
 
           jv.visit_range(lower, upper, is_inclusive)?;
 
-          self.errors.append(&mut jv.errors);
+          self.merge_nested(&mut jv);
         } else if !allow_empty_array {
           self.add_error(format!("expected array item at index {}", idx));
         }
@@ -526,16 +623,16 @@ impl<'a> Visitor<'a, ValidationError> for JSONValidator<'a> {
             )
           };
 
-          match &self.json {
+          match self.json {
             Value::Number(n) => {
-              if let Some(i) = n.as_i64() {
+              if let Some(i) = number_as_i128(n) {
                 if is_inclusive {
-                  if i < *l as i64 || i > *u as i64 {
+                  if i < *l || i > *u {
                     self.add_error(error_str);
                   } else {
                     return Ok(());
                   }
-                } else if i <= *l as i64 || i >= *u as i64 {
+                } else if i <= *l || i >= *u {
                   self.add_error(error_str);
                   return Ok(());
                 } else {
@@ -565,16 +662,16 @@ impl<'a> Visitor<'a, ValidationError> for JSONValidator<'a> {
             )
           };
 
-          match &self.json {
+          match self.json {
             Value::Number(n) => {
-              if let Some(i) = n.as_i64() {
+              if let Some(i) = number_as_i128(n) {
                 if is_inclusive {
-                  if i < *l as i64 || i > *u as i64 {
+                  if i < *l || i > i128::from(*u) {
                     self.add_error(error_str);
                   } else {
                     return Ok(());
                   }
-                } else if i <= *l as i64 || i >= *u as i64 {
+                } else if i <= *l || i >= i128::from(*u) {
                   self.add_error(error_str);
                   return Ok(());
                 } else {
@@ -613,16 +710,16 @@ impl<'a> Visitor<'a, ValidationError> for JSONValidator<'a> {
             )
           };
 
-          match &self.json {
+          match self.json {
             Value::Number(n) => {
               if let Some(i) = n.as_u64() {
                 if is_inclusive {
-                  if i < *l as u64 || i > *u as u64 {
+                  if i < *l || i > *u {
                     self.add_error(error_str);
                   } else {
                     return Ok(());
                   }
-                } else if i <= *l as u64 || i >= *u as u64 {
+                } else if i <= *l || i >= *u {
                   self.add_error(error_str);
                   return Ok(());
                 } else {
@@ -635,10 +732,10 @@ impl<'a> Visitor<'a, ValidationError> for JSONValidator<'a> {
             }
             Value::String(s) => match self.ctrl {
               Some(Token::SIZE) => {
-                let len = s.len();
+                let len = s.len() as u64;
                 let s = s.clone();
                 if is_inclusive {
-                  if s.len() < *l || s.len() > *u {
+                  if len < *l || len > *u {
                     self.add_error(format!(
                       "expected \"{}\" string length to be in the range {} <= value <= {}, got {}",
                       s, l, u, len
@@ -647,13 +744,14 @@ impl<'a> Visitor<'a, ValidationError> for JSONValidator<'a> {
                   } else {
                     return Ok(());
                   }
-                } else if s.len() <= *l || s.len() >= *u {
+                } else if len < *l || len >= *u {
                   self.add_error(format!(
-                    "expected \"{}\" string length to be in the range {} < value < {}, got {}",
+                    "expected \"{}\" string length to be in the range {} <= value < {}, got {}",
                     s, l, u, len
                   ));
-                  return Ok(());
                 }
+
+                return Ok(());
               }
               _ => {
                 self.add_error("string value cannot be validated against a range without the .size control operator".to_string());
@@ -688,7 +786,7 @@ impl<'a> Visitor<'a, ValidationError> for JSONValidator<'a> {
             )
           };
 
-          match &self.json {
+          match self.json {
             Value::Number(n) => {
               if let Some(f) = n.as_f64() {
                 if is_inclusive {
@@ -752,7 +850,7 @@ impl<'a> Visitor<'a, ValidationError> for JSONValidator<'a> {
             }
           }
           Type2::Array { group, .. } => {
-            if let Value::Array(_) = &self.json {
+            if let Value::Array(_) = self.json {
               let mut entry_counts = Vec::new();
               for gc in group.group_choices.iter() {
                 let count = entry_counts_from_group_choice(self.cddl, gc);
@@ -765,7 +863,7 @@ impl<'a> Visitor<'a, ValidationError> for JSONValidator<'a> {
             }
           }
           Type2::Map { .. } => {
-            if let Value::Object(_) = &self.json {
+            if let Value::Object(_) = self.json {
               self.ctrl = t;
               self.is_ctrl_map_equality = true;
               self.visit_type2(controller)?;
@@ -794,7 +892,7 @@ impl<'a> Visitor<'a, ValidationError> for JSONValidator<'a> {
             }
           }
           Type2::Array { .. } => {
-            if let Value::Array(_) = &self.json {
+            if let Value::Array(_) = self.json {
               self.ctrl = t;
               self.visit_type2(controller)?;
               self.ctrl = None;
@@ -802,7 +900,7 @@ impl<'a> Visitor<'a, ValidationError> for JSONValidator<'a> {
             }
           }
           Type2::Map { .. } => {
-            if let Value::Object(_) = &self.json {
+            if let Value::Object(_) = self.json {
               self.ctrl = t;
               self.is_ctrl_map_equality = true;
               self.visit_type2(controller)?;
@@ -853,6 +951,50 @@ impl<'a> Visitor<'a, ValidationError> for JSONValidator<'a> {
           Ok(())
         }
       },
+      Some(Token::BITS) => match target {
+        Type2::Typename { ident, .. } if is_ident_uint_data_type(self.cddl, ident) => {
+          let n = match self.json {
+            Value::Number(n) if n.is_u64() => n.as_u64().unwrap_or_default(),
+            _ => {
+              self.add_error(format!(
+                "expected type {} .bits {}, got {}",
+                ident, controller, self.json
+              ));
+              return Ok(());
+            }
+          };
+
+          // Each set bit must be a bit number admitted by the controller
+          let mut unexpected_bits = Vec::new();
+          for bit in (0..64u64).filter(|bit| n & (1 << bit) != 0) {
+            let bit_value = Value::from(bit);
+            let mut jv = self.nested(&bit_value);
+            jv.visit_type2(controller)?;
+
+            if !jv.errors.is_empty() {
+              unexpected_bits.push(bit.to_string());
+            }
+          }
+
+          if !unexpected_bits.is_empty() {
+            self.add_error(format!(
+              "expected only bits in {} to be set, got unexpected bits {} in {}",
+              controller,
+              unexpected_bits.join(", "),
+              self.json
+            ));
+          }
+
+          Ok(())
+        }
+        _ => {
+          self.add_error(format!(
+            "target for .bits must be a uint data type when validating JSON, got {}",
+            target
+          ));
+          Ok(())
+        }
+      },
       t @ Some(Token::AND) => {
         self.ctrl = t;
         self.visit_type2(target)?;
@@ -896,7 +1038,232 @@ impl<'a> Visitor<'a, ValidationError> for JSONValidator<'a> {
         self.ctrl = None;
         Ok(())
       }
-      t @ Some(Token::REGEXP) | t @ Some(Token::PCRE) => {
+      Some(Token::PLUS) | Some(Token::CAT) | Some(Token::DET) => {
+        match literal_from_control_operator(self.cddl, target, ctrl, controller) {
+          Some(value) => self.visit_value(&value),
+          None => {
+            self.add_error(format!(
+              "cannot compute the value of {} {} {}, expected literal operands",
+              target, ctrl, controller
+            ));
+            Ok(())
+          }
+        }
+      }
+      Some(Token::ABNF) => match target {
+        Type2::Typename { ident, .. } if is_ident_string_data_type(self.cddl, ident) => {
+          let grammar = match literal_from_type2(self.cddl, controller) {
+            Some(token::Value::TEXT(grammar)) => abnf::Grammar::parse(&grammar),
+            Some(token::Value::BYTE(grammar)) => match grammar.to_bytes() {
+              Some(grammar) => match std::str::from_utf8(&grammar) {
+                Ok(grammar) => abnf::Grammar::parse(grammar),
+                Err(e) => Err(format!("grammar is not valid UTF-8: {}", e)),
+              },
+              None => Err(format!("malformed byte string literal {}", controller)),
+            },
+            _ => Err(format!(
+              "expected text or byte string controller, got {}",
+              controller
+            )),
+          };
+          let grammar = match grammar {
+            Ok(grammar) => grammar,
+            Err(e) => {
+              self.add_error(format!("invalid ABNF grammar for {}: {}", ctrl, e));
+              return Ok(());
+            }
+          };
+
+          let is_match = match self.json {
+            Value::String(s) => grammar.matches_text(s),
+            _ => {
+              self.add_error(format!("expected type {} .abnf, got {}", ident, self.json));
+              return Ok(());
+            }
+          };
+
+          match is_match {
+            Ok(true) => (),
+            Ok(false) => self.add_error(format!(
+              "expected value matching the .abnf grammar, got {}",
+              self.json
+            )),
+            Err(e) => self.add_error(format!("error matching .abnf grammar: {}", e)),
+          }
+
+          Ok(())
+        }
+        _ => {
+          self.add_error(format!(
+            "target for .abnf must be a string data type, got {}",
+            target
+          ));
+          Ok(())
+        }
+      },
+      Some(Token::FEATURE) => {
+        let error_count = self.errors.len();
+        self.visit_type2(target)?;
+
+        // The feature is only used if the target is matched
+        if self.errors.len() == error_count {
+          self
+            .features
+            .insert(match literal_from_type2(self.cddl, controller) {
+              Some(token::Value::TEXT(feature)) => feature.into_owned(),
+              _ => controller.to_string(),
+            });
+        }
+
+        Ok(())
+      }
+      Some(t @ Token::B64U)
+      | Some(t @ Token::B64USLOPPY)
+      | Some(t @ Token::B64C)
+      | Some(t @ Token::B64CSLOPPY)
+      | Some(t @ Token::B32)
+      | Some(t @ Token::H32)
+      | Some(t @ Token::B45)
+      | Some(t @ Token::HEX)
+      | Some(t @ Token::HEXLC)
+      | Some(t @ Token::HEXUC)
+      | Some(t @ Token::BASE10)
+      | Some(t @ Token::PRINTF)
+      | Some(t @ Token::JSON) => match target {
+        Type2::Typename { ident, .. } if is_ident_string_data_type(self.cddl, ident) => {
+          let text = match self.json {
+            Value::String(text) => text,
+            _ => {
+              self.add_error(format!(
+                "expected type {} {} {}, got {}",
+                ident, ctrl, controller, self.json
+              ));
+              return Ok(());
+            }
+          };
+
+          let decoded = match t {
+            Token::BASE10 => encoding::decode_decimal(text).and_then(|value| {
+              token_value_into_json_value(value)
+                .ok_or_else(|| format!("\"{}\" can't be represented as a JSON number", text))
+            }),
+            // The format string is the first element of the decoded array
+            Token::PRINTF => match printf_format(self.cddl, controller) {
+              Some(format) => match self.schema.and_then(|schema| schema.printf_format(&format)) {
+                Some(compiled) => compiled.values(text),
+                None => encoding::printf_values(&format, text),
+              }
+              .and_then(|values| {
+                std::iter::once(Some(Value::String(format)))
+                  .chain(values.into_iter().map(token_value_into_json_value))
+                  .collect::<Option<Vec<_>>>()
+                  .map(Value::Array)
+                  .ok_or_else(|| format!("\"{}\" can't be represented as JSON values", text))
+              }),
+              None => Err(format!(
+                "expected array controller starting with a format string, got {}",
+                controller
+              )),
+            },
+            Token::JSON => serde_json::from_str::<Value>(text).map_err(|e| e.to_string()),
+            // JSON has no byte strings, so the decoded bytes are validated as
+            // CBOR
+            _ => match encoding::decode_bytes(&t, text) {
+              Ok(bytes) => {
+                for e in CBORValidator::validate_type2(
+                  self.cddl,
+                  &serde_cbor::Value::Bytes(bytes),
+                  controller,
+                  self.controls,
+                  &self.generic_rules,
+                  self.eval_generic_rule.clone(),
+                ) {
+                  self.errors.push(ValidationError {
+                    json_location: format!("{}{}", self.json_location, e.cbor_location),
+                    ..ValidationError::from_validator(self, e.reason)
+                  });
+                }
+
+                return Ok(());
+              }
+              Err(e) => Err(e),
+            },
+          };
+
+          match decoded {
+            Ok(decoded) => {
+              let mut jv = self.nested(&decoded);
+              jv.json_location.push_str(&self.json_location);
+              jv.visit_type2(controller)?;
+
+              self.merge_nested(&mut jv);
+            }
+            Err(e) => self.add_error(format!(
+              "expected type {} {} {}, decoding error: {}",
+              ident, ctrl, controller, e
+            )),
+          }
+
+          Ok(())
+        }
+        _ => {
+          self.add_error(format!(
+            "target for {} must be a string data type, got {}",
+            ctrl, target
+          ));
+          Ok(())
+        }
+      },
+      Some(Token::JOIN) => match target {
+        Type2::Typename { ident, .. } if is_ident_string_data_type(self.cddl, ident) => {
+          let text = match self.json {
+            Value::String(text) => text,
+            _ => {
+              self.add_error(format!(
+                "expected type {} .join {}, got {}",
+                ident, controller, self.json
+              ));
+              return Ok(());
+            }
+          };
+
+          let entries = match join_entries(self.cddl, controller) {
+            Some(entries) => entries,
+            None => {
+              self.add_error(format!(
+                "controller for .join must be an array without occurrence indicators, got {}",
+                controller
+              ));
+              return Ok(());
+            }
+          };
+
+          let is_match =
+            encoding::join_matches(&entries, text.as_bytes(), true, &mut |t2, part| {
+              let part = Value::String(String::from_utf8_lossy(part).into_owned());
+
+              let mut jv = self.nested(&part);
+              jv.visit_type2(t2).is_ok() && jv.errors.is_empty()
+            });
+
+          if !is_match {
+            self.add_error(format!(
+              "expected type {} .join {}, got {}",
+              ident, controller, self.json
+            ));
+          }
+
+          Ok(())
+        }
+        _ => {
+          self.add_error(format!(
+            "target for .join must be a string data type, got {}",
+            target
+          ));
+          Ok(())
+        }
+      },
+      t @ Some(Token::CREGEXP) | t @ Some(Token::PCRE) => {
         self.ctrl = t;
         match target {
           Type2::Typename { ident, .. } if is_ident_string_data_type(self.cddl, ident) => {
@@ -917,26 +1284,47 @@ impl<'a> Visitor<'a, ValidationError> for JSONValidator<'a> {
 
         Ok(())
       }
-      _ => {
-        self.add_error(format!("unsupported control operator {}", ctrl));
-        Ok(())
-      }
+      _ => match self.controls {
+        Some(controls) if controls.contains(ctrl) => {
+          let error_count = self.errors.len();
+          self.visit_type2(target)?;
+
+          // The control is only evaluated for values matching the target
+          if self.errors.len() == error_count {
+            match controls.validate_json(ctrl, target, controller, self.json) {
+              Some(Ok(())) => {}
+              Some(Err(reason)) => self.add_error(format!(
+                "expected type {} {} {}: {}",
+                target, ctrl, controller, reason
+              )),
+              None => self.add_error(format!(
+                "control operator {} is not supported for JSON",
+                ctrl
+              )),
+            }
+          }
+
+          Ok(())
+        }
+        _ => {
+          self.add_error(format!("unsupported control operator {}", ctrl));
+          Ok(())
+        }
+      },
     }
   }
 
   fn visit_type2(&mut self, t2: &Type2<'a>) -> visitor::Result<ValidationError> {
     match t2 {
-      Type2::TextValue { value, .. } => self.visit_value(&token::Value::TEXT(value)),
-      Type2::Map { group, .. } => match &self.json {
+      Type2::TextValue { value, .. } => self.visit_value(&token::Value::TEXT(value.clone())),
+      Type2::Map { group, .. } => match self.json {
         Value::Object(o) => {
-          let o = o.keys().cloned().collect::<Vec<_>>();
-
           self.visit_group(group)?;
 
           if self.values_to_validate.is_none() {
-            for k in o.into_iter() {
+            for k in o.keys() {
               if let Some(keys) = &self.validated_keys {
-                if !keys.contains(&k) {
+                if !keys.contains(k) {
                   self.add_error(format!("unexpected key {:?}", k));
                 }
               }
@@ -991,29 +1379,25 @@ impl<'a> Visitor<'a, ValidationError> for JSONValidator<'a> {
 
           if iter_items {
             for (idx, v) in a.iter().enumerate() {
-              let mut jv = JSONValidator::new(self.cddl, v.clone());
-              jv.generic_rules = self.generic_rules.clone();
-              jv.eval_generic_rule = self.eval_generic_rule;
+              let mut jv = self.nested(v);
               jv.is_multi_type_choice = self.is_multi_type_choice;
               jv.json_location
                 .push_str(&format!("{}/{}", self.json_location, idx));
 
               jv.visit_group(group)?;
 
-              self.errors.append(&mut jv.errors);
+              self.merge_nested(&mut jv);
             }
           } else if let Some(idx) = self.group_entry_idx.take() {
             if let Some(v) = a.get(idx) {
-              let mut jv = JSONValidator::new(self.cddl, v.clone());
-              jv.generic_rules = self.generic_rules.clone();
-              jv.eval_generic_rule = self.eval_generic_rule;
+              let mut jv = self.nested(v);
               jv.is_multi_type_choice = self.is_multi_type_choice;
               jv.json_location
                 .push_str(&format!("{}/{}", self.json_location, idx));
 
               jv.visit_group(group)?;
 
-              self.errors.append(&mut jv.errors);
+              self.merge_nested(&mut jv);
             } else if !allow_empty_array {
               self.add_error(format!("expected map object {} at index {}", group, idx));
             }
@@ -1028,7 +1412,7 @@ impl<'a> Visitor<'a, ValidationError> for JSONValidator<'a> {
           Ok(())
         }
       },
-      Type2::Array { group, .. } => match &self.json {
+      Type2::Array { group, .. } => match self.json {
         Value::Array(a) => {
           if group.group_choices.len() == 1
             && group.group_choices[0].group_entries.is_empty()
@@ -1061,8 +1445,7 @@ impl<'a> Visitor<'a, ValidationError> for JSONValidator<'a> {
       } => {
         if let Some(ga) = generic_args {
           if let Some(rule) = rule_from_ident(self.cddl, ident) {
-            if let Some(gr) = self
-              .generic_rules
+            if let Some(gr) = Rc::make_mut(&mut self.generic_rules)
               .iter_mut()
               .find(|gr| gr.name == ident.ident)
             {
@@ -1070,21 +1453,20 @@ impl<'a> Visitor<'a, ValidationError> for JSONValidator<'a> {
                 gr.args.push((*arg.arg).clone());
               }
             } else if let Some(params) = generic_params_from_rule(rule) {
-              self.generic_rules.push(GenericRule {
-                name: ident.ident,
+              Rc::make_mut(&mut self.generic_rules).push(GenericRule {
+                name: ident.ident.to_string(),
                 params,
                 args: ga.args.iter().cloned().map(|arg| *arg.arg).collect(),
               });
             }
 
-            let mut jv = JSONValidator::new(self.cddl, self.json.clone());
-            jv.generic_rules = self.generic_rules.clone();
-            jv.eval_generic_rule = Some(ident.ident);
+            let mut jv = self.nested(self.json);
+            jv.eval_generic_rule = Some(ident.ident.to_string());
             jv.is_group_to_choice_enum = true;
             jv.is_multi_type_choice = self.is_multi_type_choice;
             jv.visit_rule(rule)?;
 
-            self.errors.append(&mut jv.errors);
+            self.merge_nested(&mut jv);
 
             return Ok(());
           }
@@ -1117,8 +1499,7 @@ impl<'a> Visitor<'a, ValidationError> for JSONValidator<'a> {
       } => {
         if let Some(ga) = generic_args {
           if let Some(rule) = rule_from_ident(self.cddl, ident) {
-            if let Some(gr) = self
-              .generic_rules
+            if let Some(gr) = Rc::make_mut(&mut self.generic_rules)
               .iter_mut()
               .find(|gr| gr.name == ident.ident)
             {
@@ -1126,20 +1507,19 @@ impl<'a> Visitor<'a, ValidationError> for JSONValidator<'a> {
                 gr.args.push((*arg.arg).clone());
               }
             } else if let Some(params) = generic_params_from_rule(rule) {
-              self.generic_rules.push(GenericRule {
-                name: ident.ident,
+              Rc::make_mut(&mut self.generic_rules).push(GenericRule {
+                name: ident.ident.to_string(),
                 params,
                 args: ga.args.iter().cloned().map(|arg| *arg.arg).collect(),
               });
             }
 
-            let mut jv = JSONValidator::new(self.cddl, self.json.clone());
-            jv.generic_rules = self.generic_rules.clone();
-            jv.eval_generic_rule = Some(ident.ident);
+            let mut jv = self.nested(self.json);
+            jv.eval_generic_rule = Some(ident.ident.to_string());
             jv.is_multi_type_choice = self.is_multi_type_choice;
             jv.visit_rule(rule)?;
 
-            self.errors.append(&mut jv.errors);
+            self.merge_nested(&mut jv);
 
             return Ok(());
           }
@@ -1157,14 +1537,13 @@ impl<'a> Visitor<'a, ValidationError> for JSONValidator<'a> {
         ..
       } => {
         // Disregard the tag when validating JSON
-        if tag_from_token(&lookup_ident(ident.ident)).is_some() {
+        if unwrap_rule_from_ident(standard_prelude(), ident).is_some() {
           return self.visit_identifier(ident);
         }
 
         if let Some(ga) = generic_args {
           if let Some(rule) = unwrap_rule_from_ident(self.cddl, ident) {
-            if let Some(gr) = self
-              .generic_rules
+            if let Some(gr) = Rc::make_mut(&mut self.generic_rules)
               .iter_mut()
               .find(|gr| gr.name == ident.ident)
             {
@@ -1172,20 +1551,19 @@ impl<'a> Visitor<'a, ValidationError> for JSONValidator<'a> {
                 gr.args.push((*arg.arg).clone());
               }
             } else if let Some(params) = generic_params_from_rule(rule) {
-              self.generic_rules.push(GenericRule {
-                name: ident.ident,
+              Rc::make_mut(&mut self.generic_rules).push(GenericRule {
+                name: ident.ident.to_string(),
                 params,
                 args: ga.args.iter().cloned().map(|arg| *arg.arg).collect(),
               });
             }
 
-            let mut jv = JSONValidator::new(self.cddl, self.json.clone());
-            jv.generic_rules = self.generic_rules.clone();
-            jv.eval_generic_rule = Some(ident.ident);
+            let mut jv = self.nested(self.json);
+            jv.eval_generic_rule = Some(ident.ident.to_string());
             jv.is_multi_type_choice = self.is_multi_type_choice;
             jv.visit_rule(rule)?;
 
-            self.errors.append(&mut jv.errors);
+            self.merge_nested(&mut jv);
 
             return Ok(());
           }
@@ -1202,6 +1580,35 @@ impl<'a> Visitor<'a, ValidationError> for JSONValidator<'a> {
 
         Ok(())
       }
+      Type2::DataMajorType { mt, constraint, .. } => {
+        if let Some((actual_mt, arguments)) = major_type_and_arguments(self.json) {
+          if actual_mt == *mt {
+            match constraint {
+              Some(HeadNumber::Uint(c)) if arguments.contains(c) => return Ok(()),
+              Some(HeadNumber::Type(t)) => {
+                for argument in arguments {
+                  if self.is_head_number_match(t, argument)? {
+                    return Ok(());
+                  }
+                }
+              }
+              None => return Ok(()),
+              _ => (),
+            }
+          }
+        }
+
+        if let Some(constraint) = constraint {
+          self.add_error(format!(
+            "expected major type #{}.{}, got {}",
+            mt, constraint, self.json
+          ));
+        } else {
+          self.add_error(format!("expected major type #{}, got {}", mt, self.json));
+        }
+
+        Ok(())
+      }
       Type2::Any(_) => Ok(()),
       _ => {
         self.add_error(format!(
@@ -1214,13 +1621,9 @@ impl<'a> Visitor<'a, ValidationError> for JSONValidator<'a> {
   }
 
   fn visit_identifier(&mut self, ident: &Identifier<'a>) -> visitor::Result<ValidationError> {
-    if let Some(name) = self.eval_generic_rule {
-      if let Some(gr) = self
-        .generic_rules
-        .iter()
-        .cloned()
-        .find(|gr| gr.name == name)
-      {
+    if let Some(name) = &self.eval_generic_rule {
+      let generic_rules = Rc::clone(&self.generic_rules);
+      if let Some(gr) = generic_rules.iter().find(|gr| gr.name == *name) {
         for (idx, gp) in gr.params.iter().enumerate() {
           if *gp == ident.ident {
             if let Some(arg) = gr.args.get(idx) {
@@ -1235,11 +1638,32 @@ impl<'a> Visitor<'a, ValidationError> for JSONValidator<'a> {
       return self.visit_rule(r);
     }
 
+    // Names from the standard prelude are validated against their prelude
+    // definition like any other rule, unless their type is implemented
+    // natively. Tags are disregarded when validating JSON, e.g. leaving the
+    // [exponent, mantissa] array enclosed by decfrac
+    if !is_ident_native_prelude_type(ident) {
+      if let Some(tr) = type_rule_from_ident(standard_prelude(), ident) {
+        if let [TypeChoice {
+          type1: Type1 {
+            type2: Type2::TaggedData { t, .. },
+            ..
+          },
+          ..
+        }] = tr.value.type_choices.as_slice()
+        {
+          return self.visit_type(t);
+        }
+
+        return self.visit_type(&tr.value);
+      }
+    }
+
     if is_ident_any_type(self.cddl, ident) {
       return Ok(());
     }
 
-    match &self.json {
+    match self.json {
       Value::Null if is_ident_null_data_type(self.cddl, ident) => Ok(()),
       Value::Bool(b) => {
         if is_ident_bool_data_type(self.cddl, ident) {
@@ -1254,8 +1678,10 @@ impl<'a> Visitor<'a, ValidationError> for JSONValidator<'a> {
         Ok(())
       }
       Value::Number(n) => {
-        if is_ident_uint_data_type(self.cddl, ident) && n.is_u64() {
-          return Ok(());
+        if is_ident_uint_data_type(self.cddl, ident) {
+          if n.is_u64() {
+            return Ok(());
+          }
         } else if is_ident_nint_data_type(self.cddl, ident) {
           if let Some(n) = n.as_i64() {
             if n.is_negative() {
@@ -1359,29 +1785,25 @@ impl<'a> Visitor<'a, ValidationError> for JSONValidator<'a> {
 
         if iter_items {
           for (idx, v) in a.iter().enumerate() {
-            let mut jv = JSONValidator::new(self.cddl, v.clone());
-            jv.generic_rules = self.generic_rules.clone();
-            jv.eval_generic_rule = self.eval_generic_rule;
+            let mut jv = self.nested(v);
             jv.is_multi_type_choice = self.is_multi_type_choice;
             jv.json_location
               .push_str(&format!("{}/{}", self.json_location, idx));
 
             jv.visit_identifier(ident)?;
 
-            self.errors.append(&mut jv.errors);
+            self.merge_nested(&mut jv);
           }
         } else if let Some(idx) = self.group_entry_idx.take() {
           if let Some(v) = a.get(idx) {
-            let mut jv = JSONValidator::new(self.cddl, v.clone());
-            jv.generic_rules = self.generic_rules.clone();
-            jv.eval_generic_rule = self.eval_generic_rule;
+            let mut jv = self.nested(v);
             jv.is_multi_type_choice = self.is_multi_type_choice;
             jv.json_location
               .push_str(&format!("{}/{}", self.json_location, idx));
 
             jv.visit_identifier(ident)?;
 
-            self.errors.append(&mut jv.errors);
+            self.merge_nested(&mut jv);
           } else if !allow_empty_array {
             self.add_error(format!("expected type {} at index {}", ident, idx));
           }
@@ -1411,7 +1833,7 @@ impl<'a> Visitor<'a, ValidationError> for JSONValidator<'a> {
                 .filter_map(|(k, v)| {
                   if let Some(keys) = &self.validated_keys {
                     if !keys.contains(&k) {
-                      Some(v.clone())
+                      Some(v)
                     } else {
                       None
                     }
@@ -1432,7 +1854,7 @@ impl<'a> Visitor<'a, ValidationError> for JSONValidator<'a> {
           }
         }
 
-        self.visit_value(&token::Value::TEXT(ident.ident))
+        self.visit_value(&token::Value::TEXT(ident.ident.clone()))
       }
       _ => {
         if let Some(cut_value) = self.cut_value.take() {
@@ -1471,20 +1893,18 @@ impl<'a> Visitor<'a, ValidationError> for JSONValidator<'a> {
       }
     }
 
-    if let Some(values) = &self.values_to_validate {
-      for v in values.iter() {
-        let mut jv = JSONValidator::new(self.cddl, v.clone());
-        jv.generic_rules = self.generic_rules.clone();
-        jv.eval_generic_rule = self.eval_generic_rule;
+    if let Some(values) = self.values_to_validate.clone() {
+      for v in values.into_iter() {
+        let mut jv = self.nested(v);
         jv.is_multi_type_choice = self.is_multi_type_choice;
         jv.is_multi_group_choice = self.is_multi_group_choice;
         jv.json_location.push_str(&self.json_location);
-        jv.type_group_name_entry = self.type_group_name_entry;
+        jv.type_group_name_entry = self.type_group_name_entry.clone();
         jv.visit_type(&entry.entry_type)?;
 
         self.json_location = current_location.clone();
 
-        self.errors.append(&mut jv.errors);
+        self.merge_nested(&mut jv);
         if entry.occur.is_some() {
           self.occurrence = None;
         }
@@ -1494,18 +1914,16 @@ impl<'a> Visitor<'a, ValidationError> for JSONValidator<'a> {
     }
 
     if let Some(v) = self.object_value.take() {
-      let mut jv = JSONValidator::new(self.cddl, v);
-      jv.generic_rules = self.generic_rules.clone();
-      jv.eval_generic_rule = self.eval_generic_rule;
+      let mut jv = self.nested(v);
       jv.is_multi_type_choice = self.is_multi_type_choice;
       jv.is_multi_group_choice = self.is_multi_group_choice;
       jv.json_location.push_str(&self.json_location);
-      jv.type_group_name_entry = self.type_group_name_entry;
+      jv.type_group_name_entry = self.type_group_name_entry.clone();
       jv.visit_type(&entry.entry_type)?;
 
       self.json_location = current_location;
 
-      self.errors.append(&mut jv.errors);
+      self.merge_nested(&mut jv);
       if entry.occur.is_some() {
         self.occurrence = None;
       }
@@ -1522,7 +1940,7 @@ impl<'a> Visitor<'a, ValidationError> for JSONValidator<'a> {
     &mut self,
     entry: &TypeGroupnameEntry<'a>,
   ) -> visitor::Result<ValidationError> {
-    self.type_group_name_entry = Some(entry.name.ident);
+    self.type_group_name_entry = Some(entry.name.ident.to_string());
     walk_type_groupname_entry(self, entry)?;
     self.type_group_name_entry = None;
 
@@ -1538,17 +1956,17 @@ impl<'a> Visitor<'a, ValidationError> for JSONValidator<'a> {
   }
 
   fn visit_value(&mut self, value: &token::Value<'a>) -> visitor::Result<ValidationError> {
-    let error: Option<String> = match &self.json {
+    let error: Option<String> = match self.json {
       Value::Number(n) => match value {
-        token::Value::INT(v) => match n.as_i64() {
+        token::Value::INT(v) => match number_as_i128(n) {
           Some(i) => match &self.ctrl {
-            Some(Token::NE) if i != *v as i64 => None,
-            Some(Token::LT) if i < *v as i64 => None,
-            Some(Token::LE) if i <= *v as i64 => None,
-            Some(Token::GT) if i > *v as i64 => None,
-            Some(Token::GE) if i >= *v as i64 => None,
+            Some(Token::NE) if i != *v => None,
+            Some(Token::LT) if i < *v => None,
+            Some(Token::LE) if i <= *v => None,
+            Some(Token::GT) if i > *v => None,
+            Some(Token::GE) if i >= *v => None,
             None => {
-              if i == *v as i64 {
+              if i == *v {
                 None
               } else {
                 Some(format!("expected value {}, got {}", v, n))
@@ -1561,18 +1979,18 @@ impl<'a> Visitor<'a, ValidationError> for JSONValidator<'a> {
               n
             )),
           },
-          None => Some(format!("{} cannot be represented as an i64", n)),
+          None => Some(format!("{} cannot be represented as an integer", n)),
         },
         token::Value::UINT(v) => match n.as_u64() {
           Some(i) => match &self.ctrl {
-            Some(Token::NE) if i != *v as u64 => None,
-            Some(Token::LT) if i < *v as u64 => None,
-            Some(Token::LE) if i <= *v as u64 => None,
-            Some(Token::GT) if i > *v as u64 => None,
-            Some(Token::GE) if i >= *v as u64 => None,
-            Some(Token::SIZE) if i < 256u64.pow(*v as u32) => None,
+            Some(Token::NE) if i != *v => None,
+            Some(Token::LT) if i < *v => None,
+            Some(Token::LE) if i <= *v => None,
+            Some(Token::GT) if i > *v => None,
+            Some(Token::GE) if i >= *v => None,
+            Some(Token::SIZE) if *v >= 8 || i < 256u64.pow(*v as u32) => None,
             None => {
-              if i == *v as u64 {
+              if i == *v {
                 None
               } else {
                 Some(format!("expected value {}, got {}", v, n))
@@ -1589,13 +2007,13 @@ impl<'a> Visitor<'a, ValidationError> for JSONValidator<'a> {
         },
         token::Value::FLOAT(v) => match n.as_f64() {
           Some(f) => match &self.ctrl {
-            Some(Token::NE) if (f - *v).abs() > std::f64::EPSILON => None,
+            Some(Token::NE) if !float_eq(f, *v) => None,
             Some(Token::LT) if f < *v as f64 => None,
             Some(Token::LE) if f <= *v as f64 => None,
             Some(Token::GT) if f > *v as f64 => None,
             Some(Token::GE) if f >= *v as f64 => None,
             None => {
-              if (f - *v).abs() < std::f64::EPSILON {
+              if float_eq(f, *v) {
                 None
               } else {
                 Some(format!("expected value {}, got {}", v, n))
@@ -1621,16 +2039,16 @@ impl<'a> Visitor<'a, ValidationError> for JSONValidator<'a> {
               Some(format!("expected {} .ne to \"{}\"", value, s))
             }
           }
-          Some(Token::REGEXP) | Some(Token::PCRE) => {
-            let re = regex::Regex::new(
-              serde_json::from_str::<Value>(&format!("\"{}\"", t))
-                .map_err(|e| ValidationError::from_validator(self, e.to_string()))?
-                .as_str()
-                .ok_or_else(|| {
-                  ValidationError::from_validator(self, "malformed regex".to_string())
-                })?,
-            )
-            .map_err(|e| ValidationError::from_validator(self, e.to_string()))?;
+          Some(Token::CREGEXP) | Some(Token::PCRE) => {
+            let compiled;
+            let re = match self.schema.and_then(|schema| schema.regex(t)) {
+              Some(re) => re,
+              None => {
+                compiled =
+                  regex_from_text(t).map_err(|e| ValidationError::from_validator(self, e))?;
+                &compiled
+              }
+            };
 
             if re.is_match(s) {
               None
@@ -1650,7 +2068,7 @@ impl<'a> Visitor<'a, ValidationError> for JSONValidator<'a> {
         },
         token::Value::UINT(u) => match &self.ctrl {
           Some(Token::SIZE) => {
-            if s.len() == *u {
+            if s.len() as u64 == *u {
               None
             } else {
               Some(format!("expected \"{}\" .size {}, got {}", s, u, s.len()))
@@ -1707,29 +2125,25 @@ impl<'a> Visitor<'a, ValidationError> for JSONValidator<'a> {
 
         if iter_items {
           for (idx, v) in a.iter().enumerate() {
-            let mut jv = JSONValidator::new(self.cddl, v.clone());
-            jv.generic_rules = self.generic_rules.clone();
-            jv.eval_generic_rule = self.eval_generic_rule;
+            let mut jv = self.nested(v);
             jv.is_multi_type_choice = self.is_multi_type_choice;
             jv.json_location
               .push_str(&format!("{}/{}", self.json_location, idx));
 
             jv.visit_value(value)?;
 
-            self.errors.append(&mut jv.errors);
+            self.merge_nested(&mut jv);
           }
         } else if let Some(idx) = self.group_entry_idx.take() {
           if let Some(v) = a.get(idx) {
-            let mut jv = JSONValidator::new(self.cddl, v.clone());
-            jv.generic_rules = self.generic_rules.clone();
-            jv.eval_generic_rule = self.eval_generic_rule;
+            let mut jv = self.nested(v);
             jv.is_multi_type_choice = self.is_multi_type_choice;
             jv.json_location
               .push_str(&format!("{}/{}", self.json_location, idx));
 
             jv.visit_value(value)?;
 
-            self.errors.append(&mut jv.errors);
+            self.merge_nested(&mut jv);
           } else if !allow_empty_array {
             self.add_error(format!("expected value {} at index {}", value, idx));
           }
@@ -1743,7 +2157,7 @@ impl<'a> Visitor<'a, ValidationError> for JSONValidator<'a> {
         // Bareword member keys are converted to text string values
         if let token::Value::TEXT(t) = value {
           if self.is_cut_present {
-            self.cut_value = Some(t);
+            self.cut_value = Some(t.clone());
           }
 
           if *t == "any" {
@@ -1752,12 +2166,12 @@ impl<'a> Visitor<'a, ValidationError> for JSONValidator<'a> {
 
           // Retrieve the value from key unless optional/zero or more, in which
           // case advance to next group entry
-          if let Some(v) = o.get(*t) {
+          if let Some(v) = o.get(t.as_ref()) {
             self
               .validated_keys
               .get_or_insert(vec![t.to_string()])
               .push(t.to_string());
-            self.object_value = Some(v.clone());
+            self.object_value = Some(v);
             self.json_location.push_str(&format!("/{}", t));
 
             None
@@ -1795,6 +2209,59 @@ impl<'a> Visitor<'a, ValidationError> for JSONValidator<'a> {
   }
 }
 
+/// Converts a CDDL numeric or text value type to serde_json::Value
+pub fn token_value_into_json_value(value: token::Value) -> Option<Value> {
+  match value {
+    token::Value::UINT(ui) => Some(Value::from(ui)),
+    token::Value::INT(i) => i64::try_from(i).ok().map(Value::from),
+    token::Value::FLOAT(f) => serde_json::Number::from_f64(f).map(Value::Number),
+    token::Value::TEXT(t) => Some(Value::String(t.into_owned())),
+    token::Value::BYTE(_) => None,
+  }
+}
+
+// Integer value of a JSON number spanning both the i64 and u64 ranges
+fn number_as_i128(n: &serde_json::Number) -> Option<i128> {
+  n.as_i64()
+    .map(i128::from)
+    .or_else(|| n.as_u64().map(i128::from))
+}
+
+/// Returns the CBOR major type a JSON value maps to, along with the arguments
+/// it may be encoded with. The argument of a float is the additional
+/// information giving its width, and of a simple value its number
+fn major_type_and_arguments(value: &Value) -> Option<(u8, Vec<u64>)> {
+  match value {
+    Value::Number(n) => {
+      if let Some(n) = n.as_u64() {
+        return Some((0, vec![n]));
+      }
+
+      if let Some(n) = n.as_i64() {
+        return Some((1, vec![u64::try_from(-1 - n).ok()?]));
+      }
+
+      let f = n.as_f64()?;
+      let mut widths = Vec::new();
+      if is_float16_representable(f) {
+        widths.push(25);
+      }
+      if is_float32_representable(f) {
+        widths.push(26);
+      }
+      widths.push(27);
+
+      Some((7, widths))
+    }
+    Value::String(s) => Some((3, vec![s.len() as u64])),
+    Value::Array(a) => Some((4, vec![a.len() as u64])),
+    Value::Object(o) => Some((5, vec![o.len() as u64])),
+    Value::Bool(false) => Some((7, vec![20])),
+    Value::Bool(true) => Some((7, vec![21])),
+    Value::Null => Some((7, vec![22])),
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -1812,9 +2279,423 @@ mod tests {
     let cddl = cddl_from_str(&mut lexer, cddl, true).map_err(json::Error::CDDLParsing)?;
     let json = serde_json::from_str::<serde_json::Value>(json).map_err(json::Error::JSONParsing)?;
 
-    let mut jv = JSONValidator::new(&cddl, json);
+    let mut jv = JSONValidator::new(&cddl, &json);
+    jv.validate()?;
+
+    Ok(())
+  }
+
+  #[test]
+  fn validate_exponent_float() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let cddl = r#"measurement = { value: 1.5e-20, max: 0x1.8p1, range: 1e-3..1e3 }"#;
+
+    for (json, is_valid) in [
+      (r#"{ "value": 1.5e-20, "max": 3.0, "range": 0.5 }"#, true),
+      (r#"{ "value": 1.6e-20, "max": 3.0, "range": 0.5 }"#, false),
+      (r#"{ "value": 1.5e-20, "max": 3.0, "range": 1e4 }"#, false),
+    ]
+    .iter()
+    {
+      let mut lexer = lexer_from_str(cddl);
+      let cddl = cddl_from_str(&mut lexer, cddl, true).map_err(json::Error::CDDLParsing)?;
+      let json =
+        serde_json::from_str::<serde_json::Value>(json).map_err(json::Error::JSONParsing)?;
+
+      let mut jv = JSONValidator::new(&cddl, &json);
+      assert_eq!(jv.validate().is_ok(), *is_valid);
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn validate_escaped_regexp() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let cddl = r#"version = tstr .regexp "[0-9]+\\.[0-9]+""#;
+
+    for (json, is_valid) in [(r#""1.2""#, true), (r#""132""#, false)].iter() {
+      let mut lexer = lexer_from_str(cddl);
+      let cddl = cddl_from_str(&mut lexer, cddl, true).map_err(json::Error::CDDLParsing)?;
+      let json =
+        serde_json::from_str::<serde_json::Value>(json).map_err(json::Error::JSONParsing)?;
+
+      let mut jv = JSONValidator::new(&cddl, &json);
+      assert_eq!(jv.validate().is_ok(), *is_valid);
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn validate_exclusive_size_range() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let cddl = r#"code = tstr .size (2...4)"#;
+
+    for (json, is_valid) in [
+      (r#""ab""#, true),
+      (r#""abc""#, true),
+      (r#""abcd""#, false),
+      (r#""a""#, false),
+    ]
+    .iter()
+    {
+      let mut lexer = lexer_from_str(cddl);
+      let cddl = cddl_from_str(&mut lexer, cddl, true).map_err(json::Error::CDDLParsing)?;
+      let json =
+        serde_json::from_str::<serde_json::Value>(json).map_err(json::Error::JSONParsing)?;
+
+      let mut jv = JSONValidator::new(&cddl, &json);
+      assert_eq!(jv.validate().is_ok(), *is_valid, "{}", json);
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn validate_decfrac_bigfloat() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let cddl = r#"price = { amount: decfrac / bigfloat }"#;
+
+    for (json, is_valid) in [
+      (r#"{ "amount": [-2, 27315] }"#, true),
+      (r#"{ "amount": [-2, 27315, 1] }"#, false),
+      (r#"{ "amount": [1.5, 3] }"#, false),
+      (r#"{ "amount": 27315 }"#, false),
+    ]
+    .iter()
+    {
+      let mut lexer = lexer_from_str(cddl);
+      let cddl = cddl_from_str(&mut lexer, cddl, true).map_err(json::Error::CDDLParsing)?;
+      let json =
+        serde_json::from_str::<serde_json::Value>(json).map_err(json::Error::JSONParsing)?;
+
+      let mut jv = JSONValidator::new(&cddl, &json);
+      assert_eq!(jv.validate().is_ok(), *is_valid);
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn validate_bits() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let cddl = r#"permissions = { mode: uint .bits &(read: 0, write: 1, exec: 2) }"#;
+
+    for (json, is_valid) in [
+      (r#"{ "mode": 0 }"#, true),
+      (r#"{ "mode": 5 }"#, true),
+      (r#"{ "mode": 8 }"#, false),
+      (r#"{ "mode": -1 }"#, false),
+    ]
+    .iter()
+    {
+      let mut lexer = lexer_from_str(cddl);
+      let cddl = cddl_from_str(&mut lexer, cddl, true).map_err(json::Error::CDDLParsing)?;
+      let json =
+        serde_json::from_str::<serde_json::Value>(json).map_err(json::Error::JSONParsing)?;
+
+      let mut jv = JSONValidator::new(&cddl, &json);
+      assert_eq!(jv.validate().is_ok(), *is_valid);
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn validate_negative_uint() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let cddl = r#"thing = uint"#;
+
+    for (json, is_valid) in [("1", true), ("0", true), ("-1", false)].iter() {
+      let mut lexer = lexer_from_str(cddl);
+      let cddl = cddl_from_str(&mut lexer, cddl, true).map_err(json::Error::CDDLParsing)?;
+      let json =
+        serde_json::from_str::<serde_json::Value>(json).map_err(json::Error::JSONParsing)?;
+
+      let mut jv = JSONValidator::new(&cddl, &json);
+      assert_eq!(jv.validate().is_ok(), *is_valid, "{}", jv.json);
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn validate_rfc9165_controls() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let cddl = r#"device = {
+      port: base .plus 2,
+      ? path: "/api" .cat "/v1",
+      ? serial: tstr .abnf serial-abnf,
+      ? label: tstr .feature "label",
+    }
+    base = 8080
+    serial-abnf = "serial = 2ALPHA 1*DIGIT""#;
+
+    for (json, is_valid, features) in [
+      (r#"{ "port": 8082 }"#, true, vec![]),
+      (r#"{ "port": 8080 }"#, false, vec![]),
+      (r#"{ "port": 8082, "path": "/api/v1" }"#, true, vec![]),
+      (r#"{ "port": 8082, "path": "/api" }"#, false, vec![]),
+      (r#"{ "port": 8082, "serial": "AB123" }"#, true, vec![]),
+      (r#"{ "port": 8082, "serial": "A123" }"#, false, vec![]),
+      (r#"{ "port": 8082, "label": "x" }"#, true, vec!["label"]),
+    ]
+    .iter()
+    {
+      let mut lexer = lexer_from_str(cddl);
+      let cddl = cddl_from_str(&mut lexer, cddl, true).map_err(json::Error::CDDLParsing)?;
+      let json =
+        serde_json::from_str::<serde_json::Value>(json).map_err(json::Error::JSONParsing)?;
+
+      let mut jv = JSONValidator::new(&cddl, &json);
+      assert_eq!(jv.validate().is_ok(), *is_valid);
+      assert_eq!(jv.features().iter().collect::<Vec<_>>(), *features);
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn validate_rfc9741_controls() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let cddl = r#"message = {
+      id: tstr .hex (bstr .size 4),
+      ? digest: tstr .b64u bstr,
+      ? count: tstr .base10 uint,
+      ? version: tstr .join (["v", tstr .decimal uint]),
+      ? body: tstr .json numbers,
+    }
+    numbers = [* uint]"#;
+
+    for (json, is_valid) in [
+      (r#"{ "id": "0102ABcd" }"#, true),
+      (r#"{ "id": "0102ab" }"#, false),
+      (r#"{ "id": "0102abcz" }"#, false),
+      (r#"{ "id": "0102abcd", "digest": "AQID" }"#, true),
+      (r#"{ "id": "0102abcd", "digest": "AQID=" }"#, false),
+      (r#"{ "id": "0102abcd", "count": "42" }"#, true),
+      (r#"{ "id": "0102abcd", "count": "-42" }"#, false),
+      (r#"{ "id": "0102abcd", "version": "v12" }"#, true),
+      (r#"{ "id": "0102abcd", "version": "12" }"#, false),
+      (r#"{ "id": "0102abcd", "body": "[1, 2, 3]" }"#, true),
+      (r#"{ "id": "0102abcd", "body": "[1, 2, 3" }"#, false),
+    ]
+    .iter()
+    {
+      let mut lexer = lexer_from_str(cddl);
+      let cddl = cddl_from_str(&mut lexer, cddl, true).map_err(json::Error::CDDLParsing)?;
+      let json =
+        serde_json::from_str::<serde_json::Value>(json).map_err(json::Error::JSONParsing)?;
+
+      let mut jv = JSONValidator::new(&cddl, &json);
+      assert_eq!(jv.validate().is_ok(), *is_valid, "{}", jv.json);
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn validate_generic_encoded_bytes() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let cddl = r#"message = signed<bstr .size 2>
+    signed<t> = { id: tstr .hex t }"#;
+
+    let mut lexer = lexer_from_str(cddl);
+    let cddl = cddl_from_str(&mut lexer, cddl, true).map_err(json::Error::CDDLParsing)?;
+
+    let json = serde_json::from_str::<serde_json::Value>(r#"{ "id": "0102" }"#)
+      .map_err(json::Error::JSONParsing)?;
+    let mut jv = JSONValidator::new(&cddl, &json);
     jv.validate()?;
 
+    let json = serde_json::from_str::<serde_json::Value>(r#"{ "id": "010203" }"#)
+      .map_err(json::Error::JSONParsing)?;
+    let mut jv = JSONValidator::new(&cddl, &json);
+    match jv.validate() {
+      Err(Error::Validation(errors)) => {
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].json_location, "/id");
+      }
+      _ => panic!("expected validation to fail"),
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn validate_custom_controls() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let mut controls = control::CustomControls::new();
+    controls
+      .register_json(".iso4217", |_, _, value| match value.as_str() {
+        Some("EUR") | Some("USD") => Ok(()),
+        _ => Err("unknown currency code".to_string()),
+      })
+      .register_json(".multiple-of", |_, controller, value| {
+        match (controller, value.as_u64()) {
+          (Type2::UintValue { value: m, .. }, Some(v)) if v % *m == 0 => Ok(()),
+          _ => Err(format!("expected a multiple of {}", controller)),
+        }
+      });
+
+    let cddl = r#"price = {
+      amount: uint .multiple-of 5,
+      currency: tstr .iso4217 0,
+    }"#;
+
+    for (json, is_valid) in [
+      (r#"{ "amount": 10, "currency": "EUR" }"#, true),
+      (r#"{ "amount": 12, "currency": "EUR" }"#, false),
+      (r#"{ "amount": 10, "currency": "XYZ" }"#, false),
+      (r#"{ "amount": 10, "currency": 978 }"#, false),
+    ]
+    .iter()
+    {
+      let mut lexer = Lexer::new(cddl).with_custom_controls(&controls.names());
+      let cddl = cddl_from_str(&mut lexer, cddl, true).map_err(json::Error::CDDLParsing)?;
+      let json =
+        serde_json::from_str::<serde_json::Value>(json).map_err(json::Error::JSONParsing)?;
+
+      let mut jv = JSONValidator::new(&cddl, &json).with_controls(&controls);
+      assert_eq!(jv.validate().is_ok(), *is_valid, "{}", jv.json);
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn validate_major_types() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let cddl = r#"thing = {
+      ? small: #0.<uint .lt 24>,
+      ? offset: #1.2,
+      ? label: #3.<1..3>,
+      ? level: #0.<levels>,
+      ? flag: #7.<20 / 21>,
+    }
+    levels = 1 / 5 / 10"#;
+
+    for (json, is_valid) in [
+      (r#"{ "small": 23 }"#, true),
+      (r#"{ "small": 24 }"#, false),
+      (r#"{ "small": -1 }"#, false),
+      (r#"{ "offset": -3 }"#, true),
+      (r#"{ "offset": -2 }"#, false),
+      (r#"{ "label": "ab" }"#, true),
+      (r#"{ "label": "abcd" }"#, false),
+      (r#"{ "level": 5 }"#, true),
+      (r#"{ "level": 6 }"#, false),
+      (r#"{ "flag": false }"#, true),
+      (r#"{ "flag": null }"#, false),
+    ]
+    .iter()
+    {
+      let mut lexer = lexer_from_str(cddl);
+      let cddl = cddl_from_str(&mut lexer, cddl, true).map_err(json::Error::CDDLParsing)?;
+      let json =
+        serde_json::from_str::<serde_json::Value>(json).map_err(json::Error::JSONParsing)?;
+
+      let mut jv = JSONValidator::new(&cddl, &json);
+      assert_eq!(jv.validate().is_ok(), *is_valid, "{}", jv.json);
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn validate_extended_prelude() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let prelude = r#"tdate = tstr .regexp "^[0-9]{4}-[0-9]{2}-[0-9]{2}$"
+    currency-code = "EUR" / "USD""#;
+    let cddl = r#"price = { amount: uint, currency: currency-code, ? due: tdate }"#;
+
+    for (json, is_valid) in [
+      (r#"{ "amount": 10, "currency": "EUR" }"#, true),
+      (r#"{ "amount": 10, "currency": "XYZ" }"#, false),
+      (
+        r#"{ "amount": 10, "currency": "USD", "due": "2024-01-02" }"#,
+        true,
+      ),
+      (
+        r#"{ "amount": 10, "currency": "USD", "due": "2024-01-02T00:00:00Z" }"#,
+        false,
+      ),
+    ]
+    .iter()
+    {
+      assert_eq!(
+        validate_json_from_str_with_prelude(cddl, prelude, json).is_ok(),
+        *is_valid,
+        "{}",
+        json
+      );
+    }
+
+    // The names defined by the prelude are undefined without it
+    assert!(validate_json_from_str(cddl, r#"{ "amount": 10, "currency": "EUR" }"#).is_err());
+
+    Ok(())
+  }
+
+  #[test]
+  fn validate_prelude_types() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let cddl = r#"message = {
+      ? pattern: regexp,
+      ? price: decfrac,
+      ? hex: eb16,
+    }"#;
+
+    for (json, is_valid) in [
+      (r#"{ "pattern": "[a-z]+" }"#, true),
+      (r#"{ "pattern": 1 }"#, false),
+      (r#"{ "price": [-2, 2705] }"#, true),
+      (r#"{ "price": [-2, "2705"] }"#, false),
+      (r#"{ "hex": { "any": true } }"#, true),
+    ]
+    .iter()
+    {
+      assert_eq!(
+        validate_json_from_str(cddl, json).is_ok(),
+        *is_valid,
+        "{}",
+        json
+      );
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn validate_non_productive_rules() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let cddl = r#"a = b
+    b = [a] / int"#;
+
+    let mut lexer = lexer_from_str(cddl);
+    let mut cddl = cddl_from_str(&mut lexer, cddl, true).map_err(json::Error::CDDLParsing)?;
+
+    // The parser refuses non-productive rules, so remove the base case after
+    // the fact, leaving "b = [a]"
+    if let Rule::Type { rule, .. } = &mut cddl.rules[1] {
+      rule.value.type_choices.pop();
+    }
+
+    let json =
+      serde_json::from_str::<serde_json::Value>("[[1]]").map_err(json::Error::JSONParsing)?;
+
+    let mut jv = JSONValidator::new(&cddl, &json);
+    match jv.validate() {
+      Err(Error::Validation(errors)) => assert_eq!(errors.len(), 2),
+      _ => panic!("expected validation to fail"),
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn validate_unchecked_reuses_parsed_cddl() -> std::result::Result<(), Box<dyn std::error::Error>>
+  {
+    let cddl = r#"point = [x: int, y: int]"#;
+
+    let mut lexer = lexer_from_str(cddl);
+    let cddl = cddl_from_str(&mut lexer, cddl, true).map_err(json::Error::CDDLParsing)?;
+
+    for (input, valid) in &[("[1, 2]", true), ("[1, \"a\"]", false)] {
+      let json =
+        serde_json::from_str::<serde_json::Value>(input).map_err(json::Error::JSONParsing)?;
+
+      let mut jv = JSONValidator::new(&cddl, &json);
+      assert_eq!(jv.validate_unchecked().is_ok(), *valid);
+    }
+
     Ok(())
   }
 }
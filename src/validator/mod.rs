@@ -1,30 +1,58 @@
+/// ABNF grammar matching for the .abnf and .abnfb control operators
+pub mod abnf;
+
 /// CBOR validation implementation
 pub mod cbor;
 
+/// User-defined control operators
+pub mod control;
+
+/// Decoding of text strings for the control operators of RFC 9741
+pub mod encoding;
+
 /// JSON validation implementation
 pub mod json;
 
+/// Schemas compiled once for the validation of many documents
+pub mod schema;
+
 use cbor::CBORValidator;
+use control::ControlRegistry;
 use json::JSONValidator;
+use schema::CompiledSchema;
 use serde::de::Deserialize;
+use std::{borrow::Cow, convert::TryFrom};
 
 use crate::{
   ast::{
-    GroupChoice, GroupEntry, GroupRule, Identifier, Occur, Rule, Type, Type2, TypeChoice, TypeRule,
-    CDDL,
+    GroupChoice, GroupEntry, GroupRule, Identifier, Occur, Operator, RangeCtlOp, Rule, Type, Type1,
+    Type2, TypeChoice, TypeRule, CDDL,
   },
-  cddl_from_str, lexer_from_str,
+  cddl_from_str,
+  lexer::Lexer,
+  lexer_from_str,
+  parser::cddl_from_str_with_prelude,
+  prelude::{prelude_from_str, standard_prelude},
   token::*,
 };
 
+// Arguments of a generic rule being evaluated, shared by the JSON and CBOR
+// validators so either can hand them to the other
+#[derive(Clone, Debug)]
+pub(crate) struct GenericRule<'a> {
+  name: String,
+  params: Vec<String>,
+  args: Vec<Type1<'a>>,
+}
+
 /// Validate JSON string from a given CDDL document string
 pub fn validate_json_from_str(cddl: &str, json: &str) -> json::Result {
   let mut lexer = lexer_from_str(cddl);
   let cddl = cddl_from_str(&mut lexer, cddl, true).map_err(json::Error::CDDLParsing)?;
   let json = serde_json::from_str::<serde_json::Value>(json).map_err(json::Error::JSONParsing)?;
 
-  let mut jv = JSONValidator::new(&cddl, json);
-  jv.validate()
+  let mut jv = JSONValidator::new(&cddl, &json);
+  jv.validate_unchecked()
 }
 
 /// Validate CBOR slice from a given CDDL document string
@@ -34,10 +62,82 @@ pub fn validate_cbor_from_slice(cddl: &str, cbor_slice: &[u8]) -> cbor::Result {
   let cbor =
     serde_cbor::from_slice::<serde_cbor::Value>(cbor_slice).map_err(cbor::Error::CBORParsing)?;
 
-  let mut cv = CBORValidator::new(&cddl, cbor);
+  let mut cv = CBORValidator::new(&cddl, &cbor);
+  cv.validate_unchecked()
+}
+
+/// Validate JSON string from a given CDDL document string, accepting and
+/// evaluating the user-defined control operators of the given registry
+pub fn validate_json_from_str_with_controls(
+  cddl: &str,
+  json: &str,
+  controls: &dyn ControlRegistry,
+) -> json::Result {
+  let mut lexer = Lexer::new(cddl).with_custom_controls(&controls.names());
+  let cddl = cddl_from_str(&mut lexer, cddl, true).map_err(json::Error::CDDLParsing)?;
+  let json = serde_json::from_str::<serde_json::Value>(json).map_err(json::Error::JSONParsing)?;
+
+  let mut jv = JSONValidator::new(&cddl, &json).with_controls(controls);
+  jv.validate_unchecked()
+}
+
+/// Validate CBOR slice from a given CDDL document string, accepting and
+/// evaluating the user-defined control operators of the given registry
+pub fn validate_cbor_from_slice_with_controls(
+  cddl: &str,
+  cbor_slice: &[u8],
+  controls: &dyn ControlRegistry,
+) -> cbor::Result {
+  let mut lexer = Lexer::new(cddl).with_custom_controls(&controls.names());
+  let cddl = cddl_from_str(&mut lexer, cddl, true).map_err(cbor::Error::CDDLParsing)?;
+  let cbor =
+    serde_cbor::from_slice::<serde_cbor::Value>(cbor_slice).map_err(cbor::Error::CBORParsing)?;
+
+  let mut cv = CBORValidator::new(&cddl, &cbor).with_controls(controls);
+  cv.validate_unchecked()
+}
+
+/// Validate JSON string from a given CDDL document string, with the rules of
+/// the given prelude extending the standard prelude. See
+/// `prelude::prelude_from_str()`
+pub fn validate_json_from_str_with_prelude(cddl: &str, prelude: &str, json: &str) -> json::Result {
+  let mut prelude_lexer = lexer_from_str(prelude);
+  let prelude = prelude_from_str(&mut prelude_lexer, prelude).map_err(json::Error::CDDLParsing)?;
+  let mut lexer = lexer_from_str(cddl);
+  let cddl = cddl_from_str_with_prelude(&mut lexer, cddl, &prelude, true)
+    .map_err(json::Error::CDDLParsing)?;
+  let json = serde_json::from_str::<serde_json::Value>(json).map_err(json::Error::JSONParsing)?;
+
+  let mut jv = JSONValidator::new(&cddl, &json);
+  jv.validate()
+}
+
+/// Validate CBOR slice from a given CDDL document string, with the rules of
+/// the given prelude extending the standard prelude. See
+/// `prelude::prelude_from_str()`
+pub fn validate_cbor_from_slice_with_prelude(
+  cddl: &str,
+  prelude: &str,
+  cbor_slice: &[u8],
+) -> cbor::Result {
+  let mut prelude_lexer = lexer_from_str(prelude);
+  let prelude = prelude_from_str(&mut prelude_lexer, prelude).map_err(cbor::Error::CDDLParsing)?;
+  let mut lexer = lexer_from_str(cddl);
+  let cddl = cddl_from_str_with_prelude(&mut lexer, cddl, &prelude, true)
+    .map_err(cbor::Error::CDDLParsing)?;
+  let cbor =
+    serde_cbor::from_slice::<serde_cbor::Value>(cbor_slice).map_err(cbor::Error::CBORParsing)?;
+
+  let mut cv = CBORValidator::new(&cddl, &cbor);
   cv.validate()
 }
 
+/// Compiles the regex of a `.regexp` or `.pcre` controller from its text
+/// value, whose escapes have already been decoded by the lexer
+pub(crate) fn regex_from_text(text: &str) -> std::result::Result<regex::Regex, String> {
+  regex::Regex::new(text).map_err(|e| e.to_string())
+}
+
 /// Find non-choice alternate rule from a given identifier
 pub fn rule_from_ident<'a>(cddl: &'a CDDL, ident: &Identifier) -> Option<&'a Rule<'a>> {
   cddl.rules.iter().find_map(|r| match r {
@@ -105,16 +205,20 @@ pub fn type_rule_from_ident<'a>(cddl: &'a CDDL, ident: &Identifier) -> Option<&'
 }
 
 /// Retrieve the list of generic parameters for a given rule
-pub fn generic_params_from_rule<'a>(rule: &Rule<'a>) -> Option<Vec<&'a str>> {
+pub fn generic_params_from_rule(rule: &Rule) -> Option<Vec<String>> {
   match rule {
-    Rule::Type { rule, .. } => rule
-      .generic_params
-      .as_ref()
-      .map(|gp| gp.params.iter().map(|gp| gp.param.ident).collect()),
-    Rule::Group { rule, .. } => rule
-      .generic_params
-      .as_ref()
-      .map(|gp| gp.params.iter().map(|gp| gp.param.ident).collect()),
+    Rule::Type { rule, .. } => rule.generic_params.as_ref().map(|gp| {
+      gp.params
+        .iter()
+        .map(|gp| gp.param.ident.to_string())
+        .collect()
+    }),
+    Rule::Group { rule, .. } => rule.generic_params.as_ref().map(|gp| {
+      gp.params
+        .iter()
+        .map(|gp| gp.param.ident.to_string())
+        .collect()
+    }),
   }
 }
 
@@ -184,7 +288,7 @@ pub fn type_choices_from_group_choice<'a>(
 
 /// Is the given identifier associated with a null data type
 pub fn is_ident_null_data_type(cddl: &CDDL, ident: &Identifier) -> bool {
-  if let Token::NULL | Token::NIL = lookup_ident(ident.ident) {
+  if let Token::NULL | Token::NIL = lookup_ident(&ident.ident) {
     return true;
   }
 
@@ -202,7 +306,7 @@ pub fn is_ident_null_data_type(cddl: &CDDL, ident: &Identifier) -> bool {
 
 /// Is the given identifier associated with a boolean data type
 pub fn is_ident_bool_data_type(cddl: &CDDL, ident: &Identifier) -> bool {
-  if let Token::BOOL = lookup_ident(ident.ident) {
+  if let Token::BOOL = lookup_ident(&ident.ident) {
     return true;
   }
 
@@ -220,13 +324,13 @@ pub fn is_ident_bool_data_type(cddl: &CDDL, ident: &Identifier) -> bool {
 
 /// Does the given boolean identifier match the boolean value
 pub fn ident_matches_bool_value(cddl: &CDDL, ident: &Identifier, value: bool) -> bool {
-  if let Token::TRUE = lookup_ident(ident.ident) {
+  if let Token::TRUE = lookup_ident(&ident.ident) {
     if value {
       return true;
     }
   }
 
-  if let Token::FALSE = lookup_ident(ident.ident) {
+  if let Token::FALSE = lookup_ident(&ident.ident) {
     if !value {
       return true;
     }
@@ -246,7 +350,7 @@ pub fn ident_matches_bool_value(cddl: &CDDL, ident: &Identifier, value: bool) ->
 
 /// Is the given identifier associated with a URI data type
 pub fn is_ident_uri_data_type(cddl: &CDDL, ident: &Identifier) -> bool {
-  if let Token::URI = lookup_ident(ident.ident) {
+  if let Token::URI = lookup_ident(&ident.ident) {
     return true;
   }
 
@@ -264,7 +368,7 @@ pub fn is_ident_uri_data_type(cddl: &CDDL, ident: &Identifier) -> bool {
 
 /// Is the given identifier associated with a b64url data type
 pub fn is_ident_b64url_data_type(cddl: &CDDL, ident: &Identifier) -> bool {
-  if let Token::B64URL = lookup_ident(ident.ident) {
+  if let Token::B64URL = lookup_ident(&ident.ident) {
     return true;
   }
 
@@ -282,7 +386,7 @@ pub fn is_ident_b64url_data_type(cddl: &CDDL, ident: &Identifier) -> bool {
 
 /// Is the given identifier associated with a tdate data type
 pub fn is_ident_tdate_data_type(cddl: &CDDL, ident: &Identifier) -> bool {
-  if let Token::TDATE = lookup_ident(ident.ident) {
+  if let Token::TDATE = lookup_ident(&ident.ident) {
     return true;
   }
 
@@ -300,7 +404,7 @@ pub fn is_ident_tdate_data_type(cddl: &CDDL, ident: &Identifier) -> bool {
 
 /// Is the given identifier associated with a time data type
 pub fn is_ident_time_data_type(cddl: &CDDL, ident: &Identifier) -> bool {
-  if let Token::TIME = lookup_ident(ident.ident) {
+  if let Token::TIME = lookup_ident(&ident.ident) {
     return true;
   }
 
@@ -329,7 +433,7 @@ pub fn is_ident_numeric_data_type(cddl: &CDDL, ident: &Identifier) -> bool {
   | Token::FLOAT64
   | Token::FLOAT1632
   | Token::FLOAT3264
-  | Token::UNSIGNED = lookup_ident(ident.ident)
+  | Token::UNSIGNED = lookup_ident(&ident.ident)
   {
     return true;
   }
@@ -348,7 +452,7 @@ pub fn is_ident_numeric_data_type(cddl: &CDDL, ident: &Identifier) -> bool {
 
 /// Is the given identifier associated with a uint data type
 pub fn is_ident_uint_data_type(cddl: &CDDL, ident: &Identifier) -> bool {
-  if let Token::UINT = lookup_ident(ident.ident) {
+  if let Token::UINT | Token::UNSIGNED = lookup_ident(&ident.ident) {
     return true;
   }
 
@@ -366,7 +470,7 @@ pub fn is_ident_uint_data_type(cddl: &CDDL, ident: &Identifier) -> bool {
 
 /// Is the given identifier associated with a nint data type
 pub fn is_ident_nint_data_type(cddl: &CDDL, ident: &Identifier) -> bool {
-  if let Token::NINT = lookup_ident(ident.ident) {
+  if let Token::NINT = lookup_ident(&ident.ident) {
     return true;
   }
 
@@ -385,7 +489,7 @@ pub fn is_ident_nint_data_type(cddl: &CDDL, ident: &Identifier) -> bool {
 /// Is the given identifier associated with an integer data type
 pub fn is_ident_integer_data_type(cddl: &CDDL, ident: &Identifier) -> bool {
   if let Token::INT | Token::INTEGER | Token::NINT | Token::UINT | Token::NUMBER | Token::UNSIGNED =
-    lookup_ident(ident.ident)
+    lookup_ident(&ident.ident)
   {
     return true;
   }
@@ -402,6 +506,46 @@ pub fn is_ident_integer_data_type(cddl: &CDDL, ident: &Identifier) -> bool {
   })
 }
 
+/// Is the given identifier associated with a data type that admits unsigned
+/// bignums (tag 2), i.e. `biguint`, `bigint`, `unsigned` or `integer`
+pub fn is_ident_biguint_data_type(cddl: &CDDL, ident: &Identifier) -> bool {
+  if let Token::BIGUINT | Token::BIGINT | Token::UNSIGNED | Token::INTEGER =
+    lookup_ident(&ident.ident)
+  {
+    return true;
+  }
+
+  cddl.rules.iter().any(|r| match r {
+    Rule::Type { rule, .. } if rule.name == *ident => rule.value.type_choices.iter().any(|tc| {
+      if let Type2::Typename { ident, .. } = &tc.type1.type2 {
+        is_ident_biguint_data_type(cddl, ident)
+      } else {
+        false
+      }
+    }),
+    _ => false,
+  })
+}
+
+/// Is the given identifier associated with a data type that admits negative
+/// bignums (tag 3), i.e. `bignint`, `bigint` or `integer`
+pub fn is_ident_bignint_data_type(cddl: &CDDL, ident: &Identifier) -> bool {
+  if let Token::BIGNINT | Token::BIGINT | Token::INTEGER = lookup_ident(&ident.ident) {
+    return true;
+  }
+
+  cddl.rules.iter().any(|r| match r {
+    Rule::Type { rule, .. } if rule.name == *ident => rule.value.type_choices.iter().any(|tc| {
+      if let Type2::Typename { ident, .. } = &tc.type1.type2 {
+        is_ident_bignint_data_type(cddl, ident)
+      } else {
+        false
+      }
+    }),
+    _ => false,
+  })
+}
+
 /// Is the given identifier associated with a float data type
 pub fn is_ident_float_data_type(cddl: &CDDL, ident: &Identifier) -> bool {
   if let Token::FLOAT
@@ -409,7 +553,7 @@ pub fn is_ident_float_data_type(cddl: &CDDL, ident: &Identifier) -> bool {
   | Token::FLOAT1632
   | Token::FLOAT32
   | Token::FLOAT3264
-  | Token::FLOAT64 = lookup_ident(ident.ident)
+  | Token::FLOAT64 = lookup_ident(&ident.ident)
   {
     return true;
   }
@@ -428,7 +572,7 @@ pub fn is_ident_float_data_type(cddl: &CDDL, ident: &Identifier) -> bool {
 
 /// Is the given identifier associated with a string data type
 pub fn is_ident_string_data_type(cddl: &CDDL, ident: &Identifier) -> bool {
-  if let Token::TEXT | Token::TSTR = lookup_ident(ident.ident) {
+  if let Token::TEXT | Token::TSTR = lookup_ident(&ident.ident) {
     return true;
   }
 
@@ -446,7 +590,7 @@ pub fn is_ident_string_data_type(cddl: &CDDL, ident: &Identifier) -> bool {
 
 /// Is the given identifier associated with the any type
 pub fn is_ident_any_type(cddl: &CDDL, ident: &Identifier) -> bool {
-  if let Token::ANY = lookup_ident(ident.ident) {
+  if let Token::ANY = lookup_ident(&ident.ident) {
     return true;
   }
 
@@ -462,9 +606,51 @@ pub fn is_ident_any_type(cddl: &CDDL, ident: &Identifier) -> bool {
   })
 }
 
+/// Is the given identifier a name from the standard prelude whose type the
+/// validators implement natively. These are the names defined by CBOR major
+/// types, which JSON values lack, the big integers, which JSON carries as plain
+/// numbers, and `tdate`, `time`, `uri` and `b64url`, whose text is also checked
+/// beyond their definition, e.g. `tdate` as an RFC 3339 date-time. The other
+/// names of the standard prelude are validated against their definition in
+/// `prelude::standard_prelude()`
+pub fn is_ident_native_prelude_type(ident: &Identifier) -> bool {
+  matches!(
+    lookup_ident(&ident.ident),
+    Token::ANY
+      | Token::UINT
+      | Token::NINT
+      | Token::INT
+      | Token::BSTR
+      | Token::BYTES
+      | Token::TSTR
+      | Token::TEXT
+      | Token::TDATE
+      | Token::TIME
+      | Token::NUMBER
+      | Token::BIGUINT
+      | Token::BIGNINT
+      | Token::BIGINT
+      | Token::INTEGER
+      | Token::UNSIGNED
+      | Token::URI
+      | Token::B64URL
+      | Token::FLOAT16
+      | Token::FLOAT32
+      | Token::FLOAT64
+      | Token::FLOAT1632
+      | Token::FLOAT3264
+      | Token::FLOAT
+      | Token::FALSE
+      | Token::TRUE
+      | Token::BOOL
+      | Token::NIL
+      | Token::NULL
+  )
+}
+
 /// Is the given identifier associated with a byte string data type
 pub fn is_ident_byte_string_data_type(cddl: &CDDL, ident: &Identifier) -> bool {
-  if let Token::BSTR | Token::BYTES = lookup_ident(ident.ident) {
+  if let Token::BSTR | Token::BYTES = lookup_ident(&ident.ident) {
     return true;
   }
 
@@ -613,6 +799,280 @@ pub fn validate_entry_count(valid_entry_counts: &[EntryCount], num_entries: usiz
   })
 }
 
+/// Compares two floats for equality relative to their magnitude, so that
+/// literals written with large or small exponents (e.g. `1.5e-20`) are not
+/// considered equal to every other value of similar scale
+pub fn float_eq(a: f64, b: f64) -> bool {
+  a == b || (a - b).abs() <= f64::EPSILON * a.abs().max(b.abs())
+}
+
+/// Limit on nested rule references when computing a literal, reached by
+/// recursive rules
+const MAX_LITERAL_DEPTH: usize = 64;
+
+/// Computes the value of a type that consists of a single literal, resolving
+/// rule names and the results of the `.plus`, `.cat` and `.det` control
+/// operators
+pub fn literal_from_type2(cddl: &CDDL, t2: &Type2) -> Option<Value<'static>> {
+  literal_from_type2_at_depth(cddl, t2, 0)
+}
+
+/// Computes the result of a `.plus`, `.cat` or `.det` control operator applied
+/// to literal target and controller types
+pub fn literal_from_control_operator(
+  cddl: &CDDL,
+  target: &Type2,
+  ctrl: &str,
+  controller: &Type2,
+) -> Option<Value<'static>> {
+  literal_from_control_operator_at_depth(cddl, target, ctrl, controller, 0)
+}
+
+fn literal_from_type2_at_depth(cddl: &CDDL, t2: &Type2, depth: usize) -> Option<Value<'static>> {
+  if depth > MAX_LITERAL_DEPTH {
+    return None;
+  }
+
+  match t2 {
+    Type2::IntValue { value, .. } => Some(Value::INT(*value)),
+    Type2::UintValue { value, .. } => Some(Value::UINT(*value)),
+    Type2::FloatValue { value, .. } => Some(Value::FLOAT(*value)),
+    Type2::TextValue { value, .. } => Some(Value::TEXT(Cow::Owned(value.to_string()))),
+    Type2::UTF8ByteString { value, .. } => byte_literal(ByteValue::UTF8(value.clone())),
+    Type2::B16ByteString { value, .. } => byte_literal(ByteValue::B16(value.clone())),
+    Type2::B64ByteString { value, .. } => byte_literal(ByteValue::B64(value.clone())),
+    Type2::ParenthesizedType { pt, .. } => literal_from_type(cddl, pt, depth + 1),
+    Type2::Typename {
+      ident,
+      generic_args: None,
+      ..
+    } => match type_choice_alternates_from_ident(cddl, ident).as_slice() {
+      [t] if type_rule_from_ident(cddl, ident)?.generic_params.is_none() => {
+        literal_from_type(cddl, t, depth + 1)
+      }
+      _ => None,
+    },
+    _ => None,
+  }
+}
+
+fn literal_from_type(cddl: &CDDL, t: &Type, depth: usize) -> Option<Value<'static>> {
+  match t.type_choices.as_slice() {
+    [TypeChoice {
+      type1: Type1 {
+        type2, operator, ..
+      },
+      ..
+    }] => match operator {
+      None => literal_from_type2_at_depth(cddl, type2, depth),
+      Some(Operator {
+        operator: RangeCtlOp::CtlOp { ctrl, .. },
+        type2: controller,
+        ..
+      }) => literal_from_control_operator_at_depth(cddl, type2, ctrl, controller, depth),
+      _ => None,
+    },
+    _ => None,
+  }
+}
+
+fn literal_from_control_operator_at_depth(
+  cddl: &CDDL,
+  target: &Type2,
+  ctrl: &str,
+  controller: &Type2,
+  depth: usize,
+) -> Option<Value<'static>> {
+  let ctrl = lookup_control_from_str(ctrl)?;
+  if !matches!(ctrl, Token::PLUS | Token::CAT | Token::DET) {
+    return None;
+  }
+
+  let target = literal_from_type2_at_depth(cddl, target, depth + 1)?;
+  let controller = literal_from_type2_at_depth(cddl, controller, depth + 1)?;
+
+  match ctrl {
+    Token::PLUS => numeric_sum(&target, &controller),
+    Token::CAT => concatenate(&target, &controller, false),
+    _ => concatenate(&target, &controller, true),
+  }
+}
+
+fn byte_literal(value: ByteValue) -> Option<Value<'static>> {
+  Some(Value::BYTE(ByteValue::UTF8(Cow::Owned(
+    value.to_bytes()?.into_owned(),
+  ))))
+}
+
+/// Returns the entry types of an array type with a single group choice whose
+/// entries have neither member keys nor occurrence indicators, such as the
+/// controller of a `.join` or `.printf` control operator
+pub fn array_entry_types<'a>(cddl: &'a CDDL<'a>, t2: &Type2<'a>) -> Option<Vec<Type2<'a>>> {
+  match t2 {
+    Type2::Array { group, .. } => match group.group_choices.as_slice() {
+      [gc] => gc
+        .group_entries
+        .iter()
+        .map(|(ge, _)| match ge {
+          GroupEntry::ValueMemberKey { ge, span, .. }
+            if ge.occur.is_none() && ge.member_key.is_none() =>
+          {
+            Some(match operand_type2(&ge.entry_type) {
+              Some(t2) => t2.clone(),
+              None => Type2::ParenthesizedType {
+                pt: ge.entry_type.clone(),
+                span: *span,
+                comments_before_type: None,
+                comments_after_type: None,
+              },
+            })
+          }
+          GroupEntry::TypeGroupname { ge, span, .. } if ge.occur.is_none() => {
+            Some(Type2::Typename {
+              ident: ge.name.clone(),
+              generic_args: ge.generic_args.clone(),
+              span: *span,
+            })
+          }
+          _ => None,
+        })
+        .collect(),
+      _ => None,
+    },
+    Type2::ParenthesizedType { pt, .. } => array_entry_types(cddl, operand_type2(pt)?),
+    Type2::Typename {
+      ident,
+      generic_args: None,
+      ..
+    } => match type_choice_alternates_from_ident(cddl, ident).as_slice() {
+      [t] => array_entry_types(cddl, operand_type2(t)?),
+      _ => None,
+    },
+    _ => None,
+  }
+}
+
+/// Returns the sole type of a type without choices or operators
+fn operand_type2<'b, 'a>(t: &'b Type<'a>) -> Option<&'b Type2<'a>> {
+  match t.type_choices.as_slice() {
+    [TypeChoice { type1, .. }] if type1.operator.is_none() => Some(&type1.type2),
+    _ => None,
+  }
+}
+
+/// Returns the entries of the array controlling a `.join` control operator,
+/// with the bytes of the entries that are text or byte string literals
+pub fn join_entries<'a>(
+  cddl: &'a CDDL<'a>,
+  controller: &Type2<'a>,
+) -> Option<Vec<(Option<Vec<u8>>, Type2<'a>)>> {
+  Some(
+    array_entry_types(cddl, controller)?
+      .into_iter()
+      .map(|t2| {
+        let literal = match literal_from_type2(cddl, &t2) {
+          Some(Value::TEXT(t)) => Some(t.into_owned().into_bytes()),
+          Some(Value::BYTE(b)) => b.to_bytes().map(|b| b.into_owned()),
+          _ => None,
+        };
+
+        (literal, t2)
+      })
+      .collect(),
+  )
+}
+
+/// Returns the format string of the array controlling a `.printf` control
+/// operator
+pub fn printf_format<'a>(cddl: &'a CDDL<'a>, controller: &Type2<'a>) -> Option<String> {
+  match literal_from_type2(cddl, array_entry_types(cddl, controller)?.first()?)? {
+    Value::TEXT(format) => Some(format.into_owned()),
+    _ => None,
+  }
+}
+
+/// Adds two numeric literals. The sum of two integers is an integer, otherwise
+/// the sum is a float
+fn numeric_sum(target: &Value, controller: &Value) -> Option<Value<'static>> {
+  let integer = |v: &Value| match v {
+    Value::INT(i) => Some(*i),
+    Value::UINT(ui) => Some(i128::from(*ui)),
+    _ => None,
+  };
+
+  if let (Some(a), Some(b)) = (integer(target), integer(controller)) {
+    let sum = a.checked_add(b)?;
+    return Some(match u64::try_from(sum) {
+      Ok(ui) => Value::UINT(ui),
+      Err(_) => Value::INT(sum),
+    });
+  }
+
+  let float = |v: &Value| match v {
+    Value::FLOAT(f) => Some(*f),
+    v => integer(v).map(|i| i as f64),
+  };
+
+  Some(Value::FLOAT(float(target)? + float(controller)?))
+}
+
+/// Concatenates two text or byte string literals, optionally dedenting both
+/// beforehand. The result has the type of the target
+fn concatenate(target: &Value, controller: &Value, dedent: bool) -> Option<Value<'static>> {
+  let bytes = |v: &Value| -> Option<Vec<u8>> {
+    let bytes = match v {
+      Value::TEXT(t) => t.as_bytes().to_vec(),
+      Value::BYTE(b) => b.to_bytes()?.into_owned(),
+      _ => return None,
+    };
+
+    Some(if dedent { dedent_lines(&bytes) } else { bytes })
+  };
+
+  let mut concatenation = bytes(target)?;
+  concatenation.extend(bytes(controller)?);
+
+  match target {
+    Value::TEXT(_) => String::from_utf8(concatenation)
+      .ok()
+      .map(|t| Value::TEXT(Cow::Owned(t))),
+    _ => Some(Value::BYTE(ByteValue::UTF8(Cow::Owned(concatenation)))),
+  }
+}
+
+/// Removes the leading whitespace common to all lines that aren't blank, and
+/// all whitespace from blank lines
+fn dedent_lines(s: &[u8]) -> Vec<u8> {
+  let is_whitespace = |b: &u8| *b == b' ' || *b == b'\t';
+  let indentation = |line: &[u8]| line.iter().take_while(|b| is_whitespace(b)).count();
+
+  let lines = s.split(|b| *b == b'\n').collect::<Vec<_>>();
+  let mut common: Option<&[u8]> = None;
+  for line in lines.iter().filter(|l| !l.iter().all(is_whitespace)) {
+    let prefix = &line[..indentation(line)];
+    common = Some(match common {
+      Some(c) => {
+        let len = c.iter().zip(prefix).take_while(|(a, b)| a == b).count();
+        &c[..len]
+      }
+      None => prefix,
+    });
+  }
+
+  let common = common.map_or(0, |c| c.len());
+  let mut dedented = Vec::with_capacity(s.len());
+  for (idx, line) in lines.iter().enumerate() {
+    if idx > 0 {
+      dedented.push(b'\n');
+    }
+    if !line.iter().all(is_whitespace) {
+      dedented.extend_from_slice(&line[common..]);
+    }
+  }
+
+  dedented
+}
+
 /// Entry count
 #[derive(Clone)]
 pub struct EntryCount {
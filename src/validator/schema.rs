@@ -0,0 +1,345 @@
+#![cfg(feature = "std")]
+
+use crate::{
+  ast::*,
+  cddl_from_str,
+  error::{ErrorMsg, MsgType},
+  lexer_from_str,
+  parser::{self, cddl_from_str_with_prelude},
+  prelude::prelude_from_str,
+  semantic,
+  token::{lookup_control_from_str, Token},
+  visitor::{self, *},
+};
+use regex::Regex;
+use std::{
+  collections::{hash_map::Entry, HashMap},
+  fmt,
+};
+
+use super::{
+  cbor, encoding::PrintfFormat, json, printf_format, regex_from_text, type_rule_from_ident,
+  CBORValidator, JSONValidator,
+};
+
+/// Schema compilation error
+#[derive(Debug)]
+pub enum Error {
+  /// CDDL parsing error
+  CDDLParsing(parser::Error),
+  /// Names of the rules that can never be satisfied
+  NonProductiveRules(Vec<String>),
+  /// Malformed regex of a .regexp or .pcre controller
+  Regex {
+    /// Text value of the controller
+    regex: String,
+    /// Reason the regex could not be compiled
+    reason: String,
+  },
+  /// Format string of a .printf controller that could not be compiled
+  PrintfFormat {
+    /// Format string of the controller
+    format: String,
+    /// Reason the format string could not be compiled
+    reason: String,
+  },
+}
+
+impl fmt::Display for Error {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Error::CDDLParsing(error) => write!(f, "error parsing CDDL: {}", error),
+      Error::NonProductiveRules(rules) => {
+        let mut error_str = String::new();
+        for rule in rules.iter() {
+          error_str.push_str(&format!(
+            "rule \"{}\": {}\n",
+            rule,
+            ErrorMsg::from(MsgType::NonProductiveRule)
+          ));
+        }
+        write!(f, "{}", error_str)
+      }
+      Error::Regex { regex, reason } => write!(f, "malformed regex \"{}\": {}", regex, reason),
+      Error::PrintfFormat { format, reason } => {
+        write!(f, "malformed format string \"{}\": {}", format, reason)
+      }
+    }
+  }
+}
+
+impl std::error::Error for Error {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      Error::CDDLParsing(error) => Some(error),
+      _ => None,
+    }
+  }
+}
+
+/// CDDL schema that is parsed, checked and has the regexes of its .regexp and
+/// .pcre controllers and the format strings of its .printf controllers
+/// compiled once, and can then validate any number of JSON
+/// or CBOR documents. It is `Send + Sync`, so a single schema can be shared
+/// between threads, e.g. in an `Arc`
+///
+/// # Example
+///
+/// ```
+/// use cddl::validator::schema::CompiledSchema;
+///
+/// let schema = CompiledSchema::new(r#"reputon = { rating: float, id: tstr .regexp "[a-z]+" }"#)?;
+///
+/// for json in &[r#"{ "rating": 0.5, "id": "abc" }"#, r#"{ "rating": 1.0, "id": "xyz" }"#] {
+///   assert!(schema.validate_json(&serde_json::from_str(json)?).is_ok());
+/// }
+///
+/// assert!(schema
+///   .validate_json(&serde_json::from_str(r#"{ "rating": 0.5, "id": "ABC" }"#)?)
+///   .is_err());
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug)]
+pub struct CompiledSchema {
+  cddl: CDDL<'static>,
+  // Compiled regexes by the text value of their controller
+  regexes: HashMap<String, Regex>,
+  // Compiled .printf format strings by their text
+  printf_formats: HashMap<String, PrintfFormat>,
+}
+
+impl CompiledSchema {
+  /// Compiles a schema from a given CDDL document string
+  pub fn new(cddl: &str) -> std::result::Result<Self, Error> {
+    let mut lexer = lexer_from_str(cddl);
+    let cddl = cddl_from_str(&mut lexer, cddl, true).map_err(Error::CDDLParsing)?;
+
+    CompiledSchema::from_cddl(cddl)
+  }
+
+  /// Compiles a schema from a given CDDL document string, with the rules of the
+  /// given prelude extending the standard prelude. See
+  /// `prelude::prelude_from_str()`
+  pub fn with_prelude(cddl: &str, prelude: &str) -> std::result::Result<Self, Error> {
+    let mut prelude_lexer = lexer_from_str(prelude);
+    let prelude = prelude_from_str(&mut prelude_lexer, prelude).map_err(Error::CDDLParsing)?;
+    let mut lexer = lexer_from_str(cddl);
+    let cddl =
+      cddl_from_str_with_prelude(&mut lexer, cddl, &prelude, true).map_err(Error::CDDLParsing)?;
+
+    CompiledSchema::from_cddl(cddl)
+  }
+
+  /// Compiles a schema from a CDDL AST
+  pub fn from_cddl(cddl: CDDL) -> std::result::Result<Self, Error> {
+    let cddl = cddl.into_owned();
+
+    let non_productive_rules = semantic::non_productive_rules(&cddl);
+    if !non_productive_rules.is_empty() {
+      return Err(Error::NonProductiveRules(
+        non_productive_rules.iter().map(|r| r.name()).collect(),
+      ));
+    }
+
+    let mut collector = PatternCollector {
+      cddl: &cddl,
+      regexes: HashMap::new(),
+      printf_formats: HashMap::new(),
+    };
+    for rule in cddl.rules.iter() {
+      collector.visit_rule(rule)?;
+    }
+    let PatternCollector {
+      regexes,
+      printf_formats,
+      ..
+    } = collector;
+
+    Ok(CompiledSchema {
+      cddl,
+      regexes,
+      printf_formats,
+    })
+  }
+
+  /// CDDL AST of the schema
+  pub fn cddl(&self) -> &CDDL<'static> {
+    &self.cddl
+  }
+
+  /// Validates a JSON value against the schema
+  pub fn validate_json(&self, json: &serde_json::Value) -> json::Result {
+    JSONValidator::from_schema(self, json).validate()
+  }
+
+  /// Validates a CBOR value against the schema
+  pub fn validate_cbor(&self, cbor: &serde_cbor::Value) -> cbor::Result {
+    CBORValidator::from_schema(self, cbor).validate()
+  }
+
+  pub(crate) fn regex(&self, text: &str) -> Option<&Regex> {
+    self.regexes.get(text)
+  }
+
+  pub(crate) fn printf_format(&self, format: &str) -> Option<&PrintfFormat> {
+    self.printf_formats.get(format)
+  }
+}
+
+// Compiles the regexes of the .regexp and .pcre controllers, whether given
+// inline or by the name of a rule, and the format strings of the .printf
+// controllers
+struct PatternCollector<'a> {
+  cddl: &'a CDDL<'a>,
+  regexes: HashMap<String, Regex>,
+  printf_formats: HashMap<String, PrintfFormat>,
+}
+
+impl<'a> PatternCollector<'a> {
+  fn collect(&mut self, controller: &Type2<'a>) -> visitor::Result<Error> {
+    match controller {
+      Type2::TextValue { value, .. } if !self.regexes.contains_key(value.as_ref()) => {
+        let re = regex_from_text(value).map_err(|reason| Error::Regex {
+          regex: value.to_string(),
+          reason,
+        })?;
+
+        self.regexes.insert(value.to_string(), re);
+      }
+      Type2::Typename { ident, .. } => {
+        if let Some(tr) = type_rule_from_ident(self.cddl, ident) {
+          for tc in tr.value.type_choices.iter() {
+            if tc.type1.operator.is_none() {
+              self.collect(&tc.type1.type2)?;
+            }
+          }
+        }
+      }
+      Type2::ParenthesizedType { pt, .. } => {
+        for tc in pt.type_choices.iter() {
+          if tc.type1.operator.is_none() {
+            self.collect(&tc.type1.type2)?;
+          }
+        }
+      }
+      _ => (),
+    }
+
+    Ok(())
+  }
+}
+
+impl<'a> Visitor<'a, Error> for PatternCollector<'a> {
+  fn visit_control_operator(
+    &mut self,
+    target: &Type2<'a>,
+    ctrl: &str,
+    controller: &Type2<'a>,
+  ) -> visitor::Result<Error> {
+    match lookup_control_from_str(ctrl) {
+      Some(Token::CREGEXP) | Some(Token::PCRE) => self.collect(controller)?,
+      Some(Token::PRINTF) => {
+        if let Some(format) = printf_format(self.cddl, controller) {
+          if let Entry::Vacant(entry) = self.printf_formats.entry(format) {
+            let compiled =
+              PrintfFormat::new(entry.key()).map_err(|reason| Error::PrintfFormat {
+                format: entry.key().clone(),
+                reason,
+              })?;
+
+            entry.insert(compiled);
+          }
+        }
+      }
+      _ => (),
+    }
+
+    walk_control_operator(self, target, controller)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::{sync::Arc, thread};
+
+  #[test]
+  fn validate_compiled_schema() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let cddl = r##"message = {
+  id: tstr .regexp "[a-z]+[0-9]*",
+  ? version: release,
+  ? tag: tstr .pcre "#[a-z]+",
+  ? serial: tstr .printf (["SN%04x", uint]),
+}
+
+release = tstr .regexp semver
+semver = "[0-9]+\\.[0-9]+\\.[0-9]+""##;
+
+    let schema = Arc::new(CompiledSchema::new(cddl)?);
+    assert_eq!(schema.regexes.len(), 3);
+    assert_eq!(schema.printf_formats.len(), 1);
+
+    let handles = (0..4)
+      .map(|i| {
+        let schema = Arc::clone(&schema);
+        thread::spawn(move || {
+          let json = serde_json::json!({ "id": format!("abc{}", i), "tag": "#a" });
+          let cbor = serde_cbor::value::to_value(&json).unwrap();
+
+          schema.validate_json(&json).is_ok() && schema.validate_cbor(&cbor).is_ok()
+        })
+      })
+      .collect::<Vec<_>>();
+    for handle in handles {
+      assert!(handle.join().unwrap());
+    }
+
+    for (json, is_valid) in [
+      (r#"{ "id": "abc", "version": "1.2.3" }"#, true),
+      (r#"{ "id": "abc", "version": "1.2" }"#, false),
+      (r#"{ "id": "ABC" }"#, false),
+      (r#"{ "id": "abc", "tag": "a" }"#, false),
+      (r#"{ "id": "abc", "serial": "SN00ff" }"#, true),
+      (r#"{ "id": "abc", "serial": "SN0fg" }"#, false),
+    ]
+    .iter()
+    {
+      let json = serde_json::from_str::<serde_json::Value>(json)?;
+      let cbor = serde_cbor::value::to_value(&json)?;
+
+      assert_eq!(schema.validate_json(&json).is_ok(), *is_valid, "{}", json);
+      assert_eq!(schema.validate_cbor(&cbor).is_ok(), *is_valid, "{}", json);
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn verify_compile_errors() {
+    assert!(matches!(
+      CompiledSchema::new(r#"id = tstr .regexp "[a-z""#),
+      Err(Error::Regex { .. })
+    ));
+
+    let mut lexer = lexer_from_str("a = b\nb = [a] / int");
+    let mut cddl = cddl_from_str(&mut lexer, "a = b\nb = [a] / int", true).unwrap();
+
+    // The parser refuses non-productive rules, so remove the base case after
+    // the fact, leaving "b = [a]"
+    if let Rule::Type { rule, .. } = &mut cddl.rules[1] {
+      rule.value.type_choices.pop();
+    }
+
+    match CompiledSchema::from_cddl(cddl) {
+      Err(Error::NonProductiveRules(rules)) => assert_eq!(rules, vec!["a", "b"]),
+      _ => panic!("expected non-productive rules"),
+    }
+
+    assert!(matches!(
+      CompiledSchema::new("a = "),
+      Err(Error::CDDLParsing(_))
+    ));
+
+    assert!(CompiledSchema::with_prelude("a = currency", r#"currency = "EUR""#).is_ok());
+  }
+}
@@ -1,7 +1,14 @@
 #![cfg(feature = "std")]
 #![cfg(not(target_arch = "wasm32"))]
 
-use cddl::{self, validator::validate_cbor_from_slice};
+use cddl::{
+  self, ast, lexer_from_str,
+  parser::cddl_from_str,
+  validator::{
+    cbor::CBORValidator, control::CustomControls, validate_cbor_from_slice,
+    validate_cbor_from_slice_with_controls, validate_cbor_from_slice_with_prelude,
+  },
+};
 use serde::{Deserialize, Serialize};
 
 #[rustfmt::skip] // allow arbitrary indents for readability
@@ -17,11 +24,25 @@ pub mod cbor {
     pub const INT_23:       &[u8] = b"\x17";
     pub const INT_24:       &[u8] = b"\x18\x18";
     pub const NINT_1000:    &[u8] = b"\x39\x03\xe7";  // -1000
+    pub const UINT_MAX:     &[u8] = b"\x1b\xff\xff\xff\xff\xff\xff\xff\xff";  // 2^64 - 1
+    pub const NINT_MIN:     &[u8] = b"\x3b\xff\xff\xff\xff\xff\xff\xff\xff";  // -2^64
+
+    pub const BIGUINT_2_64: &[u8] = b"\xc2\x49\x01\x00\x00\x00\x00\x00\x00\x00\x00";  // 2(h'010000000000000000')
+    pub const BIGNINT_2_64: &[u8] = b"\xc3\x49\x01\x00\x00\x00\x00\x00\x00\x00\x00";  // 3(h'010000000000000000')
+    pub const BIGUINT_1:    &[u8] = b"\xc2\x41\x01";  // 2(h'01')
+
+    pub const DECFRAC_273_15: &[u8] = b"\xc4\x82\x21\x19\x6a\xb3";  // 4([-2, 27315])
+    pub const DECFRAC_BIGNUM: &[u8] = b"\xc4\x82\x21\xc2\x41\x01";  // 4([-2, 2(h'01')])
+    pub const DECFRAC_FLOAT:  &[u8] = b"\xc4\x82\xf9\x3c\x00\x01";  // 4([1.0, 1])
+    pub const BIGFLOAT_1_5:   &[u8] = b"\xc5\x82\x20\x03";  // 5([-1, 3])
+    pub const BIGFLOAT_3_ITEMS: &[u8] = b"\xc5\x83\x20\x03\x03";  // 5([-1, 3, 3])
 
     pub const FLOAT_0_0:    &[u8] = b"\xf9\x00\x00";            // #7.25 (f16)
     pub const FLOAT_1_0:    &[u8] = b"\xf9\x3c\x00";            // #7.25 (f16)
     pub const FLOAT_1E5:    &[u8] = b"\xfa\x47\xc3\x50\x00";    // #7.26 (f32)
     pub const FLOAT_1E300:  &[u8] = b"\xfb\x7e\x37\xe4\x3c\x88\x00\x75\x9c"; // #7.27 (f64)
+    pub const FLOAT_0_1:    &[u8] = b"\xfb\x3f\xb9\x99\x99\x99\x99\x99\x9a"; // #7.27 (f64)
+    pub const FLOAT_2_N24:  &[u8] = b"\xf9\x00\x01";            // #7.25 (f16 subnormal)
 
     pub const ARRAY_EMPTY:  &[u8] = b"\x80";              // []
     pub const ARRAY_123:    &[u8] = b"\x83\x01\x02\x03";  // [1,2,3]
@@ -70,10 +91,63 @@ fn validate_cbor_float() {
   validate_cbor_from_slice(cddl_input, cbor::FLOAT_1_0).unwrap();
   validate_cbor_from_slice(cddl_input, cbor::FLOAT_1E300).unwrap();
 
+  let cddl_input = r#"thing = 1e5 / 1.0E+300"#;
+  validate_cbor_from_slice(cddl_input, cbor::FLOAT_1E5).unwrap();
+  validate_cbor_from_slice(cddl_input, cbor::FLOAT_1E300).unwrap();
+  validate_cbor_from_slice(cddl_input, cbor::FLOAT_1_0).unwrap_err();
+
+  let cddl_input = r#"thing = 0x1p-1..0x1.8p17"#;
+  validate_cbor_from_slice(cddl_input, cbor::FLOAT_1_0).unwrap();
+  validate_cbor_from_slice(cddl_input, cbor::FLOAT_1E5).unwrap();
+  validate_cbor_from_slice(cddl_input, cbor::FLOAT_0_0).unwrap_err();
+
   // TODO: check that large floats don't validate against a smaller size.
   // E.g. CBOR #7.27 (64-bit) shouldn't validate against "float16" or "float32".
 }
 
+#[test]
+fn validate_cbor_major_type_7() {
+  let cddl_input = r#"thing = #7"#;
+  validate_cbor_from_slice(cddl_input, cbor::BOOL_TRUE).unwrap();
+  validate_cbor_from_slice(cddl_input, cbor::NULL).unwrap();
+  validate_cbor_from_slice(cddl_input, cbor::FLOAT_1E300).unwrap();
+  validate_cbor_from_slice(cddl_input, cbor::INT_1).unwrap_err();
+
+  // Float widths accept any float that can be represented at that width
+  let cddl_input = r#"thing = #7.25"#;
+  validate_cbor_from_slice(cddl_input, cbor::FLOAT_1_0).unwrap();
+  validate_cbor_from_slice(cddl_input, cbor::FLOAT_2_N24).unwrap();
+  validate_cbor_from_slice(cddl_input, cbor::FLOAT_1E5).unwrap_err();
+  validate_cbor_from_slice(cddl_input, cbor::FLOAT_0_1).unwrap_err();
+  let cddl_input = r#"thing = #7.26"#;
+  validate_cbor_from_slice(cddl_input, cbor::FLOAT_1_0).unwrap();
+  validate_cbor_from_slice(cddl_input, cbor::FLOAT_1E5).unwrap();
+  validate_cbor_from_slice(cddl_input, cbor::FLOAT_0_1).unwrap_err();
+  validate_cbor_from_slice(cddl_input, cbor::FLOAT_1E300).unwrap_err();
+  let cddl_input = r#"thing = #7.27"#;
+  validate_cbor_from_slice(cddl_input, cbor::FLOAT_0_1).unwrap();
+  validate_cbor_from_slice(cddl_input, cbor::FLOAT_1E300).unwrap();
+  validate_cbor_from_slice(cddl_input, cbor::BOOL_TRUE).unwrap_err();
+
+  let cddl_input = r#"thing = #7.20"#;
+  validate_cbor_from_slice(cddl_input, cbor::BOOL_FALSE).unwrap();
+  validate_cbor_from_slice(cddl_input, cbor::BOOL_TRUE).unwrap_err();
+  let cddl_input = r#"thing = #7.21"#;
+  validate_cbor_from_slice(cddl_input, cbor::BOOL_TRUE).unwrap();
+  validate_cbor_from_slice(cddl_input, cbor::NULL).unwrap_err();
+  let cddl_input = r#"thing = #7.22"#;
+  validate_cbor_from_slice(cddl_input, cbor::NULL).unwrap();
+  validate_cbor_from_slice(cddl_input, cbor::BOOL_FALSE).unwrap_err();
+  let cddl_input = r#"thing = #7.23"#;
+  validate_cbor_from_slice(cddl_input, cbor::UNDEFINED).unwrap();
+
+  // Unassigned simple values can't be decoded, so nothing matches them
+  let cddl_input = r#"thing = #7.16"#;
+  validate_cbor_from_slice(cddl_input, cbor::BOOL_FALSE).unwrap_err();
+  validate_cbor_from_slice(cddl_input, cbor::NULL).unwrap_err();
+  validate_cbor_from_slice(cddl_input, cbor::FLOAT_1_0).unwrap_err();
+}
+
 #[test]
 fn validate_cbor_integer() {
   let cddl_input = r#"thing = 23 / 24"#;
@@ -94,6 +168,72 @@ fn validate_cbor_integer() {
   validate_cbor_from_slice(cddl_input, cbor::NINT_1000).unwrap_err();
 }
 
+#[test]
+fn validate_cbor_integer_full_range() {
+  let cddl_input = r#"thing = 18446744073709551615"#;
+  validate_cbor_from_slice(cddl_input, cbor::UINT_MAX).unwrap();
+  let cddl_input = r#"thing = -18446744073709551616"#;
+  validate_cbor_from_slice(cddl_input, cbor::NINT_MIN).unwrap();
+  validate_cbor_from_slice(cddl_input, cbor::UINT_MAX).unwrap_err();
+  let cddl_input = r#"thing = -18446744073709551616..-1"#;
+  validate_cbor_from_slice(cddl_input, cbor::NINT_MIN).unwrap();
+  validate_cbor_from_slice(cddl_input, cbor::NINT_1000).unwrap();
+  validate_cbor_from_slice(cddl_input, cbor::INT_0).unwrap_err();
+  let cddl_input = r#"thing = #0.18446744073709551615"#;
+  validate_cbor_from_slice(cddl_input, cbor::UINT_MAX).unwrap();
+}
+
+#[test]
+fn validate_cbor_bignum() {
+  let cddl_input = r#"thing = integer"#;
+  validate_cbor_from_slice(cddl_input, cbor::BIGUINT_2_64).unwrap();
+  validate_cbor_from_slice(cddl_input, cbor::BIGNINT_2_64).unwrap();
+  validate_cbor_from_slice(cddl_input, cbor::INT_24).unwrap();
+  let cddl_input = r#"thing = unsigned"#;
+  validate_cbor_from_slice(cddl_input, cbor::BIGUINT_2_64).unwrap();
+  validate_cbor_from_slice(cddl_input, cbor::BIGNINT_2_64).unwrap_err();
+  validate_cbor_from_slice(cddl_input, cbor::NINT_1000).unwrap_err();
+  let cddl_input = r#"thing = int"#;
+  validate_cbor_from_slice(cddl_input, cbor::BIGUINT_2_64).unwrap_err();
+  // Only the prelude types including bigint admit bignums
+  let cddl_input = r#"thing = 1"#;
+  validate_cbor_from_slice(cddl_input, cbor::BIGUINT_1).unwrap_err();
+  validate_cbor_from_slice(cddl_input, cbor::INT_1).unwrap();
+  let cddl_input = r#"thing = 0..18446744073709551615"#;
+  validate_cbor_from_slice(cddl_input, cbor::BIGUINT_1).unwrap_err();
+  validate_cbor_from_slice(cddl_input, cbor::BIGUINT_2_64).unwrap_err();
+}
+
+#[test]
+fn validate_cbor_decfrac_bigfloat() {
+  let cddl_input = r#"thing = decfrac"#;
+  validate_cbor_from_slice(cddl_input, cbor::DECFRAC_273_15).unwrap();
+  validate_cbor_from_slice(cddl_input, cbor::DECFRAC_BIGNUM).unwrap();
+  validate_cbor_from_slice(cddl_input, cbor::DECFRAC_FLOAT).unwrap_err();
+  validate_cbor_from_slice(cddl_input, cbor::BIGFLOAT_1_5).unwrap_err();
+  validate_cbor_from_slice(cddl_input, cbor::ARRAY_123).unwrap_err();
+
+  let cddl_input = r#"thing = bigfloat"#;
+  validate_cbor_from_slice(cddl_input, cbor::BIGFLOAT_1_5).unwrap();
+  validate_cbor_from_slice(cddl_input, cbor::BIGFLOAT_3_ITEMS).unwrap_err();
+  validate_cbor_from_slice(cddl_input, cbor::DECFRAC_273_15).unwrap_err();
+
+  let cddl_input = r#"thing = { price: decfrac / bigfloat }"#;
+  let mut m = std::collections::BTreeMap::new();
+  m.insert(
+    serde_cbor::Value::Text("price".to_string()),
+    serde_cbor::Value::Tag(
+      5,
+      Box::new(serde_cbor::Value::Array(vec![
+        serde_cbor::Value::Integer(-1),
+        serde_cbor::Value::Integer(3),
+      ])),
+    ),
+  );
+  let cbor_bytes = serde_cbor::to_vec(&serde_cbor::Value::Map(m)).unwrap();
+  validate_cbor_from_slice(cddl_input, &cbor_bytes).unwrap();
+}
+
 #[test]
 fn validate_cbor_textstring() {
   let cddl_input = r#"thing = tstr"#;
@@ -110,6 +250,404 @@ fn validate_cbor_bytestring() {
   validate_cbor_from_slice(cddl_input, cbor::BYTES_1234).unwrap();
   validate_cbor_from_slice(cddl_input, cbor::TEXT_EMPTY).unwrap_err();
   validate_cbor_from_slice(cddl_input, cbor::ARRAY_123).unwrap_err();
+
+  let cddl_input = r#"thing = h'01020304'"#;
+  validate_cbor_from_slice(cddl_input, cbor::BYTES_1234).unwrap();
+  validate_cbor_from_slice(cddl_input, cbor::BYTES_EMPTY).unwrap_err();
+  validate_cbor_from_slice(cddl_input, cbor::TEXT_IETF).unwrap_err();
+
+  // Whitespace is ignored in prefixed byte strings
+  let cddl_input = r#"thing = h'01 02 03 04'"#;
+  validate_cbor_from_slice(cddl_input, cbor::BYTES_1234).unwrap();
+
+  // base64 byte strings may omit their padding
+  let cddl_input = r#"thing = b64'AQIDBA=='"#;
+  validate_cbor_from_slice(cddl_input, cbor::BYTES_1234).unwrap();
+  let cddl_input = r#"thing = b64'AQIDBA'"#;
+  validate_cbor_from_slice(cddl_input, cbor::BYTES_1234).unwrap();
+
+  let cddl_input = r#"thing = 'IETF'"#;
+  validate_cbor_from_slice(cddl_input, b"\x44IETF").unwrap();
+  validate_cbor_from_slice(cddl_input, cbor::TEXT_IETF).unwrap_err();
+  validate_cbor_from_slice(cddl_input, cbor::BYTES_1234).unwrap_err();
+
+  let cddl_input = r#"thing = h'05' / h'01020304'"#;
+  validate_cbor_from_slice(cddl_input, cbor::BYTES_1234).unwrap();
+}
+
+#[test]
+fn validate_cbor_bytestring_size() {
+  let cddl_input = r#"thing = bstr .size 4"#;
+  validate_cbor_from_slice(cddl_input, cbor::BYTES_1234).unwrap();
+  validate_cbor_from_slice(cddl_input, cbor::BYTES_EMPTY).unwrap_err();
+  // A text string of the right length isn't a byte string
+  validate_cbor_from_slice(cddl_input, cbor::TEXT_IETF).unwrap_err();
+
+  let cddl_input = r#"thing = bstr .size (1..4)"#;
+  validate_cbor_from_slice(cddl_input, cbor::BYTES_1234).unwrap();
+  validate_cbor_from_slice(cddl_input, cbor::BYTES_EMPTY).unwrap_err();
+
+  let cddl_input = r#"thing = bstr .size (0...4)"#;
+  validate_cbor_from_slice(cddl_input, cbor::BYTES_EMPTY).unwrap();
+  validate_cbor_from_slice(cddl_input, cbor::BYTES_1234).unwrap_err();
+
+  // Exclusive ranges only exclude their upper bound
+  let cddl_input = r#"thing = bstr .size (4...5)"#;
+  validate_cbor_from_slice(cddl_input, cbor::BYTES_1234).unwrap();
+  validate_cbor_from_slice(cddl_input, cbor::BYTES_EMPTY).unwrap_err();
+  let cddl_input = r#"thing = tstr .size (4...5)"#;
+  validate_cbor_from_slice(cddl_input, cbor::TEXT_IETF).unwrap();
+  validate_cbor_from_slice(cddl_input, cbor::TEXT_EMPTY).unwrap_err();
+  let cddl_input = r#"thing = tstr .size (0...4)"#;
+  validate_cbor_from_slice(cddl_input, cbor::TEXT_EMPTY).unwrap();
+  validate_cbor_from_slice(cddl_input, cbor::TEXT_IETF).unwrap_err();
+
+  let cddl_input = r#"thing = { digest: bstr .size 4 }"#;
+  let mut m = std::collections::BTreeMap::new();
+  m.insert(
+    serde_cbor::Value::Text("digest".to_string()),
+    serde_cbor::Value::Bytes(vec![1, 2, 3, 4]),
+  );
+  let cbor_bytes = serde_cbor::to_vec(&serde_cbor::Value::Map(m)).unwrap();
+  validate_cbor_from_slice(cddl_input, &cbor_bytes).unwrap();
+}
+
+#[test]
+fn validate_cbor_bytestring_map_keys() {
+  let mut m = std::collections::BTreeMap::new();
+  m.insert(
+    serde_cbor::Value::Bytes(vec![1]),
+    serde_cbor::Value::Integer(1),
+  );
+  m.insert(
+    serde_cbor::Value::Bytes(b"key".to_vec()),
+    serde_cbor::Value::Text("value".to_string()),
+  );
+  let cbor_bytes = serde_cbor::to_vec(&serde_cbor::Value::Map(m)).unwrap();
+
+  let cddl_input = r#"thing = { h'01' => int, 'key' => tstr }"#;
+  validate_cbor_from_slice(cddl_input, &cbor_bytes).unwrap();
+  let cddl_input = r#"thing = { b64'AQ' => int, 'key' => tstr }"#;
+  validate_cbor_from_slice(cddl_input, &cbor_bytes).unwrap();
+  let cddl_input = r#"thing = { h'02' => int, 'key' => tstr }"#;
+  validate_cbor_from_slice(cddl_input, &cbor_bytes).unwrap_err();
+  let cddl_input = r#"thing = { h'01' => int, 'key' => int }"#;
+  validate_cbor_from_slice(cddl_input, &cbor_bytes).unwrap_err();
+  let cddl_input = r#"thing = { * bstr => any }"#;
+  validate_cbor_from_slice(cddl_input, &cbor_bytes).unwrap();
+}
+
+#[test]
+fn validate_cbor_bits() {
+  let cddl_input = r#"thing = uint .bits &(read: 0, write: 1, exec: 2)"#;
+  validate_cbor_from_slice(cddl_input, cbor::INT_0).unwrap();
+  validate_cbor_from_slice(cddl_input, cbor::INT_1).unwrap();
+  validate_cbor_from_slice(cddl_input, b"\x07").unwrap();
+  validate_cbor_from_slice(cddl_input, b"\x0b").unwrap_err();
+  validate_cbor_from_slice(cddl_input, cbor::NINT_1000).unwrap_err();
+  validate_cbor_from_slice(cddl_input, cbor::BYTES_EMPTY).unwrap_err();
+
+  // Bit n of a byte string is found in byte n / 8
+  let cddl_input = r#"thing = bstr .bits tcpflags
+    tcpflags = &(fin: 8, syn: 9, rst: 10, psh: 11)"#;
+  validate_cbor_from_slice(cddl_input, cbor::BYTES_EMPTY).unwrap();
+  validate_cbor_from_slice(cddl_input, b"\x42\x00\x03").unwrap();
+  validate_cbor_from_slice(cddl_input, b"\x42\x00\x10").unwrap_err();
+  validate_cbor_from_slice(cddl_input, b"\x42\x01\x03").unwrap_err();
+  validate_cbor_from_slice(cddl_input, cbor::INT_1).unwrap_err();
+
+  let cddl_input = r#"thing = uint .bits (0..3)"#;
+  validate_cbor_from_slice(cddl_input, b"\x0f").unwrap();
+  validate_cbor_from_slice(cddl_input, b"\x10").unwrap_err();
+
+  let cddl_input = r#"thing = tstr .bits 1"#;
+  validate_cbor_from_slice(cddl_input, cbor::TEXT_IETF).unwrap_err();
+
+  let cddl_input = r#"thing = uint .bits (0..1)"#;
+  let mut lexer = lexer_from_str(cddl_input);
+  let cddl = cddl_from_str(&mut lexer, cddl_input, true).unwrap();
+  let mut cv = CBORValidator::new(&cddl, &serde_cbor::Value::Integer(0b1101));
+  let err = cv.validate().unwrap_err().to_string();
+  assert!(err.contains("unexpected bits 2, 3"), "{}", err);
+}
+
+#[test]
+fn validate_cbor_embedded_cbor() {
+  let cddl_input = r#"signed = { payload: bstr .cbor person }
+    person = { name: tstr, age: uint }"#;
+
+  let signed = |person: &PersonStruct| {
+    let mut m = std::collections::BTreeMap::new();
+    m.insert(
+      serde_cbor::Value::Text("payload".to_string()),
+      serde_cbor::Value::Bytes(serde_cbor::to_vec(person).unwrap()),
+    );
+    serde_cbor::to_vec(&serde_cbor::Value::Map(m)).unwrap()
+  };
+
+  let bob = PersonStruct {
+    name: "Bob".to_string(),
+    age: 43,
+  };
+  validate_cbor_from_slice(cddl_input, &signed(&bob)).unwrap();
+
+  // Errors within the embedded data item report both the outer and inner
+  // locations
+  let cddl_input = r#"signed = { payload: bstr .cbor person }
+    person = { name: tstr, age: tstr }"#;
+  let err = validate_cbor_from_slice(cddl_input, &signed(&bob))
+    .unwrap_err()
+    .to_string();
+  assert!(err.contains(r#"/"payload"/"age""#), "{}", err);
+
+  // Malformed embedded CBOR
+  let cddl_input = r#"thing = bstr .cbor int"#;
+  validate_cbor_from_slice(cddl_input, b"\x41\x01").unwrap();
+  validate_cbor_from_slice(cddl_input, b"\x41\x18").unwrap_err();
+  validate_cbor_from_slice(cddl_input, b"\x41\x60").unwrap_err();
+  validate_cbor_from_slice(cddl_input, cbor::INT_1).unwrap_err();
+
+  // A CBOR sequence is validated as an array of its data items
+  let cddl_input = r#"thing = bstr .cborseq [* int]"#;
+  validate_cbor_from_slice(cddl_input, cbor::BYTES_EMPTY).unwrap();
+  validate_cbor_from_slice(cddl_input, cbor::BYTES_1234).unwrap();
+  validate_cbor_from_slice(cddl_input, b"\x43\x01\x60\x02").unwrap_err();
+  let cddl_input = r#"thing = bstr .cborseq [int, tstr]"#;
+  validate_cbor_from_slice(cddl_input, b"\x43\x01\x61\x61").unwrap();
+  validate_cbor_from_slice(cddl_input, b"\x42\x01\x02").unwrap_err();
+
+  // Tag 24 encloses an encoded CBOR data item
+  let cddl_input = r#"thing = encoded-cbor"#;
+  validate_cbor_from_slice(cddl_input, b"\xd8\x18\x41\x01").unwrap();
+  validate_cbor_from_slice(cddl_input, b"\xd8\x18\x41\x18").unwrap_err();
+  let cddl_input = r#"thing = #6.24(bstr .cbor tstr)"#;
+  validate_cbor_from_slice(cddl_input, b"\xd8\x18\x42\x61\x61").unwrap();
+  validate_cbor_from_slice(cddl_input, b"\xd8\x18\x41\x01").unwrap_err();
+}
+
+#[test]
+fn validate_cbor_text_encodings() {
+  let cddl_input = r#"thing = tstr .b64u (bstr .size 3)"#;
+  validate_cbor_from_slice(cddl_input, b"\x64AQID").unwrap();
+  validate_cbor_from_slice(cddl_input, b"\x64AQI=").unwrap_err();
+  validate_cbor_from_slice(cddl_input, b"\x63AQI").unwrap_err();
+  validate_cbor_from_slice(cddl_input, b"\x44AQID").unwrap_err();
+
+  let cddl_input = r#"thing = tstr .b64c h'fbff'"#;
+  validate_cbor_from_slice(cddl_input, b"\x64+/8=").unwrap();
+  validate_cbor_from_slice(cddl_input, b"\x64-_8=").unwrap_err();
+
+  let cddl_input = r#"thing = tstr .hexlc bstr"#;
+  validate_cbor_from_slice(cddl_input, b"\x640aff").unwrap();
+  validate_cbor_from_slice(cddl_input, b"\x640AFF").unwrap_err();
+
+  let cddl_input = r#"thing = tstr .decimal (-10..10)"#;
+  validate_cbor_from_slice(cddl_input, b"\x62-7").unwrap();
+  validate_cbor_from_slice(cddl_input, b"\x6211").unwrap_err();
+  validate_cbor_from_slice(cddl_input, b"\x6207").unwrap_err();
+
+  let cddl_input = r#"thing = tstr .printf (["0x%04x", 0..0xffff])"#;
+  validate_cbor_from_slice(cddl_input, b"\x660x1267").unwrap();
+  validate_cbor_from_slice(cddl_input, b"\x650x126").unwrap_err();
+
+  let cddl_input = r#"thing = tstr .json person
+    person = { name: tstr, age: uint }"#;
+  validate_cbor_from_slice(cddl_input, b"\x78\x1a{\"name\": \"Bob\", \"age\": 43}").unwrap();
+  let err = validate_cbor_from_slice(cddl_input, b"\x78\x1a{\"name\": \"Bob\", \"age\": -4}")
+    .unwrap_err()
+    .to_string();
+  assert!(err.contains(r#"/"age""#), "{}", err);
+  validate_cbor_from_slice(cddl_input, b"\x65{\"na\"").unwrap_err();
+
+  let cddl_input = r#"thing = tstr .join (["v", tstr .size 1, ".", tstr .size 1])"#;
+  validate_cbor_from_slice(cddl_input, b"\x64v1.2").unwrap();
+  validate_cbor_from_slice(cddl_input, b"\x65v1.23").unwrap_err();
+
+  let cddl_input = r#"thing = bstr .join ([h'01', bstr, 'x'])"#;
+  validate_cbor_from_slice(cddl_input, b"\x44\x01\x02\x03x").unwrap();
+  validate_cbor_from_slice(cddl_input, b"\x44\x02\x02\x03x").unwrap_err();
+}
+
+#[test]
+fn validate_cbor_computed_literals() {
+  let cddl_input = r#"thing = offset .plus 3
+    offset = 4"#;
+  validate_cbor_from_slice(cddl_input, b"\x07").unwrap();
+  validate_cbor_from_slice(cddl_input, cbor::INT_1).unwrap_err();
+
+  let cddl_input = r#"thing = -3 .plus 1.5"#;
+  validate_cbor_from_slice(cddl_input, b"\xf9\xbe\x00").unwrap();
+
+  let cddl_input = r#"thing = uint .plus 3"#;
+  validate_cbor_from_slice(cddl_input, b"\x07").unwrap_err();
+
+  let cddl_input = r#"thing = "IE" .cat "TF""#;
+  validate_cbor_from_slice(cddl_input, cbor::TEXT_IETF).unwrap();
+  validate_cbor_from_slice(cddl_input, cbor::TEXT_EMPTY).unwrap_err();
+
+  // The result has the type of the target
+  let cddl_input = r#"thing = h'0102' .cat prefix
+    prefix = h'03' .cat h'04'"#;
+  validate_cbor_from_slice(cddl_input, cbor::BYTES_1234).unwrap();
+  validate_cbor_from_slice(cddl_input, cbor::TEXT_IETF).unwrap_err();
+
+  // Both sides are dedented before concatenation
+  let cddl_input = "thing = \"I\" .det '\n    ET\n    F'";
+  validate_cbor_from_slice(cddl_input, b"\x66\x49\x0a\x45\x54\x0a\x46").unwrap();
+  validate_cbor_from_slice(cddl_input, cbor::TEXT_IETF).unwrap_err();
+
+  let cddl_input = r#"thing = { ("na" .cat "me"): tstr }"#;
+  validate_cbor_from_slice(cddl_input, b"\xa1\x64name\x63Bob").unwrap();
+  validate_cbor_from_slice(cddl_input, b"\xa1\x64nome\x63Bob").unwrap_err();
+}
+
+#[test]
+fn validate_cbor_abnf() {
+  let cddl_input = "thing = tstr .abnf ('date' .det rules)
+    rules = '
+      date = year \"-\" month
+      year = 4DIGIT
+      month = %x30 %x31-39 / %x31 %x30-32
+    '";
+  validate_cbor_from_slice(cddl_input, b"\x672021-12").unwrap();
+  validate_cbor_from_slice(cddl_input, b"\x672021-13").unwrap_err();
+  validate_cbor_from_slice(cddl_input, b"\x472021-12").unwrap_err();
+
+  // Rules start in the first column, so indented lines continue a rule
+  let cddl_input = "thing = bstr .abnfb 'oid = 1*arc
+arc = [nlsb] %x00-7f
+nlsb = %x81-ff *%x80-ff'";
+  validate_cbor_from_slice(cddl_input, b"\x43\x2b\x81\x00").unwrap();
+  validate_cbor_from_slice(cddl_input, b"\x41\x81").unwrap_err();
+  validate_cbor_from_slice(cddl_input, b"\x63\x2b\x06\x01").unwrap_err();
+
+  let cddl_input = r#"thing = tstr .abnf "date = <a date>""#;
+  let err = validate_cbor_from_slice(cddl_input, cbor::TEXT_IETF)
+    .unwrap_err()
+    .to_string();
+  assert!(err.contains("invalid ABNF grammar"), "{}", err);
+}
+
+#[test]
+fn validate_cbor_feature() {
+  let cddl_input =
+    r#"thing = { name: tstr, ? age: uint .feature "age" / tstr .feature "age-text" }"#;
+  let mut lexer = lexer_from_str(cddl_input);
+  let cddl = cddl_from_str(&mut lexer, cddl_input, true).unwrap();
+
+  let bob = serde_cbor::value::to_value(PersonStruct {
+    name: "Bob".to_string(),
+    age: 43,
+  })
+  .unwrap();
+  let mut cv = CBORValidator::new(&cddl, &bob);
+  cv.validate().unwrap();
+  assert_eq!(cv.features().iter().collect::<Vec<_>>(), vec!["age"]);
+
+  let bob = serde_cbor::from_slice(b"\xa1\x64name\x63Bob").unwrap();
+  let mut cv = CBORValidator::new(&cddl, &bob);
+  cv.validate().unwrap();
+  assert!(cv.features().is_empty());
+}
+
+fn luhn(digits: &str) -> Result<(), String> {
+  let sum = digits
+    .chars()
+    .rev()
+    .enumerate()
+    .try_fold(0, |sum, (idx, c)| {
+      let d = c.to_digit(10).ok_or("expected only digits")?;
+      // Every second digit from the right is doubled, summing its digits
+      let d = if idx % 2 == 1 { d * 2 } else { d };
+      Ok::<_, String>(sum + if d > 9 { d - 9 } else { d })
+    })?;
+
+  if sum % 10 == 0 {
+    Ok(())
+  } else {
+    Err("invalid check digit".to_string())
+  }
+}
+
+#[test]
+fn validate_cbor_custom_controls() {
+  let mut controls = CustomControls::new();
+  controls
+    .register_cbor(".luhn", |_, _, value| match value {
+      serde_cbor::Value::Text(digits) => luhn(digits),
+      _ => Err("expected a text string".to_string()),
+    })
+    .register_json(".iso4217", |_, _, _| Ok(()));
+
+  let cddl_input = r#"card = tstr .luhn 0"#;
+  validate_cbor_from_slice_with_controls(cddl_input, b"k79927398713", &controls).unwrap();
+  validate_cbor_from_slice_with_controls(cddl_input, b"k79927398710", &controls).unwrap_err();
+  validate_cbor_from_slice_with_controls(cddl_input, cbor::INT_1, &controls).unwrap_err();
+
+  // Unknown controls are still rejected when parsing
+  validate_cbor_from_slice(cddl_input, b"k79927398713").unwrap_err();
+
+  // Registered for JSON only
+  let cddl_input = r#"currency = tstr .iso4217 0"#;
+  validate_cbor_from_slice_with_controls(cddl_input, b"cEUR", &controls).unwrap_err();
+}
+
+#[test]
+fn validate_cbor_escaped_strings() {
+  let cddl_input = r#"thing = "a\"b" / "caf\u00e9" / 'it\'s' / h'01 ; first byte
+    02'"#;
+  validate_cbor_from_slice(cddl_input, b"\x63a\"b").unwrap();
+  validate_cbor_from_slice(cddl_input, b"\x65caf\xc3\xa9").unwrap();
+  validate_cbor_from_slice(cddl_input, b"\x44it's").unwrap();
+  validate_cbor_from_slice(cddl_input, b"\x42\x01\x02").unwrap();
+  validate_cbor_from_slice(cddl_input, b"\x64a\\\"b").unwrap_err();
+  validate_cbor_from_slice(cddl_input, b"\x69caf\\u00e9").unwrap_err();
+}
+
+#[test]
+fn validate_cbor_escaped_regexp() {
+  let cddl_input = r#"version = tstr .regexp "[0-9]+\\.[0-9]+""#;
+  validate_cbor_from_slice(cddl_input, b"\x631.2").unwrap();
+  validate_cbor_from_slice(cddl_input, b"\x63132").unwrap_err();
+}
+
+#[test]
+fn validate_cbor_typed_head_numbers() {
+  let cddl_input = r#"thing = #6.<1000..1999>(tstr) / #6.<tagnums>(uint) / #1.2 / #2.<1..2>
+    tagnums = 32 / 35"#;
+  validate_cbor_from_slice(cddl_input, b"\xd9\x05\xdc\x61a").unwrap();
+  validate_cbor_from_slice(cddl_input, b"\xd9\x07\xd0\x61a").unwrap_err();
+  validate_cbor_from_slice(cddl_input, b"\xd8\x23\x01").unwrap();
+  validate_cbor_from_slice(cddl_input, b"\xd8\x22\x01").unwrap_err();
+  validate_cbor_from_slice(cddl_input, b"\x22").unwrap();
+  validate_cbor_from_slice(cddl_input, b"\x21").unwrap_err();
+  validate_cbor_from_slice(cddl_input, b"\x42\x01\x02").unwrap();
+  validate_cbor_from_slice(cddl_input, b"\x43\x01\x02\x03").unwrap_err();
+
+  let cddl_input = r#"thing = #7.<25 / 26>"#;
+  validate_cbor_from_slice(cddl_input, b"\xf9\x3c\x00").unwrap();
+  validate_cbor_from_slice(cddl_input, b"\xfb\x3f\xb9\x99\x99\x99\x99\x99\x9a").unwrap_err();
+}
+
+#[test]
+fn validate_cbor_extended_prelude() {
+  let prelude = r#"cose-sign1 = #6.18([protected: bstr, unprotected: {* any => any}, payload: bstr, signature: bstr])
+    time = #6.1(uint)"#;
+
+  let cddl_input = r#"thing = cose-sign1 / time"#;
+  validate_cbor_from_slice_with_prelude(cddl_input, prelude, b"\xd2\x84\x40\xa0\x40\x40").unwrap();
+  validate_cbor_from_slice_with_prelude(cddl_input, prelude, b"\xd2\x83\x40\xa0\x40").unwrap_err();
+  validate_cbor_from_slice_with_prelude(cddl_input, prelude, b"\xc1\x1a\x65\x53\xf1\x00").unwrap();
+  validate_cbor_from_slice_with_prelude(cddl_input, prelude, b"\xc1\xf9\x3c\x00").unwrap_err();
+  validate_cbor_from_slice(cddl_input, b"\xd2\x84\x40\xa0\x40\x40").unwrap_err();
+
+  // Tagged data is validated against the standard prelude
+  let cddl_input = r#"thing = tdate / eb16 / cbor-any"#;
+  validate_cbor_from_slice(cddl_input, b"\xc0\x6a2024-01-02").unwrap();
+  validate_cbor_from_slice(cddl_input, b"\xc0\x01").unwrap_err();
+  validate_cbor_from_slice(cddl_input, b"\xd7\x41\x01").unwrap();
+  validate_cbor_from_slice(cddl_input, b"\xd9\xd9\xf7\x01").unwrap();
+  validate_cbor_from_slice(cddl_input, b"\xd8\x20\x01").unwrap_err();
 }
 
 #[test]
@@ -280,3 +818,23 @@ fn validate_cbor_map() {
   let cddl_input = r#"thing = {x: int, y: int, z: int}"#;
   validate_cbor_from_slice(cddl_input, cbor::ARRAY_123).unwrap_err();
 }
+
+#[test]
+fn validate_cbor_non_productive_rules() {
+  let cddl_input = r#"root = [* node]
+node = { next: node } / int"#;
+  let mut lexer = lexer_from_str(cddl_input);
+  let mut cddl = cddl_from_str(&mut lexer, cddl_input, true).unwrap();
+
+  validate_cbor_from_slice(cddl_input, cbor::ARRAY_123).unwrap();
+
+  // The parser refuses non-productive rules, so remove the base case after the
+  // fact, leaving "node = { next: node }"
+  if let ast::Rule::Type { rule, .. } = &mut cddl.rules[1] {
+    rule.value.type_choices.pop();
+  }
+
+  let cbor = serde_cbor::from_slice(cbor::ARRAY_123).unwrap();
+  let mut cv = CBORValidator::new(&cddl, &cbor);
+  cv.validate().unwrap_err();
+}
@@ -3,7 +3,10 @@
 
 mod fixtures;
 
-use cddl::{lexer_from_str, parser, validator};
+use cddl::{
+  formatter::{format_cddl, FormatOptions},
+  lexer_from_str, parser, validator,
+};
 use std::fs;
 use validator::{json, validate_json_from_str};
 
@@ -28,6 +31,30 @@ fn verify_cddl_compiles() -> Result<(), parser::Error> {
   Ok(())
 }
 
+#[test]
+fn verify_cddl_formatting() -> Result<(), parser::Error> {
+  for file in fs::read_dir("tests/fixtures/cddl/").unwrap() {
+    let file = file.unwrap();
+
+    if file.path().extension().unwrap() != "cddl" {
+      continue;
+    }
+
+    let file_content = fs::read_to_string(file.path()).unwrap();
+    let formatted = format_cddl(&file_content, &FormatOptions::default())?;
+
+    assert_eq!(
+      format_cddl(&formatted, &FormatOptions::default())?,
+      formatted,
+      "formatting {:?} is not idempotent",
+      file.path()
+    );
+    assert!(parser::cddl_from_str(&mut lexer_from_str(&formatted), &formatted, true).is_ok());
+  }
+
+  Ok(())
+}
+
 #[test]
 fn verify_json_validation() -> json::Result {
   validate_json_from_str(
@@ -62,6 +62,7 @@ fn verify_cddl() -> Result<()> {
                 comments_before_assignt: None,
                 comments_after_assignt: None,
               },
+              comments_before_rule: None,
               comments_after_rule: None,
               span: (0, 19, 1),
             },
@@ -109,6 +110,7 @@ fn verify_cddl() -> Result<()> {
                 comments_before_assignt: None,
                 comments_after_assignt: None,
               },
+              comments_before_rule: None,
               comments_after_rule: None,
               span: (20, 39, 2),
             },
@@ -155,6 +157,7 @@ fn verify_cddl() -> Result<()> {
                 comments_before_assignt: None,
                 comments_after_assignt: None,
               },
+              comments_before_rule: None,
               comments_after_rule: None,
               span: (40, 57, 3),
             },
@@ -210,6 +213,7 @@ fn verify_cddl() -> Result<()> {
                 comments_before_assigng: None,
                 comments_after_assigng: None,
               }),
+              comments_before_rule: None,
               comments_after_rule: None,
               span: (58, 74, 4),
             },
@@ -277,6 +281,7 @@ fn verify_cddl() -> Result<()> {
                 comments_before_assignt: None,
                 comments_after_assignt: None,
               },
+              comments_before_rule: None,
               comments_after_rule: None,
               span: (75, 110, 5),
             },
@@ -425,6 +430,7 @@ fn verify_cddl() -> Result<()> {
                 comments_before_assignt: None,
                 comments_after_assignt: None,
               },
+              comments_before_rule: None,
               comments_after_rule: None,
               span: (111, 146, 6),
             },
@@ -462,6 +468,7 @@ fn verify_cddl() -> Result<()> {
                 comments_before_assignt: None,
                 comments_after_assignt: None,
               },
+              comments_before_rule: None,
               comments_after_rule: None,
               span: (147, 162, 7),
             },
@@ -530,6 +537,7 @@ fn verify_cddl() -> Result<()> {
                 comments_before_assigng: None,
                 comments_after_assigng: None,
               }),
+              comments_before_rule: None,
               comments_after_rule: None,
               span: (163, 186, 8),
             },
@@ -604,6 +612,7 @@ fn verify_cddl() -> Result<()> {
                 comments_before_assignt: None,
                 comments_after_assignt: None,
               },
+              comments_before_rule: None,
               comments_after_rule: None,
               span: (187, 211, 9),
             },